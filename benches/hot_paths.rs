@@ -0,0 +1,196 @@
+//! Benchmarks for the hot paths with large libraries.
+//! Run them with `cargo bench`, `cargo bench -- --save-baseline` stores the medians as the baseline
+//! that later runs are compared with. The run fails if a median is more than `MAX_SLOWDOWN_PERCENT` slower
+
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use time::Date;
+use uuid::Uuid;
+
+use musicbrainz_release_grabber::config::{canonical_name, Config};
+use musicbrainz_release_grabber::responses::{
+    dedup_by_title, dedup_release_groups, released_since, Album, Artist, ArtistSource, ReleaseType,
+};
+use musicbrainz_release_grabber::storage::{self, StorageFormat};
+
+const ARTISTS: usize = 1_000;
+const ALBUMS: usize = 100_000;
+/// A median this much slower than the baseline counts as a regression
+const MAX_SLOWDOWN_PERCENT: u128 = 20;
+
+/// The medians of the benchmarks in nanoseconds, by name
+type Medians = BTreeMap<String, u128>;
+
+/// The baseline file in the target dir of cargo
+fn baseline_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("hot_paths-baseline.json")
+}
+
+/// Times the benchmarks and compares them with the baseline
+struct Bencher {
+    baseline: Option<Medians>,
+    medians: Medians,
+    regressions: Vec<String>,
+}
+
+impl Bencher {
+    fn new() -> Self {
+        let baseline = std::fs::read_to_string(baseline_path())
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok());
+        Self {
+            baseline,
+            medians: Medians::new(),
+            regressions: vec![],
+        }
+    }
+
+    /// Run `f` `iterations` times and print the fastest, the median and the slowest run
+    /// with the change of the median to the baseline
+    fn bench<T>(&mut self, name: &str, iterations: u32, mut f: impl FnMut() -> T) {
+        // one warm up run
+        black_box(f());
+        let mut times = (0..iterations)
+            .map(|_| {
+                let start = Instant::now();
+                black_box(f());
+                start.elapsed()
+            })
+            .collect::<Vec<Duration>>();
+        times.sort_unstable();
+        let median = times[times.len() / 2];
+        let change = match self.baseline.as_ref().and_then(|b| b.get(name)) {
+            Some(&before) if before > 0 => {
+                let percent = (median.as_nanos() as i128 - before as i128) * 100 / before as i128;
+                if median.as_nanos() * 100 > before * (100 + MAX_SLOWDOWN_PERCENT) {
+                    self.regressions.push(name.to_string());
+                    format!("{:+}% REGRESSION", percent)
+                } else {
+                    format!("{:+}%", percent)
+                }
+            }
+            _ => String::new(),
+        };
+        println!(
+            "{:<30} min {:>12?} median {:>12?} max {:>12?} {}",
+            name,
+            times[0],
+            median,
+            times[times.len() - 1],
+            change
+        );
+        self.medians.insert(name.to_string(), median.as_nanos());
+    }
+
+    /// Store the medians as the baseline if asked to or if there is none yet, then report the regressions
+    fn finish(self, save: bool) {
+        if save || self.baseline.is_none() {
+            let s = serde_json::to_string_pretty(&self.medians).unwrap();
+            std::fs::write(baseline_path(), s).unwrap();
+            println!("Stored the baseline in {}", baseline_path().display());
+        }
+        if !self.regressions.is_empty() {
+            eprintln!(
+                "More than {}% slower than the baseline: {}",
+                MAX_SLOWDOWN_PERCENT,
+                self.regressions.join(", ")
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
+fn artist(i: usize) -> Artist {
+    Artist {
+        name: format!("The Artist’s Band & Friends No. {}", i),
+        id: Uuid::from_u128(i as u128),
+        search_string: format!("the artists band friends no {}", i),
+        sort_name: format!("Artist’s Band & Friends No. {}, The", i),
+        aliases: vec![format!("TABF {}", i)],
+        tags: vec!["rock".to_string(), "indie".to_string()],
+        source: ArtistSource::Directory,
+        ..Default::default()
+    }
+}
+
+/// Albums where every tenth is shared by two artists and every twentieth has a title of another album
+fn albums() -> Vec<Album> {
+    let first = Date::from_calendar_date(2000, time::Month::January, 1).unwrap();
+    (0..ALBUMS)
+        .map(|i| Album {
+            id: Uuid::from_u128(if i % 10 == 1 { i - 1 } else { i } as u128),
+            artist: format!("Artist {}", i % ARTISTS),
+            title: format!("Album {}", if i % 20 == 0 { i + 1 } else { i }),
+            date: first.checked_add(time::Duration::days((i * 7919 % 9000) as i64)),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn bench_canonical_name(b: &mut Bencher) {
+    let names = (0..ARTISTS)
+        .map(|i| artist(i).sort_name)
+        .collect::<Vec<String>>();
+    b.bench("canonical_name 1k", 100, || {
+        names
+            .iter()
+            .map(|n| canonical_name(n))
+            .collect::<Vec<String>>()
+    });
+}
+
+fn bench_dedup(b: &mut Bencher) {
+    let albums = albums();
+    b.bench("dedup_release_groups 100k", 10, || {
+        dedup_release_groups(albums.clone())
+    });
+    b.bench("dedup_by_title 100k", 10, || dedup_by_title(albums.clone()));
+}
+
+fn bench_date_filter(b: &mut Bencher) {
+    let albums = albums();
+    let since = Date::from_calendar_date(2020, time::Month::June, 1).unwrap();
+    b.bench("released_since 100k", 100, || {
+        released_since(&albums, since).len()
+    });
+}
+
+fn bench_config(b: &mut Bencher) {
+    let c = Config {
+        artist_full: (0..ARTISTS).map(artist).collect(),
+        previous: albums(),
+        ..Default::default()
+    };
+    let s = serde_json::to_string(&c).unwrap();
+    b.bench("config to json", 10, || serde_json::to_string(&c).unwrap());
+    b.bench("config from json", 10, || {
+        serde_json::from_str::<Config>(&s).unwrap()
+    });
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    for format in [
+        StorageFormat::Pretty,
+        StorageFormat::Compact,
+        StorageFormat::Gzip,
+    ] {
+        b.bench(&format!("config write {:?}", format), 5, || {
+            storage::write_json(&path, &c, format).unwrap()
+        });
+        b.bench(&format!("config read {:?}", format), 5, || {
+            storage::read_json::<Config>(&path).unwrap()
+        });
+    }
+}
+
+fn main() {
+    let save = std::env::args().any(|a| a == "--save-baseline");
+    let mut b = Bencher::new();
+    bench_canonical_name(&mut b);
+    bench_dedup(&mut b);
+    bench_date_filter(&mut b);
+    bench_config(&mut b);
+    b.finish(save);
+}
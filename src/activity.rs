@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use time::Date;
+
+use crate::i18n::tr;
+use crate::responses::Artist;
+
+/// How long ago the last release of an artist is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Activity {
+    Active,
+    /// no release for a while, but they might come back
+    Dormant,
+    /// no release for so long that they probably stopped
+    Defunct,
+    /// we never found a release
+    Unknown,
+}
+
+impl Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Activity::Active => tr!("active"),
+            Activity::Dormant => tr!("dormant"),
+            Activity::Defunct => tr!("defunct"),
+            Activity::Unknown => tr!("unknown"),
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// When we call an artist dormant or defunct
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ActivitySettings {
+    /// without a release for this many years an artist is dormant
+    pub dormant_years: u16,
+    /// without a release for this many years an artist is defunct
+    pub defunct_years: u16,
+}
+
+impl Default for ActivitySettings {
+    fn default() -> Self {
+        Self {
+            dormant_years: 5,
+            defunct_years: 15,
+        }
+    }
+}
+
+/// The day `years` years before `today`, the 28th for the 29th of February
+fn years_before(today: Date, years: u16) -> Date {
+    let year = today.year() - i32::from(years);
+    today
+        .replace_year(year)
+        .or_else(|_| today.replace_day(28).and_then(|d| d.replace_year(year)))
+        .unwrap_or(Date::MIN)
+}
+
+impl ActivitySettings {
+    /// The activity of `artist` from its last release
+    pub fn classify(&self, artist: &Artist, today: Date) -> Activity {
+        match artist.last_release {
+            None => Activity::Unknown,
+            Some(last) if last < years_before(today, self.defunct_years) => Activity::Defunct,
+            Some(last) if last < years_before(today, self.dormant_years) => Activity::Dormant,
+            Some(_) => Activity::Active,
+        }
+    }
+
+    /// If a quick check with `--only-active` fetches `artist`, which are all but dormant and defunct ones
+    pub fn is_checked(&self, artist: &Artist, today: Date) -> bool {
+        matches!(
+            self.classify(artist, today),
+            Activity::Active | Activity::Unknown
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::Month;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn artist(last_release: Option<Date>) -> Artist {
+        Artist {
+            name: "Artist".to_string(),
+            search_string: "artist".to_string(),
+            sort_name: "Artist".to_string(),
+            last_release,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn artists_are_classified_by_their_last_release() {
+        let settings = ActivitySettings::default();
+        let today = date(2024, Month::February, 29);
+        let classify = |d| settings.classify(&artist(d), today);
+        assert_eq!(classify(Some(date(2023, Month::June, 1))), Activity::Active);
+        assert_eq!(
+            classify(Some(date(2019, Month::February, 28))),
+            Activity::Active
+        );
+        assert_eq!(
+            classify(Some(date(2019, Month::February, 27))),
+            Activity::Dormant
+        );
+        assert_eq!(
+            classify(Some(date(2000, Month::January, 1))),
+            Activity::Defunct
+        );
+        assert_eq!(classify(None), Activity::Unknown);
+        assert!(settings.is_checked(&artist(None), today));
+        assert!(!settings.is_checked(&artist(Some(date(2010, Month::May, 1))), today));
+    }
+}
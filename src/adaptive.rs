@@ -0,0 +1,116 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::config::data_dir;
+
+/// File name of the learned rate in the data dir
+const RATE_FILE: &str = "rate.json";
+/// After this many requests without a throttle we speed up by one request
+const SPEED_UP_AFTER: u64 = 50;
+
+/// The rate we learned, in requests per five seconds like `MBRG_RATE_LIMIT`
+#[derive(Debug, Serialize, Deserialize)]
+struct Learned {
+    rate: u64,
+}
+
+/// A rate that halves when musicbrainz throttles us and slowly grows back to the configured one
+#[derive(Debug)]
+pub struct AdaptiveRate {
+    /// the configured rate, we never go faster
+    max: u64,
+    current: AtomicU64,
+    /// requests since the last change
+    since_change: AtomicU64,
+}
+
+impl AdaptiveRate {
+    /// Start at `learned` from an earlier run, at most `max`, or at `max` without one
+    pub fn new(max: u64, learned: Option<u64>) -> Self {
+        let max = max.max(1);
+        Self {
+            max,
+            current: AtomicU64::new(learned.unwrap_or(max).clamp(1, max)),
+            since_change: AtomicU64::new(0),
+        }
+    }
+
+    /// The rate we use now
+    pub fn rate(&self) -> u64 {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Musicbrainz throttled a request, returns the slower rate
+    pub fn throttled(&self) -> u64 {
+        self.since_change.store(0, Ordering::Relaxed);
+        let old = self
+            .current
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+                Some((r / 2).max(1))
+            })
+            .unwrap_or(1);
+        (old / 2).max(1)
+    }
+
+    /// A request went through, returns the faster rate if it is time to speed up
+    pub fn succeeded(&self) -> Option<u64> {
+        let count = self.since_change.fetch_add(1, Ordering::Relaxed) + 1;
+        if count < SPEED_UP_AFTER || self.rate() >= self.max {
+            return None;
+        }
+        self.since_change.store(0, Ordering::Relaxed);
+        let old = self
+            .current
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |r| {
+                Some((r + 1).min(self.max))
+            })
+            .ok()?;
+        Some((old + 1).min(self.max))
+    }
+}
+
+/// The path of the learned rate
+fn rate_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(RATE_FILE);
+    Ok(path)
+}
+
+/// The rate the last run learned
+pub fn read() -> Option<u64> {
+    let s = fs::read_to_string(rate_path().ok()?).ok()?;
+    serde_json::from_str::<Learned>(&s).ok().map(|l| l.rate)
+}
+
+/// Keep `rate` for the next run
+pub fn write(rate: u64) -> Result<()> {
+    let s = serde_json::to_string_pretty(&Learned { rate }).context("JSON to string")?;
+    fs::write(rate_path()?, s).context("Writing the learned rate")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_halves_and_grows_back() {
+        let rate = AdaptiveRate::new(30, Some(100));
+        assert_eq!(rate.rate(), 30);
+        assert_eq!(rate.throttled(), 15);
+        assert_eq!(rate.throttled(), 7);
+        for _ in 0..SPEED_UP_AFTER - 1 {
+            assert_eq!(rate.succeeded(), None);
+        }
+        assert_eq!(rate.succeeded(), Some(8));
+
+        let slow = AdaptiveRate::new(30, Some(1));
+        assert_eq!(slow.throttled(), 1);
+        let full = AdaptiveRate::new(30, None);
+        for _ in 0..SPEED_UP_AFTER * 2 {
+            assert_eq!(full.succeeded(), None);
+        }
+    }
+}
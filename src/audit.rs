@@ -0,0 +1,207 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use time::OffsetDateTime;
+use uuid::Uuid;
+use yansi::Paint;
+
+use crate::config::{canonical_name, data_dir};
+use crate::i18n::tr;
+use crate::responses::Candidate;
+
+/// File name of the audit log in the data dir, one json entry per line
+const AUDIT_FILE: &str = "audit.jsonl";
+
+/// How a search string was resolved to an artist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    #[serde(with = "time::serde::rfc3339")]
+    pub at: OffsetDateTime,
+    /// the command that resolved it, like `add`
+    pub command: String,
+    pub search: String,
+    /// none if the search failed
+    pub chosen: Option<Candidate>,
+    /// the other artists the search found
+    pub alternatives: Vec<Candidate>,
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+impl Entry {
+    /// The entry for a search that found `candidates`, the chosen one first
+    pub fn found(
+        at: OffsetDateTime,
+        command: &str,
+        search: &str,
+        mut candidates: Vec<Candidate>,
+    ) -> Self {
+        let chosen = (!candidates.is_empty()).then(|| candidates.remove(0));
+        Entry {
+            at,
+            command: command.to_string(),
+            search: search.to_string(),
+            chosen,
+            alternatives: candidates,
+            error: None,
+        }
+    }
+
+    /// The entry for a search that failed with `error`
+    pub fn failed(at: OffsetDateTime, command: &str, search: &str, error: String) -> Self {
+        Entry {
+            error: Some(error),
+            ..Entry::found(at, command, search, vec![])
+        }
+    }
+
+    /// If the entry is about `artist`, a search string, an artist name or an id
+    fn is_about(&self, artist: &str) -> bool {
+        let wanted = canonical_name(artist);
+        canonical_name(&self.search) == wanted
+            || self
+                .chosen
+                .as_ref()
+                .is_some_and(|c| canonical_name(&c.name) == wanted || c.id.to_string() == artist)
+    }
+}
+
+/// The path of the audit log
+fn audit_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(AUDIT_FILE);
+    Ok(path)
+}
+
+/// Add `entries` to the end of the log at `path`
+fn append_to(path: &Path, entries: &[Entry]) -> Result<()> {
+    let mut lines = String::new();
+    for e in entries {
+        lines.push_str(&serde_json::to_string(e)?);
+        lines.push('\n');
+    }
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(lines.as_bytes()))
+        .with_context(|| format!("Writing audit log {}", path.display()))
+}
+
+/// All entries of the log at `path`, oldest first. Lines we cannot read are skipped
+fn read_from(path: &Path) -> Result<Vec<Entry>> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let text = fs::read_to_string(path)
+        .with_context(|| format!("Reading audit log {}", path.display()))?;
+    Ok(text
+        .lines()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+/// Add `entries` to the audit log
+pub fn record(entries: &[Entry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    append_to(&audit_path()?, entries)
+}
+
+/// The entries about `artist`, oldest first
+pub fn entries_for(artist: &str) -> Result<Vec<Entry>> {
+    Ok(read_from(&audit_path()?)?
+        .into_iter()
+        .filter(|e| e.is_about(artist))
+        .collect())
+}
+
+/// The score of the latest search that chose each artist, by its id
+pub fn scores() -> Result<HashMap<Uuid, u8>> {
+    Ok(read_from(&audit_path()?)?
+        .into_iter()
+        .filter_map(|e| e.chosen)
+        .filter_map(|c| c.score.map(|s| (c.id, s)))
+        .collect())
+}
+
+/// `c` with its disambiguation and score
+pub fn describe(c: &Candidate) -> String {
+    let mut res = c.name.clone();
+    if let Some(d) = &c.disambiguation {
+        res.push_str(&format!(" ({})", d));
+    }
+    res.push_str(&format!(" {}", c.id.to_string().blue()));
+    if let Some(s) = c.score {
+        res.push_str(&format!(" {}", tr!("score {}", s)));
+    }
+    res
+}
+
+/// Print `entries` for `audit`
+pub fn print(entries: &[Entry]) {
+    for e in entries {
+        let decision = match (&e.chosen, &e.error) {
+            (Some(c), _) => describe(c).green().to_string(),
+            (None, Some(error)) => error.red().to_string(),
+            (None, None) => tr!("nothing found").red().to_string(),
+        };
+        println!(
+            "{} {} \"{}\" -> {}",
+            e.at.to_string().blue(),
+            e.command.bold(),
+            e.search,
+            decision
+        );
+        for a in &e.alternatives {
+            println!("    {} {}", tr!("alternative").yellow(), describe(a));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candidate(name: &str, n: u128, score: u8) -> Candidate {
+        Candidate {
+            id: Uuid::from_u128(n),
+            name: name.to_string(),
+            disambiguation: None,
+            score: Some(score),
+        }
+    }
+
+    #[test]
+    fn entries_are_appended_and_found_by_artist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(AUDIT_FILE);
+        let at = OffsetDateTime::UNIX_EPOCH;
+        let first = Entry::found(
+            at,
+            "add",
+            "beatles",
+            vec![
+                candidate("The Beatles", 1, 100),
+                candidate("The Beatles Revival Band", 2, 70),
+            ],
+        );
+        append_to(&path, std::slice::from_ref(&first)).unwrap();
+        let second = Entry::failed(at, "fill-ids", "nobody", "not found".to_string());
+        append_to(&path, std::slice::from_ref(&second)).unwrap();
+
+        let entries = read_from(&path).unwrap();
+        assert_eq!(entries, vec![first.clone(), second]);
+        assert_eq!(first.chosen.as_ref().unwrap().name, "The Beatles");
+        assert_eq!(first.alternatives.len(), 1);
+        assert!(first.is_about("The Beatles"));
+        assert!(first.is_about("Beatles"));
+        assert!(first.is_about(&Uuid::from_u128(1).to_string()));
+        assert!(!first.is_about("The Beatles Revival Band"));
+        assert!(entries[1].is_about("Nobody"));
+    }
+}
@@ -0,0 +1,415 @@
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use native_tls::TlsConnector;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::notify::Notifier;
+use crate::output::escape_html;
+use crate::secret;
+
+/// How long we wait for a chat server before giving up
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// IRC servers cut longer messages, so a notification has at most this many lines
+const MAX_IRC_LINES: usize = 10;
+
+/// Settings for posting notifications to an IRC channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IrcSettings {
+    pub server: String,
+    /// 6697 with tls, 6667 without
+    #[serde(default)]
+    pub port: Option<u16>,
+    #[serde(default = "default_true")]
+    pub tls: bool,
+    pub nick: String,
+    /// like `#music`, or a nick for private messages
+    pub channel: String,
+    /// server password, networks like libera.chat also take the NickServ password here.
+    /// Can be a `file:`, `env:` or `command:` reference, see `secret::resolve`
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Settings for sending notifications over XMPP
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct XmppSettings {
+    /// the account we log in with, like `bot@example.org`
+    pub jid: String,
+    /// can be a `file:`, `env:` or `command:` reference
+    pub password: String,
+    /// who gets the message, a user or a group chat
+    pub to: String,
+    /// `to` is a group chat (MUC) we join first
+    #[serde(default)]
+    pub groupchat: bool,
+    /// the server if it is not the domain of the jid
+    #[serde(default)]
+    pub server: Option<String>,
+    #[serde(default = "default_xmpp_port")]
+    pub port: u16,
+}
+
+/// Tls is on unless turned off
+fn default_true() -> bool {
+    true
+}
+
+/// The port for XMPP clients
+fn default_xmpp_port() -> u16 {
+    5222
+}
+
+/// Connect to `server` on `port` with the chat timeouts
+fn connect(server: &str, port: u16) -> Result<TcpStream> {
+    let stream = TcpStream::connect((server, port))
+        .with_context(|| format!("Could not connect to {}:{}", server, port))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Posts to an IRC channel
+pub struct IrcNotifier {
+    pub settings: IrcSettings,
+}
+
+impl Notifier for IrcNotifier {
+    fn name(&self) -> &str {
+        "irc"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        let s = &IrcSettings {
+            password: self
+                .settings
+                .password
+                .as_deref()
+                .map(secret::resolve)
+                .transpose()?,
+            ..self.settings.clone()
+        };
+        let port = s.port.unwrap_or(if s.tls { 6697 } else { 6667 });
+        let stream = connect(&s.server, port)?;
+        if s.tls {
+            let tls = TlsConnector::new()?
+                .connect(&s.server, stream)
+                .map_err(|e| anyhow!("Tls with {} failed: {}", s.server, e))?;
+            irc_session(tls, s, title, body)
+        } else {
+            irc_session(stream, s, title, body)
+        }
+    }
+}
+
+/// The lines of a notification for IRC, long bodies are cut
+fn irc_lines(title: &str, body: &str) -> Vec<String> {
+    let lines = body
+        .lines()
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<&str>>();
+    let mut res = vec![title.to_string()];
+    res.extend(lines.iter().take(MAX_IRC_LINES).map(|l| l.to_string()));
+    if lines.len() > MAX_IRC_LINES {
+        res.push(format!("… +{}", lines.len() - MAX_IRC_LINES));
+    }
+    res
+}
+
+/// Log in on the IRC connection `stream`, post the notification to the channel and quit
+fn irc_session<S: Read + Write>(
+    stream: S,
+    settings: &IrcSettings,
+    title: &str,
+    body: &str,
+) -> Result<()> {
+    let mut reader = BufReader::new(stream);
+    let send = |reader: &mut BufReader<S>, line: &str| -> Result<()> {
+        // no newlines inside a message, they would be commands to the server
+        let line = line.replace(['\r', '\n'], " ");
+        reader
+            .get_mut()
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .context("Could not write to the IRC server")
+    };
+    if let Some(p) = &settings.password {
+        send(&mut reader, &format!("PASS {}", p))?;
+    }
+    send(&mut reader, &format!("NICK {}", settings.nick))?;
+    send(
+        &mut reader,
+        &format!("USER {} 0 * :musicbrainz-release-grabber", settings.nick),
+    )?;
+    // wait for the welcome, answering pings
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("IRC server closed the connection"));
+        }
+        let line = line.trim_end();
+        let mut parts = line.split(' ');
+        let first = parts.next().unwrap_or_default();
+        if first == "PING" {
+            send(&mut reader, &line.replacen("PING", "PONG", 1))?;
+            continue;
+        }
+        match parts.next() {
+            Some("001") => break,
+            Some("432") | Some("433") | Some("464") | Some("465") => {
+                return Err(anyhow!("IRC server refused us: {}", line))
+            }
+            _ if first == "ERROR" => return Err(anyhow!("IRC server refused us: {}", line)),
+            _ => {}
+        }
+    }
+    if settings.channel.starts_with(['#', '&']) {
+        send(&mut reader, &format!("JOIN {}", settings.channel))?;
+    }
+    for l in irc_lines(title, body) {
+        send(&mut reader, &format!("PRIVMSG {} :{}", settings.channel, l))?;
+    }
+    send(&mut reader, "QUIT :done")?;
+    // the server closes the connection after the quit, everything we sent is processed then
+    let mut rest = Vec::new();
+    let _ = reader.read_to_end(&mut rest);
+    Ok(())
+}
+
+/// Sends messages over XMPP
+pub struct XmppNotifier {
+    pub settings: XmppSettings,
+}
+
+impl Notifier for XmppNotifier {
+    fn name(&self) -> &str {
+        "xmpp"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        let s = &XmppSettings {
+            password: secret::resolve(&self.settings.password)?,
+            ..self.settings.clone()
+        };
+        let domain = jid_domain(&s.jid)?;
+        let server = s.server.as_deref().unwrap_or(domain);
+        let mut stream = connect(server, s.port)?;
+        open_stream(&mut stream, domain)?;
+        let features = read_until(&mut stream, &["</stream:features>"])?;
+        if !features.contains("<starttls") {
+            return Err(anyhow!(
+                "{} does not offer tls, we do not send the password without",
+                server
+            ));
+        }
+        stream.write_all(b"<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>")?;
+        let answer = read_until(&mut stream, &["<proceed", "<failure"])?;
+        if !answer.contains("<proceed") {
+            return Err(anyhow!("{} refused tls", server));
+        }
+        let tls = TlsConnector::new()?
+            .connect(domain, stream)
+            .map_err(|e| anyhow!("Tls with {} failed: {}", server, e))?;
+        xmpp_session(tls, s, &format!("{}\n{}", title, body))
+    }
+}
+
+/// The domain of `jid`, like `example.org` for `bot@example.org/resource`
+fn jid_domain(jid: &str) -> Result<&str> {
+    let domain = jid.split('/').next().unwrap_or_default();
+    let domain = domain.rsplit('@').next().unwrap_or_default();
+    if domain.is_empty() {
+        Err(anyhow!("Invalid jid {}", jid))
+    } else {
+        Ok(domain)
+    }
+}
+
+/// Start a new XML stream to `domain`, after connecting, tls and authentication
+fn open_stream<S: Write>(stream: &mut S, domain: &str) -> Result<()> {
+    write!(
+        stream,
+        "<?xml version='1.0'?><stream:stream to='{}' version='1.0' xmlns='jabber:client' xmlns:stream='http://etherx.jabber.org/streams'>",
+        escape_html(domain)
+    )
+    .context("Could not write to the XMPP server")
+}
+
+/// Read from `stream` until one of the `markers` appears or the stream ends in an error
+fn read_until<S: Read>(stream: &mut S, markers: &[&str]) -> Result<String> {
+    let mut res = String::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream
+            .read(&mut buf)
+            .context("Could not read from the XMPP server")?;
+        if n == 0 {
+            return Err(anyhow!("XMPP server closed the connection"));
+        }
+        res.push_str(&String::from_utf8_lossy(&buf[..n]));
+        if markers.iter().any(|m| res.contains(m)) {
+            return Ok(res);
+        }
+        if res.contains("<stream:error") {
+            return Err(anyhow!("XMPP server error: {}", res));
+        }
+    }
+}
+
+/// Authenticate on the encrypted `stream`, bind a resource and send `text`
+fn xmpp_session<S: Read + Write>(mut stream: S, settings: &XmppSettings, text: &str) -> Result<()> {
+    let domain = jid_domain(&settings.jid)?;
+    let user = settings.jid.split('@').next().unwrap_or_default();
+    open_stream(&mut stream, domain)?;
+    let features = read_until(&mut stream, &["</stream:features>"])?;
+    if !features.contains("PLAIN") {
+        return Err(anyhow!("XMPP server does not allow password logins"));
+    }
+    let credentials = base64::engine::general_purpose::STANDARD
+        .encode(format!("\0{}\0{}", user, settings.password));
+    write!(
+        stream,
+        "<auth xmlns='urn:ietf:params:xml:ns:xmpp-sasl' mechanism='PLAIN'>{}</auth>",
+        credentials
+    )?;
+    let answer = read_until(&mut stream, &["<success", "<failure"])?;
+    if !answer.contains("<success") {
+        return Err(anyhow!("XMPP login of {} failed", settings.jid));
+    }
+    open_stream(&mut stream, domain)?;
+    read_until(&mut stream, &["</stream:features>"])?;
+    stream.write_all(b"<iq type='set' id='bind'><bind xmlns='urn:ietf:params:xml:ns:xmpp-bind'><resource>musicbrainz-release-grabber</resource></bind></iq>")?;
+    let bound = read_until(&mut stream, &["</iq>"])?;
+    if bound.contains("type='error'") || bound.contains("type=\"error\"") {
+        return Err(anyhow!("XMPP server did not bind a resource"));
+    }
+    let to = escape_html(&settings.to);
+    let kind = if settings.groupchat {
+        write!(
+            stream,
+            "<presence to='{}/{}'><x xmlns='http://jabber.org/protocol/muc'><history maxstanzas='0'/></x></presence>",
+            to,
+            escape_html(user)
+        )?;
+        "groupchat"
+    } else {
+        "chat"
+    };
+    write!(
+        stream,
+        "<message to='{}' type='{}'><body>{}</body></message></stream:stream>",
+        to,
+        kind,
+        escape_html(text)
+    )?;
+    stream.flush()?;
+    // the server closes its stream after ours, the message is delivered then
+    let _ = read_until(&mut stream, &["</stream:stream>"]);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// A connection that answers every read with the next of `answers` and records what we sent
+    struct FakeStream {
+        answers: VecDeque<String>,
+        output: Vec<u8>,
+    }
+
+    impl FakeStream {
+        fn new(answers: &[&str]) -> Self {
+            Self {
+                answers: answers.iter().map(|a| a.to_string()).collect(),
+                output: vec![],
+            }
+        }
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some(a) = self.answers.pop_front() else {
+                return Ok(0);
+            };
+            buf[..a.len()].copy_from_slice(a.as_bytes());
+            Ok(a.len())
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn irc_joins_and_posts() {
+        let settings = IrcSettings {
+            server: "irc.example.org".to_string(),
+            port: None,
+            tls: true,
+            nick: "mbrg".to_string(),
+            channel: "#music".to_string(),
+            password: None,
+        };
+        let mut stream = FakeStream::new(&[
+            ":irc.example.org NOTICE * :hello\r\nPING :123\r\n",
+            ":irc.example.org 001 mbrg :Welcome\r\n",
+        ]);
+        irc_session(&mut stream, &settings, "2 new albums", "A - One\nB - Two").unwrap();
+        let sent = String::from_utf8(stream.output).unwrap();
+        assert_eq!(
+            sent,
+            "NICK mbrg\r\nUSER mbrg 0 * :musicbrainz-release-grabber\r\nPONG :123\r\nJOIN #music\r\n\
+             PRIVMSG #music :2 new albums\r\nPRIVMSG #music :A - One\r\nPRIVMSG #music :B - Two\r\nQUIT :done\r\n"
+        );
+
+        let mut stream =
+            FakeStream::new(&[":irc.example.org 433 * mbrg :Nickname is already in use\r\n"]);
+        assert!(irc_session(&mut stream, &settings, "t", "b").is_err());
+        let body = (0..12)
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(irc_lines("t", &body).last().unwrap(), "… +2");
+    }
+
+    #[test]
+    fn xmpp_logs_in_and_sends() {
+        let settings = XmppSettings {
+            jid: "bot@example.org".to_string(),
+            password: "secret".to_string(),
+            to: "room@muc.example.org".to_string(),
+            groupchat: true,
+            server: None,
+            port: default_xmpp_port(),
+        };
+        assert_eq!(jid_domain("bot@example.org/phone").unwrap(), "example.org");
+        let mut stream = FakeStream::new(&[
+            "<stream:features><mechanisms><mechanism>PLAIN</mechanism></mechanisms></stream:features>",
+            "<success xmlns='urn:ietf:params:xml:ns:xmpp-sasl'/>",
+            "<stream:features><bind/></stream:features>",
+            "<iq type='result' id='bind'><bind><jid>bot@example.org/x</jid></bind></iq>",
+            "</stream:stream>",
+        ]);
+        xmpp_session(&mut stream, &settings, "New <albums>").unwrap();
+        let sent = String::from_utf8(stream.output).unwrap();
+        assert!(sent.contains("mechanism='PLAIN'>AGJvdABzZWNyZXQ=</auth>"));
+        assert!(sent.contains("<presence to='room@muc.example.org/bot'>"));
+        assert!(sent.contains(
+            "<message to='room@muc.example.org' type='groupchat'><body>New &lt;albums&gt;</body></message>"
+        ));
+
+        let mut stream = FakeStream::new(&[
+            "<stream:features><mechanisms><mechanism>PLAIN</mechanism></mechanisms></stream:features>",
+            "<failure xmlns='urn:ietf:params:xml:ns:xmpp-sasl'><not-authorized/></failure>",
+        ]);
+        assert!(xmpp_session(&mut stream, &settings, "x").is_err());
+    }
+}
@@ -0,0 +1,682 @@
+use anyhow::Result;
+use indicatif::{ProgressBar, ProgressStyle};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::config::{canonical_name, Config};
+use crate::i18n::tr;
+use crate::responses::{
+    self, get_client, released_since, Album, Artist, ArtistSource, GroupReleases, MbClient,
+    MbError, Release, ReleaseType,
+};
+use crate::{audit, discography, formats, jobs, links, preorder};
+
+/// Progress bar style
+const PROGRESS_STYLE: &str =
+    "[{spinner:.green}] [{pos:.green}/{len:.green}] ({percent:>2}%) {bar:40.cyan/blue} [ETA: {eta:>3}] |                 {msg}";
+
+/// Errors of a run together with the artist they happened for
+pub type ArtistErrors = Vec<(String, anyhow::Error)>;
+
+/// A progress bar in our style for `len` elements
+pub fn progress_bar(len: usize) -> Result<ProgressBar> {
+    let pb = ProgressBar::new(len as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(PROGRESS_STYLE)?
+            .progress_chars("##-"),
+    );
+    pb.enable_steady_tick(std::time::Duration::new(0, 500));
+    Ok(pb)
+}
+
+/// A progress bar for `len` elements if we `show` progress, a hidden one otherwise
+fn bar(len: usize, show: bool) -> Result<ProgressBar> {
+    if show {
+        progress_bar(len)
+    } else {
+        Ok(ProgressBar::hidden())
+    }
+}
+
+/// The error for stopping a whole run because of the fatal error `e` at `artist`
+pub fn abort_run(e: MbError, artist: &str) -> anyhow::Error {
+    anyhow::Error::from(e).context(format!("Stopping the run at artist {}", artist))
+}
+
+/// The artist names from the directory that do not have an artist yet
+pub fn unresolved_names(c: &Config) -> Vec<String> {
+    let already_found_artists: HashSet<&String> = c.artist_full.iter().map(|a| &a.name).collect();
+    let artist_names: HashSet<&String> = c.artist_names.iter().collect();
+    artist_names
+        .difference(&already_found_artists)
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Which artists get their albums fetched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchArtists {
+    None,
+    /// all but the archived ones
+    All,
+    /// all but the archived, dormant and defunct ones
+    OnlyActive,
+}
+
+/// What fetching the artists brought
+#[derive(Debug, Default)]
+pub struct Fetched {
+    pub albums: Vec<Album>,
+    pub errors: ArtistErrors,
+    /// the names of the artists that were left out because of the deadline
+    pub unchecked: Vec<String>,
+    /// how many artist names got an artist
+    pub resolved: usize,
+    /// how many artists got their albums fetched
+    pub fetched: usize,
+}
+
+/// Fetch the albums of all artists in the config.
+/// Albums of several followed artists are only returned once, archived artists are skipped, and with `only_active` also dormant and defunct ones
+/// With `resolve` the artist names without an artist are searched first and their albums fetched as well
+/// Remembers for every artist when it was checked and its newest release, the config is not written.
+/// No fetch starts after `deadline`
+pub fn fetch_all_albums(
+    c: &mut Config,
+    mb: &MbClient,
+    resolve: bool,
+    only_active: bool,
+    deadline: Option<Instant>,
+    progress: bool,
+) -> Result<Fetched> {
+    let fetch = if only_active {
+        FetchArtists::OnlyActive
+    } else {
+        FetchArtists::All
+    };
+    let mut fetched = fetch_artists(c, mb, resolve, fetch, deadline, progress)?;
+    discography::update(&fetched.albums, c.settings.storage)?;
+    fetched.albums = responses::dedup_release_groups(std::mem::take(&mut fetched.albums));
+    Ok(fetched)
+}
+
+/// Resolve the unresolved artist names if `resolve` and fetch the albums of the artists in `fetch`.
+/// Resolved artists are added to the config, the config is not written.
+/// Jobs do not start after `deadline`, the names of their artists are in `unchecked`
+pub fn fetch_artists(
+    c: &mut Config,
+    mb: &MbClient,
+    resolve: bool,
+    fetch: FetchArtists,
+    deadline: Option<Instant>,
+    progress: bool,
+) -> Result<Fetched> {
+    let mut jobs = Vec::new();
+    if resolve {
+        jobs.extend(unresolved_names(c).into_iter().map(jobs::Job::Resolve));
+    }
+    let today = c.settings.check.today();
+    if fetch != FetchArtists::None {
+        jobs.extend(
+            c.artist_full
+                .iter()
+                .filter(|a| !a.archived)
+                .filter(|a| fetch == FetchArtists::All || c.settings.activity.is_checked(a, today))
+                .cloned()
+                .map(jobs::Job::Fetch),
+        );
+    }
+    let pb = bar(jobs.len(), progress)?;
+    let options = jobs::JobOptions {
+        date_strategy: &c.settings.check.date_strategy,
+        countries: &c.settings.check.countries,
+        skip_types: &c.settings.others.skip,
+        fetch_resolved: fetch != FetchArtists::None,
+        artist_budget: c.settings.check.artist_budget(),
+        deadline,
+    };
+    mb.show_progress(Some(&pb));
+    let (outcomes, left) = jobs::run(mb, jobs, &options, &pb);
+    let unchecked = left
+        .into_iter()
+        .map(|j| match j {
+            jobs::Job::Resolve(name)
+            | jobs::Job::Fetch(Artist { name, .. })
+            | jobs::Job::Refetch(Artist { name, .. }) => name,
+        })
+        .collect::<Vec<_>>();
+    mb.show_progress(None);
+    pb.finish_and_clear();
+
+    let now = c.settings.check.now();
+    let audited = outcomes
+        .iter()
+        .filter_map(|o| match o {
+            jobs::Outcome::Resolved {
+                search,
+                result: Ok(_),
+                candidates,
+            } => Some(audit::Entry::found(
+                now,
+                "fill-ids",
+                search,
+                candidates.clone(),
+            )),
+            jobs::Outcome::Resolved {
+                search,
+                result: Err(e),
+                ..
+            } => Some(audit::Entry::failed(now, "fill-ids", search, e.to_string())),
+            jobs::Outcome::Fetched { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    audit::record(&audited)?;
+    let mut res = Fetched {
+        unchecked,
+        ..Default::default()
+    };
+    let mut fatal = None;
+    for outcome in outcomes {
+        match outcome {
+            jobs::Outcome::Resolved { result: Ok(a), .. } => {
+                res.resolved += 1;
+                if !c.artist_full.iter().any(|o| o.id == a.id) {
+                    c.artist_full
+                        .push(a.added_by(ArtistSource::Directory, today));
+                }
+            }
+            jobs::Outcome::Fetched {
+                artist,
+                result: Ok(mut albums),
+            } => {
+                res.fetched += 1;
+                if let Some(a) = c.artist_full.iter_mut().find(|a| a.id == artist.id) {
+                    a.last_checked = Some(today);
+                    a.last_release = albums
+                        .iter()
+                        .filter_map(|a| a.date)
+                        .filter(|d| *d <= today)
+                        .max();
+                }
+                res.albums.append(&mut albums);
+            }
+            jobs::Outcome::Resolved {
+                search: name,
+                result: Err(e),
+                ..
+            }
+            | jobs::Outcome::Fetched {
+                artist: Artist { name, .. },
+                result: Err(e),
+            } => {
+                if e.is_fatal() {
+                    fatal.get_or_insert((e, name));
+                } else {
+                    res.errors.push((name, e.into()));
+                }
+            }
+        }
+    }
+    c.artist_full.sort_unstable();
+    if let Some((e, name)) = fatal {
+        return Err(abort_run(e, &name));
+    }
+    Ok(res)
+}
+
+/// What a check does besides what the config says
+#[derive(Debug, Default, Clone)]
+pub struct CheckOptions {
+    /// also find reissues
+    pub reissues: bool,
+    /// only report releases in these countries
+    pub countries: Vec<String>,
+    /// resolve the artist names without an id in the same run
+    pub fill_ids: bool,
+    /// skip dormant and defunct artists, this does not move the check date
+    pub only_active: bool,
+    /// no artist fetch starts after this long, a check that stops early does not move the check date
+    pub max_duration: Option<Duration>,
+    /// show progress bars on the terminal
+    pub progress: bool,
+}
+
+/// What a check found, sorted into the sections of a report
+#[derive(Debug, Default)]
+pub struct CheckResult {
+    /// new albums
+    pub albums: Vec<Album>,
+    /// new releases of the other types and albums with too few tracks
+    pub others: Vec<Album>,
+    /// new episodes of followed broadcast series, oldest first
+    pub broadcasts: Vec<Album>,
+    /// new releases in known release groups, `None` if reissues were not checked
+    pub reissues: Option<Vec<Album>>,
+    /// what went wrong, with the artist it happened for
+    pub errors: ArtistErrors,
+    /// problems with the settings that did not stop the check
+    pub warnings: Vec<anyhow::Error>,
+    /// the artists that were not checked because of the max duration
+    pub unchecked: Vec<String>,
+    /// the max duration was over before all reissues were checked
+    pub reissues_stopped: bool,
+    /// how many artist names got an artist with `fill_ids`
+    pub resolved: usize,
+    /// how many artists got their albums fetched
+    pub fetched: usize,
+}
+
+impl CheckResult {
+    /// The check ended at the max duration, the next check has to do the rest
+    pub fn stopped(&self) -> bool {
+        !self.unchecked.is_empty() || self.reissues_stopped
+    }
+}
+
+/// Only keep the albums where `keep` is true for the releases inside the release group
+/// Albums where we could not get the releases are kept and the error is pushed to `errors`
+fn filter_by_releases<'a>(
+    albums: Vec<&'a Album>,
+    group_releases: &mut GroupReleases,
+    mb: &MbClient,
+    errors: &mut ArtistErrors,
+    keep: impl Fn(&[Release]) -> bool,
+) -> Vec<&'a Album> {
+    albums
+        .into_iter()
+        .filter(|a| match group_releases.get(mb, &a.id) {
+            Ok(releases) => keep(releases),
+            Err(e) => {
+                errors.push((
+                    a.artist.clone(),
+                    anyhow::Error::from(e).context(format!("Could not filter {}", a.title)),
+                ));
+                true
+            }
+        })
+        .collect()
+}
+
+/// The ids of the albums in `albums` where no release has `min_tracks` tracks, they get demoted to others
+fn demoted_albums(
+    albums: &[&Album],
+    group_releases: &mut GroupReleases,
+    mb: &MbClient,
+    errors: &mut ArtistErrors,
+    min_tracks: u32,
+) -> HashSet<Uuid> {
+    let mut demoted = HashSet::new();
+    if min_tracks == 0 {
+        return demoted;
+    }
+    for a in albums
+        .iter()
+        .filter(|a| a.release_type == ReleaseType::Album)
+    {
+        match group_releases.get(mb, &a.id) {
+            Ok(releases) => {
+                let tracks = releases.iter().map(|r| r.track_count()).max().unwrap_or(0);
+                if tracks < min_tracks {
+                    demoted.insert(a.id);
+                }
+            }
+            Err(e) => errors.push((
+                a.artist.clone(),
+                anyhow::Error::from(e).context(format!("Could not get track count of {}", a.title)),
+            )),
+        }
+    }
+    demoted
+}
+
+/// Split `found` into the albums, the others and the broadcast episodes, `demoted` albums are others
+fn split_sections(
+    found: &[&Album],
+    demoted: &HashSet<Uuid>,
+) -> (Vec<Album>, Vec<Album>, Vec<Album>) {
+    let (albums, others): (Vec<Album>, Vec<Album>) = found
+        .iter()
+        .map(|a| (*a).clone())
+        .partition(|a| a.release_type == ReleaseType::Album && !demoted.contains(&a.id));
+    let (mut episodes, others): (Vec<Album>, Vec<Album>) = others
+        .into_iter()
+        .partition(|a| a.release_type == ReleaseType::Broadcast);
+    episodes.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.artist.cmp(&b.artist)));
+    (albums, others, episodes)
+}
+
+/// The albums of the last check after a check that found `albums`.
+/// A stopped check keeps the albums of the last one for the `unchecked` artists next to the new ones,
+/// the artists it did check only have what it found
+fn next_previous(
+    previous: Vec<Album>,
+    albums: &[Album],
+    unchecked: &[String],
+    stopped: bool,
+) -> Vec<Album> {
+    if !stopped {
+        return albums.to_vec();
+    }
+    let unchecked = unchecked
+        .iter()
+        .map(|n| canonical_name(n))
+        .collect::<HashSet<_>>();
+    let ids = albums.iter().map(|a| a.id).collect::<HashSet<_>>();
+    let mut res = previous
+        .into_iter()
+        .filter(|p| !ids.contains(&p.id))
+        .filter(|p| {
+            std::iter::once(&p.artist)
+                .chain(p.other_artists.iter())
+                .any(|n| unchecked.contains(&canonical_name(n)))
+        })
+        .chain(albums.iter().cloned())
+        .collect::<Vec<_>>();
+    res.sort_unstable();
+    res
+}
+
+/// Add the links on the streaming services enabled in the `settings` to the `albums`.
+/// Returns the errors with the artist of the album they happened for and the problems with the settings
+fn add_links(
+    settings: &crate::config::Settings,
+    mb: &MbClient,
+    group_releases: &mut GroupReleases,
+    albums: Vec<&mut Album>,
+    progress: bool,
+) -> Result<(ArtistErrors, Vec<anyhow::Error>)> {
+    let (providers, setup_errors) =
+        links::providers(&settings.links, &settings.spotify, &get_client()?);
+    if providers.is_empty() || albums.is_empty() {
+        return Ok((vec![], setup_errors));
+    }
+    let providers = providers.iter().map(|p| p.as_ref()).collect::<Vec<_>>();
+    let mut errors: ArtistErrors = Vec::new();
+    let pb = bar(albums.len(), progress)?;
+    for a in albums {
+        pb.set_message(format!("Links: {} - {}", a.artists(), a.title));
+        pb.inc(1);
+        match links::resolve(mb, group_releases, &providers, a) {
+            Ok(links) => a.links = links,
+            Err(e) => errors.push((
+                a.artist.clone(),
+                e.context(format!("Could not find links for {}", a.title)),
+            )),
+        }
+    }
+    pb.finish_and_clear();
+    Ok((errors, setup_errors))
+}
+
+/// Check the artists in `c` for releases since its last check and sort them into the sections of a report.
+/// The artists, the release counts, the pre-order reminders and the albums of the last check in `c` are updated,
+/// but the config is not written. Write it with `Config::now` after a complete check, so the next one
+/// starts from today, and with `Config::write` after a stopped one or one of only the active artists.
+/// Only a fatal error, like musicbrainz refusing us, stops the check, the others are in the result
+pub fn check(c: &mut Config, mb: &MbClient, options: CheckOptions) -> Result<CheckResult> {
+    let reissues = options.reissues || c.settings.check.reissues;
+    let deadline = options.max_duration.map(|d| Instant::now() + d);
+    let Fetched {
+        albums: all_albums,
+        mut errors,
+        unchecked,
+        resolved,
+        fetched,
+    } = fetch_all_albums(
+        c,
+        mb,
+        options.fill_ids,
+        options.only_active,
+        deadline,
+        options.progress,
+    )?;
+    let mut reissues_stopped = false;
+    let mut all_reissues: Vec<Album> = Vec::new();
+    if reissues {
+        let today = c.settings.check.today();
+        let checked = c
+            .artist_full
+            .iter()
+            .filter(|a| !a.archived)
+            .filter(|a| !options.only_active || c.settings.activity.is_checked(a, today))
+            .collect::<Vec<_>>();
+        let pb = bar(checked.len(), options.progress)?;
+        mb.show_progress(Some(&pb));
+        for a in pb.wrap_iter(checked.into_iter()) {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                reissues_stopped = true;
+                break;
+            }
+            pb.set_message(format!("{}: {}", tr!("Finding reissues"), a.name));
+            match a.get_reissues(mb, &mut c.release_counts) {
+                Ok(mut albums) => all_reissues.append(&mut albums),
+                Err(e) if e.is_fatal() => return Err(abort_run(e, &a.name)),
+                Err(e) => errors.push((a.name.clone(), e.into())),
+            };
+        }
+        mb.show_progress(None);
+        pb.finish_and_clear();
+        all_reissues.sort_unstable();
+    }
+
+    let today = c.settings.check.today();
+    let mut res = released_since(&all_albums, c.last_checked_time);
+    res.sort_unstable();
+    let pb = bar(res.len(), options.progress)?;
+    pb.set_message(tr!("Filtering results"));
+
+    let mut group_releases = GroupReleases::default();
+    let countries = if options.countries.is_empty() && c.settings.check.country_filter {
+        c.settings.check.countries.clone()
+    } else {
+        options.countries
+    };
+    if !countries.is_empty() {
+        res = filter_by_releases(res, &mut group_releases, mb, &mut errors, |releases| {
+            releases.iter().any(|r| r.released_in(&countries))
+        });
+    }
+    let check = &c.settings.check;
+    if !check.exclude_languages.is_empty() || !check.exclude_scripts.is_empty() {
+        res = filter_by_releases(res, &mut group_releases, mb, &mut errors, |releases| {
+            releases
+                .iter()
+                .any(|r| r.usable_text(&check.exclude_languages, &check.exclude_scripts))
+        });
+    }
+    let demoted = demoted_albums(&res, &mut group_releases, mb, &mut errors, check.min_tracks);
+    let (mut albums, mut others, episodes) = split_sections(&res, &demoted);
+    // albums without one of the required formats are dropped
+    if c.settings.formats.fetch() {
+        let mut cache = formats::read()?;
+        for a in albums.iter_mut().chain(others.iter_mut()) {
+            match formats::formats_of(&mut cache, &mut group_releases, mb, &a.id, today) {
+                Ok(f) => a.formats = f,
+                Err(e) => errors.push((
+                    a.artist.clone(),
+                    anyhow::Error::from(e).context(format!("Could not get formats of {}", a.title)),
+                )),
+            }
+        }
+        formats::write(&mut cache, today, c.settings.storage)?;
+        albums.retain(|a| c.settings.formats.keeps(&a.formats));
+        others.retain(|a| c.settings.formats.keeps(&a.formats));
+    }
+    // upcoming albums a shop already sells get a reminder from the daemon
+    if c.settings.preorders.enabled {
+        let upcoming = albums
+            .iter_mut()
+            .chain(others.iter_mut())
+            .filter(|a| a.date.is_some_and(|d| d > today));
+        for a in upcoming {
+            match group_releases.get(mb, &a.id) {
+                Ok(releases) => {
+                    a.preorder = preorder::is_preorder(releases, a.date.unwrap_or(today));
+                    if a.preorder {
+                        preorder::schedule(
+                            &mut c.reminders,
+                            a,
+                            c.settings.preorders.remind_days,
+                            today,
+                        );
+                    }
+                }
+                Err(e) => errors.push((
+                    a.artist.clone(),
+                    anyhow::Error::from(e)
+                        .context(format!("Could not find pre-orders of {}", a.title)),
+                )),
+            }
+        }
+    }
+    pb.finish_and_clear();
+    let discographies = discography::read()?;
+    discography::mark_gaps(&discographies, &mut albums, &c.settings.gaps);
+    discography::mark_gaps(&discographies, &mut others, &c.settings.gaps);
+    let (mut link_errors, warnings) = add_links(
+        &c.settings,
+        mb,
+        &mut group_releases,
+        albums.iter_mut().chain(others.iter_mut()).collect(),
+        options.progress,
+    )?;
+    errors.append(&mut link_errors);
+
+    let result = CheckResult {
+        albums,
+        others,
+        broadcasts: episodes,
+        reissues: reissues.then_some(all_reissues),
+        errors,
+        warnings,
+        unchecked,
+        reissues_stopped,
+        resolved,
+        fetched,
+    };
+    c.previous = next_previous(
+        std::mem::take(&mut c.previous),
+        &result.albums,
+        &result.unchecked,
+        result.stopped(),
+    );
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    fn album(n: u128, release_type: ReleaseType) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "Artist".to_string(),
+            title: format!("Album {}", n),
+            release_type,
+            ..Default::default()
+        }
+    }
+
+    /// A page of releases of a release group with one release of `tracks` tracks released in `country`
+    fn releases(country: &str, tracks: u32) -> (u16, String) {
+        let body = format!(
+            r#"{{"release-count": 1, "releases": [{{"id": "00000000-0000-0000-0000-000000000009", "title": "A",
+                "release-events": [{{"area": {{"iso-3166-1-codes": ["{}"]}}}}],
+                "media": [{{"format": "CD", "track-count": {}}}]}}]}}"#,
+            country, tracks
+        );
+        (200, body)
+    }
+
+    #[test]
+    fn albums_are_filtered_by_their_releases() {
+        let server = MockServer::start(vec![
+            releases("DE", 10),
+            releases("US", 10),
+            (400, String::new()),
+        ]);
+        let mb = server.client();
+        let albums = [
+            album(1, ReleaseType::Album),
+            album(2, ReleaseType::Album),
+            album(3, ReleaseType::Album),
+        ];
+        let mut errors = Vec::new();
+        let kept = filter_by_releases(
+            albums.iter().collect(),
+            &mut GroupReleases::default(),
+            &mb,
+            &mut errors,
+            |releases| releases.iter().any(|r| r.released_in(&["DE".to_string()])),
+        );
+        // an album we could not look at is kept
+        assert_eq!(
+            kept.iter().map(|a| a.id.as_u128()).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].0, "Artist");
+    }
+
+    #[test]
+    fn albums_with_few_tracks_are_others() {
+        let server = MockServer::start(vec![releases("DE", 3), releases("DE", 10)]);
+        let mb = server.client();
+        let found = [
+            album(1, ReleaseType::Album),
+            album(2, ReleaseType::Album),
+            album(3, ReleaseType::EP),
+            album(4, ReleaseType::Broadcast),
+        ];
+        let found = found.iter().collect::<Vec<_>>();
+        let mut errors = Vec::new();
+        let demoted = demoted_albums(&found, &mut GroupReleases::default(), &mb, &mut errors, 6);
+        assert_eq!(demoted, HashSet::from([Uuid::from_u128(1)]));
+        // only albums are counted
+        assert_eq!(server.requests().len(), 2);
+        assert!(errors.is_empty());
+
+        let (albums, others, episodes) = split_sections(&found, &demoted);
+        let ids = |v: &[Album]| v.iter().map(|a| a.id.as_u128()).collect::<Vec<_>>();
+        assert_eq!(ids(&albums), vec![2]);
+        assert_eq!(ids(&others), vec![1, 3]);
+        assert_eq!(ids(&episodes), vec![4]);
+
+        let none = demoted_albums(&found, &mut GroupReleases::default(), &mb, &mut errors, 0);
+        assert!(none.is_empty());
+    }
+
+    #[test]
+    fn a_stopped_check_keeps_the_last_albums_of_unchecked_artists() {
+        let by = |n: u128, artist: &str| Album {
+            artist: artist.to_string(),
+            ..album(n, ReleaseType::Album)
+        };
+        let mut shared = by(4, "Checked");
+        shared.other_artists = vec!["Not Checked".to_string()];
+        let previous = vec![
+            by(1, "Checked"),
+            by(2, "Not Checked"),
+            by(3, "Checked"),
+            shared,
+        ];
+        let found = vec![by(3, "Checked"), by(5, "Checked")];
+        let unchecked = vec!["not checked".to_string()];
+        let ids = |v: Vec<Album>| {
+            let mut ids = v.iter().map(|a| a.id.as_u128()).collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids
+        };
+        assert_eq!(
+            ids(next_previous(previous.clone(), &found, &unchecked, false)),
+            vec![3, 5]
+        );
+        assert_eq!(
+            ids(next_previous(previous, &found, &unchecked, true)),
+            vec![2, 3, 4, 5]
+        );
+    }
+}
@@ -0,0 +1,583 @@
+use anyhow::{anyhow, Context, Result};
+use directories::ProjectDirs;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+use time::OffsetDateTime;
+use time::{format_description, Date, UtcOffset};
+use uuid::Uuid;
+use yansi::Paint;
+
+use crate::activity::ActivitySettings;
+use crate::discography::{AnniversarySettings, GapSettings};
+use crate::env::{self, Override};
+use crate::formats::{self, FormatSettings};
+use crate::funkwhale::FunkwhaleSettings;
+use crate::healthcheck::HealthcheckSettings;
+use crate::hook::HookSettings;
+use crate::i18n::{self, tr, Language};
+use crate::links::LinkSettings;
+use crate::listenbrainz::ListenBrainzSettings;
+use crate::mpd::MpdSettings;
+use crate::notify::{HeldNotifications, NotificationSettings};
+use crate::preorder::{PreorderSettings, Reminder};
+use crate::queue::QueueSettings;
+use crate::remote::{self, RemoteSettings};
+use crate::responses::{Album, Artist, DateStrategy, ReleaseType};
+use crate::spotify::SpotifySettings;
+use crate::storage::{self, StorageFormat};
+use crate::theme::{self, ThemeSettings};
+
+pub const CHARS_TO_REMOVE: &[char; 5] = &['.', '&', '\'', '’', '/'];
+
+/// The form of an artist name we compare directory names, sort names and ignores in
+pub fn canonical_name(s: &str) -> String {
+    s.replace(CHARS_TO_REMOVE, "").to_lowercase()
+}
+
+/// Settings that change how we check for releases
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CheckSettings {
+    /// also report new releases that got added to release groups we already know
+    pub reissues: bool,
+    /// which date decides if a release is new
+    pub date_strategy: DateStrategy,
+    /// ISO 3166-1 country codes used by the `country` date strategy and the country filter
+    pub countries: Vec<String>,
+    /// only report releases that have a release event in one of the `countries`
+    pub country_filter: bool,
+    /// ISO 639-3 language codes, release groups with only releases in these languages are not reported
+    pub exclude_languages: Vec<String>,
+    /// ISO 15924 script codes, e.g., "Cyrl", release groups with only releases in these scripts are not reported
+    pub exclude_scripts: Vec<String>,
+    /// albums where no release has at least this many tracks are reported under others, 0 disables this
+    pub min_tracks: u32,
+    /// utc offset like "+02:00" that decides what today is. Uses the system offset if not set
+    pub utc_offset: Option<String>,
+    /// how many days before today we start the next check, to not miss releases that got added late
+    pub safety_margin_days: u16,
+    /// `new` within this many minutes of the last run does not check again, 0 disables this
+    pub cooldown_minutes: u32,
+    /// what `new` does within the cooldown
+    pub cooldown: Cooldown,
+    /// hours between the checks of the daemon, `--interval-hours` if not set
+    pub interval_hours: Option<u64>,
+    /// seconds fetching one artist may take before it is tried again at the end of the check, 0 disables this
+    pub artist_budget_secs: u64,
+}
+
+/// What `new` does when the last run is not longer ago than the cooldown
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Cooldown {
+    /// print the albums of the last run
+    #[default]
+    Cached,
+    /// fail unless `--force` is given
+    Refuse,
+}
+
+/// Which others, the albums with a secondary type like live or remix, we fetch and how we print them
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OthersSettings {
+    /// release types we do not fetch at all, like "Live"
+    pub skip: Vec<ReleaseType>,
+    /// release types that are only counted in the output
+    pub collapse: Vec<ReleaseType>,
+}
+
+impl Default for CheckSettings {
+    fn default() -> Self {
+        Self {
+            reissues: false,
+            date_strategy: DateStrategy::default(),
+            countries: vec![],
+            country_filter: false,
+            exclude_languages: vec![],
+            exclude_scripts: vec![],
+            min_tracks: 0,
+            utc_offset: None,
+            safety_margin_days: 1,
+            cooldown_minutes: 30,
+            cooldown: Cooldown::default(),
+            interval_hours: None,
+            artist_budget_secs: 0,
+        }
+    }
+}
+
+impl CheckSettings {
+    /// How long fetching one artist may take, none for no limit
+    pub fn artist_budget(&self) -> Option<std::time::Duration> {
+        (self.artist_budget_secs > 0)
+            .then(|| std::time::Duration::from_secs(self.artist_budget_secs))
+    }
+
+    /// The offset we use for today, the configured one, otherwise the system one and utc as the last fallback
+    fn offset(&self) -> UtcOffset {
+        let configured = self.utc_offset.as_ref().and_then(|s| {
+            let format =
+                format_description::parse("[offset_hour sign:mandatory]:[offset_minute]").ok()?;
+            UtcOffset::parse(s, &format).ok()
+        });
+        configured
+            .or_else(system_utc_offset)
+            .unwrap_or(UtcOffset::UTC)
+    }
+
+    /// the current time in the users time zone
+    pub fn now(&self) -> OffsetDateTime {
+        OffsetDateTime::now_utc().to_offset(self.offset())
+    }
+
+    /// today in the users time zone
+    pub fn today(&self) -> Date {
+        self.now().date()
+    }
+}
+
+/// The offset of the local time zone as the system reports it
+#[cfg(unix)]
+fn system_utc_offset() -> Option<UtcOffset> {
+    let now = OffsetDateTime::now_utc().unix_timestamp() as libc::time_t;
+    let mut tm = std::mem::MaybeUninit::<libc::tm>::uninit();
+    // SAFETY: `localtime_r` only writes into `tm` which we own, and we check for the error case before reading it
+    let tm = unsafe {
+        if libc::localtime_r(&now, tm.as_mut_ptr()).is_null() {
+            return None;
+        }
+        tm.assume_init()
+    };
+    UtcOffset::from_whole_seconds(tm.tm_gmtoff as i32).ok()
+}
+
+/// The offset of the local time zone as the system reports it
+#[cfg(not(unix))]
+fn system_utc_offset() -> Option<UtcOffset> {
+    None
+}
+
+/// User editable settings, in contrast to the machine state in the config
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// settings for checking new releases
+    pub check: CheckSettings,
+    /// which others we fetch and print
+    #[serde(default)]
+    pub others: OthersSettings,
+    /// settings for notifications
+    pub notifications: NotificationSettings,
+    /// remote storage of everything but these settings
+    pub remote: RemoteSettings,
+    /// how the config and the run history are written
+    pub storage: StorageFormat,
+    /// language of the messages, taken from the locale if not set
+    pub language: Option<Language>,
+    /// `time` format description for printing dates, e.g., "[day].[month].[year]". Depends on the language if not set
+    pub date_format: Option<String>,
+    /// colors and styles of the printed albums
+    pub theme: ThemeSettings,
+    /// reminders of album anniversaries in the digest
+    pub anniversaries: AnniversarySettings,
+    /// releases after a long break of the artist
+    pub gaps: GapSettings,
+    /// when artists count as dormant or defunct
+    pub activity: ActivitySettings,
+    /// the ListenBrainz account for the listen-later playlist
+    pub listenbrainz: ListenBrainzSettings,
+    /// the Spotify app and account for playlists
+    pub spotify: SpotifySettings,
+    /// streaming services we find links to new albums on
+    pub links: LinkSettings,
+    /// media formats of new albums
+    pub formats: FormatSettings,
+    #[serde(default)]
+    pub preorders: PreorderSettings,
+    #[serde(default)]
+    pub hook: HookSettings,
+    #[serde(default)]
+    pub funkwhale: FunkwhaleSettings,
+    /// the m3u8 queue file for a player that every check writes
+    #[serde(default)]
+    pub queue: QueueSettings,
+    /// the MPD server for `mpd queue-new`
+    #[serde(default)]
+    pub mpd: MpdSettings,
+    /// URLs pinged around every check for monitoring
+    #[serde(default)]
+    pub healthcheck: HealthcheckSettings,
+    /// settings of newer versions that we do not know, they are written back as they are
+    #[serde(flatten)]
+    pub unknown: Map<String, Value>,
+}
+
+/// The config struct
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Artists names only, gotten from the directory
+    pub artist_names: Vec<String>,
+    /// Artists we currently check
+    pub artist_full: Vec<Artist>,
+    /// last time we checked for new
+    pub last_checked_time: Date,
+    /// paths that we ignore
+    pub ignore_paths: Vec<String>,
+    /// previous new albums,
+    pub previous: Vec<Album>,
+    /// user settings
+    #[serde(default)]
+    pub settings: Settings,
+    /// number of releases we have seen in each release group, used for finding reissues
+    #[serde(default)]
+    pub release_counts: HashMap<Uuid, usize>,
+    /// albums we watch explicitly, for example from a search
+    #[serde(default)]
+    pub watched: Vec<Album>,
+    /// the day we last sent the notification for releases coming out today
+    #[serde(default)]
+    pub last_today_notification: Option<Date>,
+    /// anniversaries the digest already reminded of, by release group and years
+    #[serde(default)]
+    pub celebrated_anniversaries: HashSet<(Uuid, i32)>,
+    /// reminders of pre-orderable albums the daemon still has to send
+    #[serde(default)]
+    pub reminders: Vec<Reminder>,
+    /// release groups the hook command already got
+    #[serde(default)]
+    pub handed_off: HashSet<Uuid>,
+    /// notifications the daemon holds back for quiet hours or the daily digest
+    #[serde(default)]
+    pub held_notifications: HeldNotifications,
+    /// etag of the remote state we read, for detecting changes by other machines
+    #[serde(skip)]
+    pub remote_etag: Option<String>,
+    /// settings the environment overrides, they are not written to the file
+    #[serde(skip)]
+    pub env_overrides: Vec<Override>,
+    /// fields of newer versions that we do not know, so going back to an older version keeps them
+    #[serde(flatten)]
+    pub unknown: Map<String, Value>,
+}
+
+impl Default for Config {
+    /// default empty config
+    fn default() -> Self {
+        Self {
+            artist_full: vec![],
+            artist_names: vec![],
+            last_checked_time: CheckSettings::default().today(),
+            ignore_paths: vec![],
+            previous: vec![],
+            settings: Settings::default(),
+            release_counts: HashMap::new(),
+            watched: vec![],
+            last_today_notification: None,
+            celebrated_anniversaries: HashSet::new(),
+            reminders: vec![],
+            handed_off: HashSet::new(),
+            held_notifications: HeldNotifications::default(),
+            remote_etag: None,
+            env_overrides: vec![],
+            unknown: Map::new(),
+        }
+    }
+}
+
+/// The directories of this program
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("io", "narfinger.github", "musicbrainz-release-grabber")
+}
+
+/// The data dir for files besides the config, gets created if it does not exist
+pub fn data_dir() -> Result<PathBuf> {
+    let dir = project_dirs()
+        .ok_or_else(|| anyhow!("Could not find project dir"))?
+        .data_dir()
+        .to_path_buf();
+    if !dir.exists() {
+        fs::create_dir_all(&dir).context("Creating data dir")?;
+    }
+    Ok(dir)
+}
+
+/// The cache dir of the program, created if it does not exist
+pub fn cache_dir() -> Result<PathBuf> {
+    let dir = project_dirs()
+        .ok_or_else(|| anyhow!("Could not find project dir"))?
+        .cache_dir()
+        .to_path_buf();
+    if !dir.exists() {
+        fs::create_dir_all(&dir).context("Creating cache dir")?;
+    }
+    Ok(dir)
+}
+
+/// The name of the config file outside of profiles
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The profile whose config we read and write, none for the default config
+static PROFILE: RwLock<Option<String>> = RwLock::new(None);
+
+/// How a config that exists but cannot be read gets recovered
+type Recover = fn(&Path, anyhow::Error) -> Result<Config>;
+
+/// Set by the binary, which asks the user how to repair a broken config
+static RECOVER: OnceLock<Recover> = OnceLock::new();
+
+/// Recover configs that cannot be read with `recover`, without one reading them fails
+pub fn set_recovery(recover: Recover) {
+    let _ = RECOVER.set(recover);
+}
+
+/// Use the config of the profile `name` from now on, the default config for none or `default`
+pub fn set_profile(name: Option<&str>) -> Result<()> {
+    let name = name.filter(|n| *n != DEFAULT_PROFILE);
+    if let Some(n) = name.filter(|n| n.is_empty() || n.starts_with('.') || n.contains(['/', '\\']))
+    {
+        return Err(anyhow!("{} is not a valid profile name", n));
+    }
+    if let Ok(mut p) = PROFILE.write() {
+        *p = name.map(str::to_string);
+    }
+    Ok(())
+}
+
+/// The profile we use, none for the default config
+pub fn profile() -> Option<String> {
+    PROFILE.read().ok().and_then(|p| p.clone())
+}
+
+/// The config file of the default profile, `MBRG_CONFIG` or the one in the config dir
+fn default_config_path() -> Result<PathBuf> {
+    if let Some(path) = env::config_path() {
+        return Ok(path);
+    }
+    let mut path = project_dirs()
+        .ok_or_else(|| anyhow!("Could not find project dir"))?
+        .config_dir()
+        .to_path_buf();
+    path.push("config.json");
+    Ok(path)
+}
+
+/// The config file of a profile `name`, in `profiles` next to the default config
+fn profile_path(default: &Path, name: &str) -> PathBuf {
+    default
+        .parent()
+        .unwrap_or(Path::new(""))
+        .join("profiles")
+        .join(format!("{}.json", name))
+}
+
+/// Print the warning `msg` to stderr, every command reads the config and some print for scripts
+fn warn(msg: &str) {
+    eprintln!("{} {}", tr!("Warning:").yellow(), msg);
+}
+
+/// The config file of the current profile
+fn config_path() -> Result<PathBuf> {
+    let default = default_config_path()?;
+    Ok(match profile() {
+        Some(name) => profile_path(&default, &name),
+        None => default,
+    })
+}
+
+impl Config {
+    /// reads the config, with the settings from the environment on top
+    pub fn read() -> Result<Config> {
+        let path = config_path()?;
+        let mut local = match Config::read_from(&path) {
+            Ok(c) => c,
+            Err(e) if storage::exists(&path) => match RECOVER.get() {
+                Some(recover) => {
+                    let c = recover(&path, e)?;
+                    c.write_local()?;
+                    c
+                }
+                None => return Err(e),
+            },
+            Err(e) => return Err(e),
+        };
+        let (settings, overrides) = env::apply(&local.settings, std::env::vars())?;
+        local.settings = settings;
+        local.env_overrides = overrides;
+        i18n::set_language(local.settings.language);
+        // a broken date format or theme must not lock us out of the command that fixes it
+        if let Err(e) = i18n::set_date_format(local.settings.date_format.as_deref()) {
+            i18n::set_date_format(None)?;
+            let e = format!("{:#}", e);
+            warn(&tr!("{}, using the date format of the language", e));
+        }
+        formats::set_highlight(&local.settings.formats.highlight);
+        if let Err(e) = theme::set(&local.settings.theme) {
+            theme::set(&ThemeSettings::default())?;
+            let e = format!("{:#}", e);
+            warn(&tr!("{}, using the default theme", e));
+        }
+        match local.settings.remote.url.clone() {
+            Some(url) => local.with_remote(&url),
+            None => Ok(local),
+        }
+    }
+
+    /// reads a config from `path`, for example one from another machine
+    /// the config can be gzip compressed
+    pub fn read_from(path: &Path) -> Result<Config> {
+        storage::read_json(path).context("Could not read config")
+    }
+
+    /// The remote state at `url` with our local settings, the local config if there is no remote state yet
+    fn with_remote(self, url: &str) -> Result<Config> {
+        match remote::fetch(&self.settings.remote, url)? {
+            Some(state) => {
+                let mut c = serde_json::from_str::<Config>(&state.body)
+                    .context("Could not read remote state")?;
+                c.settings = self.settings;
+                c.env_overrides = self.env_overrides;
+                c.remote_etag = state.etag;
+                Ok(c)
+            }
+            None => Ok(self),
+        }
+    }
+
+    /// Writes a given config to file and to the remote if one is configured
+    pub fn write(&mut self) -> Result<()> {
+        self.write_local()?;
+        if let Some(url) = self.settings.remote.url.clone() {
+            // the settings contain the credentials and stay on this machine
+            let mut shared = serde_json::to_value(&self).context("JSON to value")?;
+            if let Some(o) = shared.as_object_mut() {
+                o.remove("settings");
+            }
+            let str = serde_json::to_string_pretty(&shared).context("JSON to string")?;
+            self.remote_etag = remote::store(&self.settings.remote, &url, str, &self.remote_etag)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a given config to the local file
+    fn write_local(&self) -> Result<()> {
+        let path = config_path()?;
+        if let Some(dir) = path
+            .parent()
+            .filter(|d| !d.as_os_str().is_empty() && !d.exists())
+        {
+            fs::create_dir_all(dir).context("Creating config dir")?;
+        }
+        let mut value = serde_json::to_value(self).context("JSON to value")?;
+        if let Some(settings) = value.get_mut("settings") {
+            env::restore(settings, &self.env_overrides);
+        }
+        storage::backup(&path)?;
+        storage::write_json(&path, &value, self.settings.storage)
+    }
+
+    // writes the config with time today (minus the safety margin)
+    pub fn now(&mut self) -> Result<()> {
+        let check = &self.settings.check;
+        self.last_checked_time =
+            check.today() - time::Duration::days(check.safety_margin_days.into());
+        self.write()
+    }
+
+    pub fn add_ignore(&mut self, p: PathBuf) -> Result<()> {
+        let s = canonical_name(&p.file_name().unwrap().to_string_lossy());
+        if self.ignore_paths.contains(&s) {
+            println!("{}", tr!("Ignore already in place"));
+        }
+        self.ignore_paths.push(s);
+        self.write()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Characters for random names, with the ones we remove and letters that keep their case mapping
+    const NAME_CHARS: &[char] = &[
+        'a', 'B', 'c', 'X', 'y', 'z', '0', '9', ' ', '-', '.', '&', '\'', '’', '/', 'é', 'Ö', 'ø',
+        'Å',
+    ];
+
+    #[test]
+    fn profiles_are_next_to_the_default_config() {
+        assert_eq!(
+            profile_path(Path::new("/home/me/.config/mbrg/config.json"), "radio"),
+            PathBuf::from("/home/me/.config/mbrg/profiles/radio.json")
+        );
+        assert!(set_profile(Some("../other")).is_err());
+        assert!(set_profile(Some("")).is_err());
+        set_profile(Some(DEFAULT_PROFILE)).unwrap();
+        assert_eq!(profile(), None);
+    }
+
+    #[test]
+    fn unknown_fields_are_written_back() {
+        let mut v = serde_json::to_value(Config::default()).unwrap();
+        v["from_the_future"] = serde_json::json!({"a": [1, 2]});
+        v["settings"]["future_setting"] = serde_json::json!(true);
+        let c: Config = serde_json::from_value(v.clone()).unwrap();
+        assert_eq!(c.unknown.len(), 1);
+        assert_eq!(c.settings.unknown.len(), 1);
+        assert_eq!(serde_json::to_value(&c).unwrap(), v);
+    }
+
+    fn random_name(rng: &mut fastrand::Rng) -> String {
+        (0..rng.usize(0..20))
+            .map(|_| NAME_CHARS[rng.usize(..NAME_CHARS.len())])
+            .collect()
+    }
+
+    #[test]
+    fn canonical_name_is_idempotent() {
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..1000 {
+            let name = random_name(&mut rng);
+            let once = canonical_name(&name);
+            assert_eq!(canonical_name(&once), once, "for {:?}", name);
+        }
+    }
+
+    #[test]
+    fn canonical_name_ignores_case_and_removed_chars() {
+        let mut rng = fastrand::Rng::with_seed(2);
+        for _ in 0..1000 {
+            let name = random_name(&mut rng);
+            let expected = canonical_name(&name);
+            assert_eq!(
+                canonical_name(&name.to_uppercase()),
+                expected,
+                "for {:?}",
+                name
+            );
+            assert_eq!(
+                canonical_name(&name.to_lowercase()),
+                expected,
+                "for {:?}",
+                name
+            );
+            let mut with_removed = String::new();
+            for c in name.chars() {
+                with_removed.push(c);
+                if rng.bool() {
+                    with_removed.push(CHARS_TO_REMOVE[rng.usize(..CHARS_TO_REMOVE.len())]);
+                }
+            }
+            assert_eq!(
+                canonical_name(&with_removed),
+                expected,
+                "for {:?}",
+                with_removed
+            );
+        }
+    }
+}
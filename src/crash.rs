@@ -0,0 +1,119 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::io::{IsTerminal, Write};
+use std::panic::PanicHookInfo;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+use crate::config::data_dir;
+use crate::i18n::tr;
+
+/// File name of the crash report in the data dir
+const CRASH_REPORT_FILE: &str = "crash.json";
+
+/// Shows the cursor again, progress bars and prompts hide it while they run
+const SHOW_CURSOR: &str = "\x1b[?25h";
+
+/// What we know about a panic
+#[derive(Debug, Serialize)]
+pub struct Crash {
+    version: &'static str,
+    message: String,
+    /// file, line and column of the panic
+    location: Option<String>,
+    /// the thread that panicked, workers of a check have no name
+    thread: Option<String>,
+    backtrace: String,
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+}
+
+/// The message of a panic payload, which is a `&str` or a `String` for `panic!` and `expect`
+fn payload_message(payload: &dyn Any) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+impl Crash {
+    /// The crash for the panic in `info`
+    fn new(info: &PanicHookInfo) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            message: payload_message(info.payload()),
+            location: info.location().map(|l| l.to_string()),
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: Backtrace::force_capture().to_string(),
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Write `crash` to the crash report in the data dir and return its path
+fn write(crash: &Crash) -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(CRASH_REPORT_FILE);
+    let s = serde_json::to_string_pretty(crash).context("JSON to string")?;
+    fs::write(&path, s).context("Writing crash report")?;
+    Ok(path)
+}
+
+/// Give the terminal back the way we found it
+fn restore_terminal() {
+    let mut stderr = std::io::stderr();
+    if stderr.is_terminal() {
+        let _ = write!(stderr, "\r\n{}", SHOW_CURSOR);
+        let _ = stderr.flush();
+    }
+}
+
+/// Replace the default panic message with a short report, the details go to the crash report
+pub fn install() {
+    std::panic::set_hook(Box::new(|info| {
+        restore_terminal();
+        let crash = Crash::new(info);
+        eprintln!(
+            "{}",
+            tr!("musicbrainz-release-grabber crashed: {}", crash.message)
+        );
+        match write(&crash) {
+            Ok(path) => eprintln!("{}", tr!("Wrote the crash report to {}", path.display())),
+            Err(e) => {
+                eprintln!(
+                    "{}",
+                    tr!("Could not write the crash report: {}", format!("{:#}", e))
+                );
+                eprintln!("{}", crash.backtrace);
+            }
+        }
+        eprintln!(
+            "{}",
+            tr!(
+                "Please open an issue at {} and attach the crash report and errors.json if there is one",
+                concat!(env!("CARGO_PKG_REPOSITORY"), "/issues")
+            )
+        );
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn panic_messages_are_found_in_the_payload() {
+        let literal = std::panic::catch_unwind(|| panic!("literal")).unwrap_err();
+        assert_eq!(payload_message(literal.as_ref()), "literal");
+        let formatted = std::panic::catch_unwind(|| panic!("{} formatted", 1)).unwrap_err();
+        assert_eq!(payload_message(formatted.as_ref()), "1 formatted");
+        let other = std::panic::catch_unwind(|| std::panic::panic_any(1u8)).unwrap_err();
+        assert_eq!(payload_message(other.as_ref()), "unknown panic");
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use uuid::Uuid;
+use yansi::Paint;
+
+use crate::config::Config;
+use crate::options;
+use crate::responses::Artist;
+
+/// A field that differs between two json values, with the dotted path to it
+#[derive(Debug, PartialEq)]
+struct Change {
+    path: String,
+    ours: Option<Value>,
+    theirs: Option<Value>,
+}
+
+/// All fields that are different in `ours` and `theirs`, objects are compared field by field
+fn value_changes(path: &str, ours: Option<&Value>, theirs: Option<&Value>, out: &mut Vec<Change>) {
+    match (ours, theirs) {
+        (Some(Value::Object(a)), Some(Value::Object(b))) => {
+            let keys = a
+                .keys()
+                .chain(b.keys())
+                .collect::<std::collections::BTreeSet<&String>>();
+            for k in keys {
+                let path = if path.is_empty() {
+                    k.to_string()
+                } else {
+                    format!("{}.{}", path, k)
+                };
+                value_changes(&path, a.get(k), b.get(k), out);
+            }
+        }
+        (a, b) if a != b => out.push(Change {
+            path: path.to_string(),
+            ours: a.cloned(),
+            theirs: b.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// The changed fields between two serializable things
+fn changes<T: Serialize>(ours: &T, theirs: &T) -> Result<Vec<Change>> {
+    let ours = serde_json::to_value(ours).context("JSON to value")?;
+    let theirs = serde_json::to_value(theirs).context("JSON to value")?;
+    let mut out = Vec::new();
+    value_changes("", Some(&ours), Some(&theirs), &mut out);
+    Ok(out)
+}
+
+/// The value at `path` for printing, missing values are shown as "-" and secrets are hidden
+fn show(path: &str, v: &Option<Value>) -> String {
+    v.as_ref()
+        .map(|v| options::show(path, v))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Print the names of `artists` with a header, nothing if there are none
+fn print_artists(header: &str, artists: &[&Artist]) {
+    if !artists.is_empty() {
+        println!("{} ({})", header, artists.len());
+        for a in artists {
+            println!("  {} ({})", a.name, a.id);
+        }
+    }
+}
+
+/// Print the differences of the config `ours` and the config `theirs` that was read from `name`
+pub fn print_diff(ours: &Config, theirs: &Config, name: &str) -> Result<()> {
+    let our_artists: BTreeMap<Uuid, &Artist> = ours.artist_full.iter().map(|a| (a.id, a)).collect();
+    let their_artists: BTreeMap<Uuid, &Artist> =
+        theirs.artist_full.iter().map(|a| (a.id, a)).collect();
+
+    let only_ours = our_artists
+        .iter()
+        .filter(|(id, _)| !their_artists.contains_key(id))
+        .map(|(_, a)| *a)
+        .collect::<Vec<&Artist>>();
+    let only_theirs = their_artists
+        .iter()
+        .filter(|(id, _)| !our_artists.contains_key(id))
+        .map(|(_, a)| *a)
+        .collect::<Vec<&Artist>>();
+    print_artists(&"Artists only in this config".red().to_string(), &only_ours);
+    print_artists(
+        &format!("Artists only in {}", name).green().to_string(),
+        &only_theirs,
+    );
+
+    let mut header = false;
+    for (id, a) in &our_artists {
+        if let Some(b) = their_artists.get(id) {
+            let changes = changes(a, b)?;
+            if changes.is_empty() {
+                continue;
+            }
+            if !header {
+                println!("{}", "Changed artists".yellow());
+                header = true;
+            }
+            println!("  {}", a.name);
+            for c in changes {
+                println!(
+                    "    {}: {} -> {}",
+                    c.path,
+                    show(&c.path, &c.ours).red(),
+                    show(&c.path, &c.theirs).green()
+                );
+            }
+        }
+    }
+
+    let settings = changes(&ours.settings, &theirs.settings)?;
+    if !settings.is_empty() {
+        println!("{}", "Changed settings".yellow());
+        for c in settings {
+            println!(
+                "  {}: {} -> {}",
+                c.path,
+                show(&c.path, &c.ours).red(),
+                show(&c.path, &c.theirs).green()
+            );
+        }
+    }
+
+    let our_ignores = ours.ignore_paths.iter().collect::<HashSet<&String>>();
+    let their_ignores = theirs.ignore_paths.iter().collect::<HashSet<&String>>();
+    for i in our_ignores.difference(&their_ignores) {
+        println!("{} {}", "Ignored only in this config:".red(), i);
+    }
+    for i in their_ignores.difference(&our_ignores) {
+        println!("{} {}", format!("Ignored only in {}:", name).green(), i);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn value_changes_walks_objects() {
+        let ours = json!({"check": {"reissues": false, "countries": ["DE"]}, "old": 1});
+        let theirs = json!({"check": {"reissues": true, "countries": ["DE"]}, "new": 2});
+        let mut out = Vec::new();
+        value_changes("", Some(&ours), Some(&theirs), &mut out);
+        assert_eq!(
+            out,
+            vec![
+                Change {
+                    path: "check.reissues".to_string(),
+                    ours: Some(json!(false)),
+                    theirs: Some(json!(true)),
+                },
+                Change {
+                    path: "new".to_string(),
+                    ours: None,
+                    theirs: Some(json!(2)),
+                },
+                Change {
+                    path: "old".to_string(),
+                    ours: Some(json!(1)),
+                    theirs: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn changed_secrets_are_hidden() {
+        let value = Some(json!("hunter2"));
+        assert_eq!(show("webdav.password", &value), "***");
+        assert_eq!(show("webdav.password", &None), "-");
+        assert_eq!(show("webdav.url", &value), "\"hunter2\"");
+    }
+}
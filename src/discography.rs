@@ -0,0 +1,237 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::PathBuf;
+use time::Date;
+use uuid::Uuid;
+
+use crate::config::data_dir;
+use crate::responses::{Album, ReleaseType};
+use crate::storage::{self, StorageFormat};
+
+/// File name of the discography cache in the data dir
+const DISCOGRAPHY_FILE: &str = "discographies.json";
+
+/// The albums of every artist by name, from the last time we fetched them
+pub type Discographies = BTreeMap<String, Vec<Album>>;
+
+/// The path of the discography cache
+fn cache_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(DISCOGRAPHY_FILE);
+    Ok(path)
+}
+
+/// The cached discographies, empty if we never fetched any
+pub fn read() -> Result<Discographies> {
+    let path = cache_path()?;
+    if !storage::exists(&path) {
+        return Ok(Discographies::new());
+    }
+    storage::read_json(&path).context("Could not read discography cache")
+}
+
+/// Replace the cached discographies of the artists in `albums`, all other artists keep theirs
+pub fn update(albums: &[Album], format: StorageFormat) -> Result<()> {
+    let mut cache = read()?;
+    let mut fetched = Discographies::new();
+    for a in albums {
+        fetched.entry(a.artist.clone()).or_default().push(a.clone());
+    }
+    cache.extend(fetched);
+    storage::write_json(&cache_path()?, &cache, format).context("Writing discography cache")
+}
+
+/// Settings for pointing out releases after a long break of the artist
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GapSettings {
+    /// a release this many years after the previous one of the artist is marked, 0 disables this
+    pub min_years: u16,
+    /// print the marked releases first, the longest break first
+    pub sort_first: bool,
+}
+
+impl Default for GapSettings {
+    fn default() -> Self {
+        Self {
+            min_years: 3,
+            sort_first: false,
+        }
+    }
+}
+
+/// Whole years between `album` and the release of its artist before it in `discographies`
+pub fn years_since_previous(discographies: &Discographies, album: &Album) -> Option<u16> {
+    let date = album.date?;
+    let previous = discographies
+        .get(&album.artist)?
+        .iter()
+        .filter(|a| a.id != album.id)
+        .filter_map(|a| a.date)
+        .filter(|d| *d < date)
+        .max()?;
+    let mut years = date.year() - previous.year();
+    if (date.month() as u8, date.day()) < (previous.month() as u8, previous.day()) {
+        years -= 1;
+    }
+    u16::try_from(years).ok()
+}
+
+/// Mark the `albums` that come after a break of at least `settings.min_years`,
+/// and bring them to the front if `settings.sort_first`
+pub fn mark_gaps(discographies: &Discographies, albums: &mut [Album], settings: &GapSettings) {
+    if settings.min_years == 0 {
+        return;
+    }
+    for a in albums.iter_mut() {
+        a.gap_years = years_since_previous(discographies, a).filter(|y| *y >= settings.min_years);
+    }
+    if settings.sort_first {
+        albums.sort_by_key(|a| std::cmp::Reverse(a.gap_years));
+    }
+}
+
+/// Settings for reminding of album anniversaries in the digest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnniversarySettings {
+    pub enabled: bool,
+    /// the anniversaries we remind of
+    pub years: Vec<i32>,
+}
+
+impl Default for AnniversarySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            years: vec![10, 20, 25],
+        }
+    }
+}
+
+/// The day `d` is `years` years ago, the 29th of February is celebrated on the 28th in other years
+fn anniversary(d: Date, years: i32) -> Option<Date> {
+    let year = d.year() + years;
+    d.replace_year(year)
+        .ok()
+        .or_else(|| d.previous_day()?.replace_year(year).ok())
+}
+
+/// Albums that have one of the `years` anniversaries between `from` and `to`, with the years.
+/// Only plain albums count, and none in `celebrated`
+pub fn anniversaries(
+    discographies: &Discographies,
+    years: &[i32],
+    from: Date,
+    to: Date,
+    celebrated: &HashSet<(Uuid, i32)>,
+) -> Vec<(i32, Album)> {
+    let mut seen = HashSet::new();
+    let mut res = discographies
+        .values()
+        .flatten()
+        .filter(|a| a.release_type == ReleaseType::Album)
+        .flat_map(|a| {
+            years.iter().filter_map(move |y| {
+                let day = anniversary(a.date?, *y)?;
+                (day >= from && day <= to).then(|| (*y, a.clone()))
+            })
+        })
+        .filter(|(y, a)| !celebrated.contains(&(a.id, *y)) && seen.insert((a.id, *y)))
+        .collect::<Vec<(i32, Album)>>();
+    res.sort_unstable();
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::Month;
+
+    fn date(year: i32, month: Month, day: u8) -> Date {
+        Date::from_calendar_date(year, month, day).unwrap()
+    }
+
+    fn album(n: u128, release_type: ReleaseType, d: Date) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "Artist".to_string(),
+            title: format!("Album {}", n),
+            date: Some(d),
+            release_type,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn anniversaries_in_range_fire_once() {
+        let mut discographies = Discographies::new();
+        discographies.insert(
+            "Artist".to_string(),
+            vec![
+                album(1, ReleaseType::Album, date(2005, Month::March, 2)),
+                album(2, ReleaseType::Live, date(2005, Month::March, 2)),
+                album(3, ReleaseType::Album, date(2004, Month::February, 29)),
+                album(4, ReleaseType::Album, date(2001, Month::March, 2)),
+            ],
+        );
+        let from = date(2025, Month::February, 26);
+        let to = date(2025, Month::March, 4);
+        let found = anniversaries(&discographies, &[10, 20, 21], from, to, &HashSet::new());
+        let found = found
+            .iter()
+            .map(|(y, a)| (*y, a.id.as_u128()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![(20, 1), (21, 3)]);
+
+        let to = date(2024, Month::March, 4);
+        let from = date(2024, Month::February, 26);
+        let found = anniversaries(&discographies, &[20], from, to, &HashSet::new());
+        assert_eq!(found.len(), 1);
+        let celebrated = HashSet::from([(Uuid::from_u128(3), 20)]);
+        assert!(anniversaries(&discographies, &[20], from, to, &celebrated).is_empty());
+    }
+
+    #[test]
+    fn leap_day_anniversary() {
+        assert_eq!(
+            anniversary(date(2004, Month::February, 29), 10),
+            Some(date(2014, Month::February, 28))
+        );
+        assert_eq!(
+            anniversary(date(2004, Month::February, 29), 20),
+            Some(date(2024, Month::February, 29))
+        );
+    }
+
+    #[test]
+    fn long_breaks_are_marked() {
+        let mut discographies = Discographies::new();
+        discographies.insert(
+            "Artist".to_string(),
+            vec![
+                album(1, ReleaseType::Album, date(2010, Month::May, 1)),
+                album(2, ReleaseType::Album, date(2016, Month::June, 1)),
+                album(3, ReleaseType::Album, date(2024, Month::May, 31)),
+            ],
+        );
+        let mut new = vec![
+            album(4, ReleaseType::Album, date(2016, Month::July, 1)),
+            album(3, ReleaseType::Album, date(2024, Month::May, 31)),
+            album(5, ReleaseType::Album, date(2009, Month::January, 1)),
+        ];
+        let settings = GapSettings {
+            min_years: 3,
+            sort_first: true,
+        };
+        mark_gaps(&discographies, &mut new, &settings);
+        let marked = new
+            .iter()
+            .map(|a| (a.id.as_u128(), a.gap_years))
+            .collect::<Vec<_>>();
+        // 2016-06-01 to 2024-05-31 is not quite eight years
+        assert_eq!(marked, vec![(3, Some(7)), (4, None), (5, None)]);
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::Write;
+use std::process::Command;
+
+use crate::config::Config;
+use crate::i18n::tr;
+use crate::prompt;
+
+/// Parts of the config that can be edited
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditSection {
+    /// the artists we check
+    Artists,
+    /// the user settings
+    Settings,
+}
+
+/// The editor of the user from `$VISUAL` or `$EDITOR`, with its arguments
+fn editor() -> Result<Vec<String>> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let words = shell_words::split(&editor).context("Could not parse $EDITOR")?;
+    if words.is_empty() {
+        Err(anyhow!("$EDITOR is empty"))
+    } else {
+        Ok(words)
+    }
+}
+
+/// Open `value` as json in the editor until it parses or the user gives up. Returns `None` if the user gave up
+fn edit_value<T: Serialize + DeserializeOwned>(value: &T) -> Result<Option<T>> {
+    prompt::require_terminal("edit")?;
+    let mut file = tempfile::Builder::new()
+        .prefix("musicbrainz-release-grabber-")
+        .suffix(".json")
+        .tempfile()
+        .context("Creating temporary file")?;
+    file.write_all(
+        serde_json::to_string_pretty(value)
+            .context("JSON to string")?
+            .as_bytes(),
+    )
+    .context("Writing temporary file")?;
+    let editor = editor()?;
+    loop {
+        let status = Command::new(&editor[0])
+            .args(&editor[1..])
+            .arg(file.path())
+            .status()
+            .with_context(|| format!("Could not start editor {}", editor[0]))?;
+        if !status.success() {
+            return Err(anyhow!("Editor exited with {}", status));
+        }
+        let s = std::fs::read_to_string(file.path()).context("Reading temporary file")?;
+        match serde_json::from_str::<T>(&s) {
+            Ok(v) => return Ok(Some(v)),
+            Err(e) => {
+                println!("{}", tr!("The edited file is not valid: {}", e));
+                if !prompt::ask(tr!("Edit again? Otherwise all changes are lost"), true)? {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Edit a section of the config in the editor and write it back if it is valid
+pub fn edit(c: &mut Config, section: EditSection) -> Result<()> {
+    let changed = match section {
+        EditSection::Artists => edit_value(&c.artist_full)?.map(|mut artists| {
+            artists.sort_unstable();
+            c.artist_full = artists;
+        }),
+        EditSection::Settings => edit_value(&c.settings)?.map(|settings| c.settings = settings),
+    };
+    if changed.is_some() {
+        c.write()?;
+        println!("{}", tr!("Config written"));
+    } else {
+        println!("{}", tr!("Config unchanged"));
+    }
+    Ok(())
+}
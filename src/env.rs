@@ -0,0 +1,174 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use std::path::PathBuf;
+
+use crate::config::Settings;
+use crate::options;
+
+/// Every environment variable of ours starts with this
+const PREFIX: &str = "MBRG_";
+
+/// A setting that an environment variable overrides, the file keeps its own value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Override {
+    /// the setting, like `check.min_tracks`
+    pub key: String,
+    /// the value in the config file
+    pub file: Value,
+    /// the value from the environment
+    pub env: Value,
+}
+
+/// The environment variable `MBRG_<name>` if it is set and not empty
+fn var(name: &str) -> Option<String> {
+    std::env::var(format!("{}{}", PREFIX, name))
+        .ok()
+        .filter(|v| !v.is_empty())
+}
+
+/// `MBRG_CONFIG`, the config file to use instead of the one in the config dir
+pub fn config_path() -> Option<PathBuf> {
+    var("CONFIG").map(PathBuf::from)
+}
+
+/// `MBRG_SERVER`, a musicbrainz server to use instead of musicbrainz.org, like a local mirror
+pub fn server() -> Option<String> {
+    var("SERVER").map(|s| s.trim_end_matches('/').to_string())
+}
+
+/// `MBRG_RATE_LIMIT`, how many requests we send in five seconds
+pub fn rate_limit() -> Result<Option<u64>> {
+    var("RATE_LIMIT")
+        .map(|s| {
+            s.parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .with_context(|| format!("{}RATE_LIMIT has to be a positive number", PREFIX))
+        })
+        .transpose()
+}
+
+/// `MBRG_CACHE_MINUTES`, how long release group responses are cached on disk, not at all if not set
+pub fn cache_minutes() -> Result<Option<u64>> {
+    var("CACHE_MINUTES")
+        .map(|s| {
+            s.parse()
+                .ok()
+                .filter(|n| *n > 0)
+                .with_context(|| format!("{}CACHE_MINUTES has to be a positive number", PREFIX))
+        })
+        .transpose()
+}
+
+/// `MBRG_ADAPTIVE_RATE`, if the rate limit adapts to the throttles of musicbrainz
+pub fn adaptive_rate() -> Result<bool> {
+    match var("ADAPTIVE_RATE").as_deref() {
+        None | Some("0") | Some("false") | Some("no") => Ok(false),
+        Some("1") | Some("true") | Some("yes") => Ok(true),
+        Some(other) => Err(anyhow!(
+            "{}ADAPTIVE_RATE has to be true or false, not {}",
+            PREFIX,
+            other
+        )),
+    }
+}
+
+/// Turn colors on or off with `MBRG_COLOR` set to `always` or `never`
+pub fn apply_color() -> Result<()> {
+    match var("COLOR").as_deref() {
+        None | Some("auto") => {}
+        Some("always") => yansi::enable(),
+        Some("never") => yansi::disable(),
+        Some(other) => {
+            return Err(anyhow!(
+                "{}COLOR has to be always, never or auto, not {}",
+                PREFIX,
+                other
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// The setting an environment variable `name` is for, `MBRG_CHECK__MIN_TRACKS` is `check.min_tracks`
+fn setting_of(name: &str) -> Option<String> {
+    let key = name.strip_prefix(PREFIX)?.to_lowercase().replace("__", ".");
+    options::setting_key(&key).ok().map(|k| k.to_string())
+}
+
+/// `settings` with the settings from the environment `vars`, and what they override.
+/// Variables for other things, like those the hook command gets, are ignored
+pub fn apply(
+    settings: &Settings,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<(Settings, Vec<Override>)> {
+    let mut vars = vars
+        .filter_map(|(name, value)| setting_of(&name).map(|key| (name, key, value)))
+        .collect::<Vec<_>>();
+    // sections first, so a single setting in them wins
+    vars.sort_unstable_by_key(|(_, key, _)| key.matches('.').count());
+    let mut res = settings.clone();
+    let mut overrides = Vec::new();
+    for (name, key, value) in vars {
+        let file = options::get(settings, &key)?;
+        res = options::set(&res, &key, &value).with_context(|| format!("In {}", name))?;
+        overrides.push(Override {
+            env: options::get(&res, &key)?,
+            key,
+            file,
+        });
+    }
+    Ok((res, overrides))
+}
+
+/// Put the values of the config file back into `settings`, a config as json, unless they changed since
+pub fn restore(settings: &mut Value, overrides: &[Override]) {
+    for o in overrides {
+        let pointer = format!("/{}", o.key.replace('.', "/"));
+        if let Some(v) = settings.pointer_mut(&pointer) {
+            if *v == o.env {
+                *v = o.file.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(v: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        v.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    #[test]
+    fn environment_overrides_settings() {
+        let settings = Settings::default();
+        let (res, overrides) = apply(
+            &settings,
+            vars(&[
+                ("MBRG_CHECK__MIN_TRACKS", "5"),
+                ("MBRG_NOTIFICATIONS__COMMAND", "notify-send"),
+                ("MBRG_ALBUM", "from the hook"),
+                ("PATH", "/bin"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!(res.check.min_tracks, 5);
+        assert_eq!(res.notifications.command, vec!["notify-send"]);
+        assert_eq!(overrides.len(), 2);
+        assert!(apply(&settings, vars(&[("MBRG_CHECK__MIN_TRACKS", "x")])).is_err());
+
+        // the file keeps its values unless they were changed
+        let mut changed = res.clone();
+        changed.notifications.command = vec!["other".to_string()];
+        let mut v = serde_json::to_value(&changed).unwrap();
+        restore(&mut v, &overrides);
+        let written: Settings = serde_json::from_value(v).unwrap();
+        assert_eq!(written.check.min_tracks, 0);
+        assert_eq!(written.notifications.command, vec!["other"]);
+    }
+}
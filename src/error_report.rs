@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use time::OffsetDateTime;
+
+use crate::config::data_dir;
+use crate::responses;
+
+/// File name of the error report in the data dir
+const ERROR_REPORT_FILE: &str = "errors.json";
+
+/// Kind of an error in the error report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorKind {
+    /// the artist or release does not exist in musicbrainz
+    NotFound,
+    /// musicbrainz throttled us
+    RateLimited,
+    /// musicbrainz had an internal error
+    ServerError,
+    /// we could not talk to musicbrainz at all
+    Network,
+    /// the response was not what we expected
+    Decode,
+    /// the artist took longer than its time budget
+    OverBudget,
+    /// everything else
+    Other,
+}
+
+/// One failure of a run
+#[derive(Debug, Serialize)]
+pub struct ErrorEntry {
+    /// the artist the error happened for
+    artist: String,
+    kind: ErrorKind,
+    /// the http status code if we got a response
+    http_status: Option<u16>,
+    /// the complete error message
+    message: String,
+    #[serde(with = "time::serde::rfc3339")]
+    timestamp: OffsetDateTime,
+}
+
+impl ErrorEntry {
+    /// Create an entry for the error `e` that happened for `artist`
+    pub fn new(artist: &str, e: &anyhow::Error) -> Self {
+        let (kind, http_status) = classify(e);
+        Self {
+            artist: artist.to_owned(),
+            kind,
+            http_status,
+            message: format!("{:#}", e),
+            timestamp: OffsetDateTime::now_utc(),
+        }
+    }
+}
+
+/// Find the kind and status code of an error by looking at its causes
+fn classify(e: &anyhow::Error) -> (ErrorKind, Option<u16>) {
+    for cause in e.chain() {
+        if let Some(mb) = cause.downcast_ref::<responses::MbError>() {
+            let kind = match mb {
+                responses::MbError::NotFound(_) => ErrorKind::NotFound,
+                responses::MbError::RateLimited => ErrorKind::RateLimited,
+                responses::MbError::Decode(_) => ErrorKind::Decode,
+                responses::MbError::Network(_) => ErrorKind::Network,
+                responses::MbError::ServerError(_) => ErrorKind::ServerError,
+                responses::MbError::Replay(_) => ErrorKind::Other,
+                responses::MbError::OverBudget(_) => ErrorKind::OverBudget,
+            };
+            return (kind, mb.status());
+        }
+        if let Some(re) = cause.downcast_ref::<reqwest::Error>() {
+            let status = re.status().map(|s| s.as_u16());
+            let kind = match status {
+                Some(404) => ErrorKind::NotFound,
+                Some(429) | Some(503) => ErrorKind::RateLimited,
+                Some(s) if s >= 500 => ErrorKind::ServerError,
+                Some(_) => ErrorKind::Other,
+                None if re.is_decode() => ErrorKind::Decode,
+                None => ErrorKind::Network,
+            };
+            return (kind, status);
+        }
+        if cause.downcast_ref::<serde_json::Error>().is_some() {
+            return (ErrorKind::Decode, None);
+        }
+    }
+    (ErrorKind::Other, None)
+}
+
+/// Write the errors of a run to the error report in the data dir and return its path.
+/// Without errors an old report gets removed so it never describes an earlier run.
+pub fn write_error_report(entries: &[ErrorEntry]) -> Result<Option<PathBuf>> {
+    let mut path = data_dir()?;
+    path.push(ERROR_REPORT_FILE);
+    if entries.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).context("Removing old error report")?;
+        }
+        Ok(None)
+    } else {
+        let s = serde_json::to_string_pretty(entries).context("JSON to string")?;
+        fs::write(&path, s).context("Writing error report")?;
+        Ok(Some(path))
+    }
+}
@@ -0,0 +1,232 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::RwLock;
+use time::Date;
+use uuid::Uuid;
+use yansi::Paint;
+
+use crate::config::data_dir;
+use crate::responses::{GroupReleases, MbClient, MbError};
+use crate::storage::{self, StorageFormat};
+
+/// File name of the format cache in the data dir
+const FORMATS_FILE: &str = "formats.json";
+/// Days we trust cached formats, other editions often get announced after the first one
+const CACHE_DAYS: i64 = 7;
+
+/// The kinds of media collectors care about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MediaFormat {
+    Vinyl,
+    CD,
+    Cassette,
+    Digital,
+    Other,
+}
+
+impl MediaFormat {
+    /// The kind of a musicbrainz format like "12\" Vinyl" or "Enhanced CD"
+    pub fn classify(format: &str) -> Self {
+        let f = format.to_lowercase();
+        if f.contains("vinyl") || f == "flexi-disc" {
+            MediaFormat::Vinyl
+        } else if f.contains("cassette") {
+            MediaFormat::Cassette
+        } else if f == "digital media" {
+            MediaFormat::Digital
+        } else if f
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|w| w.ends_with("cd"))
+        {
+            MediaFormat::CD
+        } else {
+            MediaFormat::Other
+        }
+    }
+}
+
+impl fmt::Display for MediaFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Settings for the media formats of new albums
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FormatSettings {
+    /// fetch the formats of new albums even without `require` or `highlight`
+    pub enabled: bool,
+    /// only keep new albums that have one of these, albums we could not get the formats of are kept
+    pub require: Vec<MediaFormat>,
+    /// formats that stand out in the output
+    pub highlight: Vec<MediaFormat>,
+}
+
+impl FormatSettings {
+    /// Do we need the formats of new albums
+    pub fn fetch(&self) -> bool {
+        self.enabled || !self.require.is_empty() || !self.highlight.is_empty()
+    }
+
+    /// Do `formats` satisfy `require`
+    pub fn keeps(&self, formats: &BTreeSet<MediaFormat>) -> bool {
+        self.require.is_empty()
+            || formats.is_empty()
+            || self.require.iter().any(|f| formats.contains(f))
+    }
+}
+
+/// The formats that stand out in the output, from the settings
+static HIGHLIGHT: RwLock<Vec<MediaFormat>> = RwLock::new(Vec::new());
+
+/// Highlight `formats` in the output
+pub fn set_highlight(formats: &[MediaFormat]) {
+    if let Ok(mut h) = HIGHLIGHT.write() {
+        *h = formats.to_vec();
+    }
+}
+
+/// The `formats` for printing like " [CD, Vinyl]", empty without formats.
+/// With `paint` the highlighted ones are bold for the terminal
+pub fn describe(formats: &BTreeSet<MediaFormat>, paint: bool) -> String {
+    if formats.is_empty() {
+        return String::new();
+    }
+    let highlight = HIGHLIGHT.read().map(|h| h.clone()).unwrap_or_default();
+    let names = formats
+        .iter()
+        .map(|f| {
+            if paint && highlight.contains(f) {
+                f.to_string().magenta().bold().to_string()
+            } else {
+                f.to_string()
+            }
+        })
+        .collect::<Vec<String>>();
+    format!(" [{}]", names.join(", "))
+}
+
+/// Formats of a release group and when we got them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedFormats {
+    pub checked: Date,
+    pub formats: BTreeSet<MediaFormat>,
+}
+
+/// The formats of release groups by id
+pub type FormatCache = HashMap<Uuid, CachedFormats>;
+
+/// The path of the format cache
+fn cache_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(FORMATS_FILE);
+    Ok(path)
+}
+
+/// The cached formats, empty if we never fetched any
+pub fn read() -> Result<FormatCache> {
+    let path = cache_path()?;
+    if !storage::exists(&path) {
+        return Ok(FormatCache::new());
+    }
+    storage::read_json(&path).context("Could not read format cache")
+}
+
+/// Write the format cache, entries older than the cache time are dropped
+pub fn write(cache: &mut FormatCache, today: Date, format: StorageFormat) -> Result<()> {
+    cache.retain(|_, c| (today - c.checked).whole_days() < CACHE_DAYS);
+    storage::write_json(&cache_path()?, cache, format).context("Writing format cache")
+}
+
+/// The formats of the releases of the release group with `id`, from the `cache` if they are recent enough
+pub fn formats_of(
+    cache: &mut FormatCache,
+    group_releases: &mut GroupReleases,
+    mb: &MbClient,
+    id: &Uuid,
+    today: Date,
+) -> Result<BTreeSet<MediaFormat>, MbError> {
+    if let Some(c) = cache.get(id) {
+        if (today - c.checked).whole_days() < CACHE_DAYS {
+            return Ok(c.formats.clone());
+        }
+    }
+    let formats = group_releases
+        .get(mb, id)?
+        .iter()
+        .flat_map(|r| r.formats())
+        .map(MediaFormat::classify)
+        .collect::<BTreeSet<MediaFormat>>();
+    cache.insert(
+        *id,
+        CachedFormats {
+            checked: today,
+            formats: formats.clone(),
+        },
+    );
+    Ok(formats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use time::Month;
+
+    #[test]
+    fn classify_musicbrainz_formats() {
+        let kinds = [
+            "12\" Vinyl",
+            "Vinyl",
+            "Flexi-disc",
+            "CD",
+            "Enhanced CD",
+            "Hybrid SACD",
+            "SHM-CD",
+            "Cassette",
+            "Digital Media",
+            "DVD-Video",
+            "Blu-ray",
+        ]
+        .map(MediaFormat::classify);
+        use MediaFormat::*;
+        assert_eq!(
+            kinds,
+            [Vinyl, Vinyl, Vinyl, CD, CD, CD, CD, Cassette, Digital, Other, Other]
+        );
+    }
+
+    #[test]
+    fn formats_are_cached() {
+        let body = r#"{"release-count": 2, "releases": [
+            {"id": "00000000-0000-0000-0000-000000000002", "title": "A", "media": [{"format": "CD", "track-count": 9}]},
+            {"id": "00000000-0000-0000-0000-000000000003", "title": "A", "media": [{"format": "12\" Vinyl", "track-count": 5}, {"format": "12\" Vinyl", "track-count": 4}]}
+        ]}"#;
+        let server = MockServer::start(vec![(200, body.to_string())]);
+        let mb = server.client();
+        let today = Date::from_calendar_date(2025, Month::March, 1).unwrap();
+        let id = Uuid::from_u128(1);
+        let mut cache = FormatCache::new();
+        let expected = BTreeSet::from([MediaFormat::Vinyl, MediaFormat::CD]);
+        let formats = formats_of(&mut cache, &mut GroupReleases::default(), &mb, &id, today);
+        assert_eq!(formats.unwrap(), expected);
+        // a new run has no releases in memory yet, the cache answers without a request
+        let later = today + time::Duration::days(2);
+        let formats = formats_of(&mut cache, &mut GroupReleases::default(), &mb, &id, later);
+        assert_eq!(formats.unwrap(), expected);
+        assert_eq!(server.requests().len(), 1);
+
+        let settings = FormatSettings {
+            require: vec![MediaFormat::Vinyl],
+            ..Default::default()
+        };
+        assert!(settings.keeps(&expected));
+        assert!(!settings.keeps(&BTreeSet::from([MediaFormat::Digital])));
+        assert!(settings.keeps(&BTreeSet::new()));
+    }
+}
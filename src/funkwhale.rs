@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use time::Date;
+use uuid::Uuid;
+
+use crate::config::{canonical_name, Config};
+use crate::responses::{Artist, ArtistSource};
+
+/// How many artists we ask for in one page
+const PAGE_SIZE: usize = 100;
+
+/// Settings for a Funkwhale instance
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FunkwhaleSettings {
+    /// the instance, like `https://open.audio`
+    pub server: Option<String>,
+    /// an application token with the read:libraries and write:libraries scopes
+    pub token: Option<String>,
+    /// the uuid of the channel whose description lists the new releases
+    pub channel: Option<Uuid>,
+}
+
+impl FunkwhaleSettings {
+    /// The server without a trailing slash
+    pub fn server(&self) -> Result<&str> {
+        self.server
+            .as_deref()
+            .map(|s| s.trim_end_matches('/'))
+            .ok_or_else(|| anyhow!("Set settings.funkwhale.server to your Funkwhale instance"))
+    }
+}
+
+/// JSON response for a page of artists
+#[derive(Debug, Deserialize)]
+struct ArtistPage {
+    next: Option<String>,
+    results: Vec<FunkwhaleArtist>,
+}
+
+/// An artist in the libraries on Funkwhale
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FunkwhaleArtist {
+    pub name: String,
+    /// only there if the files were tagged with it
+    pub mbid: Option<Uuid>,
+}
+
+/// `request` with the `token` if we have one, public instances allow reading without
+fn authorized(request: RequestBuilder, token: Option<&str>) -> RequestBuilder {
+    match token {
+        Some(t) => request.bearer_auth(t),
+        None => request,
+    }
+}
+
+/// The `response` if it was successful, an error otherwise
+fn check(response: Response) -> Result<Response> {
+    if !response.status().is_success() {
+        return Err(anyhow!("Funkwhale answered with {}", response.status()));
+    }
+    Ok(response)
+}
+
+/// The artists in the libraries of `scope`, like `me` for your own or `all` for everything the instance knows
+pub fn artists(
+    client: &Client,
+    server: &str,
+    token: Option<&str>,
+    scope: &str,
+) -> Result<Vec<FunkwhaleArtist>> {
+    let mut res = Vec::new();
+    let mut next = Some(format!(
+        "{}/api/v1/artists/?scope={}&has_albums=true&page_size={}",
+        server, scope, PAGE_SIZE
+    ));
+    while let Some(url) = next {
+        let response = authorized(client.get(&url), token)
+            .send()
+            .context("Could not reach Funkwhale")?;
+        let page: ArtistPage = check(response)?
+            .json()
+            .context("Could not decode Funkwhale artists")?;
+        res.extend(page.results);
+        // the instance might know itself under another name than we use, like behind a proxy
+        next = page
+            .next
+            .and_then(|n| Url::parse(&n).ok())
+            .map(|n| format!("{}{}?{}", server, n.path(), n.query().unwrap_or_default()));
+    }
+    Ok(res)
+}
+
+/// Follow the `artists` we do not follow yet, those without mbid go to the artist names for `init --fill-ids`.
+/// Returns how many artists and names were added
+pub fn import(c: &mut Config, artists: Vec<FunkwhaleArtist>, today: Date) -> (usize, usize) {
+    let (mut added, mut names) = (0, 0);
+    for a in artists {
+        match a.mbid {
+            Some(id) if !c.artist_full.iter().any(|o| o.id == id) => {
+                c.artist_full.push(
+                    Artist {
+                        search_string: a.name.clone(),
+                        sort_name: a.name.clone(),
+                        name: a.name,
+                        id,
+                        ..Default::default()
+                    }
+                    .added_by(ArtistSource::Import("funkwhale".to_string()), today),
+                );
+                added += 1;
+            }
+            Some(_) => {}
+            None => {
+                let name = canonical_name(&a.name);
+                let known = c
+                    .artist_names
+                    .iter()
+                    .chain(c.artist_full.iter().map(|o| &o.name))
+                    .any(|n| canonical_name(n) == name);
+                if !known {
+                    c.artist_names.push(a.name);
+                    names += 1;
+                }
+            }
+        }
+    }
+    c.artist_full.sort_unstable();
+    (added, names)
+}
+
+/// Replace the description of the `channel` with the markdown `text`, its followers on the fediverse see the update
+pub fn publish(
+    client: &Client,
+    server: &str,
+    token: &str,
+    channel: Uuid,
+    text: &str,
+) -> Result<()> {
+    let body = json!({
+        "description": { "text": text, "content_type": "text/markdown" }
+    });
+    let response = client
+        .patch(format!("{}/api/v1/channels/{}/", server, channel))
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .context("Could not reach Funkwhale")?;
+    check(response)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use time::Month;
+
+    #[test]
+    fn artists_follow_pages() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                r#"{"count": 2, "next": "https://other.name/api/v1/artists/?page=2&scope=me", "results": [
+                    {"id": 1, "name": "Tagged", "mbid": "00000000-0000-0000-0000-000000000001"}]}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"count": 2, "next": null, "results": [{"id": 2, "name": "Untagged", "mbid": null}]}"#
+                    .to_string(),
+            ),
+        ]);
+        let found = artists(&Client::new(), server.url(), Some("t"), "me").unwrap();
+        assert_eq!(
+            found,
+            vec![
+                FunkwhaleArtist {
+                    name: "Tagged".to_string(),
+                    mbid: Some(Uuid::from_u128(1)),
+                },
+                FunkwhaleArtist {
+                    name: "Untagged".to_string(),
+                    mbid: None,
+                },
+            ]
+        );
+        assert_eq!(
+            server.requests()[1],
+            "GET /api/v1/artists/?page=2&scope=me HTTP/1.1"
+        );
+    }
+
+    #[test]
+    fn import_adds_unknown_artists() {
+        let mut c = Config {
+            artist_names: vec!["Untagged".to_string()],
+            ..Default::default()
+        };
+        let artists = vec![
+            FunkwhaleArtist {
+                name: "New".to_string(),
+                mbid: Some(Uuid::from_u128(2)),
+            },
+            FunkwhaleArtist {
+                name: "untagged".to_string(),
+                mbid: None,
+            },
+            FunkwhaleArtist {
+                name: "Other".to_string(),
+                mbid: None,
+            },
+        ];
+        let today = Date::from_calendar_date(2025, Month::March, 1).unwrap();
+        assert_eq!(import(&mut c, artists.clone(), today), (1, 1));
+        assert_eq!(
+            c.artist_full[0].source,
+            ArtistSource::Import("funkwhale".to_string())
+        );
+        assert_eq!(c.artist_names, vec!["Untagged", "Other"]);
+        assert_eq!(import(&mut c, artists, today), (0, 0));
+    }
+}
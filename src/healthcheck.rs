@@ -0,0 +1,127 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use yansi::Paint;
+
+use crate::i18n::tr;
+
+/// How long we wait for a ping, monitoring must not hold up a check
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// URLs pinged around every check, like a check of healthchecks.io, so monitoring notices missed runs
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HealthcheckSettings {
+    /// pinged when a check succeeded, `<url>/start` when it starts and `<url>/fail` when it failed
+    pub url: Option<String>,
+    /// pinged when a check starts instead of `<url>/start`
+    pub start: Option<String>,
+    /// pinged when a check failed instead of `<url>/fail`
+    pub fail: Option<String>,
+}
+
+/// When we ping
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ping {
+    Start,
+    Success,
+    Fail,
+}
+
+impl HealthcheckSettings {
+    /// The URL for `ping`, none if nothing is configured for it
+    fn url(&self, ping: Ping) -> Option<String> {
+        let base = self.url.as_deref().map(|u| u.trim_end_matches('/'));
+        match ping {
+            Ping::Start => self
+                .start
+                .clone()
+                .or_else(|| base.map(|u| format!("{}/start", u))),
+            Ping::Success => base.map(str::to_string),
+            Ping::Fail => self
+                .fail
+                .clone()
+                .or_else(|| base.map(|u| format!("{}/fail", u))),
+        }
+    }
+}
+
+/// Post `body` to `url`, healthchecks.io keeps it with the ping
+fn send(client: &Client, url: &str, body: &str) -> Result<()> {
+    let response = client
+        .post(url)
+        .timeout(TIMEOUT)
+        .body(body.to_string())
+        .send()
+        .with_context(|| format!("Could not ping {}", url))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} answered with {}", url, response.status()))
+    }
+}
+
+/// Ping the URL of `settings` for `ping` with `body`, like the error of a failed check.
+/// A ping that does not get through is only printed, the check goes on
+pub fn ping(client: &Client, settings: &HealthcheckSettings, ping: Ping, body: &str) {
+    if let Some(url) = settings.url(ping) {
+        if let Err(e) = send(client, &url, body) {
+            println!(
+                "{} {}",
+                tr!("Warning:").yellow(),
+                tr!("Could not ping the healthcheck: {}", format!("{:#}", e))
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    #[test]
+    fn pings_go_to_the_configured_urls() {
+        let settings = HealthcheckSettings {
+            url: Some("https://hc-ping.com/abc/".to_string()),
+            fail: Some("https://example.com/failed".to_string()),
+            ..HealthcheckSettings::default()
+        };
+        assert_eq!(
+            settings.url(Ping::Start).as_deref(),
+            Some("https://hc-ping.com/abc/start")
+        );
+        assert_eq!(
+            settings.url(Ping::Success).as_deref(),
+            Some("https://hc-ping.com/abc")
+        );
+        assert_eq!(
+            settings.url(Ping::Fail).as_deref(),
+            Some("https://example.com/failed")
+        );
+        assert_eq!(HealthcheckSettings::default().url(Ping::Start), None);
+    }
+
+    #[test]
+    fn failed_pings_do_not_fail() {
+        let server = MockServer::start(vec![(200, String::new()), (500, String::new())]);
+        let settings = HealthcheckSettings {
+            url: Some(server.url().to_string()),
+            ..HealthcheckSettings::default()
+        };
+        let client = Client::new();
+        ping(&client, &settings, Ping::Start, "");
+        ping(
+            &client,
+            &settings,
+            Ping::Fail,
+            "Could not reach musicbrainz",
+        );
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("/start"));
+        assert!(requests[1].contains("/fail"));
+        assert_eq!(server.bodies()[1], "Could not reach musicbrainz");
+    }
+}
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use yansi::{Paint, Style};
+
+use crate::config::{self, data_dir};
+use crate::responses::Album;
+use crate::storage::{self, StorageFormat};
+
+/// File name of the run history in the data dir
+const RUN_HISTORY_FILE: &str = "runs.json";
+/// How many runs we keep in the history
+const MAX_RUNS: usize = 50;
+
+/// One check run and what it reported
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Run {
+    /// increasing number of the run
+    pub id: usize,
+    #[serde(with = "time::serde::rfc3339")]
+    pub started: OffsetDateTime,
+    /// all albums the run reported, including others and reissues
+    pub albums: Vec<Album>,
+    /// for how many artists the run had errors
+    pub errors: usize,
+    /// in which section the albums were printed, runs before this was stored have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sections: Option<RunSections>,
+    /// the run stopped at --max-duration before all artists or reissues were checked
+    #[serde(default)]
+    pub stopped: bool,
+}
+
+/// The ids of the albums of a run in the sections it printed them in
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunSections {
+    pub albums: Vec<Uuid>,
+    pub others: Vec<Uuid>,
+    pub broadcasts: Vec<Uuid>,
+    /// `None` if the run did not check reissues
+    pub reissues: Option<Vec<Uuid>>,
+}
+
+impl Run {
+    /// The albums of the run with the `ids`, in their order
+    pub fn section(&self, ids: &[Uuid]) -> Vec<Album> {
+        let by_id = self
+            .albums
+            .iter()
+            .map(|a| (a.id, a))
+            .collect::<HashMap<Uuid, &Album>>();
+        ids.iter()
+            .filter_map(|id| by_id.get(id).map(|a| (*a).clone()))
+            .collect()
+    }
+}
+
+/// The path of the run history, profiles have their own in `runs-<profile>.json`
+fn history_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    match config::profile() {
+        Some(p) => path.push(format!("runs-{}.json", p)),
+        None => path.push(RUN_HISTORY_FILE),
+    }
+    Ok(path)
+}
+
+/// All runs in the history, oldest first
+pub fn read_runs() -> Result<Vec<Run>> {
+    let path = history_path()?;
+    if !storage::exists(&path) {
+        return Ok(vec![]);
+    }
+    storage::read_json(&path).context("Could not read run history")
+}
+
+/// Add `run` to the history with the next id, only the last `MAX_RUNS` are kept
+pub fn record_run(mut run: Run, format: StorageFormat) -> Result<()> {
+    let mut runs = read_runs()?;
+    run.id = runs.last().map(|r| r.id + 1).unwrap_or(1);
+    runs.push(run);
+    if runs.len() > MAX_RUNS {
+        runs.drain(..runs.len() - MAX_RUNS);
+    }
+    storage::write_json(&history_path()?, &runs, format).context("Writing run history")
+}
+
+/// The last run if it started less than `minutes` before `now`.
+/// A stopped run is never recent, the next run has to check the rest
+pub fn recent_run(runs: &[Run], now: OffsetDateTime, minutes: u32) -> Option<&Run> {
+    runs.last()
+        .filter(|r| !r.stopped && now - r.started < Duration::minutes(minutes.into()))
+}
+
+/// The run with `id`, negative ids count from the newest run, i.e., -1 is the last run
+pub fn find_run(runs: &[Run], id: i64) -> Result<&Run> {
+    let run = if id < 0 {
+        runs.len()
+            .checked_sub(id.unsigned_abs() as usize)
+            .and_then(|i| runs.get(i))
+    } else {
+        runs.iter().find(|r| r.id as i64 == id)
+    };
+    run.ok_or_else(|| anyhow!("There is no run {} in the history", id))
+}
+
+/// How the reported albums changed from run `a` to run `b`
+#[derive(Debug, Default)]
+pub struct RunDiff<'a> {
+    pub appeared: Vec<&'a Album>,
+    pub disappeared: Vec<&'a Album>,
+    /// the album in run `a` and in run `b`
+    pub changed: Vec<(&'a Album, &'a Album)>,
+}
+
+/// Compare the albums of two runs by release group
+pub fn diff_runs<'a>(a: &'a Run, b: &'a Run) -> RunDiff<'a> {
+    let in_a: HashMap<Uuid, &Album> = a.albums.iter().map(|x| (x.id, x)).collect();
+    let in_b: HashMap<Uuid, &Album> = b.albums.iter().map(|x| (x.id, x)).collect();
+    let mut diff = RunDiff::default();
+    for x in &b.albums {
+        match in_a.get(&x.id) {
+            None => diff.appeared.push(x),
+            Some(old) if old.date != x.date || old.release_type != x.release_type => {
+                diff.changed.push((old, x))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.disappeared = a
+        .albums
+        .iter()
+        .filter(|x| !in_b.contains_key(&x.id))
+        .collect();
+    diff
+}
+
+/// Does `a` contain all `terms` in its artists or title, ignoring case
+pub fn matches_terms(a: &Album, terms: &[String]) -> bool {
+    let text = format!("{} {}", a.artists(), a.title).to_lowercase();
+    terms.iter().all(|t| text.contains(&t.to_lowercase()))
+}
+
+/// The byte ranges of `s` that match one of the `terms` ignoring case, sorted and merged
+fn match_ranges(s: &str, terms: &[String]) -> Vec<(usize, usize)> {
+    // positions in the lowercase string are only valid in `s` if no char changes its length
+    let same_len = s.chars().all(|c| {
+        let mut lower = c.to_lowercase();
+        lower.len() == 1 && lower.next().is_some_and(|l| l.len_utf8() == c.len_utf8())
+    });
+    if !same_len {
+        return vec![];
+    }
+    let lower = s.to_lowercase();
+    let mut ranges = terms
+        .iter()
+        .map(|t| t.to_lowercase())
+        .filter(|t| !t.is_empty())
+        .flat_map(|t| {
+            lower
+                .match_indices(t.as_str())
+                .map(|(i, m)| (i, i + m.len()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<(usize, usize)>>();
+    ranges.sort_unstable();
+    let mut merged: Vec<(usize, usize)> = Vec::new();
+    for (start, end) in ranges {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end.max(*last_end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+/// `s` in `style` with everything that matches one of the `terms` highlighted
+pub fn highlight(s: &str, terms: &[String], style: Style) -> String {
+    let mut res = String::new();
+    let mut pos = 0;
+    for (start, end) in match_ranges(s, terms) {
+        res.push_str(&s[pos..start].paint(style).to_string());
+        res.push_str(&s[start..end].paint(style.bold().underline()).to_string());
+        pos = end;
+    }
+    res.push_str(&s[pos..].paint(style).to_string());
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+
+    use time::{Date, Month};
+
+    fn date(month: Month) -> Date {
+        Date::from_calendar_date(2024, month, 1).unwrap()
+    }
+
+    fn album(n: u128, date: Date) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "Artist".to_string(),
+            title: format!("Album {}", n),
+            date: Some(date),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    fn run(id: usize, albums: Vec<Album>) -> Run {
+        Run {
+            id,
+            started: OffsetDateTime::UNIX_EPOCH,
+            albums,
+            errors: 0,
+            sections: None,
+            stopped: false,
+        }
+    }
+
+    #[test]
+    fn only_the_last_run_within_the_cooldown_is_recent() {
+        let runs = [run(1, vec![]), run(2, vec![])];
+        let now = OffsetDateTime::UNIX_EPOCH + Duration::minutes(29);
+        assert_eq!(recent_run(&runs, now, 30).map(|r| r.id), Some(2));
+        assert!(recent_run(&runs, now, 29).is_none());
+        assert!(recent_run(&runs, now, 0).is_none());
+        assert!(recent_run(&[], now, 30).is_none());
+        let stopped = Run {
+            stopped: true,
+            ..run(3, vec![])
+        };
+        assert!(recent_run(&[stopped], now, 30).is_none());
+    }
+
+    #[test]
+    fn sections_keep_their_order() {
+        let mut r = run(
+            1,
+            vec![
+                album(1, date(Month::January)),
+                album(2, date(Month::January)),
+                album(3, date(Month::January)),
+            ],
+        );
+        r.sections = Some(RunSections {
+            albums: vec![Uuid::from_u128(3), Uuid::from_u128(1)],
+            others: vec![Uuid::from_u128(2)],
+            ..Default::default()
+        });
+        let sections = r.sections.clone().unwrap();
+        assert_eq!(
+            r.section(&sections.albums)
+                .iter()
+                .map(|a| a.id.as_u128())
+                .collect::<Vec<_>>(),
+            vec![3, 1]
+        );
+        assert!(r.section(&sections.broadcasts).is_empty());
+    }
+
+    #[test]
+    fn diff_runs_finds_appeared_disappeared_and_changed() {
+        let a = run(
+            1,
+            vec![
+                album(1, date(Month::January)),
+                album(2, date(Month::January)),
+            ],
+        );
+        let b = run(
+            2,
+            vec![
+                album(2, date(Month::February)),
+                album(3, date(Month::January)),
+            ],
+        );
+        let diff = diff_runs(&a, &b);
+        assert_eq!(
+            diff.appeared.iter().map(|a| a.id).collect::<Vec<_>>(),
+            vec![Uuid::from_u128(3)]
+        );
+        assert_eq!(
+            diff.disappeared.iter().map(|a| a.id).collect::<Vec<_>>(),
+            vec![Uuid::from_u128(1)]
+        );
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.date, Some(date(Month::February)));
+    }
+
+    #[test]
+    fn find_run_counts_back_with_negative_ids() {
+        let runs = vec![run(4, vec![]), run(5, vec![])];
+        assert_eq!(find_run(&runs, -1).unwrap().id, 5);
+        assert_eq!(find_run(&runs, -2).unwrap().id, 4);
+        assert_eq!(find_run(&runs, 4).unwrap().id, 4);
+        assert!(find_run(&runs, -3).is_err());
+        assert!(find_run(&runs, 1).is_err());
+    }
+
+    #[test]
+    fn search_terms() {
+        let a = album(1, date(Month::May));
+        let terms = ["artist".to_string(), "BUM 1".to_string()];
+        assert!(matches_terms(&a, &terms));
+        assert!(!matches_terms(&a, &["other".to_string()]));
+        assert_eq!(
+            match_ranges("Album Bum", &["bum".to_string(), "lb".to_string()]),
+            vec![(1, 5), (6, 9)]
+        );
+        assert!(match_ranges("İstanbul", &["bul".to_string()]).is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::responses::{Album, Artist, ReleaseType};
+
+/// Version of the payload, changes when fields get removed or change their meaning
+const PAYLOAD_VERSION: u32 = 1;
+
+/// Settings for handing new albums to another program, like a downloader
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HookSettings {
+    /// command that gets the payload of every new album as json on stdin, e.g., `["/home/me/bin/grab-album"]`.
+    /// It is not run by a shell, so `~` and variables are not expanded
+    pub command: Vec<String>,
+    /// stops all handoffs without losing the other settings
+    pub paused: bool,
+    /// hand off at most this many albums in one run, the others wait for the next run
+    pub max_per_run: usize,
+    /// the command is killed after this many seconds and the album is handed off in the next run
+    pub timeout_secs: u64,
+}
+
+impl Default for HookSettings {
+    fn default() -> Self {
+        Self {
+            command: vec![],
+            paused: false,
+            max_per_run: 10,
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// What the hook command gets for a new album
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    pub version: u32,
+    /// the followed artist
+    pub artist: String,
+    /// the followed artist and the other followed artists of the album
+    pub artists: Vec<String>,
+    pub album: String,
+    /// the release group id on musicbrainz
+    pub mbid: Uuid,
+    /// year-month-day
+    pub date: Option<String>,
+    pub release_type: ReleaseType,
+}
+
+impl From<&Album> for Payload {
+    fn from(a: &Album) -> Self {
+        Self {
+            version: PAYLOAD_VERSION,
+            artist: a.artist.clone(),
+            artists: std::iter::once(&a.artist)
+                .chain(a.other_artists.iter())
+                .cloned()
+                .collect(),
+            album: a.title.clone(),
+            mbid: a.id,
+            date: a.date.map(|d| d.to_string()),
+            release_type: a.release_type.clone(),
+        }
+    }
+}
+
+/// The albums of `albums` that go to the hook: an artist of the album opted in and it was not handed off before.
+/// At most `max_per_run` of them
+pub fn pending<'a>(
+    settings: &HookSettings,
+    artists: &[Artist],
+    handed_off: &HashSet<Uuid>,
+    albums: &'a [Album],
+) -> Vec<&'a Album> {
+    if settings.paused || settings.command.is_empty() {
+        return vec![];
+    }
+    let opted_in = artists
+        .iter()
+        .filter(|a| a.handoff && !a.archived)
+        .map(|a| a.name.as_str())
+        .collect::<HashSet<&str>>();
+    albums
+        .iter()
+        .filter(|a| {
+            std::iter::once(&a.artist)
+                .chain(a.other_artists.iter())
+                .any(|n| opted_in.contains(n.as_str()))
+        })
+        .filter(|a| !handed_off.contains(&a.id))
+        .take(settings.max_per_run)
+        .collect()
+}
+
+/// Run the hook `command` with `payload` as json on stdin, it gets killed when it takes longer than `timeout`.
+/// The most used fields are also in the environment variables `MBRG_ARTIST`, `MBRG_ALBUM`, `MBRG_MBID` and `MBRG_DATE`
+pub fn run(command: &[String], payload: &Payload, timeout: Duration) -> Result<()> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| anyhow!("Empty hook command"))?;
+    let json = serde_json::to_vec(payload).context("Payload to json")?;
+    let mut child = Command::new(program)
+        .args(args)
+        .env("MBRG_ARTIST", &payload.artist)
+        .env("MBRG_ALBUM", &payload.album)
+        .env("MBRG_MBID", payload.mbid.to_string())
+        .env("MBRG_DATE", payload.date.as_deref().unwrap_or_default())
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run hook command {}", program))?;
+    let mut stdin = child.stdin.take().context("No stdin")?;
+    // a hook that does not read its stdin is fine
+    let _ = stdin.write_all(&json);
+    drop(stdin);
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait().context("Waiting for hook command")? {
+            break status;
+        }
+        if started.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow!(
+                "Hook command {} did not finish in {} seconds",
+                program,
+                timeout.as_secs()
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    if status.success() {
+        Ok(())
+    } else {
+        Err(anyhow!("Hook command exited with {}", status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use time::{Date, Month};
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn album(n: u128, artist: &str) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: artist.to_string(),
+            title: format!("Album {}", n),
+            date: Some(Date::from_calendar_date(2025, Month::March, 7).unwrap()),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    fn artist(name: &str, handoff: bool) -> Artist {
+        Artist {
+            name: name.to_string(),
+            search_string: name.to_string(),
+            sort_name: name.to_string(),
+            source: Default::default(),
+            handoff,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn only_opted_in_artists_are_pending() {
+        let settings = HookSettings {
+            command: vec!["true".to_string()],
+            max_per_run: 2,
+            ..Default::default()
+        };
+        let artists = [artist("In", true), artist("Out", false)];
+        let mut shared = album(3, "Out");
+        shared.other_artists = vec!["In".to_string()];
+        let albums = [album(1, "In"), album(2, "Out"), shared, album(4, "In")];
+        let handed_off = HashSet::from([Uuid::from_u128(1)]);
+        let ids = pending(&settings, &artists, &handed_off, &albums)
+            .iter()
+            .map(|a| a.id.as_u128())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![3, 4]);
+
+        let paused = HookSettings {
+            paused: true,
+            ..settings
+        };
+        assert!(pending(&paused, &artists, &handed_off, &albums).is_empty());
+    }
+
+    #[test]
+    fn hook_gets_payload() {
+        let path = std::env::temp_dir().join(format!("mbrg-hook-{}", fastrand::u64(..)));
+        let command = [
+            "sh".to_string(),
+            "-c".to_string(),
+            format!(
+                "cat > {}; echo >> {0}; echo \"$MBRG_ALBUM\" >> {0}",
+                path.display()
+            ),
+        ];
+        run(&command, &Payload::from(&album(1, "In")), TIMEOUT).unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let (json, env) = written.split_once('\n').unwrap();
+        let payload: Payload = serde_json::from_str(json).unwrap();
+        assert_eq!(payload.mbid, Uuid::from_u128(1));
+        assert_eq!(payload.date.as_deref(), Some("2025-03-07"));
+        assert_eq!(env, "Album 1\n");
+        std::fs::remove_file(path).unwrap();
+        assert!(run(
+            &["false".to_string()],
+            &Payload::from(&album(1, "In")),
+            TIMEOUT
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn slow_hooks_are_killed() {
+        let command = ["sleep".to_string(), "10".to_string()];
+        let started = Instant::now();
+        let err = run(
+            &command,
+            &Payload::from(&album(1, "In")),
+            Duration::from_millis(100),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("did not finish"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::sync::{LazyLock, RwLock};
+use time::format_description::{self, BorrowedFormatItem, OwnedFormatItem};
+use time::Date;
+
+/// Languages we have translations for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Language {
+    En,
+    De,
+}
+
+/// The language from the config, overrides the one from the locale
+static CONFIGURED: RwLock<Option<Language>> = RwLock::new(None);
+
+/// Use `language` instead of the one from the locale, `None` goes back to the locale
+pub fn set_language(language: Option<Language>) {
+    if let Ok(mut l) = CONFIGURED.write() {
+        *l = language;
+    }
+}
+
+/// The language from the usual locale variables, English if none of them is set or we do not know the language
+fn locale_language() -> Language {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|v| !v.is_empty())
+        .filter(|v| v.starts_with("de"))
+        .map_or(Language::En, |_| Language::De)
+}
+
+/// The language we print messages in
+pub fn language() -> Language {
+    CONFIGURED
+        .read()
+        .ok()
+        .and_then(|l| *l)
+        .unwrap_or_else(locale_language)
+}
+
+/// The date format from the config, overrides the one of the language
+static DATE_FORMAT: RwLock<Option<OwnedFormatItem>> = RwLock::new(None);
+
+/// How English output shows dates
+static EN_DATE: LazyLock<Vec<BorrowedFormatItem<'static>>> =
+    LazyLock::new(|| format_description::parse("[year]-[month]-[day]").unwrap());
+/// How German output shows dates
+static DE_DATE: LazyLock<Vec<BorrowedFormatItem<'static>>> =
+    LazyLock::new(|| format_description::parse("[day].[month].[year]").unwrap());
+
+/// Use the `time` format description `format` for dates, `None` goes back to the format of the language
+pub fn set_date_format(format: Option<&str>) -> Result<()> {
+    let format = format
+        .map(format_description::parse_owned::<2>)
+        .transpose()
+        .with_context(|| {
+            format!(
+                "Invalid date format {:?} in settings",
+                format.unwrap_or_default()
+            )
+        })?;
+    if let Ok(mut f) = DATE_FORMAT.write() {
+        *f = format;
+    }
+    Ok(())
+}
+
+/// The date `d` for printing
+pub fn format_date(d: Option<Date>) -> String {
+    let Some(d) = d else {
+        return tr!("NONE").to_string();
+    };
+    let configured = DATE_FORMAT.read().ok();
+    let formatted = match configured.as_ref().and_then(|f| f.as_ref()) {
+        Some(f) => d.format(f),
+        None => match language() {
+            Language::En => d.format(&EN_DATE),
+            Language::De => d.format(&DE_DATE),
+        },
+    };
+    formatted.unwrap_or_else(|_| d.to_string())
+}
+
+/// The German catalog, the English message is the key and also the fallback
+const DE: &[(&str, &str)] = &[
+    ("We do not have artist names, you need to add some", "Wir haben keine Künstlernamen, du musst welche hinzufügen"),
+    ("Writing artists we found", "Schreibe die gefundenen Künstler"),
+    ("We did not find matching artist ids for the following artists", "Für die folgenden Künstler haben wir keine passenden IDs gefunden"),
+    ("{} with error {}", "{} mit Fehler {}"),
+    ("Artist where we found differences", "Künstler, bei denen wir Unterschiede gefunden haben"),
+    ("Wrote error report to {}", "Fehlerbericht nach {} geschrieben"),
+    ("Made {} requests to musicbrainz, {} today", "{} Anfragen an musicbrainz gestellt, {} heute"),
+    ("Warning:", "Warnung:"),
+    ("NONE", "KEINES"),
+    ("That is a lot for musicbrainz.org. Disable reissues, use the first-release date strategy and check less often", "Das ist viel für musicbrainz.org. Schalte Neuauflagen ab, nutze die first-release Datumsstrategie und prüfe seltener"),
+    ("Resolved {} artists, fetched {} artists, {} failed", "{} Künstler aufgelöst, {} Künstler abgerufen, {} fehlgeschlagen"),
+    ("Finding new albums from {}", "Suche neue Alben seit {}"),
+    ("Finding reissues", "Suche Neuauflagen"),
+    ("Could not get all artists. Please check manually the following:", "Konnte nicht alle Künstler abrufen. Bitte prüfe die folgenden selbst:"),
+    ("Filtering results", "Filtere die Ergebnisse"),
+    ("Printing {} Others", "Zeige {} Andere"),
+    ("Printing {} Reissues", "Zeige {} Neuauflagen"),
+    ("Printing {} Albums", "Zeige {} Alben"),
+    ("Already watching \"{}\"", "\"{}\" wird schon beobachtet"),
+    ("Watching", "Beobachte"),
+    ("Checking every {} hours puts a lot of load on musicbrainz.org, please use at least {} hours", "Alle {} Stunden zu prüfen belastet musicbrainz.org stark, bitte nutze mindestens {} Stunden"),
+    ("{} new albums", "{} neue Alben"),
+    ("Error in checking for new releases: {}", "Fehler beim Suchen nach neuen Veröffentlichungen: {}"),
+    ("Out today", "Heute erschienen"),
+    ("Out today: {}", "Heute erschienen: {}"),
+    ("No notifier {} configured", "Kein Benachrichtiger {} eingerichtet"),
+    ("Test notification", "Testbenachrichtigung"),
+    ("Sent test notification via", "Testbenachrichtigung gesendet über"),
+    ("{} releases while you were away", "{} Veröffentlichungen während du weg warst"),
+    ("Holding back notification \"{}\"", "Halte die Benachrichtigung \"{}\" zurück"),
+    ("Pre-order reminder", "Vorbestellungs-Erinnerung"),
+    ("pre-order", "vorbestellbar"),
+    ("Changes from run {} ({}) to run {} ({})", "Änderungen von Lauf {} ({}) zu Lauf {} ({})"),
+    ("Appeared", "Neu"),
+    ("Disappeared", "Verschwunden"),
+    ("Changed", "Geändert"),
+    ("Added {} artists, updated {} artists, added {} ignores and {} watched albums", "{} Künstler hinzugefügt, {} Künstler aktualisiert, {} Ignorierungen und {} beobachtete Alben hinzugefügt"),
+    ("Archiving", "Archiviere"),
+    ("Unarchiving", "Hole aus dem Archiv"),
+    ("Did not find:", "Nicht gefunden:"),
+    ("  Sort name: {}", "  Sortiername: {}"),
+    ("  Last release: {} ({})", "  Letzte Veröffentlichung: {} ({})"),
+    ("  Last checked: {}", "  Zuletzt geprüft: {}"),
+    ("  Added: {} ({})", "  Hinzugefügt: {} ({})"),
+    ("Archived", "Archiviert"),
+    ("artists found in directory but not config", "Künstler im Verzeichnis, aber nicht in der Konfiguration"),
+    ("Found artist {}", "Künstler {} gefunden"),
+    ("Found artist {}, releases since {}", "Künstler {} gefunden, Veröffentlichungen seit {}"),
+    ("Found artist \"{}\" for search \"{}\"", "Künstler \"{}\" für die Suche \"{}\" gefunden"),
+    ("Artist is already in the list", "Der Künstler ist schon in der Liste"),
+    ("Removing", "Entferne"),
+    ("Use --force to remove them with all their data", "Nutze --force, um sie mit allen Daten zu entfernen"),
+    ("We do not have any artists, did you forget to run init -f?", "Wir haben keine Künstler, hast du vergessen init -f auszuführen?"),
+    ("Change date from |{}| to |{}|", "Ändere das Datum von |{}| auf |{}|"),
+    ("No runs recorded yet", "Noch keine Läufe aufgezeichnet"),
+    ("{} - {} - {} albums - {} artists with errors", "{} - {} - {} Alben - {} Künstler mit Fehlern"),
+    ("Last checked on {}", "Zuletzt geprüft am {}"),
+    ("Watched albums", "Beobachtete Alben"),
+    ("Use at least one init argument", "Nutze mindestens ein Argument für init"),
+    ("Try init -h", "Versuche init -h"),
+    ("This will delete the whole configuration", "Das löscht die ganze Konfiguration"),
+    ("Added {} artists", "{} Künstler hinzugefügt"),
+    ("Artist not found", "Künstler nicht gefunden"),
+    ("Found {} albums", "{} Alben gefunden"),
+    ("Could not get relations of {}: {}", "Konnte die Beziehungen von {} nicht abrufen: {}"),
+    ("No suggestions found", "Keine Vorschläge gefunden"),
+    ("Which artists do you want to follow?", "Welchen Künstlern möchtest du folgen?"),
+    ("Following", "Folge"),
+    ("{} albums tagged {} since {}", "{} Alben mit dem Tag {} seit {}"),
+    ("  Search string: {}", "  Suchtext: {}"),
+    ("  Aliases: {}", "  Aliasse: {}"),
+    ("Found artist {} ({}) matching {}", "Künstler {} ({}) gefunden, passend in {}"),
+    ("No notifiers configured, not sending \"{}\"", "Keine Benachrichtigungen eingerichtet, \"{}\" wird nicht gesendet"),
+    ("Could not send notification via {}: {}", "Konnte die Benachrichtigung über {} nicht senden: {}"),
+    ("Could not send notification via {}", "Konnte die Benachrichtigung über {} nicht senden"),
+    ("Set settings.listenbrainz.token to your ListenBrainz user token first", "Setze zuerst settings.listenbrainz.token auf dein ListenBrainz-Benutzertoken"),
+    ("No albums to add", "Keine Alben zum Hinzufügen"),
+    ("Which albums do you want to listen to later?", "Welche Alben möchtest du später hören?"),
+    ("No tracks found for {} - {}", "Keine Titel gefunden für {} - {}"),
+    ("Added {} artists from Funkwhale", "{} Künstler von Funkwhale hinzugefügt"),
+    ("{} artists have no MBID, run init --fill-ids to search them", "{} Künstler haben keine MBID, init --fill-ids sucht sie"),
+    ("Set settings.funkwhale.token and settings.funkwhale.channel first", "Setze zuerst settings.funkwhale.token und settings.funkwhale.channel"),
+    ("Published {} albums", "{} Alben veröffentlicht"),
+    ("Handed off", "Übergeben"),
+    ("Handing off albums of", "Übergebe Alben von"),
+    ("Not handing off albums of", "Übergebe keine Alben von"),
+    ("Handed off to the hook", "Wird an den Hook übergeben"),
+    ("The hook is paused in settings.hook.paused", "Der Hook ist in settings.hook.paused pausiert"),
+    ("No cover for {} - {}", "Kein Cover für {} - {}"),
+    ("Exported {} albums to {}", "{} Alben nach {} exportiert"),
+    ("Adding", "Füge hinzu"),
+    ("Listen later", "Später hören"),
+    ("Created the playlist https://listenbrainz.org/playlist/{}", "Playlist https://listenbrainz.org/playlist/{} erstellt"),
+    ("Added {} tracks to ListenBrainz", "{} Titel zu ListenBrainz hinzugefügt"),
+    ("Open this url and allow the access:", "Öffne diese URL und erlaube den Zugriff:"),
+    ("Logged in to Spotify", "Bei Spotify angemeldet"),
+    ("Not found on Spotify: {} - {}", "Nicht bei Spotify gefunden: {} - {}"),
+    ("New releases from musicbrainz-release-grabber", "Neuerscheinungen von musicbrainz-release-grabber"),
+    ("Added {} tracks to https://open.spotify.com/playlist/{}", "{} Titel zu https://open.spotify.com/playlist/{} hinzugefügt"),
+    ("No tracks to add", "Keine Titel zum Hinzufügen"),
+    ("No stored album matches", "Kein gespeichertes Album passt"),
+    ("    Label: {}", "    Label: {}"),
+    ("    Catalog number: {}", "    Katalognummer: {}"),
+    ("    Barcode: {}", "    Barcode: {}"),
+    ("    Packaging: {}", "    Verpackung: {}"),
+    ("Wrote queue file {}", "Warteschlangendatei {} geschrieben"),
+    ("Queued", "Eingereiht"),
+    ("default {}", "Standard {}"),
+    ("Set {} to {}", "{} auf {} gesetzt"),
+    (
+        "Cannot ask \"{}\" without a terminal, {}",
+        "Ohne Terminal kann \"{}\" nicht gefragt werden, {}"
+    ),
+    ("pass --yes to confirm", "mit --yes bestätigen"),
+    ("use --print", "--print verwenden"),
+    ("use --all", "--all verwenden"),
+    ("{} needs a terminal", "{} braucht ein Terminal"),
+    ("Confirmed with --yes", "Mit --yes bestätigt"),
+    ("The config {} is corrupt:", "Die Konfiguration {} ist kaputt:"),
+    (
+        "Run the command in a terminal to repair it, or copy the backup {} over it",
+        "Zum Reparieren den Befehl in einem Terminal ausführen oder die Sicherung {} darüber kopieren"
+    ),
+    (
+        "Run the command in a terminal to repair it",
+        "Zum Reparieren den Befehl in einem Terminal ausführen"
+    ),
+    (
+        "Repair it, keeping everything that can be read",
+        "Reparieren und alles behalten, was lesbar ist"
+    ),
+    (
+        "Use the backup {} with {} artists",
+        "Die Sicherung {} mit {} Künstlern verwenden"
+    ),
+    ("Stop", "Abbrechen"),
+    ("What should we do?", "Was soll passieren?"),
+    ("Dropped", "Verworfen"),
+    (
+        "Kept {} artists and {} artist names",
+        "{} Künstler und {} Künstlernamen behalten"
+    ),
+    (
+        "Kept the corrupt config as {}",
+        "Die kaputte Konfiguration liegt jetzt unter {}"
+    ),
+    (
+        "No resolutions of {} in the audit log",
+        "Keine Auflösungen von {} im Prüfprotokoll"
+    ),
+    ("score {}", "Punkte {}"),
+    ("nothing found", "nichts gefunden"),
+    ("alternative", "Alternative"),
+    ("Error", "Fehler"),
+    ("No artists with low confidence", "Keine Künstler mit geringer Zuversicht"),
+    ("{} is still the best match", "{} passt weiterhin am besten"),
+    ("{} for \"{}\" might be wrong", "{} für \"{}\" ist vielleicht falsch"),
+    ("the search scored only {}", "die Suche ergab nur {} Punkte"),
+    (
+        "the name differs from the search \"{}\"",
+        "der Name weicht von der Suche \"{}\" ab"
+    ),
+    ("better", "besser"),
+    ("Keep {}", "{} behalten"),
+    ("Which artist did you mean?", "Welchen Künstler meintest du?"),
+    ("{} is already in the list", "{} ist schon in der Liste"),
+    ("Corrected", "Korrigiert"),
+    ("low", "gering"),
+    ("medium", "mittel"),
+    ("high", "hoch"),
+    ("for search \"{}\"", "für die Suche \"{}\""),
+    ("Use mismatches --fix to correct them", "Mit mismatches --fix lassen sie sich korrigieren"),
+    (
+        "It is right, use the name as search string",
+        "Stimmt so, den Namen als Suche verwenden"
+    ),
+    ("Search again", "Erneut suchen"),
+    ("Remove the artist", "Künstler entfernen"),
+    ("Search for", "Suche nach"),
+    ("None of them", "Keiner davon"),
+    ("Skip", "Überspringen"),
+    (
+        "Every artist has the name it was searched for",
+        "Jeder Künstler hat den Namen, nach dem gesucht wurde"
+    ),
+    (
+        "{} artists might not be the ones you searched for, see mismatches",
+        "{} Künstler sind vielleicht nicht die gesuchten, siehe mismatches"
+    ),
+    ("Not shown: {}", "Nicht angezeigt: {}"),
+    (
+        "and {} more, use --page {} or --all",
+        "und {} weitere, mit --page {} oder --all"
+    ),
+    (
+        "first release in {} years!",
+        "erste Veröffentlichung seit {} Jahren!"
+    ),
+    ("active", "aktiv"),
+    ("dormant", "ruhend"),
+    ("defunct", "aufgelöst"),
+    ("unknown", "unbekannt"),
+    ("{} artists, {} of them archived", "{} Künstler, {} davon archiviert"),
+    ("Printing {} Broadcasts", "Zeige {} Sendungen"),
+    ("Following broadcasts of", "Folge Sendungen von"),
+    ("Not following broadcasts of", "Folge keinen Sendungen mehr von"),
+    ("Following broadcasts", "Sendungen werden verfolgt"),
+    (
+        "Could not ping the healthcheck: {}",
+        "Konnte den Healthcheck nicht anpingen: {}"
+    ),
+    ("No artist picked", "Kein Künstler ausgewählt"),
+    (
+        "backing off for {}s due to server throttle",
+        "warte {}s, weil der Server drosselt"
+    ),
+    (
+        "Stopped at --max-duration, {} artists were not checked and the next run checks them",
+        "Bei --max-duration angehalten, {} Künstler wurden nicht geprüft, der nächste Lauf prüft sie"
+    ),
+    (
+        "Stopped at --max-duration before all reissues were checked",
+        "Bei --max-duration angehalten, bevor alle Neuauflagen geprüft waren"
+    ),
+    (
+        "musicbrainz-release-grabber crashed: {}",
+        "musicbrainz-release-grabber ist abgestürzt: {}"
+    ),
+    ("Wrote the crash report to {}", "Absturzbericht nach {} geschrieben"),
+    (
+        "Could not write the crash report: {}",
+        "Konnte den Absturzbericht nicht schreiben: {}"
+    ),
+    (
+        "Please open an issue at {} and attach the crash report and errors.json if there is one",
+        "Bitte öffne ein Issue auf {} und hänge den Absturzbericht und, falls vorhanden, errors.json an"
+    ),
+    (
+        "{}, using the date format of the language",
+        "{}, nutze das Datumsformat der Sprache"
+    ),
+    ("{}, using the default theme", "{}, nutze das Standardthema"),
+    (
+        "This will clear all artist ids!",
+        "Das löscht alle Künstler-IDs!"
+    ),
+    (
+        "This will clear all artist names.",
+        "Das löscht alle Künstlernamen."
+    ),
+    (
+        "This will clear the whole configuration!",
+        "Das löscht die ganze Konfiguration!"
+    ),
+    ("Ignore already in place", "Wird schon ignoriert"),
+    (
+        "The edited file is not valid: {}",
+        "Die bearbeitete Datei ist nicht gültig: {}"
+    ),
+    (
+        "Edit again? Otherwise all changes are lost",
+        "Nochmal bearbeiten? Sonst gehen alle Änderungen verloren"
+    ),
+    ("Config written", "Konfiguration geschrieben"),
+    ("Config unchanged", "Konfiguration unverändert"),
+    (
+        "Could not get artist {}: {}",
+        "Konnte Künstler {} nicht abrufen: {}"
+    ),
+    (
+        "This will remove {} artists with all their data",
+        "Das entfernt {} Künstler mit allen ihren Daten"
+    ),
+    (
+        "The last check was at {}, less than {} minutes ago. Use --force to check again",
+        "Die letzte Prüfung war um {}, vor weniger als {} Minuten. Mit --force wird erneut geprüft"
+    ),
+    (
+        "The last check was at {}, these are its results. Use --force to check again",
+        "Die letzte Prüfung war um {}, das sind ihre Ergebnisse. Mit --force wird erneut geprüft"
+    ),
+    ("{} tracks", "{} Titel"),
+    (
+        "{} of {} albums are not in the MPD library",
+        "{} von {} Alben sind nicht in der MPD-Bibliothek"
+    ),
+    (
+        "Error in the daemon, trying again in the next round: {}",
+        "Fehler im Daemon, der nächste Lauf versucht es erneut: {}"
+    ),
+    (
+        "Could not write the run history: {}",
+        "Konnte den Verlauf der Läufe nicht schreiben: {}"
+    ),
+    (
+        "Could not count the requests: {}",
+        "Konnte die Anfragen nicht zählen: {}"
+    ),
+];
+
+/// The message `msg` in the current language, `msg` itself if there is no translation
+pub fn translate(msg: &'static str) -> &'static str {
+    let catalog = match language() {
+        Language::En => return msg,
+        Language::De => DE,
+    };
+    catalog
+        .iter()
+        .find(|(en, _)| *en == msg)
+        .map_or(msg, |(_, translated)| translated)
+}
+
+/// Replace the `{}` in `msg` with `args` in order
+pub fn fill(msg: &str, args: &[&dyn Display]) -> String {
+    let mut res = String::with_capacity(msg.len());
+    let mut args = args.iter();
+    let mut parts = msg.split("{}").peekable();
+    while let Some(part) = parts.next() {
+        res.push_str(part);
+        if parts.peek().is_some() {
+            if let Some(a) = args.next() {
+                res.push_str(&a.to_string());
+            }
+        }
+    }
+    res
+}
+
+/// Translate a message and fill in the arguments for its `{}`
+#[macro_export]
+macro_rules! tr {
+    ($msg:literal) => {
+        $crate::i18n::translate($msg)
+    };
+    ($msg:literal, $($arg:expr),+ $(,)?) => {
+        $crate::i18n::fill(
+            $crate::i18n::translate($msg),
+            &[$(&$arg as &dyn std::fmt::Display),+],
+        )
+    };
+}
+pub use crate::tr;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn translations_keep_placeholders() {
+        let mut keys = HashSet::new();
+        for (en, de) in DE {
+            assert!(keys.insert(en), "{} is translated twice", en);
+            assert_eq!(
+                en.matches("{}").count(),
+                de.matches("{}").count(),
+                "placeholders differ for {}",
+                en
+            );
+        }
+    }
+
+    #[test]
+    fn configured_date_format() {
+        let d = Date::from_calendar_date(2024, time::Month::March, 5).unwrap();
+        set_date_format(Some("[month]/[day]/[year]")).unwrap();
+        assert_eq!(format_date(Some(d)), "03/05/2024");
+        assert!(set_date_format(Some("[nonsense]")).is_err());
+        set_date_format(None).unwrap();
+    }
+
+    #[test]
+    fn fill_does_not_touch_arguments() {
+        assert_eq!(fill("{} - {}", &[&"a {}", &3]), "a {} - 3");
+        assert_eq!(fill("no args", &[]), "no args");
+    }
+}
@@ -0,0 +1,272 @@
+use indicatif::ProgressBar;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::responses::{
+    within_budget, Album, Artist, Candidate, DateStrategy, MbClient, MbError, ReleaseType,
+};
+
+/// How many jobs run at the same time, the rate limiter decides how fast they really are
+const WORKERS: usize = 4;
+
+/// Work for the scheduler
+pub enum Job {
+    /// find the artist for the search string
+    Resolve(String),
+    /// get the albums of the artist
+    Fetch(Artist),
+    /// get the albums of an artist again that took longer than its budget
+    Refetch(Artist),
+}
+
+/// The result of a job
+pub enum Outcome {
+    Resolved {
+        search: String,
+        result: Result<Artist, MbError>,
+        /// what the search found, the chosen artist first
+        candidates: Vec<Candidate>,
+    },
+    Fetched {
+        artist: Artist,
+        result: Result<Vec<Album>, MbError>,
+    },
+}
+
+impl Outcome {
+    /// Did the job fail in a way that should stop all other jobs
+    fn is_fatal(&self) -> bool {
+        match self {
+            Outcome::Resolved { result, .. } => result.as_ref().is_err_and(|e| e.is_fatal()),
+            Outcome::Fetched { result, .. } => result.as_ref().is_err_and(|e| e.is_fatal()),
+        }
+    }
+}
+
+/// How the jobs are done
+pub struct JobOptions<'a> {
+    pub date_strategy: &'a DateStrategy,
+    pub countries: &'a [String],
+    /// secondary types of albums we do not fetch
+    pub skip_types: &'a [ReleaseType],
+    /// fetch the albums of every artist we resolved right away
+    pub fetch_resolved: bool,
+    /// how long fetching one artist may take
+    pub artist_budget: Option<Duration>,
+    /// no job starts after this
+    pub deadline: Option<Instant>,
+}
+
+/// The queue shared by the workers
+struct Queue {
+    jobs: VecDeque<Job>,
+    /// jobs that are currently worked on and might add new jobs
+    running: usize,
+}
+
+/// The albums of `artist` within the budget
+fn fetch(mb: &MbClient, artist: &Artist, options: &JobOptions) -> Result<Vec<Album>, MbError> {
+    within_budget(options.artist_budget, || {
+        artist.get_albums_basic_filtered(
+            mb,
+            options.date_strategy,
+            options.countries,
+            options.skip_types,
+        )
+    })
+}
+
+/// Do a single job, a resolved artist may give a new job.
+/// An artist over its budget gets no outcome yet but is tried once more after the others
+fn work(mb: &MbClient, job: Job, options: &JobOptions) -> (Option<Outcome>, Option<Job>) {
+    match job {
+        Job::Resolve(search) => {
+            let (result, candidates) = match Artist::resolve(mb, &search) {
+                Ok((a, candidates)) => (Ok(a), candidates),
+                Err(e) => (Err(e), vec![]),
+            };
+            let next = match &result {
+                Ok(a) if options.fetch_resolved => Some(Job::Fetch(a.clone())),
+                _ => None,
+            };
+            (
+                Some(Outcome::Resolved {
+                    search,
+                    result,
+                    candidates,
+                }),
+                next,
+            )
+        }
+        Job::Fetch(artist) => match fetch(mb, &artist, options) {
+            // the responses so far are kept, so the second try goes on where this one stopped
+            Err(MbError::OverBudget(_)) => (None, Some(Job::Refetch(artist))),
+            result => (Some(Outcome::Fetched { artist, result }), None),
+        },
+        Job::Refetch(artist) => {
+            let result = fetch(mb, &artist, options);
+            (Some(Outcome::Fetched { artist, result }), None)
+        }
+    }
+}
+
+/// Run all `jobs` on several workers that take the next job whenever they are free.
+/// After a fatal error or the deadline no new jobs are started, the ones that did not start are returned
+/// after the outcomes. `pb` counts the finished jobs
+pub fn run(
+    mb: &MbClient,
+    jobs: Vec<Job>,
+    options: &JobOptions,
+    pb: &ProgressBar,
+) -> (Vec<Outcome>, Vec<Job>) {
+    let queue = Mutex::new(Queue {
+        jobs: jobs.into(),
+        running: 0,
+    });
+    let changed = Condvar::new();
+    let aborted = AtomicBool::new(false);
+    let outcomes = Mutex::new(Vec::new());
+    std::thread::scope(|s| {
+        for _ in 0..WORKERS {
+            s.spawn(|| loop {
+                let job = {
+                    let mut q = queue.lock().unwrap();
+                    loop {
+                        if aborted.load(Ordering::Relaxed)
+                            || options.deadline.is_some_and(|d| Instant::now() >= d)
+                        {
+                            break None;
+                        }
+                        if let Some(job) = q.jobs.pop_front() {
+                            q.running += 1;
+                            break Some(job);
+                        }
+                        if q.running == 0 {
+                            break None;
+                        }
+                        q = changed.wait(q).unwrap();
+                    }
+                };
+                let Some(job) = job else {
+                    changed.notify_all();
+                    return;
+                };
+                let (Job::Resolve(name)
+                | Job::Fetch(Artist { name, .. })
+                | Job::Refetch(Artist { name, .. })) = &job;
+                pb.set_message(format!("Artist: {}", name));
+                let (outcome, next) = work(mb, job, options);
+                let finished = outcome.is_some();
+                if let Some(outcome) = outcome {
+                    if outcome.is_fatal() {
+                        aborted.store(true, Ordering::Relaxed);
+                    }
+                    outcomes.lock().unwrap().push(outcome);
+                    pb.inc(1);
+                }
+                let mut q = queue.lock().unwrap();
+                if let Some(next) = next {
+                    // a job that is tried again is still counted once
+                    if finished {
+                        pb.inc_length(1);
+                    }
+                    q.jobs.push_back(next);
+                }
+                q.running -= 1;
+                changed.notify_all();
+            });
+        }
+    });
+    let left = queue.into_inner().unwrap().jobs.into();
+    (outcomes.into_inner().unwrap(), left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::{release_group_page, MockServer};
+
+    #[test]
+    fn resolved_artists_get_fetched() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                r#"{"artists": [{"id": "f59c5520-5f46-4d2c-b2c4-822eabf53419", "name": "Test", "sort-name": "Test"}]}"#
+                    .to_string(),
+            ),
+            (200, release_group_page(0, 2, 0..2)),
+        ]);
+        let options = JobOptions {
+            date_strategy: &DateStrategy::FirstRelease,
+            countries: &[],
+            skip_types: &[],
+            fetch_resolved: true,
+            artist_budget: None,
+            deadline: None,
+        };
+        let pb = ProgressBar::with_draw_target(Some(1), indicatif::ProgressDrawTarget::hidden());
+        let (outcomes, left) = run(
+            &server.client(),
+            vec![Job::Resolve("test".to_string())],
+            &options,
+            &pb,
+        );
+        assert!(left.is_empty());
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes
+            .iter()
+            .any(|o| matches!(o, Outcome::Resolved { result: Ok(a), .. } if a.name == "Test")));
+        assert!(outcomes.iter().any(
+            |o| matches!(o, Outcome::Fetched { result: Ok(albums), .. } if albums.len() == 2)
+        ));
+        assert_eq!(pb.length(), Some(2));
+    }
+
+    #[test]
+    fn artists_over_budget_are_tried_again_and_then_fail() {
+        let server = MockServer::start(vec![]);
+        let options = JobOptions {
+            date_strategy: &DateStrategy::FirstRelease,
+            countries: &[],
+            skip_types: &[],
+            fetch_resolved: false,
+            artist_budget: Some(Duration::ZERO),
+            deadline: None,
+        };
+        let pb = ProgressBar::with_draw_target(Some(1), indicatif::ProgressDrawTarget::hidden());
+        let artist = crate::responses::tests::artist();
+        let (outcomes, _) = run(&server.client(), vec![Job::Fetch(artist)], &options, &pb);
+        assert_eq!(outcomes.len(), 1);
+        assert!(matches!(
+            &outcomes[0],
+            Outcome::Fetched {
+                result: Err(MbError::OverBudget(_)),
+                ..
+            }
+        ));
+        assert_eq!((pb.position(), pb.length()), (1, Some(1)));
+        assert!(server.requests().is_empty());
+    }
+
+    #[test]
+    fn no_jobs_start_after_the_deadline() {
+        let server = MockServer::start(vec![]);
+        let options = JobOptions {
+            date_strategy: &DateStrategy::FirstRelease,
+            countries: &[],
+            skip_types: &[],
+            fetch_resolved: false,
+            artist_budget: None,
+            deadline: Some(Instant::now()),
+        };
+        let pb = ProgressBar::with_draw_target(Some(2), indicatif::ProgressDrawTarget::hidden());
+        let artist = crate::responses::tests::artist();
+        let jobs = vec![Job::Fetch(artist.clone()), Job::Fetch(artist)];
+        let (outcomes, left) = run(&server.client(), jobs, &options, &pb);
+        assert!(outcomes.is_empty());
+        assert_eq!(left.len(), 2);
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+use crate::config::{self, data_dir};
+use crate::error_report::ErrorEntry;
+
+/// File name of the summary of the last check in the data dir
+const LAST_RUN_FILE: &str = "last-run.json";
+
+/// How many releases of each section a check found
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct Counts {
+    pub albums: usize,
+    pub others: usize,
+    pub broadcasts: usize,
+    pub reissues: usize,
+}
+
+/// The status of the last check for dashboards and scripts, always JSON whatever the output format
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    #[serde(with = "time::serde::rfc3339")]
+    pub started: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub finished: OffsetDateTime,
+    pub duration_secs: f64,
+    /// none for the default profile
+    pub profile: Option<String>,
+    /// if the check got to the end, artists can still have failed
+    pub success: bool,
+    /// the error that stopped the check
+    pub error: Option<String>,
+    pub counts: Counts,
+    /// for how many artists the check had errors
+    pub failed_artists: usize,
+    pub errors: Vec<ErrorEntry>,
+    /// artists `--max-duration` left for the next check
+    pub unchecked_artists: Vec<String>,
+}
+
+impl Summary {
+    /// The summary of a check that started at `started` and found nothing yet
+    pub fn new(started: OffsetDateTime) -> Self {
+        Self {
+            started,
+            finished: started,
+            duration_secs: 0.0,
+            profile: config::profile(),
+            success: false,
+            error: None,
+            counts: Counts::default(),
+            failed_artists: 0,
+            errors: vec![],
+            unchecked_artists: vec![],
+        }
+    }
+
+    /// The check ended at `finished` after `duration`, with `error` if it stopped early
+    pub fn finish(
+        &mut self,
+        finished: OffsetDateTime,
+        duration: Duration,
+        error: Option<&anyhow::Error>,
+    ) {
+        self.finished = finished;
+        self.duration_secs = duration.as_secs_f64();
+        self.success = error.is_none();
+        self.error = error.map(|e| format!("{:#}", e));
+    }
+}
+
+/// The path of the summary, profiles have their own in `last-run-<profile>.json`
+pub fn path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    match config::profile() {
+        Some(p) => path.push(format!("last-run-{}.json", p)),
+        None => path.push(LAST_RUN_FILE),
+    }
+    Ok(path)
+}
+
+/// Replace the summary of the last check with `summary`
+pub fn write(summary: &Summary) -> Result<()> {
+    let s = serde_json::to_string_pretty(summary).context("JSON to string")?;
+    fs::write(path()?, s).context("Writing the summary of the last run")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn failed_checks_keep_their_error() {
+        let started = OffsetDateTime::UNIX_EPOCH;
+        let mut summary = Summary::new(started);
+        summary.counts.albums = 3;
+        summary.failed_artists = 1;
+        summary.errors = vec![ErrorEntry::new("Blur", &anyhow!("not found"))];
+        summary.finish(
+            started + Duration::from_millis(1500),
+            Duration::from_millis(1500),
+            Some(&anyhow!("musicbrainz is down")),
+        );
+        let v = serde_json::to_value(&summary).unwrap();
+        assert_eq!(v["started"], "1970-01-01T00:00:00Z");
+        assert_eq!(v["duration_secs"], 1.5);
+        assert_eq!(v["success"], false);
+        assert_eq!(v["error"], "musicbrainz is down");
+        assert_eq!(v["counts"]["albums"], 3);
+        assert_eq!(v["errors"][0]["artist"], "Blur");
+    }
+}
@@ -0,0 +1,49 @@
+//! Check MusicBrainz for new releases of the artists you follow.
+//! The binary is a command line frontend over these modules: `config` keeps the followed artists,
+//! `responses` has the artists, albums and the rate limited client that fetches them, `jobs`
+//! fetches many artists at the same time and `check::check` runs a whole check and returns what it found.
+//! Prompts, the editor and the crash handler belong to the binary and are not part of the library.
+
+pub mod activity;
+pub mod adaptive;
+pub mod audit;
+pub mod chat;
+pub mod check;
+pub mod config;
+pub mod diff;
+pub mod discography;
+pub mod env;
+pub mod error_report;
+pub mod formats;
+pub mod funkwhale;
+pub mod healthcheck;
+pub mod history;
+pub mod hook;
+pub mod i18n;
+pub mod jobs;
+pub mod last_run;
+pub mod links;
+pub mod listenbrainz;
+pub mod mismatch;
+pub mod mpd;
+pub mod msgpack;
+pub mod nfo;
+pub mod notify;
+pub mod options;
+pub mod output;
+pub mod paging;
+pub mod preorder;
+pub mod query;
+pub mod queue;
+pub mod remote;
+pub mod reresolve;
+pub mod responses;
+pub mod review;
+pub mod secret;
+pub mod spotify;
+pub mod storage;
+pub mod suggest;
+pub mod sync;
+pub mod theme;
+pub mod usage;
+pub mod webhook;
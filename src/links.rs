@@ -0,0 +1,431 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::responses::{release_group_urls, Album, GroupReleases, MbClient, MbError};
+use crate::spotify::{self, Spotify, SpotifySettings};
+
+/// The Deezer api
+pub const DEEZER_SERVER: &str = "https://api.deezer.com";
+/// The iTunes api, which also knows the albums on Apple Music
+pub const ITUNES_SERVER: &str = "https://itunes.apple.com";
+/// How many barcodes of the releases of an album we try before searching
+const MAX_BARCODES: usize = 3;
+
+/// Streaming services we find links to albums on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Provider {
+    Spotify,
+    AppleMusic,
+    Deezer,
+}
+
+impl fmt::Display for Provider {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Provider::Spotify => write!(f, "Spotify"),
+            Provider::AppleMusic => write!(f, "Apple Music"),
+            Provider::Deezer => write!(f, "Deezer"),
+        }
+    }
+}
+
+/// The services we look up links for new albums on
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkSettings {
+    /// needs the app in settings.spotify
+    pub spotify: bool,
+    pub apple_music: bool,
+    pub deezer: bool,
+}
+
+/// A streaming service that can find albums in several ways, the resolver tries them in order
+pub trait LinkProvider {
+    /// the service for the links
+    fn provider(&self) -> Provider;
+    /// the link to the album if `url` from musicbrainz points to this service
+    fn link_from_url(&self, url: &str) -> Option<String>;
+    /// the link to the album with the `barcode`
+    fn by_barcode(&self, barcode: &str) -> Result<Option<String>>;
+    /// the link to the album called `title` by `artist` from a search
+    fn search(&self, artist: &str, title: &str) -> Result<Option<String>>;
+}
+
+/// Get `url` with the `query` and decode the json answer
+fn get_json<T: DeserializeOwned>(client: &Client, url: &str, query: &[(&str, &str)]) -> Result<T> {
+    let response = client
+        .get(url)
+        .query(query)
+        .send()
+        .with_context(|| format!("Could not reach {}", url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("{} answered with {}", url, response.status()));
+    }
+    response
+        .json()
+        .with_context(|| format!("Could not decode the answer of {}", url))
+}
+
+/// The last part of the path of `url` after `album/`, if it is on one of the `hosts`
+fn album_path_id(url: &str, hosts: &[&str]) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if !hosts.contains(&url.host_str()?) {
+        return None;
+    }
+    let mut segments = url.path_segments()?;
+    segments.find(|s| *s == "album")?;
+    segments
+        .next_back()
+        .filter(|id| !id.is_empty())
+        .map(String::from)
+}
+
+impl LinkProvider for Spotify {
+    fn provider(&self) -> Provider {
+        Provider::Spotify
+    }
+
+    fn link_from_url(&self, url: &str) -> Option<String> {
+        spotify::album_id_from_url(url).map(|id| spotify::album_url(&id))
+    }
+
+    fn by_barcode(&self, barcode: &str) -> Result<Option<String>> {
+        Ok(self
+            .album_by_barcode(barcode)?
+            .map(|id| spotify::album_url(&id)))
+    }
+
+    fn search(&self, artist: &str, title: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_album(artist, title)?
+            .map(|id| spotify::album_url(&id)))
+    }
+}
+
+/// JSON response for an album on Deezer
+#[derive(Debug, Deserialize)]
+struct DeezerAlbum {
+    title: String,
+    link: String,
+}
+
+/// JSON response for an album search on Deezer
+#[derive(Debug, Deserialize)]
+struct DeezerSearch {
+    data: Vec<DeezerAlbum>,
+}
+
+/// Albums on Deezer
+pub struct Deezer {
+    pub client: Client,
+    pub server: String,
+}
+
+impl LinkProvider for Deezer {
+    fn provider(&self) -> Provider {
+        Provider::Deezer
+    }
+
+    fn link_from_url(&self, url: &str) -> Option<String> {
+        album_path_id(url, &["www.deezer.com", "deezer.com"])
+            .map(|id| format!("https://www.deezer.com/album/{}", id))
+    }
+
+    fn by_barcode(&self, barcode: &str) -> Result<Option<String>> {
+        // unknown barcodes are answered with an error object and status 200
+        let v: Value = get_json(
+            &self.client,
+            &format!("{}/album/upc:{}", self.server, barcode),
+            &[],
+        )?;
+        if v.get("error").is_some() {
+            return Ok(None);
+        }
+        let album: DeezerAlbum =
+            serde_json::from_value(v).context("Could not decode the Deezer album")?;
+        Ok(Some(album.link))
+    }
+
+    fn search(&self, artist: &str, title: &str) -> Result<Option<String>> {
+        let query = format!("artist:\"{}\" album:\"{}\"", artist, title);
+        let resp: DeezerSearch = get_json(
+            &self.client,
+            &format!("{}/search/album", self.server),
+            &[("q", &query)],
+        )?;
+        let title = title.to_lowercase();
+        Ok(resp
+            .data
+            .into_iter()
+            .find(|a| a.title.to_lowercase() == title)
+            .map(|a| a.link))
+    }
+}
+
+/// JSON response for an album on iTunes
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ItunesAlbum {
+    wrapper_type: Option<String>,
+    #[serde(default)]
+    collection_name: String,
+    #[serde(default)]
+    artist_name: String,
+    collection_view_url: Option<String>,
+}
+
+/// JSON response for a lookup or search on iTunes
+#[derive(Debug, Deserialize)]
+struct ItunesResponse {
+    results: Vec<ItunesAlbum>,
+}
+
+/// Albums on Apple Music, found with the iTunes api
+pub struct AppleMusic {
+    pub client: Client,
+    pub server: String,
+}
+
+impl AppleMusic {
+    /// The links of the albums in `resp` where `keep` is true
+    fn first_album(resp: ItunesResponse, keep: impl Fn(&ItunesAlbum) -> bool) -> Option<String> {
+        resp.results
+            .into_iter()
+            .filter(|a| a.wrapper_type.as_deref() == Some("collection"))
+            .find(keep)
+            .and_then(|a| a.collection_view_url)
+            .map(|url| url.replace("itunes.apple.com", "music.apple.com"))
+    }
+}
+
+impl LinkProvider for AppleMusic {
+    fn provider(&self) -> Provider {
+        Provider::AppleMusic
+    }
+
+    fn link_from_url(&self, url: &str) -> Option<String> {
+        album_path_id(url, &["music.apple.com", "itunes.apple.com"])?;
+        let mut url = Url::parse(url).ok()?;
+        url.set_query(None);
+        Some(url.to_string())
+    }
+
+    fn by_barcode(&self, barcode: &str) -> Result<Option<String>> {
+        let resp: ItunesResponse = get_json(
+            &self.client,
+            &format!("{}/lookup", self.server),
+            &[("upc", barcode), ("entity", "album")],
+        )?;
+        Ok(Self::first_album(resp, |_| true))
+    }
+
+    fn search(&self, artist: &str, title: &str) -> Result<Option<String>> {
+        let term = format!("{} {}", artist, title);
+        let resp: ItunesResponse = get_json(
+            &self.client,
+            &format!("{}/search", self.server),
+            &[("term", &term), ("entity", "album"), ("limit", "25")],
+        )?;
+        let (artist, title) = (artist.to_lowercase(), title.to_lowercase());
+        Ok(Self::first_album(resp, |a| {
+            a.collection_name.to_lowercase() == title
+                && a.artist_name.to_lowercase().contains(&artist)
+        }))
+    }
+}
+
+/// The providers enabled in the `settings`, with the errors of the ones we could not set up
+pub fn providers(
+    settings: &LinkSettings,
+    spotify: &SpotifySettings,
+    client: &Client,
+) -> (Vec<Box<dyn LinkProvider>>, Vec<anyhow::Error>) {
+    let mut res: Vec<Box<dyn LinkProvider>> = Vec::new();
+    let mut errors = Vec::new();
+    if settings.spotify {
+        match Spotify::connect_app(
+            client.clone(),
+            spotify::ACCOUNTS_SERVER,
+            spotify::API_SERVER,
+            spotify,
+        ) {
+            Ok(s) => res.push(Box::new(s)),
+            Err(e) => errors.push(e.context("Not looking for Spotify links")),
+        }
+    }
+    if settings.apple_music {
+        res.push(Box::new(AppleMusic {
+            client: client.clone(),
+            server: ITUNES_SERVER.to_string(),
+        }));
+    }
+    if settings.deezer {
+        res.push(Box::new(Deezer {
+            client: client.clone(),
+            server: DEEZER_SERVER.to_string(),
+        }));
+    }
+    (res, errors)
+}
+
+/// The links to the album `a` on the services of the `providers`. Every service takes the first of
+/// the urls on musicbrainz, the barcodes of the releases of the album and its own search that finds it
+pub fn resolve(
+    mb: &MbClient,
+    group_releases: &mut GroupReleases,
+    providers: &[&dyn LinkProvider],
+    a: &Album,
+) -> Result<BTreeMap<Provider, String>> {
+    let mut links = BTreeMap::new();
+    // reissues have the id of a release and no urls of their own
+    let urls = match release_group_urls(mb, &a.id) {
+        Ok(urls) => urls,
+        Err(MbError::NotFound(_)) => vec![],
+        Err(e) => return Err(e.into()),
+    };
+    for p in providers {
+        if let Some(link) = urls.iter().find_map(|u| p.link_from_url(u)) {
+            links.insert(p.provider(), link);
+        }
+    }
+    if providers.iter().all(|p| links.contains_key(&p.provider())) {
+        return Ok(links);
+    }
+
+    let barcodes = match group_releases.get(mb, &a.id) {
+        Ok(releases) => releases
+            .iter()
+            .filter_map(|r| r.barcode())
+            .map(String::from)
+            .collect::<BTreeSet<String>>(),
+        Err(MbError::NotFound(_)) => BTreeSet::new(),
+        Err(e) => return Err(e.into()),
+    };
+    for p in providers {
+        if links.contains_key(&p.provider()) {
+            continue;
+        }
+        let mut link = None;
+        for b in barcodes.iter().take(MAX_BARCODES) {
+            link = p.by_barcode(b)?;
+            if link.is_some() {
+                break;
+            }
+        }
+        if link.is_none() {
+            link = p.search(&a.artist, &a.title)?;
+        }
+        if let Some(link) = link {
+            links.insert(p.provider(), link);
+        }
+    }
+    Ok(links)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use crate::responses::ReleaseType;
+    use uuid::Uuid;
+
+    #[test]
+    fn links_from_urls() {
+        let deezer = Deezer {
+            client: Client::new(),
+            server: String::new(),
+        };
+        assert_eq!(
+            deezer.link_from_url("https://www.deezer.com/de/album/302127"),
+            Some("https://www.deezer.com/album/302127".to_string())
+        );
+        assert_eq!(
+            deezer.link_from_url("https://www.deezer.com/artist/27"),
+            None
+        );
+        let apple = AppleMusic {
+            client: Client::new(),
+            server: String::new(),
+        };
+        assert_eq!(
+            apple.link_from_url("https://music.apple.com/us/album/some-album/1440833098?uo=4"),
+            Some("https://music.apple.com/us/album/some-album/1440833098".to_string())
+        );
+        assert_eq!(
+            apple.link_from_url("https://open.spotify.com/album/x"),
+            None
+        );
+    }
+
+    #[test]
+    fn chain_falls_back_from_urls_to_barcodes_to_search() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                r#"{"relations": [{"type": "streaming", "url": {"resource": "https://music.apple.com/us/album/a/1"}}]}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"release-count": 2, "releases": [{"id": "00000000-0000-0000-0000-000000000002", "title": "A", "barcode": "123"}, {"id": "00000000-0000-0000-0000-000000000003", "title": "A", "barcode": ""}]}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"error": {"type": "DataException", "message": "no data", "code": 800}}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"data": [{"title": "A (Live)", "link": "https://www.deezer.com/album/8"}, {"title": "a", "link": "https://www.deezer.com/album/9"}]}"#
+                    .to_string(),
+            ),
+        ]);
+        let apple = AppleMusic {
+            client: Client::new(),
+            server: server.url().to_string(),
+        };
+        let deezer = Deezer {
+            client: Client::new(),
+            server: server.url().to_string(),
+        };
+        let album = Album {
+            id: Uuid::from_u128(1),
+            artist: "Artist".to_string(),
+            title: "A".to_string(),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        };
+        let links = resolve(
+            &server.client(),
+            &mut GroupReleases::default(),
+            &[&apple, &deezer],
+            &album,
+        )
+        .unwrap();
+        assert_eq!(
+            links,
+            BTreeMap::from([
+                (
+                    Provider::AppleMusic,
+                    "https://music.apple.com/us/album/a/1".to_string()
+                ),
+                (
+                    Provider::Deezer,
+                    "https://www.deezer.com/album/9".to_string()
+                ),
+            ])
+        );
+        let requests = server.requests();
+        assert!(requests[0].contains("inc=url-rels"));
+        assert!(requests[2].starts_with("GET /album/upc:123 "));
+        assert_eq!(requests.len(), 4);
+    }
+}
@@ -0,0 +1,197 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, Response};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The ListenBrainz api for the data of users like playlists
+pub const API_SERVER: &str = "https://api.listenbrainz.org";
+/// The ListenBrainz labs api with the similar artists dataset
+pub const LABS_SERVER: &str = "https://labs.api.listenbrainz.org";
+/// The dataset ListenBrainz uses for its own similar artists
+const SIMILAR_ARTISTS_ALGORITHM: &str =
+    "session_based_days_7500_session_300_contribution_5_threshold_10_limit_100_filter_True_skip_30";
+
+/// An artist ListenBrainz thinks is similar to another one
+#[derive(Debug, Clone, Deserialize)]
+pub struct SimilarArtist {
+    #[serde(rename = "artist_mbid")]
+    pub id: Uuid,
+    pub name: String,
+    /// how often they were listened to together, only comparable for the same artist
+    pub score: f64,
+}
+
+/// Settings for pushing albums to a ListenBrainz playlist
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListenBrainzSettings {
+    /// the user token from https://listenbrainz.org/settings/
+    pub token: Option<String>,
+    /// the playlist albums get added to, created by the first push
+    pub playlist: Option<Uuid>,
+}
+
+/// JSON response for a newly created playlist
+#[derive(Debug, Deserialize)]
+struct CreatedPlaylist {
+    playlist_mbid: Uuid,
+}
+
+/// Wait until the rate limit resets if the response says we used it up
+fn respect_ratelimit(response: &reqwest::blocking::Response) {
+    let header = |name: &str| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+    };
+    if header("X-RateLimit-Remaining") == Some(0) {
+        std::thread::sleep(Duration::from_secs(
+            header("X-RateLimit-Reset-In").unwrap_or(1),
+        ));
+    }
+}
+
+/// The `response` if it was successful, an error otherwise
+fn check(response: Response) -> Result<Response> {
+    respect_ratelimit(&response);
+    if !response.status().is_success() {
+        return Err(anyhow!("ListenBrainz answered with {}", response.status()));
+    }
+    Ok(response)
+}
+
+/// The JSPF tracks for the `recordings`
+fn tracks(recordings: &[Uuid]) -> Value {
+    recordings
+        .iter()
+        .map(|id| json!({ "identifier": format!("https://musicbrainz.org/recording/{}", id) }))
+        .collect()
+}
+
+/// Create a private playlist called `title` with the `recordings` and return its id
+pub fn create_playlist(
+    client: &Client,
+    server: &str,
+    token: &str,
+    title: &str,
+    recordings: &[Uuid],
+) -> Result<Uuid> {
+    let body = json!({
+        "playlist": {
+            "title": title,
+            "track": tracks(recordings),
+            "extension": {
+                "https://musicbrainz.org/doc/jspf#playlist": { "public": false }
+            }
+        }
+    });
+    let response = client
+        .post(format!("{}/1/playlist/create", server))
+        .header("Authorization", format!("Token {}", token))
+        .json(&body)
+        .send()
+        .context("Could not reach ListenBrainz")?;
+    let created: CreatedPlaylist = check(response)?
+        .json()
+        .context("Could not decode the created playlist")?;
+    Ok(created.playlist_mbid)
+}
+
+/// Append the `recordings` to the playlist with `id`, false if the playlist does not exist anymore
+pub fn add_to_playlist(
+    client: &Client,
+    server: &str,
+    token: &str,
+    id: Uuid,
+    recordings: &[Uuid],
+) -> Result<bool> {
+    let response = client
+        .post(format!("{}/1/playlist/{}/item/add", server, id))
+        .header("Authorization", format!("Token {}", token))
+        .json(&json!({ "playlist": { "track": tracks(recordings) } }))
+        .send()
+        .context("Could not reach ListenBrainz")?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(false);
+    }
+    check(response)?;
+    Ok(true)
+}
+
+/// The artists similar to the artist with `id`, most similar first
+pub fn similar_artists(client: &Client, server: &str, id: Uuid) -> Result<Vec<SimilarArtist>> {
+    let response = client
+        .get(format!("{}/similar-artists/json", server))
+        .query(&[
+            ("artist_mbids", id.to_string()),
+            ("algorithm", SIMILAR_ARTISTS_ALGORITHM.to_string()),
+        ])
+        .send()
+        .context("Could not reach ListenBrainz")?;
+    let mut similar: Vec<SimilarArtist> = check(response)?
+        .json()
+        .context("Could not decode the similar artists of ListenBrainz")?;
+    similar.sort_by(|a, b| b.score.total_cmp(&a.score));
+    Ok(similar)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    #[test]
+    fn similar_artists_are_sorted_by_score() {
+        let body = r#"[
+            {"artist_mbid": "00000000-0000-0000-0000-000000000001", "name": "Less", "comment": "", "score": 10, "reference_mbid": "00000000-0000-0000-0000-000000000009"},
+            {"artist_mbid": "00000000-0000-0000-0000-000000000002", "name": "More", "score": 300, "type": "Group"}
+        ]"#;
+        let server = MockServer::start(vec![(200, body.to_string())]);
+        let similar = similar_artists(&Client::new(), server.url(), Uuid::from_u128(9)).unwrap();
+        let names = similar.iter().map(|a| a.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["More", "Less"]);
+        let request = &server.requests()[0];
+        assert!(request.starts_with(
+            "GET /similar-artists/json?artist_mbids=00000000-0000-0000-0000-000000000009"
+        ));
+    }
+
+    #[test]
+    fn playlists_get_created_and_extended() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                r#"{"playlist_mbid": "00000000-0000-0000-0000-000000000005", "status": "ok"}"#
+                    .to_string(),
+            ),
+            (200, r#"{"status": "ok"}"#.to_string()),
+            (
+                404,
+                r#"{"code": 404, "error": "Cannot find playlist"}"#.to_string(),
+            ),
+        ]);
+        let client = Client::new();
+        let recordings = [Uuid::from_u128(1)];
+        let id = create_playlist(&client, server.url(), "token", "Later", &recordings).unwrap();
+        assert_eq!(id, Uuid::from_u128(5));
+        assert!(add_to_playlist(&client, server.url(), "token", id, &recordings).unwrap());
+        assert!(!add_to_playlist(&client, server.url(), "token", id, &recordings).unwrap());
+        let requests = server.requests();
+        assert_eq!(requests[0], "POST /1/playlist/create HTTP/1.1");
+        assert_eq!(
+            requests[1],
+            "POST /1/playlist/00000000-0000-0000-0000-000000000005/item/add HTTP/1.1"
+        );
+        let body: Value = serde_json::from_str(&server.bodies()[0]).unwrap();
+        assert_eq!(body["playlist"]["title"], "Later");
+        assert_eq!(
+            body["playlist"]["track"][0]["identifier"],
+            "https://musicbrainz.org/recording/00000000-0000-0000-0000-000000000001"
+        );
+    }
+}
@@ -1,271 +1,1446 @@
 use anyhow::{anyhow, Context, Result};
+use check::{abort_run, progress_bar, ArtistErrors};
 use clap::{Parser, Subcommand, ValueEnum};
-use dialoguer::Confirm;
-use directories::ProjectDirs;
-use indicatif::{ProgressBar, ProgressIterator, ProgressStyle};
+use config::{canonical_name, Config, Cooldown};
+use error_report::{write_error_report, ErrorEntry};
+use i18n::{format_date, tr};
+use indicatif::ProgressIterator;
+use output::{ExportFormat, OutputFormat, Section};
 use ratelimit::Ratelimiter;
-use responses::{Album, Artist};
-use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
-use std::fs::create_dir;
-use std::time::Duration;
-use std::{
-    fs::{self, read_dir},
-    path::PathBuf,
-    str::FromStr,
+use responses::{get_client, Album, Artist, MbClient};
+use std::collections::{BTreeMap, HashSet};
+use std::fs::read_dir;
+use std::time::{Duration, Instant};
+use std::{path::PathBuf, str::FromStr};
+use time::{format_description, Date};
+use yansi::{Paint, Style};
+
+use musicbrainz_release_grabber::{
+    activity, adaptive, audit, check, config, diff, discography, env, error_report, formats,
+    funkwhale, healthcheck, history, hook, i18n, last_run, links, listenbrainz, mismatch, mpd,
+    msgpack, nfo, notify, options, output, paging, preorder, query, queue, reresolve, responses,
+    review, spotify, storage, suggest, sync, theme, usage,
 };
-use time::format_description;
-use time::Date;
-use time::OffsetDateTime;
-use yansi::Paint;
-
-use crate::responses::ReleaseType;
-
-pub mod responses;
-
-/// Progress bar style
-const PROGRESS_STYLE: &str =
-    "[{spinner:.green}] [{pos:.green}/{len:.green}] ({percent:>2}%) {bar:40.cyan/blue} [ETA: {eta:>3}] |                 {msg}";
-
-const CHARS_TO_REMOVE: &[char; 5] = &['.', '&', '\'', '’', '/'];
-
-/// The config struct
-#[derive(Debug, Serialize, Deserialize)]
-struct Config {
-    /// Artists names only, gotten from the directory
-    artist_names: Vec<String>,
-    /// Artists we currently check
-    artist_full: Vec<Artist>,
-    /// last time we checked for new
-    last_checked_time: Date,
-    /// paths that we ignore
-    ignore_paths: Vec<String>,
-    /// previous new albums,
-    previous: Vec<Album>,
-}
-
-impl Default for Config {
-    /// default empty config
-    fn default() -> Self {
-        Self {
-            artist_full: vec![],
-            artist_names: vec![],
-            last_checked_time: OffsetDateTime::now_utc().date(),
-            ignore_paths: vec![],
-            previous: vec![],
-        }
-    }
-}
-
-impl Config {
-    /// reads the config
-    fn read() -> Result<Config> {
-        if let Some(project_dirs) =
-            ProjectDirs::from("io", "narfinger.github", "musicbrainz-release-grabber")
-        {
-            let mut dir = project_dirs.config_dir().to_path_buf();
-            dir.push("config.json");
-            let s = fs::read_to_string(dir).context("Reading config file")?;
-            serde_json::from_str::<Config>(&s).context("Could not read config")
-        } else {
-            Err(anyhow!("Could not find project dir"))
-        }
+
+mod crash;
+mod edit;
+mod pick;
+mod prompt;
+mod repair;
+
+use responses::{ArtistSource, Candidate, DateStrategy, GroupReleases, ReleaseType};
+
+/// get the artists ids for all artists in artist_names
+fn get_artist_ids(mb: &MbClient) -> Result<()> {
+    let mut c = Config::read()?;
+
+    if c.artist_names.is_empty() {
+        println!(
+            "{}",
+            tr!("We do not have artist names, you need to add some")
+        );
+        return Ok(());
     }
 
-    /// Writes a given config to file
-    fn write(&self) -> Result<()> {
-        if let Some(project_dirs) =
-            ProjectDirs::from("io", "narfinger.github", "musicbrainz-release-grabber")
-        {
-            let mut dir = project_dirs.config_dir().to_path_buf();
-            if !dir.exists() {
-                create_dir(&dir)?;
-            }
-            dir.push("config.json");
-            let str = serde_json::to_string_pretty(&self).context("JSON to string")?;
-            fs::write(dir, str).context("Writing string")?;
-            Ok(())
-        } else {
-            Err(anyhow!("Could not find project dir"))
+    let fetched = check::fetch_artists(&mut c, mb, true, check::FetchArtists::None, None, true)?;
+    println!(
+        "{}",
+        tr!(
+            "Resolved {} artists, fetched {} artists, {} failed",
+            fetched.resolved,
+            fetched.fetched,
+            fetched.errors.len()
+        )
+    );
+    let error_artist = fetched.errors;
+    println!("{}", tr!("Writing artists we found"));
+    c.write()?;
+
+    if !error_artist.is_empty() {
+        println!(
+            "{}",
+            tr!("We did not find matching artist ids for the following artists")
+        );
+        for (artist, e) in &error_artist {
+            println!("{}", tr!("{} with error {}", artist, format!("{:?}", e)));
         }
     }
+    report_errors(&error_artist)?;
 
-    // writes the config with time today (minus one day for safety)
-    fn now(&mut self) -> Result<()> {
-        //remove one day just to be sure
-        self.last_checked_time = OffsetDateTime::now_utc().date() - time::Duration::DAY;
-        self.write()
+    let mismatches = mismatch::find(&c.artist_full);
+    if !mismatches.is_empty() {
+        println!("{}", tr!("Artist where we found differences"));
+        mismatch::print(&mismatches);
+        println!("{}", tr!("Use mismatches --fix to correct them"));
     }
 
-    fn add_ignore(&mut self, p: PathBuf) -> Result<()> {
-        let s = p
-            .file_name()
-            .unwrap()
-            .to_string_lossy()
-            .to_lowercase()
-            .to_string()
-            .replace(CHARS_TO_REMOVE, "");
-        if self.ignore_paths.contains(&s) {
-            println!("Ignore already in place");
+    Ok(())
+}
+
+/// Go through the artists whose names differ from their search strings, the most different first, and let the user correct them
+fn fix_mismatches(c: &mut Config, mb: &MbClient) -> Result<()> {
+    prompt::require_terminal("mismatches --fix")?;
+    let now = c.settings.check.now();
+    let mismatches = mismatch::find(&c.artist_full)
+        .into_iter()
+        .map(|m| (m.artist.clone(), m.severity))
+        .collect::<Vec<_>>();
+    let items = [
+        tr!("It is right, use the name as search string").to_string(),
+        tr!("Search again").to_string(),
+        tr!("Remove the artist").to_string(),
+        tr!("Skip").to_string(),
+    ];
+    let mut changed = false;
+    for (artist, severity) in mismatches {
+        let Some(index) = c.artist_full.iter().position(|a| a.id == artist.id) else {
+            continue;
+        };
+        let prompt = format!(
+            "{} {} {}",
+            severity.paint(),
+            artist.name.bold(),
+            tr!("for search \"{}\"", artist.search_string)
+        );
+        match prompt::select(&prompt, &items)? {
+            0 => c.artist_full[index].search_string = artist.name.clone(),
+            1 => {
+                let search = prompt::input(tr!("Search for"), &artist.search_string)?;
+                let found = reresolve::rank(&search, Artist::search(mb, &search)?);
+                audit::record(&[audit::Entry::found(
+                    now,
+                    "mismatches",
+                    &search,
+                    found.iter().map(|(_, c)| c.clone()).collect(),
+                )])?;
+                let mut choices = found
+                    .iter()
+                    .map(|(_, c)| audit::describe(c))
+                    .collect::<Vec<_>>();
+                choices.push(tr!("None of them").to_string());
+                let chosen = prompt::select(tr!("Which artist did you mean?"), &choices)?;
+                let Some((better, _)) = found.into_iter().nth(chosen) else {
+                    continue;
+                };
+                if better.id != artist.id && c.artist_full.iter().any(|a| a.id == better.id) {
+                    println!("{}", tr!("{} is already in the list", better.name));
+                    c.artist_full.remove(index);
+                } else {
+                    let search_string = better.search_string.clone();
+                    c.artist_full[index] = Artist {
+                        search_string,
+                        ..reresolve::correct(&artist, better)
+                    };
+                }
+            }
+            2 => {
+                println!("{} {}", tr!("Removing").green(), artist.name);
+                c.artist_full.remove(index);
+            }
+            _ => continue,
         }
-        self.ignore_paths.push(s);
-        self.write()
+        changed = true;
     }
+    if changed {
+        c.artist_full.sort_unstable();
+        c.write()?;
+    }
+    Ok(())
 }
 
-/// get the artists ids for all artists in artist_names
-fn get_artist_ids(ratelimiter: &Ratelimiter) -> Result<()> {
-    let client = get_client()?;
-    let mut c = Config::read()?;
+/// Write the error report for the errors of a run, the errors are given with the artist they happened for
+fn report_errors(errors: &[(String, anyhow::Error)]) -> Result<()> {
+    let entries = errors
+        .iter()
+        .map(|(artist, e)| ErrorEntry::new(artist, e))
+        .collect::<Vec<ErrorEntry>>();
+    if let Some(path) = write_error_report(&entries)? {
+        println!("{}", tr!("Wrote error report to {}", path.display()));
+    }
+    Ok(())
+}
 
-    if c.artist_names.is_empty() {
-        println!("We do not have artist names, you need to add some");
-        return Ok(());
+/// Persist the requests since the last report, print them and warn if we used musicbrainz.org too much today
+fn report_api_calls(mb: &MbClient, today: Date) -> Result<()> {
+    let calls = mb.take_calls();
+    let today_calls = usage::record_calls(today, calls)?;
+    if let Some(rate) = mb.learned_rate() {
+        adaptive::write(rate)?;
     }
+    println!(
+        "{}",
+        tr!(
+            "Made {} requests to musicbrainz, {} today",
+            calls,
+            today_calls
+        )
+    );
+    if mb.is_public_server() && today_calls > usage::DAILY_CALLS_WARNING {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!("That is a lot for musicbrainz.org. Disable reissues, use the first-release date strategy and check less often")
+        );
+    }
+    Ok(())
+}
 
-    //c.artist_full.clear();
-    let already_found_artists: HashSet<String> =
-        c.artist_full.iter().map(|a| a.name.clone()).collect();
-    let artist_names: HashSet<String> = c.artist_names.iter().cloned().collect();
+/// Options for a single `new` run that override the config
+#[derive(Debug, Default)]
+struct NewOptions {
+    /// what the check does
+    check: check::CheckOptions,
+    /// also write the results in this format to this file
+    report: Option<(OutputFormat, PathBuf)>,
+    /// write the queue file for a player here instead of to settings.queue.file
+    queue: Option<PathBuf>,
+    /// which part of each section we print
+    paging: paging::Paging,
+}
 
-    let mut error_artist = Vec::new();
+/// check for releases later then last checked date from artist_full, with the healthcheck pings around it
+/// Returns the new albums we found
+fn grab_new_releases(mb: &MbClient, options: NewOptions) -> Result<Vec<Album>> {
+    let mut c = Config::read()?;
+    let settings = c.settings.healthcheck.clone();
+    let client = get_client()?;
+    healthcheck::ping(&client, &settings, healthcheck::Ping::Start, "");
+    let clock = Instant::now();
+    let mut summary = last_run::Summary::new(c.settings.check.now());
+    let res = check_new_releases(&mut c, mb, options, &mut summary);
+    summary.finish(c.settings.check.now(), clock.elapsed(), res.as_ref().err());
+    last_run::write(&summary)?;
+    match &res {
+        Ok(albums) => healthcheck::ping(
+            &client,
+            &settings,
+            healthcheck::Ping::Success,
+            &format!("{} new albums", albums.len()),
+        ),
+        Err(e) => healthcheck::ping(
+            &client,
+            &settings,
+            healthcheck::Ping::Fail,
+            &format!("{:#}", e),
+        ),
+    }
+    res
+}
 
-    let pb = ProgressBar::new(c.artist_names.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(PROGRESS_STYLE)?
-            .progress_chars("##-"),
+/// Check for new releases with the config `c` and update it, what we found goes into `summary` as well
+fn check_new_releases(
+    c: &mut Config,
+    mb: &MbClient,
+    options: NewOptions,
+    summary: &mut last_run::Summary,
+) -> Result<Vec<Album>> {
+    let started = c.settings.check.now();
+    println!(
+        "{}",
+        tr!(
+            "Finding new albums from {}",
+            format_date(Some(c.last_checked_time))
+        )
     );
-    pb.enable_steady_tick(Duration::from_millis(250));
-    for i in pb.wrap_iter(artist_names.difference(&already_found_artists)) {
-        pb.set_message(format!("Artist: {}", i));
-        match Artist::new(&client, i, ratelimiter) {
-            Ok(a) => c.artist_full.push(a),
-            Err(e) => error_artist.push(format!("{} with error {:?}", i, e)),
+    let only_active = options.check.only_active;
+    let fill_ids = options.check.fill_ids;
+    let result = check::check(c, mb, options.check)?;
+    if fill_ids {
+        println!(
+            "{}",
+            tr!(
+                "Resolved {} artists, fetched {} artists, {} failed",
+                result.resolved,
+                result.fetched,
+                result.errors.len()
+            )
+        );
+    }
+    for e in &result.warnings {
+        println!("{} {:#}", tr!("Warning:").yellow(), e);
+    }
+    if !result.errors.is_empty() {
+        println!(
+            "{}",
+            tr!("Could not get all artists. Please check manually the following:")
+        );
+        for (_, i) in &result.errors {
+            println!("{:#}", i);
         }
     }
-    c.artist_full.sort_unstable();
-    println!("Writing artists we found");
-    c.write()?;
+    let today = c.settings.check.today();
+    let reissues = result.reissues.is_some();
+    let sections = history::RunSections {
+        albums: result.albums.iter().map(|a| a.id).collect(),
+        others: result.others.iter().map(|a| a.id).collect(),
+        broadcasts: result.broadcasts.iter().map(|a| a.id).collect(),
+        reissues: result
+            .reissues
+            .as_ref()
+            .map(|r| r.iter().map(|a| a.id).collect()),
+    };
+    print_sections(
+        &c.settings,
+        &result.albums,
+        &result.others,
+        &result.broadcasts,
+        result.reissues.as_deref(),
+        &options.paging,
+        today,
+    )?;
+    let stopped = result.stopped();
+    let check::CheckResult {
+        albums,
+        others,
+        broadcasts: episodes,
+        reissues: found_reissues,
+        mut errors,
+        unchecked,
+        reissues_stopped,
+        ..
+    } = result;
+    let all_reissues = found_reissues.unwrap_or_default();
+    if let Some((format, path)) = &options.report {
+        let mut sections = vec![
+            Section {
+                title: "Albums".to_string(),
+                albums: albums.clone(),
+            },
+            Section {
+                title: "Others".to_string(),
+                albums: others.clone(),
+            },
+        ];
+        if !episodes.is_empty() {
+            sections.push(Section {
+                title: "Broadcasts".to_string(),
+                albums: episodes.clone(),
+            });
+        }
+        if reissues {
+            sections.push(Section {
+                title: "Reissues".to_string(),
+                albums: all_reissues.clone(),
+            });
+        }
+        let title = format!(
+            "New releases since {}",
+            format_date(Some(c.last_checked_time))
+        );
+        let s = output::render(*format, &title, &sections)?;
+        output::write_output(&s, &Some(path.clone()))?;
+    }
+    if let Some(path) = options.queue.as_ref().or(c.settings.queue.file.as_ref()) {
+        let queued = albums
+            .iter()
+            .chain(others.iter())
+            .cloned()
+            .collect::<Vec<Album>>();
+        if !queued.is_empty() {
+            queue::write(&c.settings.queue, path, &queued)?;
+            println!("{}", tr!("Wrote queue file {}", path.display()));
+        }
+    }
+    let handoffs = hook::pending(&c.settings.hook, &c.artist_full, &c.handed_off, &albums);
+    for a in handoffs {
+        match hook::run(
+            &c.settings.hook.command,
+            &hook::Payload::from(a),
+            Duration::from_secs(c.settings.hook.timeout_secs),
+        ) {
+            Ok(()) => {
+                println!(
+                    "{} {} - {}",
+                    tr!("Handed off").green(),
+                    a.artists(),
+                    a.title
+                );
+                c.handed_off.insert(a.id);
+                // a crash or a failed check later in the run must not hand it off again
+                c.write()?;
+            }
+            Err(e) => errors.push((
+                a.artist.clone(),
+                e.context(format!("Could not hand off {}", a.title)),
+            )),
+        }
+    }
+    summary.counts = last_run::Counts {
+        albums: albums.len(),
+        others: others.len(),
+        broadcasts: episodes.len(),
+        reissues: all_reissues.len(),
+    };
+    summary.errors = errors
+        .iter()
+        .map(|(artist, e)| ErrorEntry::new(artist, e))
+        .collect();
+    report_errors(&errors)?;
+    let reported = others
+        .into_iter()
+        .chain(episodes)
+        .chain(all_reissues)
+        .chain(albums.iter().cloned())
+        .collect();
+    let failed_artists = errors.iter().map(|(a, _)| a).collect::<HashSet<_>>().len();
+    summary.failed_artists = failed_artists;
+    let run = history::Run {
+        id: 0,
+        started,
+        albums: reported,
+        errors: failed_artists,
+        sections: Some(sections),
+        stopped,
+    };
+    // the run history is only for looking back, losing a run must not lose the check
+    if let Err(e) = history::record_run(run, c.settings.storage) {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!("Could not write the run history: {}", format!("{:#}", e))
+        );
+    }
+    // the counts only feed the etiquette warnings, they must not fail a finished check
+    if let Err(e) = report_api_calls(mb, today) {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!("Could not count the requests: {}", format!("{:#}", e))
+        );
+    }
+    let mismatches = mismatch::high(&c.artist_full);
+    if !mismatches.is_empty() {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!(
+                "{} artists might not be the ones you searched for, see mismatches",
+                mismatches.len()
+            )
+        );
+    }
 
-    if !error_artist.is_empty() {
-        println!("We did not find matching artist ids for the following artists");
-        for i in error_artist {
-            println!("{}", i);
+    if !unchecked.is_empty() {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!(
+                "Stopped at --max-duration, {} artists were not checked and the next run checks them",
+                unchecked.len()
+            )
+        );
+        println!("{}", unchecked.join(", "));
+    }
+    if reissues_stopped {
+        println!(
+            "{} {}",
+            tr!("Warning:").yellow(),
+            tr!("Stopped at --max-duration before all reissues were checked")
+        );
+    }
+    summary.unchecked_artists = unchecked;
+
+    // updateing config, a quick check of the active artists or a stopped one leaves the rest to the next full check
+    if only_active || stopped {
+        c.write()?;
+    } else {
+        c.now()?;
+    }
+    Ok(albums)
+}
+
+/// Report everything released in the last `days` days and everything announced, independent of the last check
+fn digest(mb: &MbClient, days: i64, format: OutputFormat, output: &Option<PathBuf>) -> Result<()> {
+    let mut c = Config::read()?;
+    let today = c.settings.check.today();
+    let from = today - time::Duration::days(days);
+    let check::Fetched {
+        albums: mut all_albums,
+        errors,
+        ..
+    } = check::fetch_all_albums(&mut c, mb, false, false, None, true)?;
+    for (artist, e) in errors {
+        eprintln!(
+            "{}",
+            tr!("Could not get artist {}: {}", artist, format!("{:#}", e))
+        );
+    }
+    all_albums.sort_unstable();
+    let (upcoming, released): (Vec<Album>, Vec<Album>) = all_albums
+        .into_iter()
+        .filter(|a| a.date.is_some_and(|d| d >= from))
+        .partition(|a| a.date.is_some_and(|d| d > today));
+    let mut sections = vec![
+        Section {
+            title: "Released".to_string(),
+            albums: released,
+        },
+        Section {
+            title: "Upcoming".to_string(),
+            albums: upcoming,
+        },
+    ];
+    let anniversaries = &c.settings.anniversaries;
+    if anniversaries.enabled {
+        let found = discography::anniversaries(
+            &discography::read()?,
+            &anniversaries.years,
+            from,
+            today,
+            &c.celebrated_anniversaries,
+        );
+        let mut by_years: BTreeMap<i32, Vec<Album>> = BTreeMap::new();
+        for (years, a) in found {
+            c.celebrated_anniversaries.insert((a.id, years));
+            by_years.entry(years).or_default().push(a);
         }
+        sections.extend(by_years.into_iter().map(|(years, albums)| Section {
+            title: format!("{} years ago", years),
+            albums,
+        }));
+        c.write()?;
+    }
+    let title = format!("Releases from {} to {}", from, today);
+    let s = output::render(format, &title, &sections)?;
+    output::write_output(&s, output)
+}
+
+/// The albums from the previous check and the watched albums that get released today
+fn releases_today(c: &Config) -> Vec<&Album> {
+    let today = c.settings.check.today();
+    let mut res = c
+        .previous
+        .iter()
+        .chain(c.watched.iter().filter(|w| !c.previous.contains(w)))
+        .filter(|a| a.date == Some(today))
+        .collect::<Vec<&Album>>();
+    res.sort_unstable();
+    res
+}
+
+/// Print the albums tagged with `genre` since `since` from all of musicbrainz
+fn radar(
+    c: &Config,
+    mb: &MbClient,
+    genre: &str,
+    since: Date,
+    max: usize,
+    format: OutputFormat,
+    output: &Option<PathBuf>,
+) -> Result<()> {
+    let query = responses::tag_query(genre, since);
+    let mut albums = responses::search_many_release_groups(mb, &query, max)?;
+    albums.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.cmp(b)));
+    if format == OutputFormat::Text && output.is_none() {
+        println!(
+            "{}",
+            tr!(
+                "{} albums tagged {} since {}",
+                albums.len(),
+                genre,
+                format_date(Some(since))
+            )
+        );
+        print_new_albums(&albums, c.settings.check.today())
+    } else {
+        let sections = [Section {
+            title: genre.to_string(),
+            albums,
+        }];
+        let title = format!("Albums tagged {} since {}", genre, format_date(Some(since)));
+        let s = output::render(format, &title, &sections)?;
+        output::write_output(&s, output)
     }
+}
 
-    println!("Artist where we found differences");
-    for a in c.artist_full {
-        if a.name != a.search_string {
+/// Search release groups and print them, optionally add the result with number `add_watch` to the watched albums
+fn search(
+    mb: &MbClient,
+    query: &str,
+    format: OutputFormat,
+    add_watch: Option<usize>,
+) -> Result<()> {
+    let results = responses::search_release_groups(mb, query)?;
+    if format == OutputFormat::Text {
+        for (i, a) in results.iter().enumerate() {
+            let date = format_date(a.date);
             println!(
-                "Artist difference name: \"{}\" search: \"{}\"",
-                a.name, a.search_string
+                "[{}] {} - {} - {} - ({})",
+                i + 1,
+                a.artist.red().bold(),
+                date.blue().bold(),
+                a.title.green().bold(),
+                a.release_type.to_string().yellow(),
             );
         }
+    } else {
+        let sections = [Section {
+            title: "Results".to_string(),
+            albums: results.clone(),
+        }];
+        let s = output::render(format, &format!("Search for \"{}\"", query), &sections)?;
+        output::write_output(&s, &None)?;
     }
 
+    if let Some(n) = add_watch {
+        let album = results
+            .get(n.wrapping_sub(1))
+            .with_context(|| format!("There is no result number {}", n))?;
+        let mut c = Config::read()?;
+        if c.watched.contains(album) {
+            println!("{}", tr!("Already watching \"{}\"", album.title));
+        } else {
+            println!("{} \"{}\"", tr!("Watching").green(), album.title);
+            c.watched.push(album.clone());
+            c.watched.sort_unstable();
+            c.write()?;
+        }
+    }
     Ok(())
 }
 
-/// check for releases later then last checked date from artist_full
-fn grab_new_releases(ratelimiter: &Ratelimiter) -> Result<()> {
-    let client = get_client()?;
+/// Check for new releases every `interval_hours` and notify about them and releases coming out today.
+/// Each of `profiles`, or the current profile if there are none, gets its own schedule, and all of them
+/// share our musicbrainz client with its rate limit and responses
+fn run_daemon(mb: &MbClient, interval_hours: u64, profiles: &[String]) -> Result<()> {
+    let profiles = if profiles.is_empty() {
+        vec![config::profile()]
+    } else {
+        profiles.iter().map(|p| Some(p.clone())).collect()
+    };
+    let mut last_runs: Vec<Option<std::time::Instant>> = vec![None; profiles.len()];
+    loop {
+        let mut forgotten = false;
+        for (profile, last_run) in profiles.iter().zip(last_runs.iter_mut()) {
+            // a remote that is down or a config that is broken for a moment must not end the daemon
+            if let Err(e) = daemon_round(
+                mb,
+                interval_hours,
+                profile.as_deref(),
+                last_run,
+                &mut forgotten,
+            ) {
+                println!(
+                    "{}",
+                    tr!(
+                        "Error in the daemon, trying again in the next round: {}",
+                        format!("{:#}", e)
+                    )
+                );
+            }
+        }
+        std::thread::sleep(Duration::from_secs(10 * 60));
+    }
+}
 
-    let mut c = Config::read()?;
-    println!("Finding new albums from {}", c.last_checked_time);
-    let pb = ProgressBar::new(c.artist_names.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template(PROGRESS_STYLE)?
-            .progress_chars("##-"),
-    );
-    pb.enable_steady_tick(std::time::Duration::new(0, 500));
-    let mut errors = Vec::new();
-    let mut all_albums: Vec<Album> = Vec::new();
-    for a in pb.wrap_iter(c.artist_full.iter()) {
-        pb.set_message(format!("Artist: {}", a.name));
-        let res = a.get_albums_basic_filtered(&client, ratelimiter);
-        match res {
-            Ok(mut albums) => all_albums.append(&mut albums),
-            Err(e) => errors.push(e),
-        };
+/// One round of the daemon for `profile`: check it if the last check is longer ago than its interval
+/// and send the notifications that are due
+fn daemon_round(
+    mb: &MbClient,
+    interval_hours: u64,
+    profile: Option<&str>,
+    last_run: &mut Option<Instant>,
+    forgotten: &mut bool,
+) -> Result<()> {
+    config::set_profile(profile)?;
+    let c = Config::read()?;
+    let hours = c.settings.check.interval_hours.unwrap_or(interval_hours);
+    if last_run.is_none() {
+        warn_interval(mb, hours, profile);
     }
-    if !errors.is_empty() {
-        println!("Could not get all artists. Please check manually the following:");
-        for i in errors {
-            println!("{:#}", i);
+    let interval = Duration::from_secs(60 * 60 * hours);
+    if last_run.is_none_or(|t| t.elapsed() >= interval) {
+        *last_run = Some(Instant::now());
+        // profiles checked in the same round share the responses
+        if !*forgotten {
+            mb.forget();
+            *forgotten = true;
         }
+        check_profile(mb)?;
     }
+    notify_due()
+}
 
-    println!("Filtering results");
-    let mut res = all_albums
-        .iter()
-        .filter(|a| a.date.is_some() && a.date.unwrap() >= c.last_checked_time)
-        .collect::<Vec<&Album>>();
-    res.sort_unstable();
+/// Warn if checking every `hours` hours is too much for musicbrainz.org
+fn warn_interval(mb: &MbClient, hours: u64, profile: Option<&str>) {
+    if mb.is_public_server() && hours < usage::MIN_RECOMMENDED_INTERVAL_HOURS {
+        let warning = tr!(
+            "Checking every {} hours puts a lot of load on musicbrainz.org, please use at least {} hours",
+            hours,
+            usage::MIN_RECOMMENDED_INTERVAL_HOURS
+        );
+        match profile {
+            Some(p) => println!("{} {} ({})", tr!("Warning:").yellow(), warning, p),
+            None => println!("{} {}", tr!("Warning:").yellow(), warning),
+        }
+    }
+}
 
-    let others = res
-        .clone()
-        .into_iter()
-        .filter(|a| a.release_type != ReleaseType::Album)
-        .cloned()
-        .collect::<Vec<Album>>();
-    println!("Printing {} Others", others.len());
-    print_new_albums(&others)?;
-    let albums = res
-        .into_iter()
-        .filter(|a| a.release_type == ReleaseType::Album)
-        .cloned()
-        .collect::<Vec<Album>>();
+/// Check the current profile for new releases and notify about them
+fn check_profile(mb: &MbClient) -> Result<()> {
+    let options = NewOptions {
+        check: check::CheckOptions {
+            progress: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    match grab_new_releases(mb, options) {
+        Ok(albums) if !albums.is_empty() => {
+            let mut c = Config::read()?;
+            let hour = c.settings.check.now().hour();
+            notify_or_hold(
+                &mut c,
+                hour,
+                &tr!("{} new albums", albums.len()),
+                &albums.iter().collect::<Vec<&Album>>(),
+            )?;
+        }
+        Ok(_) => {}
+        Err(e) => println!(
+            "{}",
+            tr!("Error in checking for new releases: {}", format!("{:#}", e))
+        ),
+    }
+    Ok(())
+}
+
+/// Send the notifications of the current profile that are due: releases out today, pre-order reminders
+/// and the ones held back
+fn notify_due() -> Result<()> {
+    let mut c = Config::read()?;
+    let now = c.settings.check.now();
+    if c.last_today_notification != Some(now.date())
+        && now.hour() >= c.settings.notifications.today_hour
+    {
+        let today = releases_today(&c)
+            .into_iter()
+            .cloned()
+            .collect::<Vec<Album>>();
+        if !today.is_empty() {
+            notify_or_hold(
+                &mut c,
+                now.hour(),
+                tr!("Out today"),
+                &today.iter().collect::<Vec<&Album>>(),
+            )?;
+        }
+        c.last_today_notification = Some(now.date());
+        c.write()?;
+    }
+    let due = preorder::take_due(&mut c.reminders, now.date());
+    if !due.is_empty() {
+        notify_or_hold(
+            &mut c,
+            now.hour(),
+            tr!("Pre-order reminder"),
+            &due.iter().collect::<Vec<&Album>>(),
+        )?;
+        c.write()?;
+    }
+    let held = c
+        .held_notifications
+        .take_due(&c.settings.notifications, now.date(), now.hour());
+    if !held.is_empty() {
+        notify::notify_all(
+            &c.settings.notifications,
+            &c.artist_full,
+            &tr!("{} releases while you were away", held.len()),
+            &held.iter().collect::<Vec<&Album>>(),
+        );
+        c.write()?;
+    }
+    Ok(())
+}
+
+/// Notify about `albums` now, or hold them back in quiet hours and for the daily digest
+fn notify_or_hold(c: &mut Config, hour: u8, title: &str, albums: &[&Album]) -> Result<()> {
+    if c.held_notifications
+        .hold(&c.settings.notifications, hour, albums)
+    {
+        println!("{}", tr!("Holding back notification \"{}\"", title));
+        c.write()
+    } else {
+        notify::notify_all(&c.settings.notifications, &c.artist_full, title, albums);
+        Ok(())
+    }
+}
+
+/// Print the others grouped by their release type with a count, the types in `collapse` only as a count
+fn print_others(others: &[Album], collapse: &[ReleaseType], today: Date) -> Result<()> {
+    let mut by_type: BTreeMap<&ReleaseType, Vec<Album>> = BTreeMap::new();
+    for a in others {
+        by_type.entry(&a.release_type).or_default().push(a.clone());
+    }
+    let (collapsed, shown): (Vec<_>, Vec<_>) =
+        by_type.into_iter().partition(|(t, _)| collapse.contains(t));
+    for (t, albums) in shown {
+        println!("{} ({})", t.to_string().yellow().bold(), albums.len());
+        print_new_albums(&albums, today)?;
+    }
+    if !collapsed.is_empty() {
+        let counts = collapsed
+            .iter()
+            .map(|(t, albums)| format!("{} {}", albums.len(), t))
+            .collect::<Vec<_>>();
+        println!("{}", tr!("Not shown: {}", counts.join(", ")).dim());
+    }
+    Ok(())
+}
+
+/// Print the results of a check: the others, the reissues if they were checked, the broadcasts and the albums
+fn print_sections(
+    settings: &config::Settings,
+    albums: &[Album],
+    others: &[Album],
+    episodes: &[Album],
+    reissues: Option<&[Album]>,
+    paging: &paging::Paging,
+    today: Date,
+) -> Result<()> {
+    println!("{}", tr!("Printing {} Others", others.len()));
+    let (page, more) = paging.page(others);
+    print_others(page, &settings.others.collapse, today)?;
+    paging.print_footer(more);
+    if let Some(reissues) = reissues {
+        println!("---------------------------------------------------------");
+        println!("{}", tr!("Printing {} Reissues", reissues.len()));
+        print_page(reissues, paging, today)?;
+    }
+    if !episodes.is_empty() {
+        println!("---------------------------------------------------------");
+        println!("{}", tr!("Printing {} Broadcasts", episodes.len()));
+        let (page, more) = paging.page(episodes);
+        print_episodes(page, today);
+        paging.print_footer(more);
+    }
     println!("---------------------------------------------------------");
-    println!("Printing {} Albums", albums.len());
-    print_new_albums(&albums)?;
-    c.previous = albums;
+    println!("{}", tr!("Printing {} Albums", albums.len()));
+    print_page(albums, paging, today)
+}
+
+/// Print episodes of broadcast series with the date first, they are often all named like the series
+fn print_episodes(episodes: &[Album], today: Date) {
+    let theme = theme::current();
+    let upcoming = theme.upcoming();
+    for e in episodes {
+        let t = if e.date.is_some_and(|d| d > today) {
+            &upcoming
+        } else {
+            &theme
+        };
+        println!(
+            "{} - {} - {}",
+            format_date(e.date).paint(t.date),
+            e.artists().paint(t.artist),
+            e.title.paint(t.title),
+        );
+    }
+}
 
-    // updateing config
-    c.now()?;
+/// Print the albums of the last check and the watched albums for `previous`
+fn print_previous(c: &Config, paging: &paging::Paging) -> Result<()> {
+    let today = c.settings.check.today();
+    println!(
+        "{}",
+        tr!("Last checked on {}", format_date(Some(c.last_checked_time)))
+    );
+    println!("---------------------------------------------------------");
+    print_page(&c.previous, paging, today)?;
+    if !c.watched.is_empty() {
+        println!("---------------------------------------------------------");
+        println!("{}", tr!("Watched albums"));
+        print_page(&c.watched, paging, today)?;
+    }
     Ok(())
 }
 
-/// create a reqwest client with correct http header
-fn get_client() -> Result<reqwest::blocking::Client, anyhow::Error> {
-    reqwest::blocking::ClientBuilder::new()
-        .user_agent("MusicbrainzReleaseGrabber/1.0 ( https://github.com/narfinger )")
-        .build()
-        .context("Could not build client")
+/// Print the page of `albums` that `paging` selects, with how many are left out after it
+fn print_page(albums: &[Album], paging: &paging::Paging, today: Date) -> Result<()> {
+    let (page, more) = paging.page(albums);
+    print_new_albums(page, today)?;
+    paging.print_footer(more);
+    Ok(())
 }
 
 /// Print all the albums we got in the vector in a nice way
-fn print_new_albums(a: &[Album]) -> Result<()> {
-    let today = time::OffsetDateTime::now_utc().date() - time::Duration::DAY;
-    let format = format_description::parse("[year]-[month]-[day]")?;
+/// Albums released after `today` are struck through
+fn print_new_albums(a: &[Album], today: Date) -> Result<()> {
+    let theme = theme::current();
+    let upcoming = theme.upcoming();
     for i in a {
-        let date: String = i
-            .date
-            .and_then(|d| d.format(&format).ok())
-            .unwrap_or_else(|| "NONE".to_string());
-        if i.date.is_some() && i.date.unwrap() >= today {
+        let date = format_date(i.date);
+        if i.date.is_some() && i.date.unwrap() > today {
+            let preorder = if i.preorder {
+                format!(" {}", tr!("pre-order").cyan().bold())
+            } else {
+                String::new()
+            };
             println!(
-                "{} - {} - {} - ({})",
-                i.artist.red().strike(),
-                date.blue().strike(),
-                i.title.green().strike(),
-                i.release_type.to_string().yellow().strike(),
+                "{} - {} - {} - ({}){}{}",
+                i.artists().paint(upcoming.artist),
+                date.paint(upcoming.date),
+                i.title.paint(upcoming.title),
+                i.release_type.to_string().paint(upcoming.release_type),
+                formats::describe(&i.formats, true),
+                preorder,
             )
         } else {
+            let gap = i
+                .gap_years
+                .map(|y| format!(" {}", tr!("first release in {} years!", y).bold()))
+                .unwrap_or_default();
+            println!(
+                "{} - {} - {} - ({}){}{}",
+                i.artists().paint(theme.artist),
+                date.paint(theme.date),
+                i.title.paint(theme.title),
+                i.release_type.to_string().paint(theme.release_type),
+                formats::describe(&i.formats, true),
+                gap,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Suggest artists related to the followed ones and add the ones the user picks
+fn suggest_artists(
+    c: &mut Config,
+    mb: &MbClient,
+    source: suggest::SuggestSource,
+    labels: bool,
+    max: usize,
+    print: bool,
+) -> Result<()> {
+    let artists = c
+        .artist_full
+        .iter()
+        .filter(|a| !a.archived)
+        .cloned()
+        .collect::<Vec<Artist>>();
+    let pb = progress_bar(artists.len())?;
+    let progress = |a: &Artist| {
+        pb.set_message(format!("Artist: {}", a.name));
+        pb.inc(1);
+    };
+    let (mut suggestions, errors) = match source {
+        suggest::SuggestSource::Musicbrainz => {
+            let (suggestions, errors) = suggest::suggest(mb, &artists, labels, progress)?;
+            let errors = errors
+                .into_iter()
+                .map(|(artist, e)| (artist, anyhow::Error::from(e)))
+                .collect::<ArtistErrors>();
+            (suggestions, errors)
+        }
+        suggest::SuggestSource::Listenbrainz => suggest::suggest_similar(
+            &get_client()?,
+            listenbrainz::LABS_SERVER,
+            &artists,
+            progress,
+        ),
+    };
+    pb.finish_and_clear();
+    suggestions.retain(|s| !c.ignore_paths.contains(&canonical_name(&s.artist.name)));
+    for (artist, e) in &errors {
+        println!(
+            "{}",
+            tr!(
+                "Could not get relations of {}: {}",
+                artist,
+                format!("{:#}", e)
+            )
+        );
+    }
+    report_errors(&errors)?;
+    suggestions.truncate(max);
+    if suggestions.is_empty() {
+        println!("{}", tr!("No suggestions found"));
+        return Ok(());
+    }
+    let items = suggestions
+        .iter()
+        .map(|s| format!("{} ({})", s.artist.name, s.reasons.join(", ")))
+        .collect::<Vec<String>>();
+    if print {
+        for i in items {
+            println!("{}", i);
+        }
+        return Ok(());
+    }
+    let chosen = prompt::choose(
+        tr!("Which artists do you want to follow?"),
+        &items,
+        tr!("use --print"),
+    )?;
+    let today = c.settings.check.today();
+    for i in chosen {
+        let s = &suggestions[i];
+        println!("{} {}", tr!("Following").green(), s.artist.name);
+        c.artist_full.push(
+            s.artist
+                .to_artist(ArtistSource::Suggestion(s.related_to.clone()), today),
+        );
+    }
+    c.artist_full.sort_unstable();
+    c.write()
+}
+
+/// Add the tracks of stored albums to the ListenBrainz playlist, which gets created on the first push
+fn listen_later(c: &mut Config, mb: &MbClient, from: QuerySource, all: bool) -> Result<()> {
+    let token = c.settings.listenbrainz.token.clone().ok_or_else(|| {
+        anyhow!(tr!(
+            "Set settings.listenbrainz.token to your ListenBrainz user token first"
+        ))
+    })?;
+    let albums = stored_albums(c, from)?;
+    if albums.is_empty() {
+        println!("{}", tr!("No albums to add"));
+        return Ok(());
+    }
+    let chosen = if all {
+        (0..albums.len()).collect()
+    } else {
+        let items = albums
+            .iter()
+            .map(|a| format!("{} - {} ({})", a.artists(), a.title, format_date(a.date)))
+            .collect::<Vec<String>>();
+        prompt::choose(
+            tr!("Which albums do you want to listen to later?"),
+            &items,
+            tr!("use --all"),
+        )?
+    };
+    let mut recordings = Vec::new();
+    for i in chosen {
+        let a = &albums[i];
+        let found = responses::release_group_recordings(mb, &a.id)?;
+        if found.is_empty() {
             println!(
-                "{} - {} - {} - ({})",
-                i.artist.red().bold(),
-                date.blue().blue().bold(),
-                i.title.green().bold(),
-                i.release_type.to_string().yellow(),
+                "{}",
+                tr!("No tracks found for {} - {}", a.artists(), a.title)
             );
+        } else {
+            println!("{} {} - {}", tr!("Adding").green(), a.artists(), a.title);
+        }
+        recordings.extend(found);
+    }
+    if recordings.is_empty() {
+        return Ok(());
+    }
+
+    let client = get_client()?;
+    let server = listenbrainz::API_SERVER;
+    let added = match c.settings.listenbrainz.playlist {
+        Some(id) => listenbrainz::add_to_playlist(&client, server, &token, id, &recordings)?,
+        None => false,
+    };
+    if !added {
+        let id = listenbrainz::create_playlist(
+            &client,
+            server,
+            &token,
+            tr!("Listen later"),
+            &recordings,
+        )?;
+        println!(
+            "{}",
+            tr!(
+                "Created the playlist https://listenbrainz.org/playlist/{}",
+                id
+            )
+        );
+        c.settings.listenbrainz.playlist = Some(id);
+        c.write()?;
+    }
+    println!(
+        "{}",
+        tr!("Added {} tracks to ListenBrainz", recordings.len())
+    );
+    Ok(())
+}
+
+/// Create a Spotify playlist `name` with the tracks of the stored albums, or the most popular one of each
+fn spotify_playlist(
+    c: &Config,
+    mb: &MbClient,
+    name: &str,
+    from: QuerySource,
+    top_track: bool,
+) -> Result<()> {
+    let sp = spotify::Spotify::connect(
+        get_client()?,
+        spotify::ACCOUNTS_SERVER,
+        spotify::API_SERVER,
+        &c.settings.spotify,
+    )?;
+    let albums = stored_albums(c, from)?;
+    let pb = progress_bar(albums.len())?;
+    let mut uris = Vec::new();
+    let mut missing = Vec::new();
+    let mut group_releases = GroupReleases::default();
+    for a in &albums {
+        pb.set_message(format!("Album: {} - {}", a.artists(), a.title));
+        pb.inc(1);
+        let links = links::resolve(mb, &mut group_releases, &[&sp], a)?;
+        let Some(id) = links
+            .get(&links::Provider::Spotify)
+            .and_then(|l| spotify::album_id_from_url(l))
+        else {
+            missing.push(a);
+            continue;
+        };
+        let tracks = sp.album_tracks(&id)?;
+        if top_track {
+            uris.extend(sp.most_popular(&tracks)?.map(|t| t.uri));
+        } else {
+            uris.extend(tracks.into_iter().map(|t| t.uri));
+        }
+    }
+    pb.finish_and_clear();
+    for a in missing {
+        println!(
+            "{}",
+            tr!("Not found on Spotify: {} - {}", a.artists(), a.title)
+        );
+    }
+    if uris.is_empty() {
+        println!("{}", tr!("No tracks to add"));
+        return Ok(());
+    }
+    let playlist =
+        sp.create_playlist(name, tr!("New releases from musicbrainz-release-grabber"))?;
+    sp.add_tracks(&playlist, &uris)?;
+    println!(
+        "{}",
+        tr!(
+            "Added {} tracks to https://open.spotify.com/playlist/{}",
+            uris.len(),
+            playlist
+        )
+    );
+    Ok(())
+}
+
+/// Print the releases of the stored albums matching the `terms`, or of the release group if they are its id
+fn album_details(c: &Config, mb: &MbClient, terms: &[String], json: bool) -> Result<()> {
+    let stored = stored_albums(c, QuerySource::All)?;
+    let name = |a: &Album| format!("{} - {}", a.artists(), a.title);
+    let id = match terms {
+        [t] => uuid::Uuid::parse_str(t).ok(),
+        _ => None,
+    };
+    let albums = match id {
+        Some(id) => vec![stored
+            .iter()
+            .find(|a| a.id == id)
+            .map_or((id, id.to_string()), |a| (id, name(a)))],
+        None => stored
+            .iter()
+            .filter(|a| history::matches_terms(a, terms))
+            .map(|a| (a.id, name(a)))
+            .collect(),
+    };
+    if albums.is_empty() {
+        println!("{}", tr!("No stored album matches"));
+        return Ok(());
+    }
+
+    let mut all = Vec::new();
+    for (id, album) in albums {
+        let releases = responses::release_details(mb, &id)?;
+        if json {
+            all.push(serde_json::json!({ "id": id, "album": album, "releases": releases }));
+            continue;
+        }
+        println!("{}", album.green().bold());
+        for r in releases {
+            println!(
+                "  {} {} {} ({})",
+                r.date.as_deref().unwrap_or(tr!("NONE")).blue(),
+                r.country.as_deref().unwrap_or("--"),
+                r.media(),
+                r.status.as_deref().unwrap_or("?")
+            );
+            if !r.labels.is_empty() {
+                println!("{}", tr!("    Label: {}", r.labels.join(", ")));
+            }
+            if !r.catalog_numbers.is_empty() {
+                println!(
+                    "{}",
+                    tr!("    Catalog number: {}", r.catalog_numbers.join(", "))
+                );
+            }
+            if let Some(b) = &r.barcode {
+                println!("{}", tr!("    Barcode: {}", b));
+            }
+            if let Some(p) = &r.packaging {
+                println!("{}", tr!("    Packaging: {}", p));
+            }
+        }
+    }
+    if json {
+        println!(
+            "{}",
+            serde_json::to_string_pretty(&all).context("JSON to string")?
+        );
+    }
+    Ok(())
+}
+
+/// Stored albums the query command looks at
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum QuerySource {
+    /// the albums of the last run
+    Previous,
+    /// the watched albums
+    Watched,
+    /// all albums in the run history
+    Runs,
+    /// all of them
+    All,
+}
+
+/// The albums stored in `source`, every release group only once
+fn stored_albums(c: &Config, source: QuerySource) -> Result<Vec<Album>> {
+    let mut albums = Vec::new();
+    if matches!(source, QuerySource::Previous | QuerySource::All) {
+        albums.extend(c.previous.iter().cloned());
+    }
+    if matches!(source, QuerySource::Watched | QuerySource::All) {
+        albums.extend(c.watched.iter().cloned());
+    }
+    if matches!(source, QuerySource::Runs | QuerySource::All) {
+        albums.extend(history::read_runs()?.into_iter().flat_map(|r| r.albums));
+    }
+    // the same album is in many runs
+    let mut seen = HashSet::new();
+    albums.retain(|a| seen.insert(a.id));
+    albums.sort_unstable();
+    Ok(albums)
+}
+
+/// Print the `select` fields of the albums from `source` that match `filter`
+fn query(c: &Config, select: &str, filter: Option<&str>, source: QuerySource) -> Result<()> {
+    let fields = query::parse_fields(select)?;
+    let filter = filter.map(query::Filter::from_str).transpose()?;
+    for a in stored_albums(c, source)?
+        .iter()
+        .filter(|a| filter.as_ref().is_none_or(|f| f.matches(a)))
+    {
+        println!("{}", query::row(a, &fields).join("\t"));
+    }
+    Ok(())
+}
+
+/// Order for listing artists
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListSort {
+    /// by sort name
+    Name,
+    /// newest release first
+    Recent,
+    /// oldest addition to the config first
+    Added,
+}
+
+/// How artists are listed
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ListFormat {
+    /// for people to read
+    Text,
+    /// one artist per line without colors, the name and the MBID separated by a tab, for scripts and pickers
+    Plain,
+}
+
+/// What `list` prints of each artist
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ListStyle {
+    /// the name
+    Short,
+    /// everything we know
+    Long,
+    /// unadorned lines for scripts
+    Plain(pick::Fields),
+}
+
+/// Print the `artists` from the config
+fn list_artists(
+    mut artists: Vec<Artist>,
+    activity: &activity::ActivitySettings,
+    today: Date,
+    style: ListStyle,
+    sort: ListSort,
+    filter: Option<String>,
+    count: bool,
+) -> Result<()> {
+    if let Some(filter) = filter {
+        artists.retain(|a| !a.matching_fields(&filter).is_empty());
+    }
+    if count {
+        println!("{}", artists.len());
+        return Ok(());
+    }
+    match sort {
+        ListSort::Name => artists.sort_by_key(|a| a.sort_name.to_lowercase()),
+        ListSort::Recent => artists.sort_by_key(|a| std::cmp::Reverse(a.last_release)),
+        ListSort::Added => artists.sort_by_key(|a| a.added),
+    }
+    for a in artists {
+        match style {
+            ListStyle::Short => println!("{}", a.name),
+            ListStyle::Long => print_artist_details(&a, activity.classify(&a, today))?,
+            ListStyle::Plain(fields) => println!("{}", pick::line(&a, fields)),
         }
     }
     Ok(())
 }
 
+/// Print how the albums reported changed from run `a` to run `b`
+fn print_run_diff(a: &history::Run, b: &history::Run, today: Date) -> Result<()> {
+    println!(
+        "{}",
+        tr!(
+            "Changes from run {} ({}) to run {} ({})",
+            a.id,
+            a.started,
+            b.id,
+            b.started
+        )
+    );
+    let diff = history::diff_runs(a, b);
+    println!("---------------------------------------------------------");
+    println!("{} {}", tr!("Appeared").green(), diff.appeared.len());
+    print_new_albums(
+        &diff.appeared.into_iter().cloned().collect::<Vec<Album>>(),
+        today,
+    )?;
+    println!("---------------------------------------------------------");
+    println!("{} {}", tr!("Disappeared").red(), diff.disappeared.len());
+    print_new_albums(
+        &diff
+            .disappeared
+            .into_iter()
+            .cloned()
+            .collect::<Vec<Album>>(),
+        today,
+    )?;
+    println!("---------------------------------------------------------");
+    println!("{} {}", tr!("Changed").yellow(), diff.changed.len());
+    for (old, new) in diff.changed {
+        println!(
+            "{} - {}: {} ({}) -> {} ({})",
+            new.artists().red().bold(),
+            new.title.green().bold(),
+            format_date(old.date),
+            old.release_type,
+            format_date(new.date),
+            new.release_type
+        );
+    }
+    Ok(())
+}
+
+/// Print what a merge changed
+fn print_merge_summary(summary: &sync::MergeSummary) {
+    println!(
+        "{}",
+        tr!(
+            "Added {} artists, updated {} artists, added {} ignores and {} watched albums",
+            summary.added_artists,
+            summary.updated_artists,
+            summary.added_ignores,
+            summary.added_watched
+        )
+    );
+}
+
+/// Archive or unarchive the artists with `names` and write the config
+fn set_archived(c: &mut Config, names: &[String], archived: bool) -> Result<()> {
+    let verb = if archived {
+        tr!("Archiving")
+    } else {
+        tr!("Unarchiving")
+    };
+    for name in names {
+        if let Some(a) = c.artist_full.iter_mut().find(|a| a.name == *name) {
+            println!("{} {}", verb.green(), name);
+            a.archived = archived;
+        } else {
+            println!("{} {}", tr!("Did not find:").red(), name);
+        }
+    }
+    c.write()
+}
+
+/// Let the hook command get new albums of the artists with `names` or not and write the config
+fn set_handoff(c: &mut Config, names: &[String], handoff: bool) -> Result<()> {
+    let verb = if handoff {
+        tr!("Handing off albums of")
+    } else {
+        tr!("Not handing off albums of")
+    };
+    for name in names {
+        if let Some(a) = c.artist_full.iter_mut().find(|a| a.name == *name) {
+            println!("{} {}", verb.green(), name);
+            a.handoff = handoff;
+        } else {
+            println!("{} {}", tr!("Did not find:").red(), name);
+        }
+    }
+    c.write()
+}
+
+/// Follow the broadcast series of the artists with `names` or not and write the config
+fn set_broadcasts(c: &mut Config, names: &[String], broadcasts: bool) -> Result<()> {
+    let verb = if broadcasts {
+        tr!("Following broadcasts of")
+    } else {
+        tr!("Not following broadcasts of")
+    };
+    for name in names {
+        if let Some(a) = c.artist_full.iter_mut().find(|a| a.name == *name) {
+            println!("{} {}", verb.green(), name);
+            a.broadcasts = broadcasts;
+        } else {
+            println!("{} {}", tr!("Did not find:").red(), name);
+        }
+    }
+    c.write()
+}
+
+/// Print everything we know about the artist `a`
+fn print_artist_details(a: &Artist, activity: activity::Activity) -> Result<()> {
+    let date = format_date;
+    println!("{}", a.name.red().bold());
+    println!("  MBID: {}", a.id);
+    println!("{}", tr!("  Sort name: {}", a.sort_name));
+    println!("  Tags: {}", a.tags.join(", "));
+    println!(
+        "{}",
+        tr!(
+            "  Last release: {} ({})",
+            date(a.last_release).blue(),
+            activity
+        )
+    );
+    println!("{}", tr!("  Last checked: {}", date(a.last_checked).blue()));
+    println!(
+        "{}",
+        tr!("  Added: {} ({})", date(a.added).blue(), a.source)
+    );
+    if a.archived {
+        println!("  {}", tr!("Archived").yellow());
+    }
+    if a.handoff {
+        println!("  {}", tr!("Handed off to the hook").yellow());
+    }
+    if a.broadcasts {
+        println!("  {}", tr!("Following broadcasts").yellow());
+    }
+    Ok(())
+}
+
 /// fill all artist_names into the config from a directory
 fn get_artists_from_directory(dir: PathBuf) -> Result<()> {
     //let dir = PathBuf::from_str(&base_dir)?;
@@ -279,7 +1454,7 @@ fn get_artists_from_directory(dir: PathBuf) -> Result<()> {
 
     entries.sort_unstable();
 
-    let c = Config {
+    let mut c = Config {
         artist_names: entries,
         ..Default::default()
     };
@@ -296,17 +1471,14 @@ fn artists_not_in_config(dir: &PathBuf) -> Result<()> {
         .filter(|res| res.is_dir())
         .filter_map(|p| p.file_name().and_then(|p| p.to_str()).map(String::from))
         .filter(|r| !r.contains('-') && !r.contains("Best") && !r.contains("Greatest"))
-        .map(|i| i.replace(CHARS_TO_REMOVE, ""))
-        .map(|s| s.to_ascii_lowercase())
+        .map(|i| canonical_name(&i))
         .collect::<HashSet<String>>();
 
     let config = Config::read()?;
     let artist_in_config = config
         .artist_full
         .into_iter()
-        .map(|a| a.sort_name)
-        .map(|i| i.replace(CHARS_TO_REMOVE, ""))
-        .map(|i| i.to_ascii_lowercase())
+        .map(|a| canonical_name(&a.sort_name))
         .collect::<HashSet<String>>();
 
     // remove things that we do not need
@@ -319,7 +1491,7 @@ fn artists_not_in_config(dir: &PathBuf) -> Result<()> {
         .cloned()
         .collect::<Vec<&String>>();
 
-    println!("artists found in directory but not config");
+    println!("{}", tr!("artists found in directory but not config"));
     res.sort_unstable();
 
     for i in res {
@@ -328,22 +1500,75 @@ fn artists_not_in_config(dir: &PathBuf) -> Result<()> {
     Ok(())
 }
 
-fn get_specific_artist_id(str: &str, ratelimiter: &Ratelimiter) -> Result<()> {
-    let client = get_client()?;
-    let artist = Artist::new(&client, str, ratelimiter)?;
-    println!("Foudn artist {}", artist.name);
-    let mut albums = artist.get_albums_basic_filtered(&client, ratelimiter)?;
+fn get_specific_artist_id(str: &str, mb: &MbClient) -> Result<()> {
+    let artist = Artist::new(mb, str)?;
+    println!("{}", tr!("Found artist {}", artist.name));
+    let mut albums = artist.get_albums_basic_filtered(mb, &DateStrategy::FirstRelease, &[], &[])?;
     albums.sort_by_cached_key(|a| a.date);
 
     for i in albums {
-        let format = format_description::parse("[year]-[month]-[day]")?;
-        let date: String = i
-            .date
-            .and_then(|d| d.format(&format).ok())
-            .unwrap_or_else(|| "NONE".to_string());
+        let date = format_date(i.date);
         println!("{} - {}", date.red(), i.title.green());
     }
-    Ok(())
+    Ok(())
+}
+
+/// Show the releases of the last `years` years of an artist grouped by type, without storing anything
+fn browse_artist(
+    str: &str,
+    years: i64,
+    format: OutputFormat,
+    today: Date,
+    mb: &MbClient,
+) -> Result<()> {
+    let artist = Artist::new(mb, str)?;
+    let from = today - time::Duration::days(365 * years);
+    let albums = artist.get_albums_basic_filtered(mb, &DateStrategy::FirstRelease, &[], &[])?;
+
+    let mut by_type: BTreeMap<ReleaseType, Vec<Album>> = BTreeMap::new();
+    for a in albums
+        .into_iter()
+        .filter(|a| a.date.is_some_and(|d| d >= from))
+    {
+        by_type.entry(a.release_type.clone()).or_default().push(a);
+    }
+    // albums first, the rest in the order of the types
+    let mut sections = Vec::new();
+    if let Some(albums) = by_type.remove(&ReleaseType::Album) {
+        sections.push(Section {
+            title: ReleaseType::Album.to_string(),
+            albums,
+        });
+    }
+    sections.extend(by_type.into_iter().map(|(t, albums)| Section {
+        title: t.to_string(),
+        albums,
+    }));
+
+    if format == OutputFormat::Text {
+        println!(
+            "{}",
+            tr!(
+                "Found artist {}, releases since {}",
+                artist.name,
+                format_date(Some(from))
+            )
+        );
+        for s in sections {
+            println!("---------------------------------------------------------");
+            println!("{} ({})", s.title.yellow().bold(), s.albums.len());
+            print_new_albums(&s.albums, today)?;
+        }
+        Ok(())
+    } else {
+        let title = format!(
+            "Releases of {} since {}",
+            artist.name,
+            format_date(Some(from))
+        );
+        let s = output::render(format, &title, &sections)?;
+        output::write_output(&s, &None)
+    }
 }
 
 /// Which values to clear in the config
@@ -368,19 +1593,237 @@ enum SubCommands {
         /// Clear config values
         #[clap(short, long, value_enum, group = "init")]
         clear: Option<ClearValues>,
+        /// Do not ask before deleting or clearing the config
+        #[arg(short, long)]
+        yes: bool,
     },
 
     /// Adds an artist to our list
     Add { name: String },
 
     /// List artists
-    List,
+    List {
+        /// Show MBID, sort name, tags, last release found, last check and where the artist came from
+        #[arg(short, long)]
+        long: bool,
+        /// Order of the artists
+        #[arg(short, long, value_enum, default_value_t = ListSort::Added)]
+        sort: ListSort,
+        /// Only list artists where name, sort name, alias or search string contain this
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Only print the number of artists
+        #[arg(short, long)]
+        count: bool,
+        /// List the archived artists instead
+        #[arg(short, long)]
+        archived: bool,
+        /// How the artists are printed
+        #[arg(long, value_enum, default_value_t = ListFormat::Text, conflicts_with = "long")]
+        format: ListFormat,
+        /// Only print the names, implies --format plain
+        #[arg(long, conflicts_with_all = ["long", "mbids_only"])]
+        names_only: bool,
+        /// Only print the MBIDs, implies --format plain
+        #[arg(long, conflicts_with = "long")]
+        mbids_only: bool,
+    },
+
+    /// Pick an artist with fzf and print its MBID
+    Pick {
+        /// Only offer artists where name, sort name, alias or search string contain this
+        #[arg(short, long)]
+        filter: Option<String>,
+        /// Offer the archived artists instead
+        #[arg(short, long)]
+        archived: bool,
+    },
+
+    /// Archive an artist or a list of artists, with --force delete them with all their data
+    Delete {
+        names: Vec<String>,
+        /// Really remove the artists instead of archiving them
+        #[arg(short, long)]
+        force: bool,
+        /// Do not ask before removing them with --force
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Stop checking artists but keep them in the config
+    Archive { names: Vec<String> },
 
-    /// Delete an artist or a list of artists
-    Delete { names: Vec<String> },
+    /// Check archived artists again
+    Unarchive { names: Vec<String> },
 
     /// Find new albums
-    New,
+    New {
+        /// Also report new releases in release groups we already know, like remasters
+        #[arg(short, long)]
+        reissues: bool,
+        /// Only report releases with a release event in this country (ISO 3166-1 code), can be given multiple times
+        #[arg(short, long)]
+        country: Vec<String>,
+        /// Also find the ids of artist names without one and check them in the same run, like init --fill-ids
+        #[arg(short, long)]
+        fill_ids: bool,
+        /// Also write the results in this format to the --output file
+        #[arg(long, value_enum, requires = "output")]
+        format: Option<OutputFormat>,
+        /// File for the results in --format
+        #[arg(short, long, requires = "format")]
+        output: Option<PathBuf>,
+        /// Check even if the last run is not longer ago than settings.check.cooldown_minutes
+        #[arg(long)]
+        force: bool,
+        /// Write an m3u8 queue file for a player with the new albums, local files if they are in settings.queue.library
+        #[arg(short, long)]
+        queue: Option<PathBuf>,
+        #[command(flatten)]
+        paging: paging::Paging,
+        /// Skip dormant and defunct artists for a quick check, the next full check still covers them
+        #[arg(long)]
+        only_active: bool,
+        /// Start no artist fetch after this long, like 20m or 1h30m, the next run checks the rest
+        #[arg(long, value_parser = valid_duration, value_name = "DURATION")]
+        max_duration: Option<Duration>,
+    },
+
+    /// List the albums that get released today
+    Today {
+        /// Also send a notification
+        #[arg(short, long)]
+        notify: bool,
+    },
+
+    /// Report all releases of the last day, independent of the last check
+    Digest {
+        /// Report the last seven days
+        #[arg(short, long)]
+        week: bool,
+        /// Format of the report
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Suggest related artists we do not follow yet, like band members and collaborations
+    Suggest {
+        /// Where the suggestions come from
+        #[arg(short, long, value_enum, default_value_t = suggest::SuggestSource::Musicbrainz)]
+        source: suggest::SuggestSource,
+        /// Also suggest artists of the same labels, this needs a request per label. Only for musicbrainz
+        #[arg(short, long)]
+        labels: bool,
+        /// How many suggestions to show
+        #[arg(short, long, default_value_t = 20)]
+        max: usize,
+        /// Only print the suggestions instead of asking which to add
+        #[arg(short, long)]
+        print: bool,
+    },
+
+    /// Summarize a year from the stored albums
+    Report {
+        /// The year to summarize, the current one if not given
+        #[arg(short, long)]
+        year: Option<i32>,
+        /// Format of the report
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Search for albums in musicbrainz
+    Search {
+        /// Query in the musicbrainz search syntax, e.g., `"album title" AND artist:"artist"`
+        query: String,
+        /// Format of the results
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Watch the result with this number, it will be included in `today`
+        #[arg(short, long, value_name = "NUMBER")]
+        add_watch: Option<usize>,
+    },
+
+    /// Recent albums with a tag from all of musicbrainz, also of artists we do not follow
+    Radar {
+        /// The tag, like "shoegaze"
+        #[arg(short, long)]
+        genre: String,
+        /// Albums first released on or after this date, like 2025-01-31. Defaults to 30 days ago
+        #[arg(short, long, value_parser = valid_date)]
+        since: Option<Date>,
+        /// At most this many albums, every 100 need a request
+        #[arg(short, long, default_value_t = 100)]
+        max: usize,
+        /// Format of the results
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
+        /// Write the results to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Add stored albums to a ListenBrainz playlist for listening to them later
+    ListenLater {
+        /// Which stored albums to choose from
+        #[arg(short, long, value_enum, default_value_t = QuerySource::Previous)]
+        from: QuerySource,
+        /// Add all of them instead of asking which
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    /// Play new releases that are in the library of MPD
+    Mpd {
+        #[command(subcommand)]
+        cmd: MpdCommands,
+    },
+
+    /// Follow the artists of a Funkwhale instance or publish new releases to a channel there
+    Funkwhale {
+        #[command(subcommand)]
+        cmd: FunkwhaleCommands,
+    },
+
+    /// Check the notifiers
+    Notify {
+        #[command(subcommand)]
+        cmd: NotifyCommands,
+    },
+
+    /// Hand new albums of some artists to the command in settings.hook, like a downloader
+    Handoff {
+        #[command(subcommand)]
+        cmd: HandoffCommands,
+    },
+
+    /// Follow broadcast release groups of some artists, like radio shows or podcasts
+    Broadcasts {
+        #[command(subcommand)]
+        cmd: BroadcastCommands,
+    },
+
+    /// Playlists on Spotify
+    Spotify {
+        #[command(subcommand)]
+        cmd: SpotifyCommands,
+    },
+
+    /// Periodically check for new albums and send notifications
+    Daemon {
+        /// Hours between checks, settings.check.interval_hours of a profile replaces it
+        #[arg(short, long, default_value_t = 24)]
+        interval_hours: u64,
+        /// Check these profiles in one process, each on its own schedule and with its own notifications
+        #[arg(long, value_delimiter = ',', value_name = "NAMES")]
+        profiles: Vec<String>,
+    },
 
     /// Add To Ignore List
     Ignore { name: PathBuf },
@@ -389,27 +1832,290 @@ enum SubCommands {
     BumpBack { days: u64 },
 
     /// List the previous albums
-    Previous,
+    Previous {
+        #[command(flatten)]
+        paging: paging::Paging,
+    },
+
+    /// Same as previous, or search all stored albums
+    History {
+        #[command(subcommand)]
+        cmd: Option<HistoryCommands>,
+    },
+
+    /// List the check runs in the run history
+    Runs,
+
+    /// How many artists we follow and how active they are
+    Stats,
+
+    /// How the searches of `add` and `init --fill-ids` for an artist were resolved
+    Audit {
+        /// Artist name, search string or id
+        artist: String,
+    },
+
+    /// Artists whose names differ from the search strings they were found with, the most different first
+    Mismatches {
+        /// Go through them and correct them
+        #[arg(long)]
+        fix: bool,
+    },
 
-    /// Same as previous
-    History,
+    /// Search the artists again and propose a better match if there is one
+    ReResolve {
+        /// Only artists whose search scored low or whose name differs greatly from the search string
+        #[arg(long)]
+        low_confidence: bool,
+        /// Searches that scored below this have low confidence
+        #[arg(long, default_value_t = 90)]
+        min_score: u8,
+    },
+
+    /// Select fields of stored albums, e.g., `query --select artist,title --where "type=Album && date>2025-01-01"`
+    Query {
+        /// Comma separated fields out of id, artist, title, date and type
+        #[arg(short, long, default_value = "artist,title,date,type")]
+        select: String,
+        /// Conditions like `type=Album`, `date>=2025-01-01` or `title~live`, combined with && and ||
+        #[arg(short = 'w', long = "where")]
+        filter: Option<String>,
+        /// Which stored albums to query
+        #[arg(long, value_enum, default_value_t = QuerySource::All)]
+        from: QuerySource,
+    },
+
+    /// Show which albums appeared, disappeared or changed between two check runs
+    DiffRuns {
+        /// Id of the older run, negative numbers count back from the last run
+        #[arg(allow_negative_numbers = true, default_value_t = -2)]
+        run_a: i64,
+        /// Id of the newer run, negative numbers count back from the last run
+        #[arg(allow_negative_numbers = true, default_value_t = -1)]
+        run_b: i64,
+    },
 
     /// Artists not in config
     NotInConfig {
         #[clap(value_parser = valid_dir, value_name = "DIR")]
-        path: PathBuf
+        path: PathBuf,
     },
 
     /// Search a specific artist and print complete discography
-    Discography {
+    Discography { artist_search: String },
+
+    /// Show the recent releases of an artist grouped by type without adding it to the config
+    Browse {
         artist_search: String,
+        /// How many years back we show releases
+        #[arg(short, long, default_value_t = 3)]
+        years: i64,
+        /// Format of the output
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        format: OutputFormat,
     },
 
-    /// Searches if an artist is in the config
-    ConfigSearch {
-        artist_search: String,
+    /// Work with the config file itself
+    Config {
+        #[command(subcommand)]
+        cmd: ConfigCommands,
+    },
+
+    /// Share artists, ignores and watched albums between machines
+    Sync {
+        #[command(subcommand)]
+        cmd: SyncCommands,
+    },
+
+    /// Export the follow list for another user with --share, or with --kodi-nfo metadata and covers
+    /// of stored albums for a media server. `sync export` exports the config for another machine
+    Export {
+        /// Export a compact list of the followed artists
+        #[arg(short, long, required_unless_present = "kodi_nfo")]
+        share: bool,
+        /// Encrypt the shared list with this program
+        #[arg(short, long, value_enum, requires = "recipient", requires = "share")]
+        encrypt: Option<sync::Encryption>,
+        /// Recipient of the encrypted list, a key for age or gpg
+        #[arg(short, long)]
+        recipient: Option<String>,
+        /// Format of the export, msgpack cannot be encrypted
+        #[arg(long, value_enum, default_value_t = ExportFormat::Json, conflicts_with = "encrypt")]
+        format: ExportFormat,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Write album.nfo files and covers for Kodi or Jellyfin into this staging directory
+        #[arg(long, value_name = "DIR", conflicts_with_all = ["share", "output"])]
+        kodi_nfo: Option<PathBuf>,
+        /// Which stored albums get exported with --kodi-nfo
+        #[arg(long, value_enum, default_value_t = QuerySource::Previous, requires = "kodi_nfo")]
+        from: QuerySource,
+    },
+
+    /// Import the follow list of another user with --share. `sync merge` imports a config from another machine
+    Import {
+        /// File or http(s) url
+        source: String,
+        /// Import a shared follow list, it gets decrypted with age or gpg if needed
+        #[arg(short, long, required = true)]
+        share: bool,
+        /// Identity file for decrypting an age encrypted list
+        #[arg(short, long, requires = "share")]
+        identity: Option<PathBuf>,
+    },
+
+    /// Edit the artists or the settings in $EDITOR
+    Edit {
+        #[arg(value_enum)]
+        section: edit::EditSection,
+    },
+
+    /// Show everything we know about the artists in the config matching the search
+    Info { artist_search: String },
+
+    /// Show the releases of stored albums with their barcodes, catalog numbers, labels and packaging
+    Details {
+        /// A release group id, or words that all have to be in the artist or title of a stored album
+        #[arg(required = true)]
+        terms: Vec<String>,
+        /// Print json instead of text
+        #[arg(short, long)]
+        json: bool,
+    },
+
+    /// Searches if an artist is in the config by name, sort name, alias, search string or MBID
+    ConfigSearch { artist_search: String },
+}
+
+/// Subcommands for the config file
+#[derive(Subcommand, Debug)]
+enum ConfigCommands {
+    /// Show the differences to another config, e.g., from another machine
+    Diff {
+        /// The other config file
+        other: PathBuf,
+    },
+    /// Print every setting with its value, its default and what it does
+    Show {
+        /// Print the defaults instead of the values in the config
+        #[arg(short, long)]
+        default: bool,
+    },
+    /// Print the value of a setting, like `check.countries`
+    Get { key: String },
+    /// Change a setting, like `config set check.countries DE,AT`. Values are json, text or lists separated by commas
+    Set { key: String, value: String },
+}
+
+/// Subcommands for syncing configs
+#[derive(Subcommand, Debug)]
+enum SyncCommands {
+    /// Merge another config or export into this one
+    Merge {
+        /// File or http(s) url of the other config
+        source: String,
+    },
+    /// Write the artists, ignores and watched albums without the state of this machine
+    Export {
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Commands for the stored albums
+#[derive(Subcommand, Debug)]
+enum HistoryCommands {
+    /// Find albums of the last run, the watched albums and the run history by artist and title
+    Search {
+        /// All of these have to be in the artist or the title, ignoring case
+        #[arg(required = true)]
+        terms: Vec<String>,
+        /// Only albums released on or after this date, like 2024-01-31
+        #[arg(long, value_parser = valid_date)]
+        since: Option<Date>,
+        /// Only albums released on or before this date, like 2024-12-31
+        #[arg(long, value_parser = valid_date)]
+        until: Option<Date>,
+    },
+}
+
+/// Commands for Funkwhale
+#[derive(Subcommand, Debug)]
+enum FunkwhaleCommands {
+    /// Follow the artists in libraries on the instance in settings.funkwhale.server
+    Import {
+        /// Whose libraries, `me`, `all`, `actor:<name>` or `domain:<instance>`
+        #[arg(short, long, default_value = "me")]
+        scope: String,
+    },
+    /// Put the stored albums into the description of the channel in settings.funkwhale.channel
+    Publish {
+        /// Which stored albums get published
+        #[arg(short, long, value_enum, default_value_t = QuerySource::Previous)]
+        from: QuerySource,
+    },
+}
+
+/// Commands for MPD
+#[derive(Subcommand, Debug)]
+enum MpdCommands {
+    /// Append the stored albums that MPD has, matched by their tags, to its queue
+    QueueNew {
+        /// Which stored albums get queued
+        #[arg(short, long, value_enum, default_value_t = QuerySource::Previous)]
+        from: QuerySource,
+    },
+}
+
+/// Commands for the notifiers
+#[derive(Subcommand, Debug)]
+enum NotifyCommands {
+    /// Send a test notification
+    Test {
+        /// Only this notifier: command, mastodon, irc, xmpp, matrix, slack or discord
+        name: Option<String>,
     },
+}
+
+/// Commands for the handoff hook
+#[derive(Subcommand, Debug)]
+enum HandoffCommands {
+    /// Hand new albums of these artists to the hook
+    Allow { names: Vec<String> },
+    /// Stop handing new albums of these artists to the hook
+    Deny { names: Vec<String> },
+    /// Print the payloads the hook would get for the albums of the last run, without running it
+    Pending,
+}
+
+/// Commands for broadcast series
+#[derive(Subcommand, Debug)]
+enum BroadcastCommands {
+    /// Print new broadcasts of these artists as episodes
+    Follow { names: Vec<String> },
+    /// Stop following broadcasts of these artists
+    Unfollow { names: Vec<String> },
+}
 
+/// Commands for Spotify
+#[derive(Subcommand, Debug)]
+enum SpotifyCommands {
+    /// Allow the app from settings.spotify to create playlists for your account
+    Login,
+    /// Create a playlist with the tracks of stored albums
+    Playlist {
+        /// Name of the new playlist
+        #[arg(short, long, default_value = "New Releases")]
+        name: String,
+        /// Which stored albums go into the playlist
+        #[arg(short, long, value_enum, default_value_t = QuerySource::Previous)]
+        from: QuerySource,
+        /// Only the most popular track of every album
+        #[arg(short, long)]
+        top_track: bool,
+    },
 }
 
 /// Arguments for the program
@@ -418,6 +2124,60 @@ enum SubCommands {
 struct Args {
     #[clap(subcommand)]
     commands: Option<SubCommands>,
+
+    /// Save all musicbrainz responses into this directory
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Answer all musicbrainz requests from the responses saved with --record in this directory
+    #[arg(long, global = true, value_name = "DIR", value_parser = valid_dir)]
+    replay: Option<PathBuf>,
+
+    /// Ask musicbrainz again instead of using the responses cached with MBRG_CACHE_MINUTES
+    #[arg(long, global = true)]
+    refresh: bool,
+
+    /// Never ask, questions fail instead. This is the default when stdin is not a terminal
+    #[arg(long, global = true)]
+    non_interactive: bool,
+
+    /// Use the config in profiles/<NAME>.json next to the default config, which is the profile default
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+}
+
+/// is this a date like 2024-01-31
+fn valid_date(s: &str) -> Result<Date, String> {
+    let format = format_description::parse("[year]-[month]-[day]").map_err(|e| e.to_string())?;
+    Date::parse(s, &format).map_err(|_| format!("{} is not a date like 2024-01-31", s))
+}
+
+/// is this a duration like 90s, 20m or 1h30m
+fn valid_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("{} is not a duration like 90s, 20m or 1h30m", s);
+    let mut secs = 0u64;
+    let mut number = String::new();
+    for ch in s.trim().chars() {
+        if ch.is_ascii_digit() {
+            number.push(ch);
+            continue;
+        }
+        let unit = match ch {
+            'h' => 60 * 60,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        let n: u64 = std::mem::take(&mut number).parse().map_err(|_| invalid())?;
+        secs = n
+            .checked_mul(unit)
+            .and_then(|n| secs.checked_add(n))
+            .ok_or_else(invalid)?;
+    }
+    if !number.is_empty() || secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(secs))
 }
 
 /// is this directory a valid direcotry
@@ -432,46 +2192,490 @@ fn valid_dir(s: &str) -> Result<PathBuf, String> {
     }
 }
 
-fn run_subcommand(cmd: SubCommands, ratelimiter: Ratelimiter) -> Result<(), anyhow::Error> {
+/// Search the artists again, or only the doubtful ones with `low_confidence`, and let the user pick better matches.
+/// Without a terminal the better matches are only printed
+fn re_resolve(c: &mut Config, mb: &MbClient, low_confidence: bool, min_score: u8) -> Result<()> {
+    let scores = audit::scores()?;
+    let todo = if low_confidence {
+        reresolve::suspects(&c.artist_full, &scores, min_score)
+            .into_iter()
+            .map(|(a, r)| (a.clone(), Some(r)))
+            .collect::<Vec<_>>()
+    } else {
+        c.artist_full
+            .iter()
+            .filter(|a| !a.archived)
+            .map(|a| (a.clone(), None))
+            .collect()
+    };
+    if todo.is_empty() {
+        println!("{}", tr!("No artists with low confidence"));
+        return Ok(());
+    }
+    let now = c.settings.check.now();
+    let mut changed = false;
+    for (artist, reason) in todo {
+        let found = match Artist::search(mb, &artist.search_string) {
+            Ok(found) => reresolve::rank(&artist.search_string, found),
+            Err(e) if e.is_fatal() => return Err(abort_run(e, &artist.name)),
+            Err(e) => {
+                println!("{} {}: {}", tr!("Error").red(), artist.name, e);
+                continue;
+            }
+        };
+        audit::record(&[audit::Entry::found(
+            now,
+            "re-resolve",
+            &artist.search_string,
+            found.iter().map(|(_, c)| c.clone()).collect(),
+        )])?;
+        if found[0].0.id == artist.id {
+            println!("{}", tr!("{} is still the best match", artist.name).green());
+            continue;
+        }
+        let why = reason.map(|r| r.describe(&artist)).unwrap_or_default();
+        println!(
+            "{} {}",
+            tr!(
+                "{} for \"{}\" might be wrong",
+                artist.name.bold(),
+                artist.search_string
+            ),
+            why
+        );
+        if !prompt::is_interactive() {
+            println!(
+                "    {} {}",
+                tr!("better").yellow(),
+                audit::describe(&found[0].1)
+            );
+            continue;
+        }
+        let keep = Candidate {
+            id: artist.id,
+            name: artist.name.clone(),
+            disambiguation: None,
+            score: scores.get(&artist.id).copied(),
+        };
+        let mut items = vec![tr!("Keep {}", audit::describe(&keep))];
+        items.extend(
+            found
+                .iter()
+                .filter(|(a, _)| a.id != artist.id)
+                .map(|(_, c)| audit::describe(c)),
+        );
+        let chosen = prompt::select(tr!("Which artist did you mean?"), &items)?;
+        let Some((better, _)) = found
+            .into_iter()
+            .filter(|(a, _)| a.id != artist.id)
+            .nth(chosen.wrapping_sub(1))
+        else {
+            continue;
+        };
+        let Some(index) = c.artist_full.iter().position(|a| a.id == artist.id) else {
+            continue;
+        };
+        if c.artist_full.iter().any(|a| a.id == better.id) {
+            println!("{}", tr!("{} is already in the list", better.name));
+            c.artist_full.remove(index);
+        } else {
+            println!(
+                "{} {} -> {}",
+                tr!("Corrected").green(),
+                artist.name,
+                better.name
+            );
+            c.artist_full[index] = reresolve::correct(&artist, better);
+        }
+        changed = true;
+    }
+    if changed {
+        c.artist_full.sort_unstable();
+        c.write()?;
+    }
+    Ok(())
+}
+
+/// Run `cmd` and return the day in the time zone of the config, which counts the requests
+fn run_subcommand(cmd: SubCommands, mb: &MbClient) -> Result<Date> {
     let mut c = Config::read()?;
+    let today = c.settings.check.today();
     match cmd {
         SubCommands::Add { name } => {
-            let client = get_client()?;
-            let new_artist = Artist::new(&client, &name, &ratelimiter)?;
+            let now = c.settings.check.now();
+            let (new_artist, candidates) = match Artist::resolve(mb, &name) {
+                Ok(found) => found,
+                Err(e) => {
+                    audit::record(&[audit::Entry::failed(now, "add", &name, e.to_string())])?;
+                    return Err(e.into());
+                }
+            };
+            audit::record(&[audit::Entry::found(now, "add", &name, candidates)])?;
+            let new_artist = new_artist.added_by(ArtistSource::Manual, c.settings.check.today());
             println!(
-                "Found artist \"{}\" for search \"{}\"",
-                new_artist.name, new_artist.search_string
+                "{}",
+                tr!(
+                    "Found artist \"{}\" for search \"{}\"",
+                    new_artist.name,
+                    new_artist.search_string
+                )
             );
             if c.artist_full.iter().any(|a| a.id == new_artist.id) {
-                println!("Artist is already in the list");
+                println!("{}", tr!("Artist is already in the list"));
             } else {
                 c.artist_full.push(new_artist);
                 c.artist_full.sort_unstable();
                 c.write()?;
             }
         }
-        SubCommands::List => {
-            for i in c.artist_full {
-                println!("{}", i.name);
+        SubCommands::List {
+            long,
+            sort,
+            filter,
+            count,
+            archived,
+            format,
+            names_only,
+            mbids_only,
+        } => {
+            let style = if names_only {
+                ListStyle::Plain(pick::Fields::Names)
+            } else if mbids_only {
+                ListStyle::Plain(pick::Fields::Mbids)
+            } else if format == ListFormat::Plain {
+                ListStyle::Plain(pick::Fields::Both)
+            } else if long {
+                ListStyle::Long
+            } else {
+                ListStyle::Short
+            };
+            let today = c.settings.check.today();
+            let artists = c
+                .artist_full
+                .into_iter()
+                .filter(|a| a.archived == archived)
+                .collect();
+            list_artists(
+                artists,
+                &c.settings.activity,
+                today,
+                style,
+                sort,
+                filter,
+                count,
+            )?;
+        }
+        SubCommands::Pick { filter, archived } => {
+            let mut artists = c
+                .artist_full
+                .into_iter()
+                .filter(|a| a.archived == archived)
+                .collect::<Vec<_>>();
+            if let Some(filter) = filter {
+                artists.retain(|a| !a.matching_fields(&filter).is_empty());
+            }
+            artists.sort_by_key(|a| a.sort_name.to_lowercase());
+            match pick::pick(&artists)? {
+                Some(id) => println!("{}", id),
+                None => return Err(anyhow!(tr!("No artist picked"))),
             }
         }
-        SubCommands::Delete { names } => {
+        SubCommands::Delete {
+            names,
+            force: true,
+            yes,
+        } => {
+            let prompt = tr!(
+                "This will remove {} artists with all their data",
+                names.len()
+            );
+            if !prompt::confirm(&prompt, yes)? {
+                return Ok(today);
+            }
             for name in names {
                 if let Some(index) = c.artist_full.iter().position(|a| a.name == name) {
-                    println!("{} {}", "Removing".green(), name);
+                    println!("{} {}", tr!("Removing").green(), name);
                     c.artist_full.remove(index);
                 } else {
-                    println!("{} {}", "Did not find:".red(), name);
+                    println!("{} {}", tr!("Did not find:").red(), name);
                 }
             }
             c.write()?;
         }
-        SubCommands::New => {
-            if c.artist_full.is_empty() {
-                println!("We do not have any artists, did you forget to run init -f?");
-                return Ok(());
+        SubCommands::Delete {
+            names,
+            force: false,
+            ..
+        } => {
+            set_archived(&mut c, &names, true)?;
+            println!("{}", tr!("Use --force to remove them with all their data"));
+        }
+        SubCommands::Archive { names } => {
+            set_archived(&mut c, &names, true)?;
+        }
+        SubCommands::Unarchive { names } => {
+            set_archived(&mut c, &names, false)?;
+        }
+        SubCommands::New {
+            reissues,
+            country,
+            fill_ids,
+            format,
+            output,
+            force,
+            queue,
+            paging,
+            only_active,
+            max_duration,
+        } => {
+            if c.artist_full.is_empty() && (!fill_ids || c.artist_names.is_empty()) {
+                println!(
+                    "{}",
+                    tr!("We do not have any artists, did you forget to run init -f?")
+                );
+                return Ok(today);
+            }
+            let check = &c.settings.check;
+            let runs = if force { vec![] } else { history::read_runs()? };
+            if let Some(run) = history::recent_run(&runs, check.now(), check.cooldown_minutes) {
+                let started = run.started.to_offset(check.now().offset()).time();
+                let started = format!("{:02}:{:02}", started.hour(), started.minute());
+                match check.cooldown {
+                    Cooldown::Refuse => {
+                        return Err(anyhow!(tr!(
+                            "The last check was at {}, less than {} minutes ago. Use --force to check again",
+                            started,
+                            check.cooldown_minutes
+                        )))
+                    }
+                    Cooldown::Cached => {
+                        println!(
+                            "{}",
+                            tr!(
+                                "The last check was at {}, these are its results. Use --force to check again",
+                                started
+                            )
+                        );
+                        match &run.sections {
+                            Some(sections) => print_sections(
+                                &c.settings,
+                                &run.section(&sections.albums),
+                                &run.section(&sections.others),
+                                &run.section(&sections.broadcasts),
+                                sections
+                                    .reissues
+                                    .as_ref()
+                                    .map(|ids| run.section(ids))
+                                    .as_deref(),
+                                &paging,
+                                check.today(),
+                            )?,
+                            None => print_page(&run.albums, &paging, check.today())?,
+                        }
+                        return Ok(today);
+                    }
+                }
+            }
+            grab_new_releases(
+                mb,
+                NewOptions {
+                    check: check::CheckOptions {
+                        reissues,
+                        countries: country,
+                        fill_ids,
+                        only_active,
+                        max_duration,
+                        progress: true,
+                    },
+                    report: format.zip(output),
+                    queue,
+                    paging,
+                },
+            )?;
+        }
+        SubCommands::Today { notify } => {
+            let today = releases_today(&c);
+            println!("{}", tr!("Out today: {}", today.len()));
+            let theme = theme::current();
+            for i in &today {
+                println!(
+                    "{} - {}",
+                    i.artists().paint(theme.artist),
+                    i.title.paint(theme.title)
+                );
+            }
+            if notify && !today.is_empty() {
+                notify::notify_all(
+                    &c.settings.notifications,
+                    &c.artist_full,
+                    tr!("Out today"),
+                    &today,
+                );
+            }
+        }
+        SubCommands::Digest {
+            week,
+            format,
+            output,
+        } => {
+            let days = if week { 7 } else { 1 };
+            digest(mb, days, format, &output)?;
+        }
+        SubCommands::Suggest {
+            source,
+            labels,
+            max,
+            print,
+        } => {
+            suggest_artists(&mut c, mb, source, labels, max, print)?;
+        }
+        SubCommands::Report {
+            year,
+            format,
+            output,
+        } => {
+            let year = year.unwrap_or_else(|| c.settings.check.today().year());
+            let review = review::YearReview::new(year, stored_albums(&c, QuerySource::All)?);
+            output::write_output(&review.render(format)?, &output)?;
+        }
+        SubCommands::Search {
+            query,
+            format,
+            add_watch,
+        } => {
+            search(mb, &query, format, add_watch)?;
+        }
+        SubCommands::Radar {
+            genre,
+            since,
+            max,
+            format,
+            output,
+        } => {
+            let since =
+                since.unwrap_or_else(|| c.settings.check.today() - time::Duration::days(30));
+            radar(&c, mb, &genre, since, max, format, &output)?;
+        }
+        SubCommands::ListenLater { from, all } => {
+            listen_later(&mut c, mb, from, all)?;
+        }
+        SubCommands::Mpd { cmd } => match cmd {
+            MpdCommands::QueueNew { from } => {
+                let albums = stored_albums(&c, from)?;
+                let queued = mpd::queue(&c.settings.mpd, &albums)?;
+                for (a, tracks) in &queued {
+                    println!(
+                        "{} {} - {} ({})",
+                        tr!("Queued").green(),
+                        a.artists(),
+                        a.title,
+                        tr!("{} tracks", tracks)
+                    );
+                }
+                println!(
+                    "{}",
+                    tr!(
+                        "{} of {} albums are not in the MPD library",
+                        albums.len() - queued.len(),
+                        albums.len()
+                    )
+                );
+            }
+        },
+        SubCommands::Funkwhale { cmd } => match cmd {
+            FunkwhaleCommands::Import { scope } => {
+                let settings = &c.settings.funkwhale;
+                let artists = funkwhale::artists(
+                    &get_client()?,
+                    settings.server()?,
+                    settings.token.as_deref(),
+                    &scope,
+                )?;
+                let today = c.settings.check.today();
+                let (added, names) = funkwhale::import(&mut c, artists, today);
+                c.write()?;
+                println!("{}", tr!("Added {} artists from Funkwhale", added));
+                if names > 0 {
+                    println!(
+                        "{}",
+                        tr!(
+                            "{} artists have no MBID, run init --fill-ids to search them",
+                            names
+                        )
+                    );
+                }
+            }
+            FunkwhaleCommands::Publish { from } => {
+                let settings = &c.settings.funkwhale;
+                let (Some(token), Some(channel)) = (&settings.token, settings.channel) else {
+                    return Err(anyhow!(tr!(
+                        "Set settings.funkwhale.token and settings.funkwhale.channel first"
+                    )));
+                };
+                let sections = [Section {
+                    title: "Albums".to_string(),
+                    albums: stored_albums(&c, from)?,
+                }];
+                let text = output::render(OutputFormat::Markdown, "New releases", &sections)?;
+                funkwhale::publish(
+                    &get_client()?,
+                    settings.server()?,
+                    token,
+                    channel,
+                    &String::from_utf8_lossy(&text),
+                )?;
+                println!("{}", tr!("Published {} albums", sections[0].albums.len()));
+            }
+        },
+        SubCommands::Notify {
+            cmd: NotifyCommands::Test { name },
+        } => {
+            notify::test(&c.settings.notifications, name.as_deref())?;
+        }
+        SubCommands::Broadcasts { cmd } => match cmd {
+            BroadcastCommands::Follow { names } => set_broadcasts(&mut c, &names, true)?,
+            BroadcastCommands::Unfollow { names } => set_broadcasts(&mut c, &names, false)?,
+        },
+        SubCommands::Handoff { cmd } => match cmd {
+            HandoffCommands::Allow { names } => set_handoff(&mut c, &names, true)?,
+            HandoffCommands::Deny { names } => set_handoff(&mut c, &names, false)?,
+            HandoffCommands::Pending => {
+                if c.settings.hook.paused {
+                    println!("{}", tr!("The hook is paused in settings.hook.paused"));
+                }
+                let pending =
+                    hook::pending(&c.settings.hook, &c.artist_full, &c.handed_off, &c.previous);
+                for a in pending {
+                    let s =
+                        serde_json::to_string(&hook::Payload::from(a)).context("JSON to string")?;
+                    println!("{}", s);
+                }
+            }
+        },
+        SubCommands::Spotify { cmd } => match cmd {
+            SpotifyCommands::Login => {
+                let token = spotify::login(
+                    &get_client()?,
+                    spotify::ACCOUNTS_SERVER,
+                    &c.settings.spotify,
+                )?;
+                c.settings.spotify.refresh_token = Some(token);
+                c.write()?;
+                println!("{}", tr!("Logged in to Spotify"));
             }
-            grab_new_releases(&ratelimiter)?;
+            SpotifyCommands::Playlist {
+                name,
+                from,
+                top_track,
+            } => {
+                spotify_playlist(&c, mb, &name, from, top_track)?;
+            }
+        },
+        SubCommands::Daemon {
+            interval_hours,
+            profiles,
+        } => {
+            run_daemon(mb, interval_hours, &profiles)?;
         }
         SubCommands::Ignore { name } => {
             c.add_ignore(name)?;
@@ -480,61 +2684,166 @@ fn run_subcommand(cmd: SubCommands, ratelimiter: Ratelimiter) -> Result<(), anyh
             let last_date = c.last_checked_time;
             c.last_checked_time -= Duration::new(60 * 60 * 24 * days, 0);
             println!(
-                "Change date from |{}| to |{}|",
-                last_date, c.last_checked_time
+                "{}",
+                tr!(
+                    "Change date from |{}| to |{}|",
+                    format_date(Some(last_date)),
+                    format_date(Some(c.last_checked_time))
+                )
             );
             c.write()?;
         }
-        SubCommands::Previous | SubCommands::History => {
-            println!("Last checked on {}", c.last_checked_time);
-            println!("---------------------------------------------------------");
-            print_new_albums(&c.previous)?;
+        SubCommands::Query {
+            select,
+            filter,
+            from,
+        } => {
+            query(&c, &select, filter.as_deref(), from)?;
+        }
+        SubCommands::Audit { artist } => {
+            let entries = audit::entries_for(&artist)?;
+            if entries.is_empty() {
+                println!("{}", tr!("No resolutions of {} in the audit log", artist));
+            }
+            audit::print(&entries);
+        }
+        SubCommands::Mismatches { fix: true } => {
+            fix_mismatches(&mut c, mb)?;
+        }
+        SubCommands::Mismatches { fix: false } => {
+            let mismatches = mismatch::find(&c.artist_full);
+            if mismatches.is_empty() {
+                println!("{}", tr!("Every artist has the name it was searched for"));
+            }
+            mismatch::print(&mismatches);
+        }
+        SubCommands::ReResolve {
+            low_confidence,
+            min_score,
+        } => {
+            re_resolve(&mut c, mb, low_confidence, min_score)?;
+        }
+        SubCommands::Stats => {
+            let today = c.settings.check.today();
+            let (archived, followed): (Vec<&Artist>, Vec<&Artist>) =
+                c.artist_full.iter().partition(|a| a.archived);
+            println!(
+                "{}",
+                tr!(
+                    "{} artists, {} of them archived",
+                    c.artist_full.len(),
+                    archived.len()
+                )
+            );
+            let mut by_activity = BTreeMap::new();
+            for a in followed {
+                *by_activity
+                    .entry(c.settings.activity.classify(a, today))
+                    .or_insert(0) += 1;
+            }
+            for (activity, count) in by_activity {
+                println!("  {:>8}: {}", activity.to_string(), count);
+            }
+        }
+        SubCommands::Runs => {
+            let runs = history::read_runs()?;
+            if runs.is_empty() {
+                println!("{}", tr!("No runs recorded yet"));
+            }
+            for r in runs {
+                println!(
+                    "{}",
+                    tr!(
+                        "{} - {} - {} albums - {} artists with errors",
+                        r.id.to_string().bold(),
+                        r.started.to_string().blue(),
+                        r.albums.len(),
+                        r.errors
+                    )
+                );
+            }
+        }
+        SubCommands::DiffRuns { run_a, run_b } => {
+            let runs = history::read_runs()?;
+            let a = history::find_run(&runs, run_a)?;
+            let b = history::find_run(&runs, run_b)?;
+            print_run_diff(a, b, c.settings.check.today())?;
+        }
+        SubCommands::History {
+            cmd:
+                Some(HistoryCommands::Search {
+                    terms,
+                    since,
+                    until,
+                }),
+        } => {
+            let today = c.settings.check.today();
+            let found = stored_albums(&c, QuerySource::All)?
+                .into_iter()
+                .filter(|a| history::matches_terms(a, &terms))
+                .filter(|a| since.is_none_or(|s| a.date.is_some_and(|d| d >= s)))
+                .filter(|a| until.is_none_or(|u| a.date.is_some_and(|d| d <= u)))
+                .collect::<Vec<Album>>();
+            for a in &found {
+                let date = format_date(a.date);
+                let date = if a.date.is_some_and(|d| d > today) {
+                    date.blue().strike()
+                } else {
+                    date.blue().bold()
+                };
+                println!(
+                    "{} - {} - {} - ({})",
+                    history::highlight(&a.artists(), &terms, Style::new().red()),
+                    date,
+                    history::highlight(&a.title, &terms, Style::new().green()),
+                    a.release_type.to_string().yellow(),
+                );
+            }
+            println!("{}", tr!("Found {} albums", found.len()));
+        }
+        SubCommands::Previous { paging } => {
+            print_previous(&c, &paging)?;
+        }
+        SubCommands::History { cmd: None } => {
+            print_previous(&c, &paging::Paging::default())?;
         }
         SubCommands::Init {
             dir,
             fill_ids,
             clear,
+            yes,
         } => {
             if dir.is_none() && !fill_ids && clear.is_none() {
-                println!("Use at least one init argument");
-                println!("Try init -h");
-                return Ok(());
+                println!("{}", tr!("Use at least one init argument"));
+                println!("{}", tr!("Try init -h"));
+                return Ok(today);
             }
 
             if let Some(d) = dir {
-                let confirmation = Confirm::new()
-                    .default(false)
-                    .with_prompt("This will delete the whole configuration")
-                    .interact()
-                    .unwrap();
-                if confirmation {
+                if prompt::confirm(tr!("This will delete the whole configuration"), yes)? {
                     get_artists_from_directory(d)?;
                 }
             } else if fill_ids {
-                get_artist_ids(&ratelimiter)?;
+                get_artist_ids(mb)?;
             } else if let Some(cl) = clear {
                 let mut c = Config::read()?;
                 let confirm_string = match cl {
                     ClearValues::Ids => {
                         c.artist_full = vec![];
-                        "This will clear all artist ids!"
+                        tr!("This will clear all artist ids!")
                     }
                     ClearValues::Artists => {
                         c.artist_names = vec![];
-                        "This will clear all artist names."
+                        tr!("This will clear all artist names.")
                     }
                     ClearValues::WholeConfig => {
                         c = Config::default();
-                        "This will clear the whole configuration!"
+                        tr!("This will clear the whole configuration!")
                     }
                 };
-                let confirmation = Confirm::new()
-                    .default(false)
-                    .with_prompt(confirm_string)
-                    .interact()
-                    .unwrap();
-                if confirmation {
-                    return c.write();
+                if prompt::confirm(confirm_string, yes)? {
+                    c.write()?;
+                    return Ok(today);
                 }
             }
         }
@@ -542,27 +2851,217 @@ fn run_subcommand(cmd: SubCommands, ratelimiter: Ratelimiter) -> Result<(), anyh
             artists_not_in_config(&path)?;
         }
         SubCommands::Discography { artist_search } => {
-            get_specific_artist_id(&artist_search, &ratelimiter)?;
+            get_specific_artist_id(&artist_search, mb)?;
         }
-        SubCommands::ConfigSearch { artist_search } => {
-            let artist_found = c.artist_full.iter().find(|p| p.name.contains(&artist_search) || p.search_string.contains(&artist_search));
-            if let Some(a) = artist_found {
-                println!("Found artist {}", a.name);
+        SubCommands::Browse {
+            artist_search,
+            years,
+            format,
+        } => {
+            browse_artist(&artist_search, years, format, c.settings.check.today(), mb)?;
+        }
+        SubCommands::Config {
+            cmd: ConfigCommands::Diff { other },
+        } => {
+            let theirs = Config::read_from(&other)
+                .with_context(|| format!("Reading {}", other.display()))?;
+            diff::print_diff(&c, &theirs, &other.display().to_string())?;
+        }
+        SubCommands::Config {
+            cmd: ConfigCommands::Show { default },
+        } => {
+            let settings = if default {
+                config::Settings::default()
             } else {
-                println!("Artist not found");
+                c.settings.clone()
+            };
+            for r in options::rows(&settings) {
+                let value = if default || r.default.as_ref() == Some(&r.value) {
+                    r.value.green().to_string()
+                } else {
+                    r.value.yellow().bold().to_string()
+                };
+                match r.default.filter(|_| !default) {
+                    Some(d) => println!(
+                        "settings.{} = {} ({})",
+                        r.path.bold(),
+                        value,
+                        tr!("default {}", d)
+                    ),
+                    None => println!("settings.{} = {}", r.path.bold(), value),
+                }
+                println!("    {}", r.description.dim());
             }
+        }
+        SubCommands::Config {
+            cmd: ConfigCommands::Get { key },
+        } => match options::get(&c.settings, &key)? {
+            serde_json::Value::String(s) => println!("{}", s),
+            v => println!("{}", v),
         },
+        SubCommands::Config {
+            cmd: ConfigCommands::Set { key, value },
+        } => {
+            c.settings = options::set(&c.settings, &key, &value)?;
+            c.write()?;
+            println!(
+                "{}",
+                tr!("Set {} to {}", key, options::get(&c.settings, &key)?)
+            );
+        }
+        SubCommands::Sync {
+            cmd: SyncCommands::Merge { source },
+        } => {
+            let other = sync::SyncFile::read(&source, &get_client()?)?;
+            let summary = sync::merge(&mut c, other);
+            c.write()?;
+            print_merge_summary(&summary);
+        }
+        SubCommands::Sync {
+            cmd: SyncCommands::Export { output },
+        } => {
+            let s = serde_json::to_string_pretty(&sync::SyncFile::export(&c))
+                .context("JSON to string")?;
+            output::write_output(format!("{}\n", s).as_bytes(), &output)?;
+        }
+        SubCommands::Export {
+            kodi_nfo: Some(dir),
+            from,
+            ..
+        } => {
+            let albums = stored_albums(&c, from)?;
+            let without_cover = nfo::export(&get_client()?, nfo::COVER_ART_SERVER, &dir, &albums)?;
+            for a in &without_cover {
+                println!("{}", tr!("No cover for {} - {}", a.artists(), a.title));
+            }
+            println!(
+                "{}",
+                tr!("Exported {} albums to {}", albums.len(), dir.display())
+            );
+        }
+        SubCommands::Export {
+            format: ExportFormat::Msgpack,
+            output,
+            ..
+        } => {
+            let s = msgpack::to_vec_versioned(&sync::ShareList::export(&c))?;
+            output::write_output(&s, &output)?;
+        }
+        SubCommands::Export {
+            encrypt,
+            recipient,
+            format: ExportFormat::Json,
+            output,
+            ..
+        } => {
+            let s =
+                serde_json::to_string(&sync::ShareList::export(&c)).context("JSON to string")?;
+            let s = match (encrypt, recipient) {
+                (Some(e), Some(r)) => sync::encrypt(&s, e, &r)?,
+                _ => format!("{}\n", s),
+            };
+            output::write_output(s.as_bytes(), &output)?;
+        }
+        SubCommands::Import {
+            source, identity, ..
+        } => {
+            let s = sync::read_source(&source, &get_client()?)?;
+            let s = sync::decrypt(&s, &identity)?;
+            let list: sync::ShareList =
+                serde_json::from_str(&s).with_context(|| format!("Could not read {}", source))?;
+            let today = c.settings.check.today();
+            let added = list.import(&mut c, today);
+            c.write()?;
+            println!("{}", tr!("Added {} artists", added));
+        }
+        SubCommands::Edit { section } => {
+            edit::edit(&mut c, section)?;
+        }
+        SubCommands::Info { artist_search } => {
+            let artists = c
+                .artist_full
+                .iter()
+                .filter(|a| !a.matching_fields(&artist_search).is_empty())
+                .collect::<Vec<&Artist>>();
+            if artists.is_empty() {
+                println!("{}", tr!("Artist not found"));
+            }
+            let today = c.settings.check.today();
+            for a in artists {
+                print_artist_details(a, c.settings.activity.classify(a, today))?;
+                println!("{}", tr!("  Search string: {}", a.search_string));
+                println!("{}", tr!("  Aliases: {}", a.aliases.join(", ")));
+            }
+        }
+        SubCommands::Details { terms, json } => {
+            album_details(&c, mb, &terms, json)?;
+        }
+        SubCommands::ConfigSearch { artist_search } => {
+            let mut found = false;
+            for a in &c.artist_full {
+                let fields = a.matching_fields(&artist_search);
+                if !fields.is_empty() {
+                    found = true;
+                    println!(
+                        "{}",
+                        tr!(
+                            "Found artist {} ({}) matching {}",
+                            a.name.green(),
+                            a.id,
+                            fields.join(", ")
+                        )
+                    );
+                }
+            }
+            if !found {
+                println!("{}", tr!("Artist not found"));
+            }
+        }
     }
-    Ok(())
+    Ok(today)
 }
 
 fn main() -> Result<()> {
+    crash::install();
+    config::set_recovery(repair::recover);
     let args = Args::parse();
-    let ratelimiter = Ratelimiter::builder(30, Duration::from_secs(5))
-        .max_tokens(30)
+    prompt::setup(args.non_interactive);
+    config::set_profile(args.profile.as_deref())?;
+    env::apply_color()?;
+    let requests = env::rate_limit()?.unwrap_or(30);
+    let ratelimiter = Ratelimiter::builder(requests, Duration::from_secs(5))
+        .max_tokens(requests)
         .build()?;
+    let adaptive =
+        env::adaptive_rate()?.then(|| adaptive::AdaptiveRate::new(requests, adaptive::read()));
+    let cache = match env::cache_minutes()? {
+        Some(minutes) => {
+            let cache = responses::ResponseCache::new(
+                config::cache_dir()?.join("responses"),
+                Duration::from_secs(minutes * 60),
+                args.refresh,
+            );
+            cache.prune();
+            Some(cache)
+        }
+        None => None,
+    };
+    let mb = MbClient::new(get_client()?, ratelimiter)
+        .server(env::server())
+        .record(args.record)
+        .replay(args.replay)
+        .cache(cache)
+        .adaptive(adaptive);
     if let Some(cmd) = args.commands {
-        run_subcommand(cmd, ratelimiter)?;
+        let today = run_subcommand(cmd, &mb)?;
+        // commands besides checks do not report their requests, but they count for the day as well
+        if let Some(rate) = mb.learned_rate() {
+            adaptive::write(rate)?;
+        }
+        let calls = mb.take_calls();
+        if calls > 0 {
+            usage::record_calls(today, calls)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,134 @@
+use yansi::Paint;
+
+use crate::config::canonical_name;
+use crate::i18n::tr;
+use crate::reresolve::{name_similarity, MIN_SIMILARITY};
+use crate::responses::Artist;
+
+/// Above this similarity a mismatch is only a small difference, like a missing "The"
+const LOW_SIMILARITY: f64 = 0.8;
+
+/// How much the name of an artist differs from its search string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Low,
+    Medium,
+    /// probably another artist with a similar name
+    High,
+}
+
+impl Severity {
+    /// The severity of a name with `similarity` to its search string
+    fn of(similarity: f64) -> Self {
+        if similarity < MIN_SIMILARITY {
+            Severity::High
+        } else if similarity < LOW_SIMILARITY {
+            Severity::Medium
+        } else {
+            Severity::Low
+        }
+    }
+
+    /// The severity for the user, colored
+    pub fn paint(&self) -> String {
+        match self {
+            Severity::Low => tr!("low").green().to_string(),
+            Severity::Medium => tr!("medium").yellow().to_string(),
+            Severity::High => tr!("high").red().to_string(),
+        }
+    }
+}
+
+/// An artist whose name is not its search string
+#[derive(Debug)]
+pub struct Mismatch<'a> {
+    pub artist: &'a Artist,
+    /// of the best fitting name, sort name or alias, 1 is the same
+    pub similarity: f64,
+    pub severity: Severity,
+}
+
+/// The artists whose names are not their search strings, the most different first.
+/// Differences in case and characters like `'` do not count, neither do names that are an alias
+pub fn find(artists: &[Artist]) -> Vec<Mismatch<'_>> {
+    let mut res = artists
+        .iter()
+        .filter(|a| !a.archived)
+        .filter(|a| {
+            let search = canonical_name(&a.search_string);
+            std::iter::once(&a.name)
+                .chain(&a.aliases)
+                .all(|n| canonical_name(n) != search)
+        })
+        .map(|artist| {
+            let similarity = name_similarity(artist);
+            Mismatch {
+                artist,
+                similarity,
+                severity: Severity::of(similarity),
+            }
+        })
+        .collect::<Vec<_>>();
+    res.sort_by(|a, b| a.similarity.total_cmp(&b.similarity));
+    res
+}
+
+/// The mismatches with high severity, for the warnings of `new`
+pub fn high(artists: &[Artist]) -> Vec<Mismatch<'_>> {
+    find(artists)
+        .into_iter()
+        .filter(|m| m.severity == Severity::High)
+        .collect()
+}
+
+/// Print `mismatches` for `mismatches`
+pub fn print(mismatches: &[Mismatch]) {
+    for m in mismatches {
+        println!(
+            "{:>8} {} {}",
+            m.severity.paint(),
+            m.artist.name.bold(),
+            tr!("for search \"{}\"", m.artist.search_string)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artist(name: &str, search: &str) -> Artist {
+        Artist {
+            name: name.to_string(),
+            search_string: search.to_string(),
+            sort_name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mismatches_are_ranked_by_severity() {
+        let mut aliased = artist("Die Ärzte", "die arzte");
+        aliased.aliases = vec!["Die Arzte".to_string()];
+        let artists = vec![
+            artist("Guns N' Roses", "guns n roses"),
+            artist("The Beatles", "beatles"),
+            artist("Nirvana Tribute Band", "nirvana"),
+            artist("The Beatless", "the beatles"),
+            aliased,
+        ];
+        let found = find(&artists)
+            .into_iter()
+            .map(|m| (m.artist.name.as_str(), m.severity))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            vec![
+                ("Nirvana Tribute Band", Severity::High),
+                ("The Beatles", Severity::Medium),
+                ("The Beatless", Severity::Low),
+            ]
+        );
+        assert_eq!(high(&artists).len(), 1);
+    }
+}
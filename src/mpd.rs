@@ -0,0 +1,243 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+
+use crate::config::canonical_name;
+use crate::responses::Album;
+
+/// Settings for the MPD server whose queue gets the new albums
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpdSettings {
+    pub host: String,
+    pub port: u16,
+    pub password: Option<String>,
+}
+
+impl Default for MpdSettings {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 6600,
+            password: None,
+        }
+    }
+}
+
+/// A song in the MPD database with the tags we match on
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Song {
+    file: String,
+    artist: String,
+    album_artist: String,
+    album: String,
+}
+
+impl Song {
+    /// If the tags of the song say it is on album `a`
+    fn is_on(&self, a: &Album) -> bool {
+        let artist = canonical_name(&a.artist);
+        canonical_name(&self.album) == canonical_name(&a.title)
+            && [&self.album_artist, &self.artist]
+                .iter()
+                .any(|n| canonical_name(n) == artist)
+    }
+}
+
+/// `s` as an argument of an MPD command
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// A connection to MPD
+struct Mpd<S: Read + Write> {
+    reader: BufReader<S>,
+}
+
+impl<S: Read + Write> Mpd<S> {
+    /// Read the greeting of the server on `stream` and log in with `password`
+    fn new(stream: S, password: Option<&str>) -> Result<Self> {
+        let mut reader = BufReader::new(stream);
+        let mut greeting = String::new();
+        reader.read_line(&mut greeting)?;
+        if !greeting.starts_with("OK MPD") {
+            return Err(anyhow!("Not an MPD server: {}", greeting.trim_end()));
+        }
+        let mut mpd = Self { reader };
+        if let Some(p) = password {
+            mpd.command(&format!("password {}", quote(p)))?;
+        }
+        Ok(mpd)
+    }
+
+    /// Run `command` and return the key value pairs of the answer
+    fn command(&mut self, command: &str) -> Result<Vec<(String, String)>> {
+        self.reader
+            .get_mut()
+            .write_all(format!("{}\n", command.replace('\n', " ")).as_bytes())
+            .context("Could not write to MPD")?;
+        let mut res = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(anyhow!("MPD closed the connection"));
+            }
+            let line = line.trim_end_matches('\n');
+            if line == "OK" {
+                return Ok(res);
+            }
+            if let Some(e) = line.strip_prefix("ACK ") {
+                return Err(anyhow!("MPD refused {}: {}", command, e));
+            }
+            if let Some((k, v)) = line.split_once(": ") {
+                res.push((k.to_string(), v.to_string()));
+            }
+        }
+    }
+
+    /// The files of album `a` in the library, tags decide and not the directories
+    fn album_files(&mut self, a: &Album) -> Result<Vec<String>> {
+        let answer = self.command(&format!("search album {}", quote(&a.title)))?;
+        let mut songs: Vec<Song> = Vec::new();
+        for (k, v) in answer {
+            match k.as_str() {
+                "file" => songs.push(Song {
+                    file: v,
+                    ..Default::default()
+                }),
+                "Artist" | "AlbumArtist" | "Album" => {
+                    let Some(s) = songs.last_mut() else { continue };
+                    match k.as_str() {
+                        "Artist" => s.artist = v,
+                        "AlbumArtist" => s.album_artist = v,
+                        _ => s.album = v,
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(songs
+            .into_iter()
+            .filter(|s| s.is_on(a))
+            .map(|s| s.file)
+            .collect())
+    }
+}
+
+/// Append the albums of `albums` that are in the library of MPD to its queue.
+/// Returns the added albums with how many tracks they have
+fn queue_on<'a, S: Read + Write>(
+    stream: S,
+    password: Option<&str>,
+    albums: &'a [Album],
+) -> Result<Vec<(&'a Album, usize)>> {
+    let mut mpd = Mpd::new(stream, password)?;
+    let mut res = Vec::new();
+    for a in albums {
+        let files = mpd.album_files(a)?;
+        if files.is_empty() {
+            continue;
+        }
+        for f in &files {
+            mpd.command(&format!("add {}", quote(f)))?;
+        }
+        res.push((a, files.len()));
+    }
+    // MPD does not answer this, it just hangs up
+    let _ = mpd.reader.get_mut().write_all(b"close\n");
+    Ok(res)
+}
+
+/// Append the albums of `albums` that are in the library of the MPD server in `settings` to its queue
+pub fn queue<'a>(settings: &MpdSettings, albums: &'a [Album]) -> Result<Vec<(&'a Album, usize)>> {
+    let stream = TcpStream::connect((settings.host.as_str(), settings.port))
+        .with_context(|| format!("Could not connect to MPD on {}", settings.host))?;
+    queue_on(stream, settings.password.as_deref(), albums)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+    use std::collections::VecDeque;
+    use uuid::Uuid;
+
+    struct FakeMpd {
+        answers: VecDeque<String>,
+        output: Vec<u8>,
+    }
+
+    impl Read for FakeMpd {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let Some(a) = self.answers.pop_front() else {
+                return Ok(0);
+            };
+            buf[..a.len()].copy_from_slice(a.as_bytes());
+            Ok(a.len())
+        }
+    }
+
+    impl Write for FakeMpd {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.output.write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn album(n: u128, artist: &str, title: &str) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: artist.to_string(),
+            title: title.to_string(),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn queues_albums_matched_by_tags() {
+        let answers = [
+            "OK MPD 0.23.5\n",
+            "OK\n",
+            "file: a/1.flac\nArtist: Guest\nAlbumArtist: Guns N' Roses\nAlbum: Live\n\
+             file: b/1.flac\nArtist: Other\nAlbum: Live\nfile: a/2.flac\nAlbumArtist: guns n roses\nAlbum: live\nOK\n",
+            "OK\n",
+            "OK\n",
+            "file: c/1.mp3\nArtist: Someone\nAlbum: Say \"Hi\"\nOK\n",
+        ];
+        let mut stream = FakeMpd {
+            answers: answers.iter().map(|a| a.to_string()).collect(),
+            output: vec![],
+        };
+        let albums = [
+            album(1, "Guns N' Roses", "Live"),
+            album(2, "Nobody", "Say \"Hi\""),
+        ];
+        let queued = queue_on(&mut stream, Some("secret"), &albums).unwrap();
+        assert_eq!(queued, vec![(&albums[0], 2)]);
+        assert_eq!(
+            String::from_utf8(stream.output).unwrap(),
+            "password \"secret\"\nsearch album \"Live\"\nadd \"a/1.flac\"\nadd \"a/2.flac\"\n\
+             search album \"Say \\\"Hi\\\"\"\nclose\n"
+        );
+    }
+
+    #[test]
+    fn refused_commands_are_errors() {
+        let mut stream = FakeMpd {
+            answers: [
+                "OK MPD 0.23.5\n",
+                "ACK [3@0] {password} incorrect password\n",
+            ]
+            .iter()
+            .map(|a| a.to_string())
+            .collect(),
+            output: vec![],
+        };
+        let e = queue_on(&mut stream, Some("wrong"), &[]).unwrap_err();
+        assert!(e.to_string().contains("incorrect password"));
+    }
+}
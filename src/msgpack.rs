@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Version of the layout of the data we write, increased whenever a field changes incompatibly
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The data together with the version of its layout
+#[derive(Serialize)]
+struct Versioned<'a, T> {
+    schema_version: u32,
+    data: &'a T,
+}
+
+/// Encode `value` as MessagePack in a map with the `schema_version` and the `data`
+pub fn to_vec_versioned<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    let value = serde_json::to_value(Versioned {
+        schema_version: SCHEMA_VERSION,
+        data: value,
+    })
+    .context("Converting to a value")?;
+    let mut out = Vec::new();
+    encode(&value, &mut out)?;
+    Ok(out)
+}
+
+/// Append the marker for a string, array or map of `len` elements. `small` is the fix marker
+/// for lengths below `small_max`, `wide` the markers for 8, 16 and 32 bit lengths
+fn write_len(
+    out: &mut Vec<u8>,
+    len: usize,
+    small: u8,
+    small_max: usize,
+    wide: [Option<u8>; 3],
+) -> Result<()> {
+    if len < small_max {
+        out.push(small | len as u8);
+    } else if let (Some(marker), Ok(len)) = (wide[0], u8::try_from(len)) {
+        out.push(marker);
+        out.push(len);
+    } else if let (Some(marker), Ok(len)) = (wide[1], u16::try_from(len)) {
+        out.push(marker);
+        out.extend_from_slice(&len.to_be_bytes());
+    } else if let (Some(marker), Ok(len)) = (wide[2], u32::try_from(len)) {
+        out.push(marker);
+        out.extend_from_slice(&len.to_be_bytes());
+    } else {
+        return Err(anyhow!("{} elements are too many for MessagePack", len));
+    }
+    Ok(())
+}
+
+/// Append the MessagePack encoding of `value` to `out`
+fn encode(value: &Value, out: &mut Vec<u8>) -> Result<()> {
+    match value {
+        Value::Null => out.push(0xc0),
+        Value::Bool(false) => out.push(0xc2),
+        Value::Bool(true) => out.push(0xc3),
+        Value::Number(n) => {
+            if let Some(u) = n.as_u64() {
+                if u < 0x80 {
+                    out.push(u as u8);
+                } else if let Ok(u) = u8::try_from(u) {
+                    out.extend_from_slice(&[0xcc, u]);
+                } else if let Ok(u) = u16::try_from(u) {
+                    out.push(0xcd);
+                    out.extend_from_slice(&u.to_be_bytes());
+                } else if let Ok(u) = u32::try_from(u) {
+                    out.push(0xce);
+                    out.extend_from_slice(&u.to_be_bytes());
+                } else {
+                    out.push(0xcf);
+                    out.extend_from_slice(&u.to_be_bytes());
+                }
+            } else if let Some(i) = n.as_i64() {
+                // only negative numbers end up here
+                if i >= -32 {
+                    out.push(i as u8);
+                } else if let Ok(i) = i8::try_from(i) {
+                    out.push(0xd0);
+                    out.extend_from_slice(&i.to_be_bytes());
+                } else if let Ok(i) = i16::try_from(i) {
+                    out.push(0xd1);
+                    out.extend_from_slice(&i.to_be_bytes());
+                } else if let Ok(i) = i32::try_from(i) {
+                    out.push(0xd2);
+                    out.extend_from_slice(&i.to_be_bytes());
+                } else {
+                    out.push(0xd3);
+                    out.extend_from_slice(&i.to_be_bytes());
+                }
+            } else {
+                let f = n.as_f64().unwrap_or(f64::NAN);
+                out.push(0xcb);
+                out.extend_from_slice(&f.to_be_bytes());
+            }
+        }
+        Value::String(s) => {
+            write_len(out, s.len(), 0xa0, 32, [Some(0xd9), Some(0xda), Some(0xdb)])?;
+            out.extend_from_slice(s.as_bytes());
+        }
+        Value::Array(a) => {
+            write_len(out, a.len(), 0x90, 16, [None, Some(0xdc), Some(0xdd)])?;
+            for v in a {
+                encode(v, out)?;
+            }
+        }
+        Value::Object(o) => {
+            write_len(out, o.len(), 0x80, 16, [None, Some(0xde), Some(0xdf)])?;
+            for (k, v) in o {
+                write_len(out, k.len(), 0xa0, 32, [Some(0xd9), Some(0xda), Some(0xdb)])?;
+                out.extend_from_slice(k.as_bytes());
+                encode(v, out)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encoded(v: Value) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode(&v, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn encodes_like_the_spec() {
+        assert_eq!(encoded(json!(null)), [0xc0]);
+        assert_eq!(encoded(json!(true)), [0xc3]);
+        assert_eq!(encoded(json!(5)), [0x05]);
+        assert_eq!(encoded(json!(200)), [0xcc, 200]);
+        assert_eq!(encoded(json!(1000)), [0xcd, 0x03, 0xe8]);
+        assert_eq!(encoded(json!(-1)), [0xff]);
+        assert_eq!(encoded(json!(-100)), [0xd0, 0x9c]);
+        assert_eq!(encoded(json!(1.5)), [0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(encoded(json!("ab")), [0xa2, b'a', b'b']);
+        assert_eq!(encoded(json!([1, [2]])), [0x92, 0x01, 0x91, 0x02]);
+        assert_eq!(encoded(json!({"a": 1})), [0x81, 0xa1, b'a', 0x01]);
+        let long = "x".repeat(40);
+        assert_eq!(encoded(json!(long))[..2], [0xd9, 40]);
+        let many = vec![0; 20];
+        assert_eq!(encoded(json!(many))[..3], [0xdc, 0, 20]);
+    }
+
+    #[test]
+    fn versioned_wraps_the_data() {
+        let out = to_vec_versioned(&vec!["a"]).unwrap();
+        let mut expected = vec![0x82, 0xa4];
+        expected.extend_from_slice(b"data");
+        expected.extend_from_slice(&[0x91, 0xa1, b'a', 0xae]);
+        expected.extend_from_slice(b"schema_version");
+        expected.push(SCHEMA_VERSION as u8);
+        assert_eq!(out, expected);
+    }
+}
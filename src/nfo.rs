@@ -0,0 +1,171 @@
+use anyhow::{Context, Result};
+use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+use crate::output::escape_html;
+use crate::responses::Album;
+
+/// The Cover Art Archive, which has the covers of the release groups on musicbrainz
+pub const COVER_ART_SERVER: &str = "https://coverartarchive.org";
+/// Kodi and Jellyfin read the album metadata from this file in the album directory
+const NFO_FILE: &str = "album.nfo";
+/// and the cover from this one
+const COVER_FILE: &str = "cover.jpg";
+
+/// `s` without the characters file systems do not allow in names
+fn file_name(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c => c,
+        })
+        .collect::<String>()
+        .trim_end_matches(['.', ' '])
+        .to_string()
+}
+
+/// The directory of album `a` in `dir`, like `dir/Artist/Title`
+pub fn album_dir(dir: &Path, a: &Album) -> PathBuf {
+    dir.join(file_name(&a.artist)).join(file_name(&a.title))
+}
+
+/// The Kodi album nfo of `a`
+pub fn album_nfo(a: &Album) -> String {
+    let mut nfo =
+        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\" ?>\n<album>\n");
+    let mut tag = |name: &str, value: &str| {
+        nfo.push_str(&format!("    <{0}>{1}</{0}>\n", name, escape_html(value)));
+    };
+    tag("title", &a.title);
+    tag("musicbrainzreleasegroupid", &a.id.to_string());
+    for artist in std::iter::once(&a.artist).chain(a.other_artists.iter()) {
+        tag("artist", artist);
+    }
+    tag("artistdesc", &a.artists());
+    tag("type", &a.release_type.to_string());
+    if let Some(d) = a.date {
+        tag("releasedate", &d.to_string());
+        tag("year", &d.year().to_string());
+    }
+    for f in &a.formats {
+        tag("style", &f.to_string());
+    }
+    if let Some(r) = a.rating {
+        nfo.push_str(&format!(
+            "    <rating max=\"10\">{:.1}</rating>\n    <votes>{}</votes>\n",
+            f32::from(r.score) / 10.0,
+            r.votes
+        ));
+    }
+    nfo.push_str("</album>\n");
+    nfo
+}
+
+/// The front cover of the release group with `id`, `None` if it has none
+pub fn fetch_cover(client: &Client, server: &str, id: &Uuid) -> Result<Option<Vec<u8>>> {
+    let response = client
+        .get(format!("{}/release-group/{}/front-500", server, id))
+        .send()
+        .context("Could not reach the Cover Art Archive")?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let bytes = response
+        .error_for_status()
+        .context("Cover Art Archive error")?
+        .bytes()
+        .context("Could not read cover")?;
+    Ok(Some(bytes.to_vec()))
+}
+
+/// Write the nfo and cover of every album into its directory in `dir`, existing covers are kept.
+/// Returns the albums we found no cover for
+pub fn export<'a>(
+    client: &Client,
+    server: &str,
+    dir: &Path,
+    albums: &'a [Album],
+) -> Result<Vec<&'a Album>> {
+    let mut without_cover = vec![];
+    for a in albums {
+        let path = album_dir(dir, a);
+        fs::create_dir_all(&path).with_context(|| format!("Could not create {:?}", path))?;
+        fs::write(path.join(NFO_FILE), album_nfo(a))
+            .with_context(|| format!("Could not write nfo of {}", a.title))?;
+        let cover = path.join(COVER_FILE);
+        if cover.exists() {
+            continue;
+        }
+        match fetch_cover(client, server, &a.id)
+            .with_context(|| format!("Could not get cover of {}", a.title))?
+        {
+            Some(bytes) => fs::write(&cover, bytes)
+                .with_context(|| format!("Could not write cover of {}", a.title))?,
+            None => without_cover.push(a),
+        }
+    }
+    Ok(without_cover)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use crate::responses::{Rating, ReleaseType};
+
+    use time::{Date, Month};
+
+    fn album(n: u128, title: &str) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "AC/DC".to_string(),
+            title: title.to_string(),
+            date: Some(Date::from_calendar_date(2025, Month::March, 7).unwrap()),
+            release_type: ReleaseType::Album,
+            rating: Some(Rating {
+                score: 84,
+                votes: 3,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nfo_of_album() {
+        let nfo = album_nfo(&album(1, "Rock & Roll"));
+        assert!(nfo.contains("<title>Rock &amp; Roll</title>"));
+        assert!(nfo.contains(
+            "<musicbrainzreleasegroupid>00000000-0000-0000-0000-000000000001</musicbrainzreleasegroupid>"
+        ));
+        assert!(nfo.contains("<releasedate>2025-03-07</releasedate>"));
+        assert!(nfo.contains("<year>2025</year>"));
+        assert!(nfo.contains("<rating max=\"10\">8.4</rating>"));
+        assert_eq!(
+            album_dir(Path::new("/s"), &album(1, "What?")),
+            PathBuf::from("/s/AC_DC/What_")
+        );
+    }
+
+    #[test]
+    fn export_writes_nfo_and_cover() {
+        let server = MockServer::start(vec![
+            (200, "jpeg".to_string()),
+            (404, "Not found".to_string()),
+        ]);
+        let dir = std::env::temp_dir().join(format!("mbrg-nfo-{}", fastrand::u64(..)));
+        let albums = [album(1, "One"), album(2, "Two")];
+        let without = export(&Client::new(), server.url(), &dir, &albums).unwrap();
+        assert_eq!(without, vec![&albums[1]]);
+        let one = album_dir(&dir, &albums[0]);
+        assert_eq!(fs::read_to_string(one.join(COVER_FILE)).unwrap(), "jpeg");
+        assert!(album_dir(&dir, &albums[1]).join(NFO_FILE).exists());
+        assert_eq!(
+            server.requests()[0],
+            "GET /release-group/00000000-0000-0000-0000-000000000001/front-500 HTTP/1.1"
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
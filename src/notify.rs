@@ -0,0 +1,672 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::process::Command;
+use time::Date;
+use yansi::Paint;
+
+use crate::chat::{IrcNotifier, IrcSettings, XmppNotifier, XmppSettings};
+use crate::i18n::{format_date, tr};
+use crate::output::escape_html;
+use crate::responses::{Album, Artist, ReleaseType};
+use crate::secret;
+use crate::webhook::{DiscordNotifier, SlackNotifier, WebhookSettings};
+
+/// Settings for sending notifications
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationSettings {
+    /// command that gets called with title and body appended as arguments, e.g., `["notify-send"]`
+    pub command: Vec<String>,
+    /// local hour after which the daemon sends the "out today" notification
+    pub today_hour: u8,
+    /// post to a Mastodon account
+    pub mastodon: Option<MastodonSettings>,
+    /// post to an IRC channel
+    pub irc: Option<IrcSettings>,
+    /// send XMPP messages
+    pub xmpp: Option<XmppSettings>,
+    /// post to a Matrix room
+    pub matrix: Option<MatrixSettings>,
+    /// post to an incoming webhook of Slack
+    pub slack: Option<WebhookSettings>,
+    /// post to a webhook of a Discord channel
+    pub discord: Option<WebhookSettings>,
+    /// which albums a notifier gets, by the name of the notifier like `matrix`
+    pub filters: BTreeMap<String, NotifyFilter>,
+    /// the daemon holds notifications in these hours and sends them as one when they end
+    pub quiet_hours: Option<QuietHours>,
+    /// the daemon holds all notifications and sends them as one at this local hour every day
+    pub digest_hour: Option<u8>,
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        Self {
+            command: vec![],
+            today_hour: 8,
+            mastodon: None,
+            irc: None,
+            xmpp: None,
+            matrix: None,
+            slack: None,
+            discord: None,
+            filters: BTreeMap::new(),
+            quiet_hours: None,
+            digest_hour: None,
+        }
+    }
+}
+
+impl NotificationSettings {
+    /// Are notifications held back at `hour`
+    fn quiet(&self, hour: u8) -> bool {
+        self.quiet_hours.is_some_and(|q| q.contains(hour))
+    }
+}
+
+/// Which albums a notifier gets
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyFilter {
+    /// only plain albums, no singles, EPs, live albums and so on
+    pub albums_only: bool,
+    /// only albums of artists with one of these tags
+    pub tags: Vec<String>,
+    /// only albums of these artists
+    pub artists: Vec<String>,
+    /// no notification with fewer albums than this
+    pub min_albums: usize,
+}
+
+impl NotifyFilter {
+    /// The `albums` that pass the filter, the tags come from the followed `artists`.
+    /// Empty if there are less than `min_albums`
+    pub fn apply<'a>(&self, artists: &[Artist], albums: &[&'a Album]) -> Vec<&'a Album> {
+        let has_tag = |name: &str| {
+            artists.iter().any(|a| {
+                a.name == name
+                    && a.tags
+                        .iter()
+                        .any(|t| self.tags.iter().any(|f| f.eq_ignore_ascii_case(t)))
+            })
+        };
+        let res = albums
+            .iter()
+            .filter(|a| !self.albums_only || a.release_type == ReleaseType::Album)
+            .filter(|a| {
+                let mut names = std::iter::once(&a.artist).chain(a.other_artists.iter());
+                (self.artists.is_empty()
+                    || names
+                        .clone()
+                        .any(|n| self.artists.iter().any(|f| f.eq_ignore_ascii_case(n))))
+                    && (self.tags.is_empty() || names.any(|n| has_tag(n)))
+            })
+            .copied()
+            .collect::<Vec<&Album>>();
+        if res.len() < self.min_albums.max(1) {
+            vec![]
+        } else {
+            res
+        }
+    }
+}
+
+/// Local hours from `from` until before `until`, they can span midnight like 22 to 7
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub from: u8,
+    pub until: u8,
+}
+
+impl QuietHours {
+    /// Is `hour` in the quiet hours
+    pub fn contains(&self, hour: u8) -> bool {
+        if self.from <= self.until {
+            self.from <= hour && hour < self.until
+        } else {
+            hour >= self.from || hour < self.until
+        }
+    }
+}
+
+/// Albums the daemon did not notify about yet because of quiet hours or the daily digest
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HeldNotifications {
+    pub albums: Vec<Album>,
+    /// the day we last sent the daily digest
+    pub last_digest: Option<Date>,
+}
+
+impl HeldNotifications {
+    /// Hold back `albums` if notifications at `hour` wait, true if they got held
+    pub fn hold(&mut self, settings: &NotificationSettings, hour: u8, albums: &[&Album]) -> bool {
+        if settings.digest_hour.is_none() && !settings.quiet(hour) {
+            return false;
+        }
+        for a in albums {
+            if !self.albums.contains(a) {
+                self.albums.push((*a).clone());
+            }
+        }
+        true
+    }
+
+    /// Take the held albums if we can send them on `date` at `hour`
+    pub fn take_due(
+        &mut self,
+        settings: &NotificationSettings,
+        date: Date,
+        hour: u8,
+    ) -> Vec<Album> {
+        if self.albums.is_empty() || settings.quiet(hour) {
+            return vec![];
+        }
+        if let Some(h) = settings.digest_hour {
+            if hour < h || self.last_digest == Some(date) {
+                return vec![];
+            }
+            self.last_digest = Some(date);
+        }
+        let mut res = std::mem::take(&mut self.albums);
+        res.sort_unstable();
+        res
+    }
+}
+
+/// Settings for posting to Mastodon or another server with its api
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MastodonSettings {
+    /// the instance, like `https://mastodon.social`
+    pub server: String,
+    /// access token of an application with the write:statuses scope, or a `file:`, `env:` or `command:` reference
+    pub token: String,
+    /// one post for every album instead of one summary
+    #[serde(default)]
+    pub per_album: bool,
+    /// public, unlisted, private or direct
+    #[serde(default = "default_visibility")]
+    pub visibility: String,
+}
+
+/// Settings for posting to a Matrix room
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MatrixSettings {
+    /// like `https://matrix.org`
+    pub homeserver: String,
+    /// access token of the account that posts, or a `file:`, `env:` or `command:` reference
+    pub token: String,
+    /// the id of the room like `!abc:matrix.org`, the account has to be in it
+    pub room: String,
+}
+
+/// Posts should not flood the public timelines
+fn default_visibility() -> String {
+    "unlisted".to_string()
+}
+
+/// Something that can tell the user about releases
+pub trait Notifier {
+    /// name of the notifier for error messages
+    fn name(&self) -> &str;
+    /// send a notification with `title` and `body`
+    fn send(&self, title: &str, body: &str) -> Result<()>;
+    /// send a notification with `title` about `albums`, notifiers that can show more than text override this
+    fn send_albums(&self, title: &str, albums: &[&Album]) -> Result<()> {
+        self.send(title, &albums_body(albums))
+    }
+}
+
+/// Runs a user configured command like `notify-send`
+struct CommandNotifier {
+    command: Vec<String>,
+}
+
+impl Notifier for CommandNotifier {
+    fn name(&self) -> &str {
+        "command"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        let (program, args) = self
+            .command
+            .split_first()
+            .ok_or_else(|| anyhow!("Empty notification command"))?;
+        let status = Command::new(program)
+            .args(args)
+            .arg(title)
+            .arg(body)
+            .status()
+            .with_context(|| format!("Could not run notification command {}", program))?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Notification command exited with {}", status))
+        }
+    }
+}
+
+/// Longest post Mastodon allows by default
+const MASTODON_MAX_CHARS: usize = 500;
+
+/// Posts statuses to a Mastodon account
+struct MastodonNotifier {
+    client: Client,
+    settings: MastodonSettings,
+}
+
+impl MastodonNotifier {
+    /// Post `status`, cut to the length Mastodon allows
+    fn post(&self, status: &str) -> Result<()> {
+        let status = if status.chars().count() > MASTODON_MAX_CHARS {
+            let cut = status
+                .chars()
+                .take(MASTODON_MAX_CHARS - 1)
+                .collect::<String>();
+            format!("{}…", cut)
+        } else {
+            status.to_string()
+        };
+        let response = self
+            .client
+            .post(format!(
+                "{}/api/v1/statuses",
+                self.settings.server.trim_end_matches('/')
+            ))
+            .bearer_auth(secret::resolve(&self.settings.token)?)
+            .json(&json!({ "status": status, "visibility": self.settings.visibility }))
+            .send()
+            .context("Could not reach Mastodon")?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Mastodon answered with {}", response.status()))
+        }
+    }
+}
+
+impl Notifier for MastodonNotifier {
+    fn name(&self) -> &str {
+        "mastodon"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        self.post(&format!("{}\n\n{}", title, body))
+    }
+
+    fn send_albums(&self, title: &str, albums: &[&Album]) -> Result<()> {
+        if !self.settings.per_album {
+            return self.send(title, &albums_body(albums));
+        }
+        for a in albums {
+            self.post(&format!(
+                "{} - {} ({}, {})\n{}",
+                a.artists(),
+                a.title,
+                a.release_type,
+                format_date(a.date),
+                musicbrainz_url(a)
+            ))?;
+        }
+        Ok(())
+    }
+}
+
+/// Posts messages to a Matrix room
+struct MatrixNotifier {
+    client: Client,
+    settings: MatrixSettings,
+}
+
+impl MatrixNotifier {
+    /// Post a message with the `plain` text and the same as `html`
+    fn post(&self, plain: &str, html: &str) -> Result<()> {
+        let mut url = Url::parse(self.settings.homeserver.trim_end_matches('/'))
+            .context("Invalid Matrix homeserver")?;
+        // every message needs its own transaction id, or the homeserver ignores it as a retry
+        let txn = format!(
+            "mbrg-{}",
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos()
+        );
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid Matrix homeserver"))?
+            .extend([
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &self.settings.room,
+                "send",
+                "m.room.message",
+                &txn,
+            ]);
+        let response = self
+            .client
+            .put(url)
+            .bearer_auth(secret::resolve(&self.settings.token)?)
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": plain,
+                "format": "org.matrix.custom.html",
+                "formatted_body": html,
+            }))
+            .send()
+            .context("Could not reach the Matrix homeserver")?;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Matrix answered with {}", response.status()))
+        }
+    }
+}
+
+impl Notifier for MatrixNotifier {
+    fn name(&self) -> &str {
+        "matrix"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        let lines = body
+            .lines()
+            .map(escape_html)
+            .collect::<Vec<String>>()
+            .join("<br>");
+        self.post(
+            &format!("{}\n{}", title, body),
+            &format!("<b>{}</b><br>{}", escape_html(title), lines),
+        )
+    }
+
+    fn send_albums(&self, title: &str, albums: &[&Album]) -> Result<()> {
+        let items = albums
+            .iter()
+            .map(|a| {
+                format!(
+                    "<li>{} - <a href=\"{}\">{}</a> ({}, {})</li>",
+                    escape_html(&a.artists()),
+                    musicbrainz_url(a),
+                    escape_html(&a.title),
+                    a.release_type,
+                    format_date(a.date)
+                )
+            })
+            .collect::<String>();
+        self.post(
+            &format!("{}\n{}", title, albums_body(albums)),
+            &format!("<b>{}</b><ul>{}</ul>", escape_html(title), items),
+        )
+    }
+}
+
+/// The page of the release group of `a` on musicbrainz
+pub fn musicbrainz_url(a: &Album) -> String {
+    format!("https://musicbrainz.org/release-group/{}", a.id)
+}
+
+/// All notifiers that are configured
+fn notifiers(settings: &NotificationSettings) -> Vec<Box<dyn Notifier>> {
+    let mut res: Vec<Box<dyn Notifier>> = Vec::new();
+    if !settings.command.is_empty() {
+        res.push(Box::new(CommandNotifier {
+            command: settings.command.clone(),
+        }));
+    }
+    if let Some(m) = &settings.mastodon {
+        res.push(Box::new(MastodonNotifier {
+            client: Client::new(),
+            settings: m.clone(),
+        }));
+    }
+    if let Some(i) = &settings.irc {
+        res.push(Box::new(IrcNotifier {
+            settings: i.clone(),
+        }));
+    }
+    if let Some(x) = &settings.xmpp {
+        res.push(Box::new(XmppNotifier {
+            settings: x.clone(),
+        }));
+    }
+    if let Some(m) = &settings.matrix {
+        res.push(Box::new(MatrixNotifier {
+            client: Client::new(),
+            settings: m.clone(),
+        }));
+    }
+    if let Some(w) = &settings.slack {
+        res.push(Box::new(SlackNotifier {
+            client: Client::new(),
+            settings: w.clone(),
+        }));
+    }
+    if let Some(w) = &settings.discord {
+        res.push(Box::new(DiscordNotifier {
+            client: Client::new(),
+            settings: w.clone(),
+        }));
+    }
+    res
+}
+
+/// Send a notification about `albums` to all configured notifiers, errors get printed and do not stop the other notifiers
+/// Every notifier only gets the albums its filter in the settings lets through, the tags come from the followed `artists`
+pub fn notify_all(
+    settings: &NotificationSettings,
+    artists: &[Artist],
+    title: &str,
+    albums: &[&Album],
+) {
+    let notifiers = notifiers(settings);
+    if notifiers.is_empty() {
+        println!(
+            "{}",
+            tr!("No notifiers configured, not sending \"{}\"", title)
+        );
+    }
+    for n in notifiers {
+        let filtered = match settings.filters.get(n.name()) {
+            Some(f) => f.apply(artists, albums),
+            None => albums.to_vec(),
+        };
+        if filtered.is_empty() {
+            continue;
+        }
+        if let Err(e) = n.send_albums(title, &filtered) {
+            println!(
+                "{}",
+                tr!(
+                    "Could not send notification via {}: {}",
+                    n.name(),
+                    format!("{:#}", e)
+                )
+            );
+        }
+    }
+}
+
+/// Send a test notification to the notifier called `name`, or to all configured ones
+pub fn test(settings: &NotificationSettings, name: Option<&str>) -> Result<()> {
+    let notifiers = notifiers(settings)
+        .into_iter()
+        .filter(|n| name.is_none_or(|name| n.name() == name))
+        .collect::<Vec<_>>();
+    if notifiers.is_empty() {
+        return Err(anyhow!(tr!(
+            "No notifier {} configured",
+            name.unwrap_or_default()
+        )));
+    }
+    let album = Album {
+        id: uuid::Uuid::nil(),
+        artist: "musicbrainz-release-grabber".to_string(),
+        title: tr!("Test notification").to_string(),
+        release_type: ReleaseType::Album,
+        ..Default::default()
+    };
+    for n in notifiers {
+        n.send_albums(tr!("Test notification"), &[&album])
+            .with_context(|| tr!("Could not send notification via {}", n.name()))?;
+        println!("{} {}", tr!("Sent test notification via").green(), n.name());
+    }
+    Ok(())
+}
+
+/// One line per album for the body of a notification
+pub fn albums_body(albums: &[&Album]) -> String {
+    albums
+        .iter()
+        .map(|a| format!("{} - {}", a.artists(), a.title))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use uuid::Uuid;
+
+    fn album(n: u128) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "Artist".to_string(),
+            title: format!("Album {}", n),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn mastodon_posts_summary_or_albums() {
+        let server = MockServer::start(vec![(200, "{}".to_string()); 3]);
+        let mut mastodon = MastodonNotifier {
+            client: Client::new(),
+            settings: MastodonSettings {
+                server: format!("{}/", server.url()),
+                token: "t".to_string(),
+                per_album: false,
+                visibility: default_visibility(),
+            },
+        };
+        let (one, two) = (album(1), album(2));
+        mastodon.send_albums("2 new albums", &[&one, &two]).unwrap();
+        mastodon.settings.per_album = true;
+        mastodon.send_albums("2 new albums", &[&one, &two]).unwrap();
+        assert_eq!(server.requests()[0], "POST /api/v1/statuses HTTP/1.1");
+        let bodies = server
+            .bodies()
+            .iter()
+            .map(|b| serde_json::from_str::<serde_json::Value>(b).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bodies[0]["status"],
+            "2 new albums\n\nArtist - Album 1\nArtist - Album 2"
+        );
+        assert_eq!(bodies[0]["visibility"], "unlisted");
+        assert!(bodies[2]["status"]
+            .as_str()
+            .unwrap()
+            .ends_with("/release-group/00000000-0000-0000-0000-000000000002"));
+    }
+
+    #[test]
+    fn matrix_posts_formatted_albums() {
+        let server = MockServer::start(vec![(200, r#"{"event_id": "$1"}"#.to_string())]);
+        let matrix = MatrixNotifier {
+            client: Client::new(),
+            settings: MatrixSettings {
+                homeserver: server.url().to_string(),
+                token: "t".to_string(),
+                room: "!room:example.org".to_string(),
+            },
+        };
+        let mut a = album(1);
+        a.title = "<One>".to_string();
+        matrix.send_albums("1 new albums", &[&a]).unwrap();
+        let request = &server.requests()[0];
+        assert!(request.starts_with(
+            "PUT /_matrix/client/v3/rooms/!room:example.org/send/m.room.message/mbrg-"
+        ));
+        let body: serde_json::Value = serde_json::from_str(&server.bodies()[0]).unwrap();
+        assert_eq!(body["body"], "1 new albums\nArtist - <One>");
+        assert!(body["formatted_body"]
+            .as_str()
+            .unwrap()
+            .contains("<a href=\"https://musicbrainz.org/release-group/00000000-0000-0000-0000-000000000001\">&lt;One&gt;</a>"));
+    }
+
+    #[test]
+    fn filters_pick_albums() {
+        let artist = |name: &str, tags: &[&str]| Artist {
+            name: name.to_string(),
+            search_string: name.to_string(),
+            sort_name: name.to_string(),
+            tags: tags.iter().map(|t| t.to_string()).collect(),
+            source: Default::default(),
+            ..Default::default()
+        };
+        let artists = [artist("Tagged", &["Jazz"]), artist("Artist", &[])];
+        let mut live = album(1);
+        live.release_type = ReleaseType::Live;
+        let mut jazz = album(2);
+        jazz.artist = "Tagged".to_string();
+        let plain = album(3);
+        let albums = [&live, &jazz, &plain];
+        let ids = |f: &NotifyFilter| {
+            f.apply(&artists, &albums)
+                .iter()
+                .map(|a| a.id.as_u128())
+                .collect::<Vec<_>>()
+        };
+        let mut filter = NotifyFilter {
+            albums_only: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter), vec![2, 3]);
+        filter.tags = vec!["jazz".to_string()];
+        assert_eq!(ids(&filter), vec![2]);
+        filter.min_albums = 2;
+        assert!(ids(&filter).is_empty());
+        let filter = NotifyFilter {
+            artists: vec!["artist".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(ids(&filter), vec![1, 3]);
+    }
+
+    #[test]
+    fn quiet_hours_and_digest_hold_notifications() {
+        let date = Date::from_calendar_date(2025, time::Month::March, 1).unwrap();
+        let mut settings = NotificationSettings {
+            quiet_hours: Some(QuietHours { from: 22, until: 7 }),
+            ..Default::default()
+        };
+        let mut held = HeldNotifications::default();
+        let (one, two) = (album(1), album(2));
+        assert!(!held.hold(&settings, 12, &[&one]));
+        assert!(held.hold(&settings, 23, &[&one, &two]));
+        assert!(held.hold(&settings, 3, &[&one]));
+        assert!(held.take_due(&settings, date, 6).is_empty());
+        assert_eq!(
+            held.take_due(&settings, date, 7),
+            vec![one.clone(), two.clone()]
+        );
+        assert!(held.take_due(&settings, date, 8).is_empty());
+
+        settings.digest_hour = Some(9);
+        assert!(held.hold(&settings, 12, &[&one]));
+        assert!(held.take_due(&settings, date, 13).len() == 1);
+        assert!(held.hold(&settings, 14, &[&two]));
+        // only one digest a day
+        assert!(held.take_due(&settings, date, 15).is_empty());
+        let tomorrow = date.next_day().unwrap();
+        assert!(held.take_due(&settings, tomorrow, 8).is_empty());
+        assert_eq!(held.take_due(&settings, tomorrow, 9), vec![two]);
+    }
+}
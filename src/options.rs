@@ -0,0 +1,462 @@
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+use time::format_description;
+
+use crate::config::Settings;
+use crate::theme::Theme;
+
+/// The description of every setting by its path in the config, sections describe everything in them
+const OPTIONS: &[(&str, &str)] = &[
+    (
+        "check.reissues",
+        "also report new releases in release groups we already know",
+    ),
+    (
+        "check.date_strategy",
+        "which date decides if a release is new: first-release, country or digital",
+    ),
+    (
+        "check.countries",
+        "ISO 3166-1 country codes for the country date strategy and filter",
+    ),
+    (
+        "check.country_filter",
+        "only report releases with a release event in one of check.countries",
+    ),
+    (
+        "check.exclude_languages",
+        "ISO 639-3 codes, release groups only in these languages are left out",
+    ),
+    (
+        "check.exclude_scripts",
+        "ISO 15924 codes like Cyrl, release groups only in these scripts are left out",
+    ),
+    (
+        "check.min_tracks",
+        "albums without a release with this many tracks are reported as others, 0 disables",
+    ),
+    (
+        "check.utc_offset",
+        "utc offset like +02:00 that decides what today is, the system one if not set",
+    ),
+    (
+        "check.safety_margin_days",
+        "start the next check this many days before the last one, for late additions",
+    ),
+    (
+        "check.cooldown_minutes",
+        "new within this many minutes of the last run does not check again, 0 disables",
+    ),
+    (
+        "check.cooldown",
+        "what new does within the cooldown: cached or refuse",
+    ),
+    (
+        "check.interval_hours",
+        "hours between the checks of the daemon for this profile, --interval-hours if not set",
+    ),
+    (
+        "check.artist_budget_secs",
+        "seconds one artist may take before it is tried again at the end of the check, 0 disables",
+    ),
+    (
+        "others.skip",
+        "release types of others that are not fetched, like [\"Live\", \"Remix\"]",
+    ),
+    (
+        "others.collapse",
+        "release types of others that are only counted in the output",
+    ),
+    (
+        "notifications.command",
+        "command that gets title and body as arguments, like [\"notify-send\"]",
+    ),
+    (
+        "notifications.today_hour",
+        "local hour after which the daemon sends the out today notification",
+    ),
+    (
+        "notifications.mastodon",
+        "post to Mastodon: server, token, per_album and visibility",
+    ),
+    (
+        "notifications.irc",
+        "post to an IRC channel: server, port, tls, nick, channel and password",
+    ),
+    (
+        "notifications.xmpp",
+        "send XMPP messages: jid, password, to and groupchat",
+    ),
+    (
+        "notifications.matrix",
+        "post to a Matrix room: homeserver, token and room",
+    ),
+    (
+        "notifications.slack",
+        "post to the incoming webhook url of Slack",
+    ),
+    (
+        "notifications.discord",
+        "post to the webhook url of a Discord channel",
+    ),
+    (
+        "notifications.filters",
+        "which albums a notifier gets, by notifier name",
+    ),
+    (
+        "notifications.quiet_hours",
+        "the daemon holds notifications between from and until",
+    ),
+    (
+        "notifications.digest_hour",
+        "the daemon sends all notifications as one at this local hour",
+    ),
+    ("remote.url", "url of the state file on a WebDAV server"),
+    ("remote.username", "user on the WebDAV server"),
+    ("remote.password", "password on the WebDAV server"),
+    (
+        "storage",
+        "how the config and the run history are written: json or msgpack",
+    ),
+    (
+        "language",
+        "language of the messages, en or de, from the locale if not set",
+    ),
+    (
+        "date_format",
+        "time format description for dates like [day].[month].[year]",
+    ),
+    (
+        "theme.palette",
+        "colors of the output: colorblind, which does not need red and green, or classic",
+    ),
+    (
+        "theme.artist",
+        "style of artists like \"bold blue\", \"#0072b2\" or \"bright-yellow underline\"",
+    ),
+    ("theme.date", "style of dates"),
+    ("theme.title", "style of titles"),
+    ("theme.release_type", "style of release types"),
+    (
+        "theme.upcoming",
+        "added to the styles of albums that are not released yet, like \"strike\"",
+    ),
+    (
+        "anniversaries.enabled",
+        "remind of album anniversaries in the digest",
+    ),
+    ("anniversaries.years", "the anniversaries we remind of"),
+    (
+        "activity.dormant_years",
+        "artists without a release for this many years are dormant",
+    ),
+    (
+        "activity.defunct_years",
+        "artists without a release for this many years are defunct",
+    ),
+    (
+        "gaps.min_years",
+        "mark releases this many years after the previous one of the artist, 0 disables",
+    ),
+    (
+        "gaps.sort_first",
+        "print the marked releases first, the longest break first",
+    ),
+    (
+        "listenbrainz.token",
+        "the user token from https://listenbrainz.org/settings/",
+    ),
+    (
+        "listenbrainz.playlist",
+        "the listen later playlist, created by the first push",
+    ),
+    (
+        "spotify.client_id",
+        "client id of an app from https://developer.spotify.com/dashboard",
+    ),
+    ("spotify.client_secret", "client secret of the same app"),
+    ("spotify.refresh_token", "written by spotify login"),
+    (
+        "links.spotify",
+        "find Spotify links to new albums, needs the app in spotify",
+    ),
+    ("links.apple_music", "find Apple Music links to new albums"),
+    ("links.deezer", "find Deezer links to new albums"),
+    ("formats.enabled", "fetch the media formats of new albums"),
+    (
+        "formats.require",
+        "only keep new albums with one of these formats",
+    ),
+    ("formats.highlight", "formats that stand out in the output"),
+    (
+        "preorders.enabled",
+        "mark upcoming albums that can be ordered already",
+    ),
+    (
+        "preorders.remind_days",
+        "the daemon reminds of pre-orders this many days before the release",
+    ),
+    (
+        "hook.command",
+        "command that gets every new album of allowed artists as json on stdin",
+    ),
+    ("hook.paused", "stop all handoffs"),
+    (
+        "hook.max_per_run",
+        "hand off at most this many albums in one run",
+    ),
+    (
+        "hook.timeout_secs",
+        "kill the command after this many seconds, the album is handed off in the next run",
+    ),
+    ("funkwhale.server", "the Funkwhale instance"),
+    (
+        "funkwhale.token",
+        "application token for private libraries and publishing",
+    ),
+    (
+        "funkwhale.channel",
+        "uuid of the channel funkwhale publish writes to",
+    ),
+    ("queue.file", "m3u8 queue file every check writes"),
+    (
+        "queue.library",
+        "music directories with Artist/Album directories for local files in the queue",
+    ),
+    (
+        "queue.search_url",
+        "url for albums we do not have, with {artist}, {title} and {mbid}",
+    ),
+    ("mpd.host", "the MPD server for mpd queue-new"),
+    ("mpd.port", "port of the MPD server"),
+    ("mpd.password", "password of the MPD server"),
+    (
+        "healthcheck.url",
+        "pinged after every successful check, <url>/start and <url>/fail around it",
+    ),
+    (
+        "healthcheck.start",
+        "pinged when a check starts instead of <url>/start",
+    ),
+    (
+        "healthcheck.fail",
+        "pinged with the error when a check fails instead of <url>/fail",
+    ),
+];
+
+/// A setting with its values
+#[derive(Debug, PartialEq)]
+pub struct Row {
+    pub path: String,
+    pub value: String,
+    /// none for settings in optional sections that are not there by default
+    pub default: Option<String>,
+    pub description: &'static str,
+}
+
+/// Secrets are not printed
+pub(crate) fn is_secret(path: &str) -> bool {
+    let key = path.rsplit('.').next().unwrap_or(path);
+    ["token", "password", "secret"]
+        .iter()
+        .any(|s| key.contains(s))
+}
+
+/// `v` as it is written in the config, secrets hidden
+pub(crate) fn show(path: &str, v: &Value) -> String {
+    if is_secret(path) && !v.is_null() {
+        "***".to_string()
+    } else {
+        v.to_string()
+    }
+}
+
+/// All leaves of `v` with their dotted path, sorted by the path
+fn leaves<'a>(prefix: &str, v: &'a Value, res: &mut Vec<(String, &'a Value)>) {
+    match v {
+        Value::Object(m) if !m.is_empty() => {
+            for (k, v) in m {
+                let path = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{}.{}", prefix, k)
+                };
+                leaves(&path, v, res);
+            }
+        }
+        _ => res.push((prefix.to_string(), v)),
+    }
+}
+
+/// The description of `path`, from the nearest documented section for settings like the filters
+fn description(path: &str) -> &'static str {
+    let mut p = path;
+    loop {
+        if let Some((_, d)) = OPTIONS.iter().find(|(o, _)| *o == p) {
+            return d;
+        }
+        match p.rsplit_once('.') {
+            Some((parent, _)) => p = parent,
+            None => return "",
+        }
+    }
+}
+
+/// Every setting in `settings` with its default
+pub fn rows(settings: &Settings) -> Vec<Row> {
+    let current = serde_json::to_value(settings).unwrap_or_default();
+    let defaults = serde_json::to_value(Settings::default()).unwrap_or_default();
+    let mut found = Vec::new();
+    leaves("", &current, &mut found);
+    found
+        .into_iter()
+        .map(|(path, v)| {
+            let pointer = format!("/{}", path.replace('.', "/"));
+            Row {
+                value: show(&path, v),
+                default: defaults.pointer(&pointer).map(|d| show(&path, d)),
+                description: description(&path),
+                path,
+            }
+        })
+        .collect()
+}
+
+/// `key` without the `settings.` that `config show` prints, if it is a setting
+pub fn setting_key(key: &str) -> Result<&str> {
+    let key = key.strip_prefix("settings.").unwrap_or(key);
+    if description(key).is_empty() {
+        return Err(anyhow!(
+            "There is no setting {}, config show lists all of them",
+            key
+        ));
+    }
+    Ok(key)
+}
+
+/// The value of the setting or section `key`
+pub fn get(settings: &Settings, key: &str) -> Result<Value> {
+    let key = setting_key(key)?;
+    let v = serde_json::to_value(settings)?;
+    Ok(v.pointer(&format!("/{}", key.replace('.', "/")))
+        .cloned()
+        .unwrap_or(Value::Null))
+}
+
+/// `settings` with `key` set to `value`.
+/// The value is json if it fits the setting, otherwise a string, or a list of strings separated by commas like `album,ep`
+pub fn set(settings: &Settings, key: &str, value: &str) -> Result<Settings> {
+    let key = setting_key(key)?;
+    let mut candidates = Vec::new();
+    if let Ok(v) = serde_json::from_str::<Value>(value) {
+        candidates.push(v);
+    }
+    candidates.push(Value::String(value.to_string()));
+    candidates.push(Value::Array(
+        value
+            .split(',')
+            .map(|s| Value::String(s.trim().to_string()))
+            .filter(|s| s.as_str() != Some(""))
+            .collect(),
+    ));
+    let mut error = None;
+    for candidate in candidates {
+        let mut v = serde_json::to_value(settings)?;
+        let mut slot = &mut v;
+        for part in key.split('.') {
+            if !slot.is_object() {
+                *slot = Value::Object(Default::default());
+            }
+            slot = slot
+                .as_object_mut()
+                .map(|m| m.entry(part).or_insert(Value::Null))
+                .ok_or_else(|| anyhow!("{} is not a section", part))?;
+        }
+        *slot = candidate;
+        match serde_json::from_value::<Settings>(v) {
+            Ok(s) => {
+                // a theme or date format we cannot print with would break every later command
+                Theme::from_settings(&s.theme)?;
+                if let Some(f) = &s.date_format {
+                    format_description::parse_owned::<2>(f)
+                        .with_context(|| format!("Invalid date format {:?}", f))?;
+                }
+                return Ok(s);
+            }
+            Err(e) => {
+                error.get_or_insert(e);
+            }
+        }
+    }
+    Err(anyhow!(
+        "{} is not a valid value for {}: {}",
+        value,
+        key,
+        error.map(|e| e.to_string()).unwrap_or_default()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notify::MatrixSettings;
+
+    #[test]
+    fn every_default_setting_is_documented() {
+        let missing = rows(&Settings::default())
+            .into_iter()
+            .filter(|r| !OPTIONS.iter().any(|(o, _)| *o == r.path))
+            .map(|r| r.path)
+            .collect::<Vec<String>>();
+        assert!(missing.is_empty(), "missing descriptions: {:?}", missing);
+    }
+
+    #[test]
+    fn rows_hide_secrets_and_describe_sections() {
+        let mut settings = Settings::default();
+        settings.check.min_tracks = 5;
+        settings.notifications.matrix = Some(MatrixSettings {
+            homeserver: "https://matrix.org".to_string(),
+            token: "secret".to_string(),
+            room: "!a:matrix.org".to_string(),
+        });
+        let rows = rows(&settings);
+        let row = |p: &str| rows.iter().find(|r| r.path == p).unwrap();
+        assert_eq!(row("check.min_tracks").value, "5");
+        assert_eq!(row("check.min_tracks").default.as_deref(), Some("0"));
+        let token = row("notifications.matrix.token");
+        assert_eq!(token.value, "***");
+        assert_eq!(token.default, None);
+        assert_eq!(token.description, description("notifications.matrix"));
+        assert_eq!(row("notifications.xmpp").value, "null");
+    }
+
+    #[test]
+    fn set_validates_and_guesses_types() {
+        let s = Settings::default();
+        let s = set(&s, "settings.check.min_tracks", "6").unwrap();
+        assert_eq!(s.check.min_tracks, 6);
+        let s = set(&s, "mpd.host", "1234").unwrap();
+        assert_eq!(s.mpd.host, "1234");
+        let s = set(&s, "check.countries", "DE, AT").unwrap();
+        assert_eq!(s.check.countries, vec!["DE", "AT"]);
+        let s = set(&s, "check.date_strategy", "digital").unwrap();
+        assert_eq!(get(&s, "check.date_strategy").unwrap(), "digital");
+        assert_eq!(
+            set(&s, "notifications.slack.url", "https://hooks.slack.com/x")
+                .unwrap()
+                .notifications
+                .slack
+                .unwrap()
+                .url,
+            "https://hooks.slack.com/x"
+        );
+        assert!(set(&s, "check.min_tracks", "many").is_err());
+        assert!(set(&s, "check.date_strategy", "newest").is_err());
+        assert!(set(&s, "check.types", "album,ep").is_err());
+        assert!(set(&s, "theme.title", "bold blurple").is_err());
+        assert!(set(&s, "date_format", "[bogus").is_err());
+        assert!(set(&s, "date_format", "[day]/[month]/[year]").is_ok());
+        assert!(get(&s, "network.server_url").is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::formats;
+use crate::i18n::format_date;
+use crate::msgpack;
+use crate::responses::Album;
+
+/// Formats we can render reports in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Text,
+    Markdown,
+    Html,
+    Json,
+    /// MessagePack with a schema version, for other programs
+    Msgpack,
+}
+
+/// Formats we can export the config in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    /// MessagePack with a schema version, for other programs
+    Msgpack,
+}
+
+/// A titled group of albums in a report
+#[derive(Debug, Serialize)]
+pub struct Section {
+    pub title: String,
+    pub albums: Vec<Album>,
+}
+
+/// A whole report
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    title: &'a str,
+    sections: &'a [Section],
+}
+
+/// Groups consecutive albums of the same artists, albums should be sorted by artist
+fn group_by_artist(albums: &[Album]) -> Vec<(String, Vec<&Album>)> {
+    let mut res: Vec<(String, Vec<&Album>)> = Vec::new();
+    for a in albums {
+        let artists = a.artists();
+        match res.last_mut() {
+            Some((artist, list)) if *artist == artists => list.push(a),
+            _ => res.push((artists, vec![a])),
+        }
+    }
+    res
+}
+
+/// The stars of the community rating and the media formats after the album
+fn extras(a: &Album) -> String {
+    let stars = a
+        .rating
+        .map(|r| format!(" {}", r.stars()))
+        .unwrap_or_default();
+    let preorder = if a.preorder { " (pre-order)" } else { "" };
+    let gap = a
+        .gap_years
+        .map(|y| format!(" (first release in {} years)", y))
+        .unwrap_or_default();
+    format!(
+        "{}{}{}{}",
+        stars,
+        formats::describe(&a.formats, false),
+        preorder,
+        gap
+    )
+}
+
+/// The links to streaming services after the album in markdown, empty if we have none
+fn markdown_links(a: &Album) -> String {
+    a.links
+        .iter()
+        .map(|(p, url)| format!(" [{}]({})", p, url))
+        .collect()
+}
+
+/// The links to streaming services after the album in html, empty if we have none
+fn html_links(a: &Album) -> String {
+    a.links
+        .iter()
+        .map(|(p, url)| format!(" <a href=\"{}\">{}</a>", escape_html(url), p))
+        .collect()
+}
+
+/// Escape a string for html
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Render the sections of a report with the `title` in the given `format`
+pub fn render(format: OutputFormat, title: &str, sections: &[Section]) -> Result<Vec<u8>> {
+    let date = |a: &Album| format_date(a.date);
+    let mut out = String::new();
+    match format {
+        OutputFormat::Text => {
+            out.push_str(&format!(
+                "{}\n{}\n",
+                title,
+                "=".repeat(title.chars().count())
+            ));
+            for s in sections {
+                out.push_str(&format!("\n{} ({})\n", s.title, s.albums.len()));
+                for (artist, albums) in group_by_artist(&s.albums) {
+                    out.push_str(&format!("  {}\n", artist));
+                    for a in albums {
+                        out.push_str(&format!(
+                            "    {} - {} ({}){}\n",
+                            date(a),
+                            a.title,
+                            a.release_type,
+                            extras(a)
+                        ));
+                    }
+                }
+            }
+        }
+        OutputFormat::Markdown => {
+            out.push_str(&format!("# {}\n", title));
+            for s in sections {
+                out.push_str(&format!("\n## {} ({})\n", s.title, s.albums.len()));
+                for (artist, albums) in group_by_artist(&s.albums) {
+                    out.push_str(&format!("\n### {}\n", artist));
+                    for a in albums {
+                        out.push_str(&format!(
+                            "- {} - {} ({}){}{}\n",
+                            date(a),
+                            a.title,
+                            a.release_type,
+                            extras(a),
+                            markdown_links(a)
+                        ));
+                    }
+                }
+            }
+        }
+        OutputFormat::Html => {
+            let title = escape_html(title);
+            out.push_str(&format!(
+                "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n",
+                title, title
+            ));
+            for s in sections {
+                out.push_str(&format!(
+                    "<h2>{} ({})</h2>\n",
+                    escape_html(&s.title),
+                    s.albums.len()
+                ));
+                for (artist, albums) in group_by_artist(&s.albums) {
+                    out.push_str(&format!("<h3>{}</h3>\n<ul>\n", escape_html(&artist)));
+                    for a in albums {
+                        out.push_str(&format!(
+                            "<li>{} - <a href=\"https://musicbrainz.org/release-group/{}\">{}</a> ({}){}{} <a href=\"https://critiquebrainz.org/release-group/{}\">reviews</a>{}</li>\n",
+                            date(a),
+                            a.id,
+                            escape_html(&a.title),
+                            a.release_type,
+                            escape_html(&formats::describe(&a.formats, false)),
+                            a.rating
+                                .map(|r| format!(
+                                    " <span title=\"{} votes\">{}</span>",
+                                    r.votes,
+                                    r.stars()
+                                ))
+                                .unwrap_or_default(),
+                            a.id,
+                            html_links(a)
+                        ));
+                    }
+                    out.push_str("</ul>\n");
+                }
+            }
+            out.push_str("</body>\n</html>\n");
+        }
+        OutputFormat::Json => {
+            out = serde_json::to_string_pretty(&Report { title, sections })
+                .context("JSON to string")?;
+            out.push('\n');
+        }
+        OutputFormat::Msgpack => {
+            return msgpack::to_vec_versioned(&Report { title, sections });
+        }
+    }
+    Ok(out.into_bytes())
+}
+
+/// Write `s` to the file `output` or to stdout if it is not given
+pub fn write_output(s: &[u8], output: &Option<PathBuf>) -> Result<()> {
+    if let Some(path) = output {
+        std::fs::write(path, s).with_context(|| format!("Writing to {:?}", path))
+    } else {
+        let mut stdout = std::io::stdout().lock();
+        stdout
+            .write_all(s)
+            .and_then(|_| stdout.flush())
+            .context("Writing to stdout")
+    }
+}
@@ -0,0 +1,95 @@
+use clap::Args;
+
+use crate::i18n::tr;
+
+/// How many albums of a section we print by default
+const DEFAULT_LIMIT: usize = 50;
+
+/// Which part of a long list of albums is printed, the history and reports always get all of them
+#[derive(Debug, Clone, PartialEq, Eq, Args)]
+pub struct Paging {
+    /// Print at most this many albums in each section
+    #[arg(short, long, default_value_t = DEFAULT_LIMIT)]
+    pub limit: usize,
+    /// Which page of --limit albums to print, starting at 1
+    #[arg(short, long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub page: u64,
+    /// Print all albums
+    #[arg(long)]
+    pub all: bool,
+}
+
+impl Default for Paging {
+    fn default() -> Self {
+        Paging {
+            limit: DEFAULT_LIMIT,
+            page: 1,
+            all: false,
+        }
+    }
+}
+
+impl Paging {
+    /// The part of `items` on our page and how many come after it
+    pub fn page<'a, T>(&self, items: &'a [T]) -> (&'a [T], usize) {
+        if self.all || self.limit == 0 {
+            return (items, 0);
+        }
+        let start = (self.page as usize - 1)
+            .saturating_mul(self.limit)
+            .min(items.len());
+        let end = start.saturating_add(self.limit).min(items.len());
+        (&items[start..end], items.len() - end)
+    }
+
+    /// The line after a page with `more` albums after it, none if there are not any
+    pub fn footer(&self, more: usize) -> Option<String> {
+        (more > 0).then(|| tr!("and {} more, use --page {} or --all", more, self.page + 1))
+    }
+
+    /// Print the footer for `more` albums after the page
+    pub fn print_footer(&self, more: usize) {
+        if let Some(f) = self.footer(more) {
+            println!("{}", f);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_are_cut_from_the_list() {
+        let items = (0..120).collect::<Vec<u32>>();
+        let paging = Paging::default();
+        let (page, more) = paging.page(&items);
+        assert_eq!((page.len(), page[0], more), (50, 0, 70));
+        let footer = paging.footer(more).unwrap();
+        assert!(
+            footer.contains("70") && footer.contains("--page 2"),
+            "{}",
+            footer
+        );
+
+        let third = Paging {
+            page: 3,
+            ..Paging::default()
+        };
+        let (page, more) = third.page(&items);
+        assert_eq!((page.len(), page[0], more), (20, 100, 0));
+        assert_eq!(third.footer(more), None);
+
+        let beyond = Paging {
+            page: 9,
+            ..Paging::default()
+        };
+        assert_eq!(beyond.page(&items), (&items[120..], 0));
+
+        let all = Paging {
+            all: true,
+            ..Paging::default()
+        };
+        assert_eq!(all.page(&items).0.len(), 120);
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::{anyhow, Context, Result};
+use std::io::{ErrorKind, Write};
+use std::process::{Command, Stdio};
+use uuid::Uuid;
+
+use crate::responses::Artist;
+
+/// The picker `pick` runs
+const PICKER: &str = "fzf";
+
+/// What `list --format plain` prints of each artist
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fields {
+    /// the name and the MBID, separated by a tab
+    Both,
+    Names,
+    Mbids,
+}
+
+/// `s` on one line without tabs, so it cannot break the columns
+fn one_line(s: &str) -> String {
+    s.replace(['\t', '\n', '\r'], " ")
+}
+
+/// The unadorned line for `artist`, stable for scripts and pickers
+pub fn line(artist: &Artist, fields: Fields) -> String {
+    match fields {
+        Fields::Both => format!("{}\t{}", one_line(&artist.name), artist.id),
+        Fields::Names => one_line(&artist.name),
+        Fields::Mbids => artist.id.to_string(),
+    }
+}
+
+/// The MBID in the line the picker printed, the last column of a `Fields::Both` line
+fn selected_id(output: &str) -> Option<Uuid> {
+    let line = output.lines().next()?;
+    line.rsplit('\t')
+        .next()
+        .and_then(|id| Uuid::parse_str(id.trim()).ok())
+}
+
+/// Let the user pick one of `artists` with fzf, none if they cancelled
+pub fn pick(artists: &[Artist]) -> Result<Option<Uuid>> {
+    let mut child = match Command::new(PICKER)
+        .args(["--delimiter", "\t", "--with-nth", "1", "--no-multi"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+    {
+        Ok(c) => c,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return Err(anyhow!(
+                "{} is not installed, pipe list --format plain into another picker instead",
+                PICKER
+            ))
+        }
+        Err(e) => return Err(e).with_context(|| format!("Could not run {}", PICKER)),
+    };
+    let input = artists
+        .iter()
+        .map(|a| line(a, Fields::Both) + "\n")
+        .collect::<String>();
+    let mut stdin = child.stdin.take().context("No stdin")?;
+    // fzf closes its input when the user picks before reading all of it
+    let _ = stdin.write_all(input.as_bytes());
+    drop(stdin);
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("Waiting for {}", PICKER))?;
+    // fzf exits with 1 without a match and 130 when cancelled
+    if !output.status.success() {
+        return Ok(None);
+    }
+    Ok(selected_id(&String::from_utf8_lossy(&output.stdout)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_round_trip_through_the_picker() {
+        let artist = Artist {
+            name: "Tab\tBand".to_string(),
+            id: Uuid::from_u128(7),
+            search_string: "tab band".to_string(),
+            sort_name: "Tab Band".to_string(),
+            ..Default::default()
+        };
+        let both = line(&artist, Fields::Both);
+        assert_eq!(both, format!("Tab Band\t{}", artist.id));
+        assert_eq!(line(&artist, Fields::Names), "Tab Band");
+        assert_eq!(line(&artist, Fields::Mbids), artist.id.to_string());
+        assert_eq!(selected_id(&(both + "\n")), Some(artist.id));
+        assert_eq!(selected_id(""), None);
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+use time::Date;
+
+use crate::responses::{Album, Release};
+
+/// Settings for finding upcoming albums that can be pre-ordered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PreorderSettings {
+    pub enabled: bool,
+    /// the daemon reminds of a pre-orderable album this many days before its release
+    pub remind_days: i64,
+}
+
+impl Default for PreorderSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            remind_days: 7,
+        }
+    }
+}
+
+/// A reminder of an upcoming album that the daemon sends on `on`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub on: Date,
+    pub album: Album,
+}
+
+/// Can the album released on `date` with `releases` be ordered already.
+/// That is a release links to a shop, or has a release event before `date`
+pub fn is_preorder(releases: &[Release], date: Date) -> bool {
+    releases
+        .iter()
+        .any(|r| r.store_urls().next().is_some() || r.event_dates().any(|d| d < date))
+}
+
+/// Schedule a reminder `days` before the release of `album`, or `today` if that already passed.
+/// Albums without a date or with a reminder already get none
+pub fn schedule(reminders: &mut Vec<Reminder>, album: &Album, days: i64, today: Date) {
+    let Some(date) = album.date else {
+        return;
+    };
+    if reminders.iter().any(|r| r.album.id == album.id) {
+        return;
+    }
+    reminders.push(Reminder {
+        on: (date - time::Duration::days(days)).max(today),
+        album: album.clone(),
+    });
+}
+
+/// Remove the reminders that are due on `today` and return their albums.
+/// Albums that got released meanwhile are dropped without a reminder
+pub fn take_due(reminders: &mut Vec<Reminder>, today: Date) -> Vec<Album> {
+    let mut due = vec![];
+    reminders.retain(|r| {
+        if r.album.date.is_some_and(|d| d < today) {
+            false
+        } else if r.on <= today {
+            due.push(r.album.clone());
+            false
+        } else {
+            true
+        }
+    });
+    due.sort_unstable();
+    due
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+
+    use time::Month;
+    use uuid::Uuid;
+
+    fn date(month: Month, day: u8) -> Date {
+        Date::from_calendar_date(2025, month, day).unwrap()
+    }
+
+    fn album(n: u128, d: Date) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "Artist".to_string(),
+            title: format!("Album {}", n),
+            date: Some(d),
+            release_type: ReleaseType::Album,
+            preorder: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn detect_preorders() {
+        let releases: Vec<Release> = serde_json::from_str(
+            r#"[{"id": "00000000-0000-0000-0000-000000000002", "title": "A",
+                 "release-events": [{"date": "2025-05-02", "area": null}],
+                 "relations": [{"type": "discogs", "url": {"resource": "https://www.discogs.com/release/1"}}]}]"#,
+        )
+        .unwrap();
+        assert!(!is_preorder(&releases, date(Month::May, 2)));
+        assert!(is_preorder(&releases, date(Month::May, 9)));
+
+        let releases: Vec<Release> = serde_json::from_str(
+            r#"[{"id": "00000000-0000-0000-0000-000000000002", "title": "A", "release-events": [],
+                 "relations": [{"type": "purchase for mail-order", "url": {"resource": "https://shop.example/a"}}]}]"#,
+        )
+        .unwrap();
+        assert!(is_preorder(&releases, date(Month::May, 2)));
+    }
+
+    #[test]
+    fn reminders_fire_once_before_release() {
+        let today = date(Month::March, 1);
+        let mut reminders = vec![];
+        schedule(&mut reminders, &album(1, date(Month::March, 20)), 7, today);
+        schedule(&mut reminders, &album(2, date(Month::March, 3)), 7, today);
+        schedule(&mut reminders, &album(1, date(Month::March, 20)), 3, today);
+        assert_eq!(reminders.len(), 2);
+        assert_eq!(reminders[0].on, date(Month::March, 13));
+        assert_eq!(reminders[1].on, today);
+
+        let due = take_due(&mut reminders, today);
+        assert_eq!(due, vec![album(2, date(Month::March, 3))]);
+        assert!(take_due(&mut reminders, date(Month::March, 12)).is_empty());
+        // a daemon that was not running gets no reminder for released albums
+        assert!(take_due(&mut reminders, date(Month::March, 21)).is_empty());
+        assert!(reminders.is_empty());
+    }
+}
@@ -0,0 +1,101 @@
+use anyhow::{anyhow, Result};
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, Ordering};
+use yansi::Paint;
+
+use crate::i18n::tr;
+
+/// If we may ask the user, false in cron jobs and containers
+static INTERACTIVE: AtomicBool = AtomicBool::new(true);
+
+/// Never ask with `non_interactive` or when stdin is not a terminal
+pub fn setup(non_interactive: bool) {
+    INTERACTIVE.store(
+        !non_interactive && std::io::stdin().is_terminal(),
+        Ordering::Relaxed,
+    );
+}
+
+/// If we may ask the user
+pub fn is_interactive() -> bool {
+    INTERACTIVE.load(Ordering::Relaxed)
+}
+
+/// The error for a question we cannot ask, `hint` says how to answer it with arguments
+fn cannot_ask(prompt: &str, hint: &str) -> anyhow::Error {
+    anyhow!(tr!(
+        "Cannot ask \"{}\" without a terminal, {}",
+        prompt,
+        hint
+    ))
+}
+
+/// Ask for a confirmation of something that cannot be undone, `yes` from `--yes` confirms without asking
+pub fn confirm(prompt: &str, yes: bool) -> Result<bool> {
+    if yes {
+        println!("{} {}", prompt, tr!("Confirmed with --yes").yellow());
+        return Ok(true);
+    }
+    if !is_interactive() {
+        return Err(cannot_ask(prompt, tr!("pass --yes to confirm")));
+    }
+    Ok(Confirm::new()
+        .default(false)
+        .with_prompt(prompt)
+        .interact()?)
+}
+
+/// Ask a yes or no question, without a terminal the answer is `default`
+pub fn ask(prompt: &str, default: bool) -> Result<bool> {
+    if !is_interactive() {
+        return Ok(default);
+    }
+    Ok(Confirm::new()
+        .default(default)
+        .with_prompt(prompt)
+        .interact()?)
+}
+
+/// Ask which of `items` the user wants, `hint` says how to choose without a terminal
+pub fn choose(prompt: &str, items: &[String], hint: &str) -> Result<Vec<usize>> {
+    if !is_interactive() {
+        return Err(cannot_ask(prompt, hint));
+    }
+    Ok(MultiSelect::new()
+        .with_prompt(prompt)
+        .items(items)
+        .interact()?)
+}
+
+/// Ask which one of `items` the user wants, only call this when `is_interactive`
+pub fn select(prompt: &str, items: &[String]) -> Result<usize> {
+    if !is_interactive() {
+        return Err(cannot_ask(prompt, ""));
+    }
+    Ok(Select::new()
+        .with_prompt(prompt)
+        .items(items)
+        .default(0)
+        .interact()?)
+}
+
+/// Ask for a text, `default` if the user just presses enter, only call this when `is_interactive`
+pub fn input(prompt: &str, default: &str) -> Result<String> {
+    if !is_interactive() {
+        return Err(cannot_ask(prompt, ""));
+    }
+    Ok(Input::new()
+        .with_prompt(prompt)
+        .default(default.to_string())
+        .interact_text()?)
+}
+
+/// Fail if we cannot use the terminal for `what`, like an editor
+pub fn require_terminal(what: &str) -> Result<()> {
+    if is_interactive() {
+        Ok(())
+    } else {
+        Err(anyhow!(tr!("{} needs a terminal", what)))
+    }
+}
@@ -0,0 +1,226 @@
+use anyhow::{anyhow, Context, Result};
+use std::str::FromStr;
+use time::{format_description, Date};
+
+use crate::i18n::format_date;
+use crate::responses::Album;
+
+/// A field of an album we can select and filter on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    Id,
+    /// the artist and the other followed artists
+    Artist,
+    Title,
+    Date,
+    Type,
+}
+
+impl FromStr for Field {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().as_str() {
+            "id" => Ok(Field::Id),
+            "artist" => Ok(Field::Artist),
+            "title" => Ok(Field::Title),
+            "date" => Ok(Field::Date),
+            "type" => Ok(Field::Type),
+            other => Err(anyhow!(
+                "Unknown field \"{}\", use id, artist, title, date or type",
+                other
+            )),
+        }
+    }
+}
+
+/// The fields in a comma separated list like "artist,title,date"
+pub fn parse_fields(s: &str) -> Result<Vec<Field>> {
+    s.split(',').map(Field::from_str).collect()
+}
+
+/// The `fields` of the album `a` for printing
+pub fn row(a: &Album, fields: &[Field]) -> Vec<String> {
+    fields
+        .iter()
+        .map(|f| match f {
+            Field::Id => a.id.to_string(),
+            Field::Artist => a.artists(),
+            Field::Title => a.title.clone(),
+            Field::Date => format_date(a.date),
+            Field::Type => a.release_type.to_string(),
+        })
+        .collect()
+}
+
+/// How a field gets compared to a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    NotEq,
+    Less,
+    LessEq,
+    Greater,
+    GreaterEq,
+    /// contains, ignoring case
+    Contains,
+}
+
+/// Operators with the longer ones first, so `>=` is not read as `>`
+const OPS: &[(&str, Op)] = &[
+    ("!=", Op::NotEq),
+    ("<=", Op::LessEq),
+    (">=", Op::GreaterEq),
+    ("=", Op::Eq),
+    ("<", Op::Less),
+    (">", Op::Greater),
+    ("~", Op::Contains),
+];
+
+/// The value a field gets compared to
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Date(Date),
+    /// lowercase text
+    Text(String),
+}
+
+/// A single comparison like `date>2025-01-01`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Condition {
+    field: Field,
+    op: Op,
+    value: Value,
+}
+
+impl FromStr for Condition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (pos, token, op) = OPS
+            .iter()
+            .filter_map(|(token, op)| s.find(token).map(|pos| (pos, *token, *op)))
+            .min_by_key(|(pos, token, _)| (*pos, std::cmp::Reverse(token.len())))
+            .ok_or_else(|| anyhow!("No operator in \"{}\"", s))?;
+        let field = Field::from_str(&s[..pos])?;
+        let raw = s[pos + token.len()..].trim().trim_matches('"');
+        let value = if field == Field::Date {
+            let format = format_description::parse("[year]-[month]-[day]")?;
+            Value::Date(
+                Date::parse(raw, &format)
+                    .with_context(|| format!("\"{}\" is not a date like 2025-01-31", raw))?,
+            )
+        } else {
+            Value::Text(raw.to_lowercase())
+        };
+        let ordered = matches!(op, Op::Less | Op::LessEq | Op::Greater | Op::GreaterEq);
+        if ordered && field != Field::Date {
+            return Err(anyhow!("{} can only be compared with = != and ~", token));
+        }
+        if op == Op::Contains && field == Field::Date {
+            return Err(anyhow!("Dates cannot be compared with ~"));
+        }
+        Ok(Condition { field, op, value })
+    }
+}
+
+impl Condition {
+    fn matches(&self, a: &Album) -> bool {
+        match &self.value {
+            Value::Date(d) => a.date.is_some_and(|date| match self.op {
+                Op::Eq => date == *d,
+                Op::NotEq => date != *d,
+                Op::Less => date < *d,
+                Op::LessEq => date <= *d,
+                Op::Greater => date > *d,
+                Op::GreaterEq => date >= *d,
+                Op::Contains => false,
+            }),
+            Value::Text(t) => {
+                let texts = match self.field {
+                    Field::Id => vec![a.id.to_string()],
+                    Field::Artist => std::iter::once(&a.artist)
+                        .chain(a.other_artists.iter())
+                        .map(|s| s.to_lowercase())
+                        .collect(),
+                    Field::Title => vec![a.title.to_lowercase()],
+                    // both the musicbrainz name like "DJ-mix" and ours like "DJMix"
+                    Field::Type => vec![
+                        String::from(a.release_type.clone()).to_lowercase(),
+                        format!("{:?}", a.release_type).to_lowercase(),
+                    ],
+                    Field::Date => vec![],
+                };
+                match self.op {
+                    Op::Eq => texts.iter().any(|s| s == t),
+                    Op::NotEq => texts.iter().all(|s| s != t),
+                    Op::Contains => texts.iter().any(|s| s.contains(t.as_str())),
+                    _ => false,
+                }
+            }
+        }
+    }
+}
+
+/// A filter like `type=Album && date>2025-01-01 || artist~metal`, `&&` binds stronger than `||`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Filter {
+    /// alternatives, each of them with all the conditions that have to hold
+    any: Vec<Vec<Condition>>,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let any = s
+            .split("||")
+            .map(|all| all.split("&&").map(Condition::from_str).collect())
+            .collect::<Result<Vec<Vec<Condition>>>>()
+            .with_context(|| format!("Could not parse the filter \"{}\"", s))?;
+        Ok(Filter { any })
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, a: &Album) -> bool {
+        self.any.iter().any(|all| all.iter().all(|c| c.matches(a)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+
+    fn album(title: &str, release_type: ReleaseType, year: i32) -> Album {
+        Album {
+            artist: "Some Artist".to_string(),
+            title: title.to_string(),
+            date: Some(Date::from_calendar_date(year, time::Month::June, 1).unwrap()),
+            release_type,
+            other_artists: vec!["Guest".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn filter_combines_conditions() {
+        let f = Filter::from_str("type=Album && date>2025-01-01 || title~live").unwrap();
+        assert!(f.matches(&album("New", ReleaseType::Album, 2025)));
+        assert!(!f.matches(&album("Old", ReleaseType::Album, 2024)));
+        assert!(!f.matches(&album("New", ReleaseType::EP, 2025)));
+        assert!(f.matches(&album("Live at Home", ReleaseType::Live, 2020)));
+        let f = Filter::from_str("artist=guest && date>=2024-06-01").unwrap();
+        assert!(f.matches(&album("Old", ReleaseType::Album, 2024)));
+    }
+
+    #[test]
+    fn invalid_filters() {
+        assert!(Filter::from_str("title>abc").is_err());
+        assert!(Filter::from_str("date=yesterday").is_err());
+        assert!(Filter::from_str("label=x").is_err());
+        assert!(Filter::from_str("type Album").is_err());
+        assert!(parse_fields("artist,size").is_err());
+    }
+}
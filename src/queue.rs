@@ -0,0 +1,196 @@
+use anyhow::{Context, Result};
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::canonical_name;
+use crate::responses::Album;
+
+/// Files a player can play, the others in an album directory like covers are left out
+const AUDIO_EXTENSIONS: &[&str] = &[
+    "flac", "mp3", "ogg", "opus", "m4a", "aac", "wav", "wv", "ape",
+];
+
+/// Settings for writing a queue file for a player after a check
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QueueSettings {
+    /// the m3u8 file every check writes, none by default
+    pub file: Option<PathBuf>,
+    /// music directories with one directory per artist and one per album in it
+    pub library: Vec<PathBuf>,
+    /// the url for albums we do not have, `{artist}`, `{title}` and `{mbid}` get replaced.
+    /// Without it a streaming link of the album is used, or its musicbrainz page
+    pub search_url: Option<String>,
+}
+
+/// The directory of `name` in `dir`, comparing names like artists are compared
+fn find_dir(dir: &Path, name: &str, matches: impl Fn(&str, &str) -> bool) -> Option<PathBuf> {
+    let name = canonical_name(name);
+    let mut found = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.is_dir())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|f| f.to_str())
+                .is_some_and(|f| matches(&canonical_name(f), &name))
+        })
+        .collect::<Vec<PathBuf>>();
+    found.sort_unstable();
+    found.into_iter().next()
+}
+
+/// The directory of album `a` in one of the `library` roots.
+/// Album directories may have something in front of the title, like "2025 - Title"
+pub fn local_album(library: &[PathBuf], a: &Album) -> Option<PathBuf> {
+    library.iter().find_map(|root| {
+        let artist = find_dir(root, &a.artist, |dir, name| dir == name)?;
+        find_dir(&artist, &a.title, |dir, title| dir.ends_with(title))
+    })
+}
+
+/// The audio files in `dir` and its subdirectories like CD1, in order
+fn audio_files(dir: &Path) -> Vec<PathBuf> {
+    let mut entries = fs::read_dir(dir)
+        .map(|d| {
+            d.filter_map(|e| e.ok().map(|e| e.path()))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    entries.sort_unstable();
+    entries
+        .into_iter()
+        .flat_map(|p| {
+            if p.is_dir() {
+                audio_files(&p)
+            } else if p
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|e| AUDIO_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            {
+                vec![p]
+            } else {
+                vec![]
+            }
+        })
+        .collect()
+}
+
+/// The url for album `a` that we do not have locally
+fn remote_url(settings: &QueueSettings, a: &Album) -> String {
+    let encode = |s: &str| {
+        Url::parse_with_params("https://x", [("q", s)])
+            .ok()
+            .and_then(|u| u.query().map(|q| q[2..].to_string()))
+            .unwrap_or_default()
+    };
+    match &settings.search_url {
+        Some(template) => template
+            .replace("{artist}", &encode(&a.artist))
+            .replace("{title}", &encode(&a.title))
+            .replace("{mbid}", &a.id.to_string()),
+        None => a
+            .links
+            .values()
+            .next()
+            .cloned()
+            .unwrap_or_else(|| crate::notify::musicbrainz_url(a)),
+    }
+}
+
+/// The m3u8 playlist with the files of every album in the library and an url for the others
+pub fn playlist(settings: &QueueSettings, albums: &[Album]) -> String {
+    let mut res = String::from("#EXTM3U\n");
+    for a in albums {
+        let name = format!("{} - {}", a.artists(), a.title).replace('\n', " ");
+        let files = local_album(&settings.library, a)
+            .map(|d| audio_files(&d))
+            .unwrap_or_default();
+        if files.is_empty() {
+            res.push_str(&format!(
+                "#EXTINF:-1,{}\n{}\n",
+                name,
+                remote_url(settings, a)
+            ));
+        }
+        for f in files {
+            let track = f.file_stem().unwrap_or_default().to_string_lossy();
+            res.push_str(&format!(
+                "#EXTINF:-1,{} - {}\n{}\n",
+                a.artists(),
+                track,
+                f.display()
+            ));
+        }
+    }
+    res
+}
+
+/// Write the queue file for the `albums` to `path`.
+/// It is replaced atomically so a player watching the directory never sees half of it
+pub fn write(settings: &QueueSettings, path: &Path, albums: &[Album]) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    fs::write(&tmp, playlist(settings, albums))
+        .with_context(|| format!("Writing {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Replacing {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+
+    use uuid::Uuid;
+
+    fn album(n: u128, artist: &str, title: &str) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: artist.to_string(),
+            title: title.to_string(),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn queue_has_local_files_and_urls() {
+        let root = std::env::temp_dir().join(format!("mbrg-queue-{}", fastrand::u64(..)));
+        let dir = root
+            .join("Guns N Roses")
+            .join("2025 - Power Up")
+            .join("CD1");
+        fs::create_dir_all(&dir).unwrap();
+        for f in ["02 Two.flac", "01 One.FLAC", "cover.jpg"] {
+            fs::write(dir.join(f), "").unwrap();
+        }
+        let settings = QueueSettings {
+            library: vec![root.clone()],
+            search_url: Some("https://example.org/search?q={artist}+{title}".to_string()),
+            ..Default::default()
+        };
+        let albums = [
+            album(1, "Guns N' Roses", "Power Up"),
+            album(2, "Someone & Else", "Missing"),
+        ];
+        let s = playlist(&settings, &albums);
+        assert_eq!(
+            s,
+            format!(
+                "#EXTM3U\n#EXTINF:-1,Guns N' Roses - 01 One\n{}\n#EXTINF:-1,Guns N' Roses - 02 Two\n{}\n\
+                 #EXTINF:-1,Someone & Else - Missing\nhttps://example.org/search?q=Someone+%26+Else+Missing\n",
+                dir.join("01 One.FLAC").display(),
+                dir.join("02 Two.flac").display()
+            )
+        );
+        let file = root.join("new-releases.m3u8");
+        write(&QueueSettings::default(), &file, &albums[1..]).unwrap();
+        assert!(fs::read_to_string(&file).unwrap().ends_with(
+            "https://musicbrainz.org/release-group/00000000-0000-0000-0000-000000000002\n"
+        ));
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::{ETAG, IF_MATCH, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use crate::secret;
+
+/// Where the state is stored besides the local config, only WebDAV is supported
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteSettings {
+    /// url of the state file on a WebDAV server, e.g., `https://cloud.example.com/remote.php/dav/files/me/mbrg.json`
+    pub url: Option<String>,
+    pub username: Option<String>,
+    /// can be a `file:`, `env:` or `command:` reference
+    pub password: Option<String>,
+}
+
+/// The remote state and the etag of the version we got
+pub struct RemoteState {
+    pub body: String,
+    pub etag: Option<String>,
+}
+
+/// A blocking client for talking to the remote
+fn client() -> Result<Client> {
+    Client::builder()
+        .user_agent("MusicbrainzReleaseGrabber/1.0 ( https://github.com/narfinger )")
+        .build()
+        .context("Could not build client")
+}
+
+/// Add the credentials to the request if they are configured
+fn authenticated(settings: &RemoteSettings, request: RequestBuilder) -> Result<RequestBuilder> {
+    match &settings.username {
+        Some(user) => {
+            let password = settings
+                .password
+                .as_deref()
+                .map(secret::resolve)
+                .transpose()?;
+            Ok(request.basic_auth(user, password))
+        }
+        None => Ok(request),
+    }
+}
+
+/// The etag header of a response
+fn etag(response: &reqwest::blocking::Response) -> Option<String> {
+    response
+        .headers()
+        .get(ETAG)
+        .and_then(|e| e.to_str().ok())
+        .map(|e| e.to_string())
+}
+
+/// Get the remote state, `None` if it does not exist yet
+pub fn fetch(settings: &RemoteSettings, url: &str) -> Result<Option<RemoteState>> {
+    let response = authenticated(settings, client()?.get(url))?
+        .send()
+        .with_context(|| format!("Could not get remote state from {}", url))?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+    let response = response
+        .error_for_status()
+        .context("Could not get remote state")?;
+    let etag = etag(&response);
+    let body = response.text().context("Could not read remote state")?;
+    Ok(Some(RemoteState { body, etag }))
+}
+
+/// Store `body` as the remote state if it is still at the version with `etag`, `None` means it must not exist yet.
+/// Returns the etag of the new version
+pub fn store(
+    settings: &RemoteSettings,
+    url: &str,
+    body: String,
+    etag: &Option<String>,
+) -> Result<Option<String>> {
+    let client = client()?;
+    let request = authenticated(settings, client.put(url))?.body(body);
+    let request = match etag {
+        Some(e) => request.header(IF_MATCH, e),
+        None => request.header(IF_NONE_MATCH, "*"),
+    };
+    let response = request
+        .send()
+        .with_context(|| format!("Could not store remote state at {}", url))?;
+    if response.status() == StatusCode::PRECONDITION_FAILED {
+        return Err(anyhow!(
+            "The remote state at {} was changed by another machine since we read it, not overwriting it. The local config was written, run again to continue from the remote state",
+            url
+        ));
+    }
+    let response = response
+        .error_for_status()
+        .context("Could not store remote state")?;
+    if let Some(e) = self::etag(&response) {
+        return Ok(Some(e));
+    }
+    // not every server sends the etag on put
+    let response = authenticated(settings, client.head(url))?
+        .send()
+        .and_then(|r| r.error_for_status())
+        .context("Could not get etag of remote state")?;
+    Ok(self::etag(&response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    fn settings() -> RemoteSettings {
+        RemoteSettings {
+            username: Some("me".to_string()),
+            password: Some("secret".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_missing_remote_is_no_state() {
+        let server = MockServer::start(vec![(404, String::new())]);
+        let url = format!("{}/state.json", server.url());
+        assert!(fetch(&settings(), &url).unwrap().is_none());
+        assert!(server.header(0, "authorization").is_some());
+    }
+
+    #[test]
+    fn fetch_remembers_the_etag() {
+        let server = MockServer::start_with_headers(vec![(
+            200,
+            vec![("ETag".to_string(), "\"v1\"".to_string())],
+            "{}".to_string(),
+        )]);
+        let url = format!("{}/state.json", server.url());
+        let state = fetch(&settings(), &url).unwrap().unwrap();
+        assert_eq!(state.body, "{}");
+        assert_eq!(state.etag.as_deref(), Some("\"v1\""));
+    }
+
+    #[test]
+    fn store_only_overwrites_the_version_we_read() {
+        let server = MockServer::start_with_headers(vec![(
+            204,
+            vec![("ETag".to_string(), "\"v2\"".to_string())],
+            String::new(),
+        )]);
+        let url = format!("{}/state.json", server.url());
+        let etag = store(
+            &settings(),
+            &url,
+            "{}".to_string(),
+            &Some("\"v1\"".to_string()),
+        )
+        .unwrap();
+        assert_eq!(etag.as_deref(), Some("\"v2\""));
+        assert_eq!(server.header(0, "if-match").as_deref(), Some("\"v1\""));
+        assert_eq!(server.header(0, "if-none-match"), None);
+        assert_eq!(server.bodies(), vec!["{}".to_string()]);
+    }
+
+    #[test]
+    fn store_creates_a_new_state_only_if_there_is_none() {
+        let server = MockServer::start(vec![(201, String::new()), (200, String::new())]);
+        let url = format!("{}/state.json", server.url());
+        // without an etag on the put the etag comes from a head request
+        assert_eq!(
+            store(&settings(), &url, "{}".to_string(), &None).unwrap(),
+            None
+        );
+        assert_eq!(server.header(0, "if-none-match").as_deref(), Some("*"));
+        assert!(server.requests()[1].starts_with("HEAD "));
+    }
+
+    #[test]
+    fn store_refuses_to_overwrite_a_changed_state() {
+        let server = MockServer::start(vec![(412, String::new())]);
+        let url = format!("{}/state.json", server.url());
+        let err = store(
+            &settings(),
+            &url,
+            "{}".to_string(),
+            &Some("\"v1\"".to_string()),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("changed by another machine"));
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+use yansi::Paint;
+
+use crate::config::{Config, Settings};
+use crate::i18n::tr;
+use crate::prompt;
+use crate::responses::{Album, Artist};
+use crate::storage;
+
+/// How much of a long line we show around an error
+const CONTEXT_CHARS: usize = 40;
+
+/// Where the json error `e` is in `bytes`, with the line and a mark at the column
+pub fn locate(bytes: &[u8], e: &serde_json::Error) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let line = text
+        .lines()
+        .nth(e.line().saturating_sub(1))
+        .unwrap_or_default()
+        .chars()
+        .collect::<Vec<char>>();
+    // compact json is a single line, only show the part around the error
+    let column = e.column().saturating_sub(1).min(line.len());
+    let start = column.saturating_sub(CONTEXT_CHARS);
+    let end = (column + CONTEXT_CHARS).min(line.len());
+    format!(
+        "{}\n    {}\n    {}^",
+        e,
+        line[start..end].iter().collect::<String>(),
+        " ".repeat(column - start)
+    )
+}
+
+/// The entries of the list `v` that are a `T`, the others are reported in `problems`
+fn valid_items<T: DeserializeOwned>(key: &str, v: Value, problems: &mut Vec<String>) -> Value {
+    let Value::Array(items) = v else {
+        return v;
+    };
+    Value::Array(
+        items
+            .into_iter()
+            .enumerate()
+            .filter_map(
+                |(i, item)| match serde_json::from_value::<T>(item.clone()) {
+                    Ok(_) => Some(item),
+                    Err(e) => {
+                        problems.push(format!("{}[{}]: {}", key, i, e));
+                        None
+                    }
+                },
+            )
+            .collect(),
+    )
+}
+
+/// `found` on top of `base` where every field keeps its value in `base` if it does not fit `T`
+fn valid_fields<T: DeserializeOwned>(
+    key: &str,
+    base: Value,
+    found: Map<String, Value>,
+    problems: &mut Vec<String>,
+) -> Value {
+    let mut res = base;
+    for (k, v) in found {
+        let mut candidate = res.clone();
+        candidate[&k] = v;
+        match serde_json::from_value::<T>(candidate.clone()) {
+            Ok(_) => res = candidate,
+            Err(e) => problems.push(format!("{}{}: {}", key, k, e)),
+        }
+    }
+    res
+}
+
+/// The artists in the broken entries of `artist_full`, with only their name and id if they have one
+fn broken_artists(found: &Map<String, Value>) -> (Vec<Artist>, Vec<String>) {
+    let (mut artists, mut names) = (Vec::new(), Vec::new());
+    let entries = found.get("artist_full").and_then(|v| v.as_array());
+    for entry in entries.into_iter().flatten() {
+        if serde_json::from_value::<Artist>(entry.clone()).is_ok() {
+            continue;
+        }
+        let Some(name) = entry.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        match entry
+            .get("id")
+            .and_then(|i| i.as_str())
+            .and_then(|i| Uuid::parse_str(i).ok())
+        {
+            Some(id) => artists.push(Artist {
+                name: name.to_string(),
+                id,
+                search_string: name.to_lowercase(),
+                sort_name: name.to_string(),
+                ..Default::default()
+            }),
+            None => names.push(name.to_string()),
+        }
+    }
+    (artists, names)
+}
+
+/// Everything of the config in `bytes` that can be read, with what could not.
+/// Broken artists are kept with their name and id, or only their name for `init --fill-ids`
+pub fn salvage(bytes: &[u8]) -> Result<(Config, Vec<String>)> {
+    let found: Value = serde_json::from_slice(bytes).context("The config is not json anymore")?;
+    let Value::Object(found) = found else {
+        return Err(anyhow!("The config is not a json object"));
+    };
+    let mut problems = Vec::new();
+    let (artists, names) = broken_artists(&found);
+    let mut res = serde_json::to_value(Config::default())?;
+    for (k, v) in found {
+        let v = match (k.as_str(), v) {
+            ("artist_full", v) => valid_items::<Artist>(&k, v, &mut problems),
+            ("previous" | "watched", v) => valid_items::<Album>(&k, v, &mut problems),
+            ("settings", Value::Object(settings)) => valid_fields::<Settings>(
+                "settings.",
+                serde_json::to_value(Settings::default())?,
+                settings,
+                &mut problems,
+            ),
+            (_, v) => v,
+        };
+        let mut candidate = res.clone();
+        candidate[&k] = v;
+        match serde_json::from_value::<Config>(candidate.clone()) {
+            Ok(_) => res = candidate,
+            Err(e) => problems.push(format!("{}: {}", k, e)),
+        }
+    }
+    let mut c: Config = serde_json::from_value(res)?;
+    for a in artists {
+        if !c.artist_full.iter().any(|o| o.id == a.id) {
+            c.artist_full.push(a);
+        }
+    }
+    c.artist_full.sort_unstable();
+    c.artist_names.extend(names);
+    Ok((c, problems))
+}
+
+/// The config in the backup of `path` if it can be read
+fn read_backup(path: &Path) -> Option<(PathBuf, Config)> {
+    let backup = storage::backup_path(path);
+    let bytes = storage::read_bytes(&backup).ok()?;
+    serde_json::from_slice(&bytes).ok().map(|c| (backup, c))
+}
+
+/// Recover the config at `path` that could not be read because of `error`: from its backup or by salvaging what can be read.
+/// The broken file is kept next to it. Without a terminal this only explains the problem
+pub fn recover(path: &Path, error: anyhow::Error) -> Result<Config> {
+    let file = storage::existing_file(path);
+    let bytes = storage::read_bytes(&file)?;
+    let Err(json_error) = serde_json::from_slice::<Config>(&bytes) else {
+        return Err(error);
+    };
+    println!(
+        "{} {}",
+        tr!("The config {} is corrupt:", file.display()).red(),
+        locate(&bytes, &json_error)
+    );
+    let backup = read_backup(path);
+    if !prompt::is_interactive() {
+        let hint = match &backup {
+            Some((b, _)) => tr!(
+                "Run the command in a terminal to repair it, or copy the backup {} over it",
+                b.display()
+            ),
+            None => tr!("Run the command in a terminal to repair it").to_string(),
+        };
+        return Err(error.context(hint));
+    }
+    let mut items = vec![tr!("Repair it, keeping everything that can be read").to_string()];
+    if let Some((b, c)) = &backup {
+        items.push(tr!(
+            "Use the backup {} with {} artists",
+            b.display(),
+            c.artist_full.len()
+        ));
+    }
+    items.push(tr!("Stop").to_string());
+    let chosen = prompt::select(tr!("What should we do?"), &items)?;
+    let c = match (chosen, backup) {
+        (0, _) => {
+            let (c, problems) = salvage(&bytes)?;
+            for p in &problems {
+                println!("{} {}", tr!("Dropped").yellow(), p);
+            }
+            println!(
+                "{}",
+                tr!(
+                    "Kept {} artists and {} artist names",
+                    c.artist_full.len(),
+                    c.artist_names.len()
+                )
+            );
+            c
+        }
+        (1, Some((_, c))) => c,
+        _ => return Err(error),
+    };
+    let mut corrupt = file.clone().into_os_string();
+    corrupt.push(".corrupt");
+    let corrupt = PathBuf::from(corrupt);
+    fs::rename(&file, &corrupt).with_context(|| format!("Moving {}", file.display()))?;
+    println!(
+        "{}",
+        tr!("Kept the corrupt config as {}", corrupt.display())
+    );
+    Ok(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_marks_the_column() {
+        let bytes = b"{\n  \"artist_names\": [\"A\",,]\n}";
+        let e = serde_json::from_slice::<Value>(bytes).unwrap_err();
+        let s = locate(bytes, &e);
+        assert!(s.starts_with("expected value at line 2 column 24"), "{}", s);
+        assert!(
+            s.ends_with("\n      \"artist_names\": [\"A\",,]\n                           ^"),
+            "{}",
+            s
+        );
+    }
+
+    #[test]
+    fn salvage_keeps_the_artists() {
+        let id = "00000000-0000-0000-0000-000000000001";
+        let bytes = format!(
+            r#"{{"artist_names": ["Name"], "last_checked_time": "not a date", "ignore_paths": [], "previous": [],
+            "artist_full": [
+                {{"name": "Good", "id": "00000000-0000-0000-0000-000000000002", "search_string": "good", "sort_name": "Good"}},
+                {{"name": "Broken", "id": "{}", "search_string": "broken", "sort_name": "Broken", "added": 5}},
+                {{"name": "No id"}}
+            ],
+            "settings": {{"check": {{"min_tracks": "many"}}, "language": "de"}}}}"#,
+            id
+        );
+        let (c, problems) = salvage(bytes.as_bytes()).unwrap();
+        let names = c
+            .artist_full
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["Broken", "Good"]);
+        assert_eq!(c.artist_full[0].id, Uuid::parse_str(id).unwrap());
+        assert_eq!(c.artist_names, vec!["Name", "No id"]);
+        assert_eq!(c.settings.language, Some(crate::i18n::Language::De));
+        assert_eq!(c.settings.check.min_tracks, 0);
+        assert_eq!(problems.len(), 4, "{:?}", problems);
+        assert!(salvage(b"{\"artist_names\": [").is_err());
+    }
+}
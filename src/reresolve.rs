@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::config::canonical_name;
+use crate::i18n::tr;
+use crate::responses::{Artist, Candidate};
+
+/// Below this similarity between the search string and every name of an artist the match is doubtful
+pub const MIN_SIMILARITY: f64 = 0.5;
+
+/// Why we doubt that an artist is the one that was searched for
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// the search scored this low
+    LowScore(u8),
+    /// the name is this similar to the search string, from 0 to 1
+    NameDiffers(f64),
+}
+
+impl Reason {
+    /// What the user sees
+    pub fn describe(&self, artist: &Artist) -> String {
+        match self {
+            Reason::LowScore(score) => tr!("the search scored only {}", score),
+            Reason::NameDiffers(_) => tr!(
+                "the name differs from the search \"{}\"",
+                artist.search_string
+            ),
+        }
+    }
+}
+
+/// The levenshtein distance between `a` and `b`, in characters
+fn distance(a: &[char], b: &[char]) -> usize {
+    let mut row = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, ca) in a.iter().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// How similar `a` and `b` are with our canonicalization, 1 is the same name and 0 nothing in common
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a = canonical_name(a).chars().collect::<Vec<char>>();
+    let b = canonical_name(b).chars().collect::<Vec<char>>();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    1.0 - distance(&a, &b) as f64 / longest as f64
+}
+
+/// How similar the best fitting name, sort name or alias of `artist` is to its search string
+pub fn name_similarity(artist: &Artist) -> f64 {
+    std::iter::once(&artist.name)
+        .chain(std::iter::once(&artist.sort_name))
+        .chain(&artist.aliases)
+        .map(|n| similarity(n, &artist.search_string))
+        .fold(0.0, f64::max)
+}
+
+/// The artists we doubt with why, `scores` are the stored scores of their searches.
+/// Archived artists are left out
+pub fn suspects<'a>(
+    artists: &'a [Artist],
+    scores: &HashMap<Uuid, u8>,
+    min_score: u8,
+) -> Vec<(&'a Artist, Reason)> {
+    artists
+        .iter()
+        .filter(|a| !a.archived)
+        .filter_map(|a| {
+            if let Some(score) = scores.get(&a.id).filter(|s| **s < min_score) {
+                return Some((a, Reason::LowScore(*score)));
+            }
+            let similar = name_similarity(a);
+            (similar < MIN_SIMILARITY).then_some((a, Reason::NameDiffers(similar)))
+        })
+        .collect()
+}
+
+/// The search results for `search` with the best match first: names that are the search string
+/// after canonicalization before the others, then by score
+pub fn rank(search: &str, mut found: Vec<(Artist, Candidate)>) -> Vec<(Artist, Candidate)> {
+    let wanted = canonical_name(search);
+    let exact = |a: &Artist| {
+        std::iter::once(&a.name)
+            .chain(&a.aliases)
+            .any(|n| canonical_name(n) == wanted)
+    };
+    found.sort_by_key(|(a, c)| (!exact(a), std::cmp::Reverse(c.score.unwrap_or(0))));
+    found
+}
+
+/// `artist` changed to be `correct`, keeping what we know about why we follow it
+pub fn correct(artist: &Artist, correct: Artist) -> Artist {
+    Artist {
+        search_string: artist.search_string.clone(),
+        added: artist.added,
+        source: artist.source.clone(),
+        archived: artist.archived,
+        handoff: artist.handoff,
+        broadcasts: artist.broadcasts,
+        ..correct
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn artist(name: &str, search: &str, id: u128) -> Artist {
+        Artist {
+            name: name.to_string(),
+            id: Uuid::from_u128(id),
+            search_string: search.to_string(),
+            sort_name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn candidate(a: &Artist, score: u8) -> Candidate {
+        Candidate {
+            id: a.id,
+            name: a.name.clone(),
+            disambiguation: None,
+            score: Some(score),
+        }
+    }
+
+    #[test]
+    fn similarity_uses_canonical_names() {
+        assert_eq!(similarity("Guns N' Roses", "guns n roses"), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+        assert!((similarity("kitten", "sitting") - (1.0 - 3.0 / 7.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn suspects_have_low_scores_or_other_names() {
+        let mut archived = artist("Something Else", "other", 4);
+        archived.archived = true;
+        let mut aliased = artist("Die Ärzte", "die arzte", 5);
+        aliased.aliases = vec!["Die Arzte".to_string()];
+        let artists = vec![
+            artist("The Beatles", "beatles", 1),
+            artist("Beatallica", "beatles", 2),
+            artist("Blur", "blur", 3),
+            archived,
+            aliased,
+            artist("Completely Different", "nirvana", 6),
+        ];
+        let scores = HashMap::from([(Uuid::from_u128(2), 60), (Uuid::from_u128(3), 100)]);
+        let found = suspects(&artists, &scores, 90)
+            .into_iter()
+            .map(|(a, r)| (a.name.as_str(), r))
+            .collect::<Vec<_>>();
+        assert_eq!(found.len(), 2, "{:?}", found);
+        assert_eq!(found[0], ("Beatallica", Reason::LowScore(60)));
+        assert_eq!(found[1].0, "Completely Different");
+    }
+
+    #[test]
+    fn rank_prefers_exact_names() {
+        let tribute = artist("Nirvana Tribute", "nirvana", 1);
+        let band = artist("Nirvana", "nirvana", 2);
+        let ranked = rank(
+            "nirvana",
+            vec![
+                (tribute.clone(), candidate(&tribute, 100)),
+                (band.clone(), candidate(&band, 90)),
+            ],
+        );
+        assert_eq!(ranked[0].0.name, "Nirvana");
+
+        let mut old = artist("Nirvana Tribute", "nirvana", 1);
+        old.added = time::Date::from_calendar_date(2024, time::Month::January, 1).ok();
+        old.last_checked = old.added;
+        let fixed = correct(&old, ranked[0].0.clone());
+        assert_eq!(fixed.id, band.id);
+        assert_eq!(fixed.added, old.added);
+        assert_eq!(fixed.last_checked, None);
+    }
+}
@@ -1,23 +1,74 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
+use indicatif::ProgressBar;
 use ratelimit::Ratelimiter;
 use reqwest::blocking::Client;
+use reqwest::StatusCode;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess,
+    Visitor,
+};
 use serde::{Deserialize, Serialize};
 use time::{format_description, Date};
 use uuid::Uuid;
 
+use crate::adaptive::AdaptiveRate;
+use crate::formats::MediaFormat;
+use crate::i18n::tr;
+use crate::links::Provider;
+
 const HOW_MANY_RELEASE_RESULT: i32 = 100;
-const ARTIST_SEARCH_URL: &str = "https://musicbrainz.org/ws/2/artist/";
-const ALBUM_QUERY_STRING: &str = "https://musicbrainz.org/ws/2/release-group";
+/// Upper bound of pages for one browse request, so a wrong count can not keep us fetching forever
+const MAX_PAGES: usize = 100;
+/// How often we retry a request that failed with a retryable error
+const RETRIES: u32 = 3;
+/// Pause before the first retry, doubled for every further one
+const RETRY_PAUSE: Duration = Duration::from_secs(2);
+/// How long we wait for a connection to a server
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long a request may take from sending it to reading the whole response
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Pause of all requests after musicbrainz said that we exceed the rate limit without saying for how long
+const THROTTLE_PAUSE: Duration = Duration::from_secs(10);
+const MUSICBRAINZ_SERVER: &str = "https://musicbrainz.org";
+const ARTIST_SEARCH_URL: &str = "/ws/2/artist/";
+const ALBUM_QUERY_STRING: &str = "/ws/2/release-group";
+const RELEASE_QUERY_STRING: &str = "/ws/2/release";
+const LABEL_LOOKUP_URL: &str = "/ws/2/label/";
 
 /// Json response for an artist
 #[derive(Debug, Serialize, Deserialize)]
 struct ArtistsResponse {
-    id: String,
+    id: Uuid,
     name: String,
     #[serde(rename(deserialize = "sort-name"))]
     sort_name: String,
+    #[serde(default)]
+    aliases: Vec<AliasResponse>,
+    #[serde(default)]
+    tags: Vec<TagResponse>,
+    #[serde(default)]
+    score: Option<u8>,
+    #[serde(default)]
+    disambiguation: Option<String>,
+}
+
+/// Json response for an alias of an artist
+#[derive(Debug, Serialize, Deserialize)]
+struct AliasResponse {
+    name: String,
+}
+
+/// Json response for a tag
+#[derive(Debug, Serialize, Deserialize)]
+struct TagResponse {
+    name: String,
 }
 
 /// JSON response for a search query
@@ -26,17 +77,237 @@ struct SearchResponse {
     artists: Vec<ArtistsResponse>,
 }
 
+/// Json response for an artist, label or release group that we looked up with its relations
+#[derive(Debug, Deserialize)]
+struct RelationsResponse {
+    #[serde(default)]
+    relations: Vec<RelationResponse>,
+}
+
+/// Json response for a relation to an artist, a label or an url
+#[derive(Debug, Deserialize)]
+struct RelationResponse {
+    #[serde(rename = "type")]
+    relation_type: String,
+    artist: Option<RelatedEntityResponse>,
+    label: Option<RelatedEntityResponse>,
+    url: Option<UrlResponse>,
+}
+
+/// Json response for the url of a relation
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct UrlResponse {
+    resource: String,
+}
+
+/// Json response for the other side of a relation
+#[derive(Debug, Deserialize)]
+struct RelatedEntityResponse {
+    id: Uuid,
+    name: String,
+    #[serde(rename = "sort-name")]
+    sort_name: String,
+}
+
+/// An artist that has a relation to one of ours
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RelatedArtist {
+    pub id: Uuid,
+    pub name: String,
+    pub sort_name: String,
+    /// how they are related, like "member of band" or "recording contract with Some Label"
+    pub relation: String,
+}
+
+impl RelatedArtist {
+    /// The artist for adding it to the config
+    pub fn to_artist(&self, source: ArtistSource, today: Date) -> Artist {
+        Artist {
+            name: self.name.clone(),
+            id: self.id,
+            search_string: self.name.clone(),
+            sort_name: self.sort_name.clone(),
+            ..Default::default()
+        }
+        .added_by(source, today)
+    }
+}
+
+/// An artist that an artist search found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub id: Uuid,
+    pub name: String,
+    /// how musicbrainz tells artists with the same name apart
+    #[serde(default)]
+    pub disambiguation: Option<String>,
+    /// how well the artist fits the search, up to 100
+    #[serde(default)]
+    pub score: Option<u8>,
+}
+
+/// The body musicbrainz sends with error statuses
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+/// How long every request has to wait after a throttled one, from the `Retry-After` header or the
+/// rate limit hint in the error `body`. None if musicbrainz did not signal a throttle
+fn throttle_pause(retry_after: Option<&str>, body: &str, default: Duration) -> Option<Duration> {
+    if let Some(secs) = retry_after.and_then(|r| r.trim().parse::<u64>().ok()) {
+        return Some(Duration::from_secs(secs));
+    }
+    let error = serde_json::from_str::<ErrorResponse>(body).ok()?.error;
+    error
+        .to_lowercase()
+        .contains("rate limit")
+        .then_some(default)
+}
+
+/// Errors when talking to musicbrainz
+#[derive(Debug)]
+pub enum MbError {
+    /// the searched entity does not exist
+    NotFound(String),
+    /// musicbrainz throttles us
+    RateLimited,
+    /// the response did not have the format we expected
+    Decode(String),
+    /// we did not get a response
+    Network(reqwest::Error),
+    /// musicbrainz answered with an error status
+    ServerError(u16),
+    /// reading or writing a recorded response failed
+    Replay(String),
+    /// the artist took longer than its time budget of this long
+    OverBudget(Duration),
+}
+
+impl MbError {
+    /// Does it make sense to do the request again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            MbError::RateLimited | MbError::Network(_) => true,
+            MbError::ServerError(status) => *status >= 500,
+            MbError::NotFound(_)
+            | MbError::Decode(_)
+            | MbError::Replay(_)
+            | MbError::OverBudget(_) => false,
+        }
+    }
+
+    /// Should we stop the whole run instead of continuing with the next artist
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, MbError::RateLimited)
+    }
+
+    /// The http status code if we got a response
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            MbError::NotFound(_) => Some(404),
+            MbError::RateLimited => Some(503),
+            MbError::ServerError(status) => Some(*status),
+            MbError::Decode(_) | MbError::Replay(_) | MbError::OverBudget(_) => None,
+            MbError::Network(e) => e.status().map(|s| s.as_u16()),
+        }
+    }
+}
+
+impl Display for MbError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MbError::NotFound(s) => write!(f, "could not find {}", s),
+            MbError::RateLimited => write!(f, "musicbrainz is rate limiting us"),
+            MbError::Decode(s) => write!(f, "could not decode response: {}", s),
+            MbError::Network(e) => write!(f, "network error: {}", e),
+            MbError::ServerError(status) => {
+                write!(f, "musicbrainz answered with status {}", status)
+            }
+            MbError::Replay(s) => write!(f, "recorded response: {}", s),
+            MbError::OverBudget(budget) => {
+                write!(f, "took longer than the budget of {}s", budget.as_secs())
+            }
+        }
+    }
+}
+
+impl std::error::Error for MbError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            MbError::Network(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 /// Artist from musicbrainz
-#[derive(Debug, Serialize, Deserialize, Eq)]
-pub(crate) struct Artist {
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq)]
+pub struct Artist {
     /// Artist String from musicbrainz
-    pub(crate) name: String,
+    pub name: String,
     /// Musicbrainz Artist UUID
-    pub(crate) id: Uuid,
+    pub id: Uuid,
     /// The original search string, i.e., the directory. Good to see where our search went wrong
-    pub(crate) search_string: String,
+    pub search_string: String,
     /// sort name in musicbrainz
-    pub(crate) sort_name: String,
+    pub sort_name: String,
+    /// other names of the artist in musicbrainz
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// tags of the artist in musicbrainz
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// date of the newest release we found in the last check
+    #[serde(default)]
+    pub last_release: Option<Date>,
+    /// the day we last checked this artist successfully
+    #[serde(default)]
+    pub last_checked: Option<Date>,
+    /// the day the artist was added to the config
+    #[serde(default)]
+    pub added: Option<Date>,
+    /// how the artist was added to the config
+    #[serde(default)]
+    pub source: ArtistSource,
+    /// archived artists stay in the config but are not checked
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub archived: bool,
+    /// new albums of the artist get handed to the hook command
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub handoff: bool,
+    /// broadcast release groups of the artist are followed like episodes of a series
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub broadcasts: bool,
+}
+
+/// How an artist got into the config
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArtistSource {
+    /// added before we recorded this
+    #[default]
+    Unknown,
+    /// added with the add command
+    Manual,
+    /// found in a music directory
+    Directory,
+    /// imported from another service, with its name
+    Import(String),
+    /// added from the suggestions for an artist we follow, with its name
+    Suggestion(String),
+}
+
+impl Display for ArtistSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArtistSource::Unknown => write!(f, "unknown"),
+            ArtistSource::Manual => write!(f, "added manually"),
+            ArtistSource::Directory => write!(f, "directory scan"),
+            ArtistSource::Import(s) => write!(f, "import from {}", s),
+            ArtistSource::Suggestion(s) => write!(f, "suggested for {}", s),
+        }
+    }
 }
 
 impl PartialEq for Artist {
@@ -47,7 +318,7 @@ impl PartialEq for Artist {
 
 impl PartialOrd for Artist {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.name.partial_cmp(&other.name)
+        Some(self.cmp(other))
     }
 }
 
@@ -58,18 +329,102 @@ impl Ord for Artist {
 }
 
 /// Album that got released
-#[derive(Clone, Debug, Eq, Serialize, Deserialize)]
-pub(crate) struct Album {
+#[derive(Clone, Debug, Default, Eq, Serialize, Deserialize)]
+pub struct Album {
     /// the uuid on musicbrainz
-    pub(crate) id: Uuid,
+    pub id: Uuid,
     /// the artist
-    pub(crate) artist: String,
+    pub artist: String,
     /// the title of the album
-    pub(crate) title: String,
+    pub title: String,
     /// the date of the album
-    pub(crate) date: Option<Date>,
+    pub date: Option<Date>,
     /// type of the release
-    pub(crate) release_type: ReleaseType,
+    pub release_type: ReleaseType,
+    /// other followed artists that have the same release group
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub other_artists: Vec<String>,
+    /// the community rating of the release group, if anybody rated it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rating: Option<Rating>,
+    /// links to the album on streaming services
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub links: BTreeMap<Provider, String>,
+    /// the kinds of media the releases of the album come on, empty if we did not fetch them
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub formats: BTreeSet<MediaFormat>,
+    /// a store already takes orders for the upcoming album
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub preorder: bool,
+    /// years since the previous release of the artist, only when that is long
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gap_years: Option<u16>,
+}
+
+/// Community rating of a release group
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rating {
+    /// the average rating from 0 to 100, musicbrainz shows it as 0 to 5 stars
+    pub score: u8,
+    /// how many people rated
+    pub votes: u32,
+}
+
+impl Rating {
+    /// The rating as five stars with the exact value, like "★★★★☆ 4.2"
+    pub fn stars(&self) -> String {
+        let full = (usize::from(self.score.min(100)) + 10) / 20;
+        format!(
+            "{}{} {:.1}",
+            "★".repeat(full),
+            "☆".repeat(5 - full),
+            f32::from(self.score) / 20.0
+        )
+    }
+}
+
+impl Album {
+    /// The artist and all other followed artists of this album
+    pub fn artists(&self) -> String {
+        std::iter::once(&self.artist)
+            .chain(self.other_artists.iter())
+            .cloned()
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+/// Remove albums with the same release group id, the artists of the removed albums get added to `other_artists`
+pub fn dedup_release_groups(albums: Vec<Album>) -> Vec<Album> {
+    let mut index: HashMap<Uuid, usize> = HashMap::new();
+    let mut res: Vec<Album> = Vec::new();
+    for a in albums {
+        if let Some(&i) = index.get(&a.id) {
+            let existing = &mut res[i];
+            if existing.artist != a.artist && !existing.other_artists.contains(&a.artist) {
+                existing.other_artists.push(a.artist);
+            }
+        } else {
+            index.insert(a.id, res.len());
+            res.push(a);
+        }
+    }
+    res
+}
+
+/// Only keep one album of the ones with the same title, the result is sorted by title
+pub fn dedup_by_title(mut albums: Vec<Album>) -> Vec<Album> {
+    albums.sort_by(|a, b| a.title.cmp(&b.title)); // this is necessary to remove all duplicated elements
+    albums.dedup_by(|a, b| a.title.eq(&b.title));
+    albums
+}
+
+/// The albums with a release date on or after `since`
+pub fn released_since(albums: &[Album], since: Date) -> Vec<&Album> {
+    albums
+        .iter()
+        .filter(|a| a.date.is_some_and(|d| d >= since))
+        .collect()
 }
 
 impl PartialEq for Album {
@@ -80,10 +435,7 @@ impl PartialEq for Album {
 
 impl PartialOrd for Album {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.artist
-            .partial_cmp(&other.artist)
-            .or_else(|| self.date.partial_cmp(&other.date))
-            .or_else(|| self.title.partial_cmp(&other.title))
+        Some(self.cmp(other))
     }
 }
 
@@ -96,191 +448,2253 @@ impl Ord for Album {
     }
 }
 
-impl Artist {
-    /// Search for an artist given by string `s` and construct an artist object
-    pub(crate) fn new(client: &Client, s: &str, ratelimit: &Ratelimiter) -> Result<Self> {
-        for _ in 0..10 {
-            if let Err(sleep) = ratelimit.try_wait() {
-                std::thread::sleep(sleep);
-                continue;
+/// Search release groups with a free form `query` in the musicbrainz search syntax
+pub fn search_release_groups(mb: &MbClient, query: &str) -> Result<Vec<Album>, MbError> {
+    let resp: ReleaseGroupSearchResponse = mb.fetch_json(
+        ALBUM_QUERY_STRING,
+        &[
+            ("query", query.to_string()),
+            ("limit", "25".to_string()),
+            ("fmt", "json".to_string()),
+        ],
+    )?;
+    Ok(resp
+        .release_groups
+        .into_iter()
+        .map(album_from_search)
+        .collect())
+}
+
+/// Search release groups like `search_release_groups` but with up to `max` results over several pages
+pub fn search_many_release_groups(
+    mb: &MbClient,
+    query: &str,
+    max: usize,
+) -> Result<Vec<Album>, MbError> {
+    let mut res = Vec::new();
+    while res.len() < max {
+        let limit = (max - res.len()).min(HOW_MANY_RELEASE_RESULT as usize);
+        let resp: ReleaseGroupSearchResponse = mb.fetch_json(
+            ALBUM_QUERY_STRING,
+            &[
+                ("query", query.to_string()),
+                ("limit", limit.to_string()),
+                ("offset", res.len().to_string()),
+                ("fmt", "json".to_string()),
+            ],
+        )?;
+        let len = resp.release_groups.len();
+        res.extend(resp.release_groups.into_iter().map(album_from_search));
+        if len < limit || resp.count.is_some_and(|c| res.len() >= c) {
+            break;
+        }
+    }
+    Ok(res)
+}
+
+/// The search query for albums tagged with `tag` that came out on or after `since`
+pub fn tag_query(tag: &str, since: Date) -> String {
+    format!(
+        "tag:\"{}\" AND primarytype:album AND firstreleasedate:[{} TO *]",
+        tag.replace('\\', "\\\\").replace('"', "\\\""),
+        since
+    )
+}
+
+/// The album for a release group from a search, which has the artists in the credit
+fn album_from_search(rg: ReleaseGroup) -> Album {
+    Album {
+        id: rg.id,
+        artist: rg
+            .artist_credit
+            .iter()
+            .map(|c| format!("{}{}", c.name, c.joinphrase))
+            .collect(),
+        title: rg.title,
+        date: rg.first_release_date.as_deref().and_then(parse_date),
+        release_type: rg
+            .secondary_types
+            .first()
+            .or(rg.primary_type.as_ref())
+            .unwrap_or(&ReleaseType::None)
+            .to_owned(),
+        rating: rg.rating.as_ref().and_then(RatingResponse::to_rating),
+        ..Default::default()
+    }
+}
+
+/// Browse all releases linked to the `entity` with `id`, e.g., all releases of an artist
+/// `inc` are the additional includes for the browse request
+fn browse_releases(
+    mb: &MbClient,
+    entity: &str,
+    id: &Uuid,
+    inc: &str,
+) -> Result<Vec<Release>, MbError> {
+    paginate(&format!("releases of {} {}", entity, id), |offset| {
+        let resp: ReleaseBrowseResponse = mb.fetch_json(
+            RELEASE_QUERY_STRING,
+            &[
+                (entity, id.to_string()),
+                ("inc", inc.to_string()),
+                ("offset", offset.to_string()),
+                ("limit", HOW_MANY_RELEASE_RESULT.to_string()),
+                ("fmt", "json".to_string()),
+            ],
+        )?;
+        Ok(Page::new(resp.releases, resp.release_count))
+    })
+}
+
+/// The urls the release group with `id` links to, like pages on streaming services
+pub fn release_group_urls(mb: &MbClient, id: &Uuid) -> Result<Vec<String>, MbError> {
+    let resp: RelationsResponse = mb.fetch_json(
+        &format!("{}/{}", ALBUM_QUERY_STRING, id),
+        &[("inc", "url-rels".to_string()), ("fmt", "json".to_string())],
+    )?;
+    Ok(resp
+        .relations
+        .into_iter()
+        .filter_map(|r| r.url.map(|u| u.resource))
+        .collect())
+}
+
+/// The recordings of the release group with `id` in track order.
+/// They come from the earliest official release, or the earliest release if none is official
+pub fn release_group_recordings(mb: &MbClient, id: &Uuid) -> Result<Vec<Uuid>, MbError> {
+    let releases = browse_releases(mb, "release-group", id, "recordings")?;
+    let release = releases
+        .into_iter()
+        .filter(|r| r.media.iter().any(|m| !m.tracks.is_empty()))
+        .min_by_key(|r| {
+            let date = r.date.as_deref().and_then(parse_date);
+            (r.status != Some(Status::Official), date.is_none(), date)
+        });
+    Ok(release
+        .map(|r| {
+            r.media
+                .into_iter()
+                .flat_map(|m| m.tracks)
+                .map(|t| t.recording.id)
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// One page of a browse request
+struct Page<T> {
+    /// the entries we kept
+    items: Vec<T>,
+    /// how many entries the page had before filtering
+    len: usize,
+    /// the total count the server reported
+    count: Option<usize>,
+}
+
+impl<T> Page<T> {
+    /// A page where we kept all entries
+    fn new(items: Vec<T>, count: Option<usize>) -> Self {
+        Self {
+            len: items.len(),
+            items,
+            count,
+        }
+    }
+}
+
+/// Fetch all pages of a browse request. `fetch` gets the offset and returns the page.
+/// Stops on an empty page, after `MAX_PAGES` or, without a count, on a page that is not full
+fn paginate<T>(
+    what: &str,
+    mut fetch: impl FnMut(usize) -> Result<Page<T>, MbError>,
+) -> Result<Vec<T>, MbError> {
+    let mut all = Vec::new();
+    let mut seen = 0;
+    let mut count = None;
+    for page in 0.. {
+        if page == MAX_PAGES {
+            eprintln!("Stopping after {} pages of {}", MAX_PAGES, what);
+            break;
+        }
+        let mut p = fetch(seen)?;
+        seen += p.len;
+        all.append(&mut p.items);
+        count = p.count.or(count);
+        let done = match count {
+            Some(count) => seen >= count,
+            None => p.len < HOW_MANY_RELEASE_RESULT as usize,
+        };
+        if p.len == 0 || done {
+            break;
+        }
+    }
+    if let Some(count) = count.filter(|c| *c != seen) {
+        eprintln!(
+            "Musicbrainz reported {} {} but we got {}",
+            count, what, seen
+        );
+    }
+    Ok(all)
+}
+
+/// Reads a page of release groups and only keeps the ones where `keep` is true while parsing.
+/// The response text is already read completely, only the release groups we drop are never turned into structs
+struct ReleaseGroupPageSeed<'a, F> {
+    keep: &'a F,
+}
+
+impl<'de, F: Fn(&ReleaseGroup) -> bool> DeserializeSeed<'de> for ReleaseGroupPageSeed<'_, F> {
+    type Value = Page<ReleaseGroup>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, F: Fn(&ReleaseGroup) -> bool> Visitor<'de> for ReleaseGroupPageSeed<'_, F> {
+    type Value = Page<ReleaseGroup>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a page of release groups")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut page = Page::new(vec![], None);
+        let mut found_groups = false;
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "release-group-count" => page.count = map.next_value()?,
+                "release-groups" => {
+                    found_groups = true;
+                    let (items, len) = map.next_value_seed(FilteredSeq { keep: self.keep })?;
+                    page.items = items;
+                    page.len = len;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
             }
         }
-        let resp: SearchResponse = client
-            .get(ARTIST_SEARCH_URL)
-            .query(&[("query", s), ("limit", &3.to_string()), ("fmt", "json")])
-            .send()
-            .context("Error in getting artist id")?
-            .error_for_status()
-            .context("Error in getting status")?
-            .json()
-            .context("Error in decoding artist id response")?;
-        if resp.artists.is_empty() {
-            Err(anyhow!("could not find UUID for {}", s))
+        if found_groups {
+            Ok(page)
         } else {
-            let id = Uuid::parse_str(&resp.artists[0].id).context("Error in parsing uuid")?;
-            Ok(Artist {
-                name: resp.artists[0].name.clone(),
-                id,
-                search_string: s.to_owned(),
-                sort_name: resp.artists[0].sort_name.clone(),
-            })
+            Err(de::Error::missing_field("release-groups"))
         }
     }
+}
 
-    /// Get albums for this artist
-    fn get_albums(&self, client: &Client, ratelimit: &Ratelimiter) -> Result<Vec<ReleaseGroup>> {
-        let mut all_releases = Vec::new();
+/// Reads a json array and only keeps the elements where `keep` is true, together with the length of the array
+struct FilteredSeq<'a, F> {
+    keep: &'a F,
+}
 
-        for _ in 0..10 {
-            if let Err(sleep) = ratelimit.try_wait() {
-                std::thread::sleep(sleep);
-                continue;
+impl<'de, F: Fn(&ReleaseGroup) -> bool> DeserializeSeed<'de> for FilteredSeq<'_, F> {
+    type Value = (Vec<ReleaseGroup>, usize);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, F: Fn(&ReleaseGroup) -> bool> Visitor<'de> for FilteredSeq<'_, F> {
+    type Value = (Vec<ReleaseGroup>, usize);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a list of release groups")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut items = Vec::new();
+        let mut len = 0;
+        while let Some(rg) = seq.next_element::<ReleaseGroup>()? {
+            len += 1;
+            if (self.keep)(&rg) {
+                items.push(rg);
             }
         }
+        Ok((items, len))
+    }
+}
 
-        let mut resp: LookupResponse = client
-            .get(ALBUM_QUERY_STRING)
-            .query(&[
-                ("artist", self.id.to_string()),
-                ("limit", HOW_MANY_RELEASE_RESULT.to_string()),
-                ("fmt", "json".to_string()),
-            ])
-            .send()
-            .context("Error in getting albums")?
-            .error_for_status()?
-            .json()
-            .with_context(|| format!("Error in decoding albums for artist {}", self.name))?;
-        all_releases.append(&mut resp.release_groups);
-        let total_results = resp.release_count.unwrap_or(0);
-        while all_releases.len() < total_results {
-            for _ in 0..10 {
-                if let Err(sleep) = ratelimit.try_wait() {
-                    std::thread::sleep(sleep);
-                    continue;
-                }
-            }
-            let response = client
-                .get(ALBUM_QUERY_STRING)
-                .query(&[
-                    ("artist", self.id.to_string()),
-                    ("offset", all_releases.len().to_string()),
-                    ("limit", HOW_MANY_RELEASE_RESULT.to_string()),
-                    ("fmt", "json".to_string()),
-                ])
-                .send()
-                .context("Error in getting albums step")?
-                .error_for_status()
-                .with_context(|| {
-                    format!("Error in getting status code for artist {}", self.name)
-                })?;
+/// Releases inside of release groups, every release group gets fetched only once on first use
+#[derive(Debug, Default)]
+pub struct GroupReleases {
+    releases: HashMap<Uuid, Vec<Release>>,
+}
 
-            let mut resp: LookupResponse = response.json().context("Error in decoding albums")?;
-            all_releases.append(&mut resp.release_groups);
+impl GroupReleases {
+    /// The releases of the release group with `id`
+    pub fn get(&mut self, mb: &MbClient, id: &Uuid) -> Result<&[Release], MbError> {
+        if !self.releases.contains_key(id) {
+            let releases = browse_releases(mb, "release-group", id, "media+url-rels")?;
+            self.releases.insert(*id, releases);
         }
+        Ok(&self.releases[id])
+    }
+}
+
+thread_local! {
+    /// when the work of this thread on the current artist has to be done, with its budget
+    static DEADLINE: Cell<Option<(Instant, Duration)>> = const { Cell::new(None) };
+}
+
+/// Do `f` in `budget`, requests after it fail with `MbError::OverBudget` and a running one is cut short.
+/// The budget is for this thread only, so every worker has one for its artist
+pub fn within_budget<T>(budget: Option<Duration>, f: impl FnOnce() -> T) -> T {
+    let before = DEADLINE.replace(budget.map(|b| (Instant::now() + b, b)));
+    let res = f();
+    DEADLINE.set(before);
+    res
+}
 
-        Ok(all_releases)
+/// How much of the budget of this thread is left, fails when nothing is
+fn remaining_budget() -> Result<Option<Duration>, MbError> {
+    match DEADLINE.get() {
+        None => Ok(None),
+        Some((deadline, budget)) => match deadline.checked_duration_since(Instant::now()) {
+            Some(left) if !left.is_zero() => Ok(Some(left)),
+            _ => Err(MbError::OverBudget(budget)),
+        },
     }
+}
 
-    /// Filter albums by simple release type and returns the albums found
-    /// Notice that this filters out also albums that do not have a specific year-month-day release date in the db
-    pub(crate) fn get_albums_basic_filtered(
-        &self,
-        client: &Client,
-        ratelimit: &Ratelimiter,
-    ) -> Result<Vec<Album>> {
-        let albs_resp = self.get_albums(client, ratelimit)?;
-        let format = format_description::parse("[year]-[month]-[day]")?;
-        let mut albs = albs_resp
-            .into_iter()
-            .filter(|a| a.primary_type == Some(ReleaseType::Album))
-            .map(|a: ReleaseGroup| {
-                let date = a
-                    .first_release_date
-                    .and_then(|s| Date::parse(&s, &format).ok());
-                Album {
-                    id: a.id,
-                    artist: self.name.to_owned(),
-                    title: a.title,
-                    date,
-                    release_type: a
-                        .secondary_types
-                        .first()
-                        .unwrap_or(&ReleaseType::Album)
-                        .to_owned(),
-                }
-            })
-            .filter(|a| a.date.is_some())
-            .collect::<Vec<_>>();
-        albs.sort_by_key(|a| a.title.clone()); // this is necessary to remove all duplicated elements
-        albs.dedup_by(|a, b| a.title.eq(&b.title));
-        albs.sort_by_key(|a| a.date);
-        Ok(albs)
+/// Wait until the ratelimiter allows us to do the next request
+fn wait_for_ratelimit(ratelimit: &Ratelimiter) {
+    loop {
+        match ratelimit.try_wait() {
+            Ok(()) => return,
+            Err(sleep) => std::thread::sleep(sleep),
+        }
     }
 }
 
-/// JSON response for the releases lookup
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct LookupResponse {
-    #[serde(rename = "release-offset")]
-    release_offset: Option<usize>,
-    #[serde(rename = "release-count")]
-    release_count: Option<usize>,
-    #[serde(rename = "release-groups")]
-    release_groups: Vec<ReleaseGroup>,
+/// Parse a complete year-month-day date, partial dates like only a year return `None`
+fn parse_date(s: &str) -> Option<Date> {
+    let format = format_description::parse("[year]-[month]-[day]").ok()?;
+    Date::parse(s, &format).ok()
 }
 
-/// JSON response for ReleaseGroup
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct ReleaseGroup {
-    id: Uuid,
-    title: String,
-    #[serde(rename = "primary-type")]
-    primary_type: Option<ReleaseType>,
-    #[serde(rename = "first-release-date")]
-    first_release_date: Option<String>,
-    #[serde(rename = "secondary-types")]
-    secondary_types: Vec<ReleaseType>,
+/// Release group responses on disk, so runs within the ttl do not ask musicbrainz for them again
+pub struct ResponseCache {
+    dir: PathBuf,
+    /// how long a response stays fresh
+    ttl: Duration,
+    /// never answer from the cache, only write the new responses into it
+    refresh: bool,
 }
 
-/// type/status of release
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
-enum Status {
-    Official,
-    Promotion,
-    Bootleg,
-    #[serde(rename = "Pseudo-Release")]
-    PseudoRelease,
-    Withdrawn,
-    Cancelled,
+impl ResponseCache {
+    pub fn new(dir: PathBuf, ttl: Duration, refresh: bool) -> Self {
+        Self { dir, ttl, refresh }
+    }
+
+    /// Only the release groups of one artist get cached, searches should always be current
+    fn caches(path: &str, query: &[(&str, String)]) -> bool {
+        path == ALBUM_QUERY_STRING && query.iter().any(|(k, _)| *k == "artist")
+    }
+
+    /// The file for the response to `url` with `query`, the server is part of the url
+    /// so a mirror never answers with the responses of musicbrainz.org
+    fn file(&self, url: &str, query: &[(&str, String)]) -> PathBuf {
+        self.dir.join(recording_file_name(url, query))
+    }
+
+    /// Is the file `file` younger than the ttl
+    fn is_fresh(&self, file: &Path) -> bool {
+        std::fs::metadata(file)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|m| m.elapsed().ok())
+            .is_some_and(|age| age < self.ttl)
+    }
+
+    /// The cached response for `url` with `query` if it is younger than the ttl
+    fn get(&self, url: &str, query: &[(&str, String)]) -> Option<String> {
+        if self.refresh {
+            return None;
+        }
+        let file = self.file(url, query);
+        self.is_fresh(&file)
+            .then(|| std::fs::read_to_string(&file).ok())
+            .flatten()
+    }
+
+    /// Keep `text`, a cache we cannot write to only makes the next run slower
+    fn put(&self, url: &str, query: &[(&str, String)], text: &str) {
+        let file = self.file(url, query);
+        let _ = std::fs::create_dir_all(&self.dir).and_then(|_| std::fs::write(file, text));
+    }
+
+    /// Remove the responses older than the ttl, they would never be used again
+    pub fn prune(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            return;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().is_some_and(|e| e == "json") && !self.is_fresh(&path) {
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
 }
 
-/// release type
-#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
-pub(crate) enum ReleaseType {
-    None,
-    EP,
-    Album,
-    Single,
-    Other,
-    Broadcast,
-    Compilation,
-    Spokenword,
-    Interview,
-    Soundtrack,
-    Audiobook,
-    #[serde(rename = "DJ-mix")]
-    DJMix,
-    #[serde(rename = "Mixtape/Street")]
-    Mixtape,
-    Live,
-    Remix,
-    Demo,
-    #[serde(rename = "Audio drama")]
-    AudioDrama,
+/// How many bytes of responses the memo of a client keeps at most
+const MEMO_BYTES: usize = 8 * 1024 * 1024;
+
+/// Responses by request, the least recently used ones are dropped when they get bigger than the limit
+struct Memo {
+    /// the response and when it was used last
+    entries: HashMap<String, (Arc<str>, u64)>,
+    bytes: usize,
+    limit: usize,
+    uses: u64,
 }
 
-impl Display for ReleaseType {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{:?}", self)
+impl Memo {
+    fn new(limit: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            bytes: 0,
+            limit,
+            uses: 0,
+        }
+    }
+
+    fn get(&mut self, key: &str) -> Option<Arc<str>> {
+        self.uses += 1;
+        let uses = self.uses;
+        self.entries.get_mut(key).map(|(text, used)| {
+            *used = uses;
+            text.clone()
+        })
+    }
+
+    /// Keep `text` for `key`, responses bigger than the limit are not kept at all
+    fn insert(&mut self, key: String, text: Arc<str>) {
+        if text.len() > self.limit {
+            return;
+        }
+        self.uses += 1;
+        self.bytes += text.len();
+        if let Some((old, _)) = self.entries.insert(key, (text, self.uses)) {
+            self.bytes -= old.len();
+        }
+        while self.bytes > self.limit {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone())
+            else {
+                break;
+            };
+            if let Some((t, _)) = self.entries.remove(&oldest) {
+                self.bytes -= t.len();
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.bytes = 0;
+    }
+}
+
+/// create a reqwest client with correct http header
+pub fn get_client() -> anyhow::Result<Client> {
+    use anyhow::Context;
+    Client::builder()
+        .user_agent("MusicbrainzReleaseGrabber/1.0 ( https://github.com/narfinger )")
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .context("Could not build client")
+}
+
+/// Client for the musicbrainz api that keeps the rate limit.
+/// It can record all responses into a directory or replay them from there instead of using the network
+pub struct MbClient {
+    client: Client,
+    ratelimit: Ratelimiter,
+    /// directory where we save all responses
+    record: Option<PathBuf>,
+    /// directory where we read all responses from
+    replay: Option<PathBuf>,
+    /// release group responses of earlier runs
+    cache: Option<ResponseCache>,
+    /// server the api paths are relative to
+    server: String,
+    /// pause before the first retry
+    retry_pause: Duration,
+    /// pause of all requests when musicbrainz throttles us without saying for how long
+    throttle_pause: Duration,
+    /// no request goes out before this, after musicbrainz throttled us
+    paused_until: Mutex<Option<Instant>>,
+    /// the progress bar of the running check, which shows when we back off
+    progress: Mutex<Option<ProgressBar>>,
+    /// changes the rate limit with the throttles we get
+    adaptive: Option<AdaptiveRate>,
+    /// requests we sent since the last `take_calls`, including retries
+    calls: AtomicUsize,
+    /// the recent responses of this invocation by request, so we do not ask for the same thing twice
+    memo: Mutex<Memo>,
+}
+
+impl MbClient {
+    pub fn new(client: Client, ratelimit: Ratelimiter) -> Self {
+        Self {
+            client,
+            ratelimit,
+            record: None,
+            replay: None,
+            cache: None,
+            server: MUSICBRAINZ_SERVER.to_string(),
+            retry_pause: RETRY_PAUSE,
+            throttle_pause: THROTTLE_PAUSE,
+            paused_until: Mutex::new(None),
+            progress: Mutex::new(None),
+            adaptive: None,
+            calls: AtomicUsize::new(0),
+            memo: Mutex::new(Memo::new(MEMO_BYTES)),
+        }
+    }
+
+    /// Save every response into the directory `dir`
+    pub fn record(mut self, dir: Option<PathBuf>) -> Self {
+        self.record = dir;
+        self
+    }
+
+    /// Use the musicbrainz api on `server`, like a mirror, instead of musicbrainz.org
+    pub fn server(mut self, server: Option<String>) -> Self {
+        if let Some(s) = server {
+            self.server = s;
+        }
+        self
+    }
+
+    /// Answer every request from the responses in `dir`, recorded with `record`
+    pub fn replay(mut self, dir: Option<PathBuf>) -> Self {
+        self.replay = dir;
+        self
+    }
+
+    /// Answer release group requests from `cache` while they are fresh and keep the new responses there
+    pub fn cache(mut self, cache: Option<ResponseCache>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Slow down when musicbrainz throttles us and speed up again after a while, starting at the rate of `adaptive`
+    pub fn adaptive(mut self, adaptive: Option<AdaptiveRate>) -> Self {
+        if let Some(a) = &adaptive {
+            self.set_rate(a.rate());
+        }
+        self.adaptive = adaptive;
+        self
+    }
+
+    /// The rate the adaptive rate limit learned, none without it
+    pub fn learned_rate(&self) -> Option<u64> {
+        self.adaptive.as_ref().map(AdaptiveRate::rate)
+    }
+
+    /// Allow `rate` requests per refill interval, without a burst of saved up requests
+    fn set_rate(&self, rate: u64) {
+        let available = self.ratelimit.available();
+        // the burst size can not be below the refill amount, so the order depends on the direction
+        let res = if rate > self.ratelimit.refill_amount() {
+            self.ratelimit
+                .set_max_tokens(rate)
+                .and_then(|_| self.ratelimit.set_refill_amount(rate))
+        } else {
+            self.ratelimit
+                .set_refill_amount(rate)
+                .and_then(|_| self.ratelimit.set_max_tokens(rate))
+        };
+        if res.is_ok() {
+            let _ = self.ratelimit.set_available(available.min(rate));
+        }
+    }
+
+    /// The number of requests since the last call and reset the count
+    pub fn take_calls(&self) -> usize {
+        self.calls.swap(0, Ordering::Relaxed)
+    }
+
+    /// Show in `pb` when we back off because musicbrainz throttles us, print it without one
+    pub fn show_progress(&self, pb: Option<&ProgressBar>) {
+        *self.progress.lock().unwrap_or_else(|e| e.into_inner()) = pb.cloned();
+    }
+
+    /// Hold back all requests for `pause`, a longer pause that is already running stays
+    fn back_off(&self, pause: Duration) {
+        let until = Instant::now() + pause;
+        let mut paused = self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+        if paused.is_some_and(|p| p >= until) {
+            return;
+        }
+        *paused = Some(until);
+        drop(paused);
+        let message = tr!(
+            "backing off for {}s due to server throttle",
+            pause.as_secs_f64().ceil()
+        );
+        match &*self.progress.lock().unwrap_or_else(|e| e.into_inner()) {
+            Some(pb) => pb.set_message(message),
+            None => println!("{}", message),
+        }
+    }
+
+    /// Wait until the pause after a throttle is over
+    fn wait_for_backoff(&self) {
+        let until = *self.paused_until.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(wait) = until.and_then(|u| u.checked_duration_since(Instant::now())) {
+            std::thread::sleep(wait);
+        }
+    }
+
+    /// Forget the responses we got so far, for long running processes like the daemon
+    pub fn forget(&self) {
+        self.memo.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
+    /// Do we use the public musicbrainz server, where we have to follow its etiquette
+    pub fn is_public_server(&self) -> bool {
+        self.server == MUSICBRAINZ_SERVER && self.replay.is_none()
+    }
+
+    /// Get the api `path` with `query` and decode the json response.
+    fn fetch_json<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+    ) -> Result<T, MbError> {
+        let text = self.fetch_text(path, query)?;
+        serde_json::from_str(&text).map_err(|e| MbError::Decode(format!("{} for {}", e, path)))
+    }
+
+    /// Get the api `path` with `query` and return the body.
+    /// Retryable errors are retried with an increasing pause.
+    /// Recent responses are kept in memory, asking for the same again does not send a request.
+    fn fetch_text(&self, path: &str, query: &[(&str, String)]) -> Result<Arc<str>, MbError> {
+        let key = query
+            .iter()
+            .fold(path.to_string(), |k, (n, v)| format!("{}&{}={}", k, n, v));
+        let memo = || self.memo.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(text) = memo().get(&key) {
+            return Ok(text);
+        }
+        let url = format!("{}{}", self.server, path);
+        let cache = self
+            .cache
+            .as_ref()
+            .filter(|_| ResponseCache::caches(path, query));
+        let text: Arc<str> = if let Some(dir) = &self.replay {
+            let file = dir.join(recording_file_name(path, query));
+            std::fs::read_to_string(&file)
+                .map_err(|e| MbError::Replay(format!("{}: {}", file.display(), e)))?
+                .into()
+        } else if let Some(text) = cache.and_then(|c| c.get(&url, query)) {
+            text.into()
+        } else {
+            let mut attempt = 0;
+            let text = loop {
+                self.wait_for_backoff();
+                wait_for_ratelimit(&self.ratelimit);
+                match self.fetch_text_once(&url, query) {
+                    Err(e) if e.is_retryable() && attempt < RETRIES => {
+                        std::thread::sleep(self.retry_pause * 2u32.pow(attempt));
+                        attempt += 1;
+                    }
+                    res => break res?,
+                }
+            };
+            if let Some(c) = cache {
+                c.put(&url, query, &text);
+            }
+            text.into()
+        };
+        if let Some(dir) = &self.record {
+            let file = dir.join(recording_file_name(path, query));
+            std::fs::create_dir_all(dir)
+                .and_then(|_| std::fs::write(&file, text.as_bytes()))
+                .map_err(|e| MbError::Replay(format!("{}: {}", file.display(), e)))?;
+        }
+        memo().insert(key, text.clone());
+        Ok(text)
+    }
+
+    /// Do a single request for `fetch_json` and return the body, it ends at the latest with the budget
+    fn fetch_text_once(&self, url: &str, query: &[(&str, String)]) -> Result<String, MbError> {
+        let left = remaining_budget()?;
+        self.calls.fetch_add(1, Ordering::Relaxed);
+        let mut request = self.client.get(url).query(query);
+        if let Some(left) = left.filter(|l| *l < REQUEST_TIMEOUT) {
+            request = request.timeout(left);
+        }
+        let response = request.send().map_err(|e| {
+            match DEADLINE
+                .get()
+                .filter(|_| e.is_timeout() && remaining_budget().is_err())
+            {
+                Some((_, budget)) => MbError::OverBudget(budget),
+                None => MbError::Network(e),
+            }
+        })?;
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(MbError::NotFound(url.to_string()));
+        } else if status == StatusCode::SERVICE_UNAVAILABLE
+            || status == StatusCode::TOO_MANY_REQUESTS
+        {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+            let body = response.text().unwrap_or_default();
+            if let Some(pause) = throttle_pause(retry_after.as_deref(), &body, self.throttle_pause)
+            {
+                self.back_off(pause);
+            }
+            if let Some(a) = &self.adaptive {
+                self.set_rate(a.throttled());
+            }
+            return Err(MbError::RateLimited);
+        } else if !status.is_success() {
+            return Err(MbError::ServerError(status.as_u16()));
+        }
+        if let Some(rate) = self.adaptive.as_ref().and_then(AdaptiveRate::succeeded) {
+            self.set_rate(rate);
+        }
+        response.text().map_err(MbError::Network)
+    }
+}
+
+/// The file name for a recorded response. It is readable and stable between runs and versions of the program
+fn recording_file_name(path: &str, query: &[(&str, String)]) -> String {
+    let mut key = path.to_string();
+    for (k, v) in query {
+        key.push_str(&format!("&{}={}", k, v));
+    }
+    // FNV-1a, std's hasher is not guaranteed to stay the same between versions
+    let hash = key.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+        (h ^ u64::from(b)).wrapping_mul(0x100000001b3)
+    });
+    let endpoint = path
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .unwrap_or("response");
+    format!("{}-{:016x}.json", endpoint, hash)
+}
+
+impl Artist {
+    /// Remember that the artist was added on `date` by `source`
+    pub fn added_by(mut self, source: ArtistSource, date: Date) -> Self {
+        self.source = source;
+        self.added = Some(date);
+        self
+    }
+
+    /// The fields of this artist that match `query`, case insensitive. A query that is an MBID only matches the id
+    pub fn matching_fields(&self, query: &str) -> Vec<String> {
+        if let Ok(id) = Uuid::parse_str(query.trim()) {
+            return if id == self.id {
+                vec!["mbid".to_string()]
+            } else {
+                vec![]
+            };
+        }
+        let query = query.to_lowercase();
+        let matches = |s: &str| s.to_lowercase().contains(&query);
+        let mut res = Vec::new();
+        if matches(&self.name) {
+            res.push("name".to_string());
+        }
+        if matches(&self.sort_name) {
+            res.push("sort name".to_string());
+        }
+        if matches(&self.search_string) {
+            res.push("search string".to_string());
+        }
+        for alias in self.aliases.iter().filter(|a| matches(a)) {
+            res.push(format!("alias \"{}\"", alias));
+        }
+        res
+    }
+
+    /// Search for an artist given by string `s` and construct an artist object
+    pub fn new(mb: &MbClient, s: &str) -> Result<Self, MbError> {
+        Artist::resolve(mb, s).map(|(a, _)| a)
+    }
+
+    /// Search for an artist given by string `s`, with all candidates of the search, the chosen one first
+    pub fn resolve(mb: &MbClient, s: &str) -> Result<(Self, Vec<Candidate>), MbError> {
+        let found = Artist::search(mb, s)?;
+        let artist = found[0].0.clone();
+        Ok((artist, found.into_iter().map(|(_, c)| c).collect()))
+    }
+
+    /// All artists the search for `s` finds, best first, as artist objects and as candidates
+    pub fn search(mb: &MbClient, s: &str) -> Result<Vec<(Self, Candidate)>, MbError> {
+        let resp: SearchResponse = mb.fetch_json(
+            ARTIST_SEARCH_URL,
+            &[
+                ("query", s.to_string()),
+                ("limit", 3.to_string()),
+                ("fmt", "json".to_string()),
+            ],
+        )?;
+        if resp.artists.is_empty() {
+            return Err(MbError::NotFound(format!("UUID for {}", s)));
+        }
+        Ok(resp
+            .artists
+            .into_iter()
+            .map(|found| {
+                let candidate = Candidate {
+                    id: found.id,
+                    name: found.name.clone(),
+                    disambiguation: found.disambiguation.filter(|d| !d.is_empty()),
+                    score: found.score,
+                };
+                let artist = Artist {
+                    name: found.name,
+                    id: found.id,
+                    search_string: s.to_owned(),
+                    sort_name: found.sort_name,
+                    aliases: found.aliases.into_iter().map(|a| a.name).collect(),
+                    tags: found.tags.into_iter().map(|t| t.name).collect(),
+                    ..Default::default()
+                };
+                (artist, candidate)
+            })
+            .collect())
+    }
+
+    /// Artists related to this one, like band members and collaborations.
+    /// With `labels` also the artists of the labels this one had a contract with, this needs a request per label
+    pub fn get_related_artists(
+        &self,
+        mb: &MbClient,
+        labels: bool,
+    ) -> Result<Vec<RelatedArtist>, MbError> {
+        let inc = if labels {
+            "artist-rels+label-rels"
+        } else {
+            "artist-rels"
+        };
+        let resp: RelationsResponse = mb.fetch_json(
+            &format!("{}{}", ARTIST_SEARCH_URL, self.id),
+            &[("inc", inc.to_string()), ("fmt", "json".to_string())],
+        )?;
+        let mut res = Vec::new();
+        for r in resp.relations {
+            if let Some(a) = r.artist {
+                res.push(RelatedArtist {
+                    id: a.id,
+                    name: a.name,
+                    sort_name: a.sort_name,
+                    relation: r.relation_type,
+                });
+            } else if let Some(l) = r.label {
+                let label: RelationsResponse = mb.fetch_json(
+                    &format!("{}{}", LABEL_LOOKUP_URL, l.id),
+                    &[
+                        ("inc", "artist-rels".to_string()),
+                        ("fmt", "json".to_string()),
+                    ],
+                )?;
+                res.extend(label.relations.into_iter().filter_map(|lr| {
+                    lr.artist.map(|a| RelatedArtist {
+                        id: a.id,
+                        name: a.name,
+                        sort_name: a.sort_name,
+                        relation: format!("{} with {}", lr.relation_type, l.name),
+                    })
+                }));
+            }
+        }
+        res.retain(|a| a.id != self.id);
+        Ok(res)
+    }
+
+    /// Get the release groups of this artist where `keep` is true, the others are dropped while parsing
+    fn get_albums(
+        &self,
+        mb: &MbClient,
+        keep: impl Fn(&ReleaseGroup) -> bool,
+    ) -> Result<Vec<ReleaseGroup>, MbError> {
+        paginate(&format!("release groups of {}", self.name), |offset| {
+            let text = mb.fetch_text(
+                ALBUM_QUERY_STRING,
+                &[
+                    ("artist", self.id.to_string()),
+                    ("offset", offset.to_string()),
+                    ("limit", HOW_MANY_RELEASE_RESULT.to_string()),
+                    ("inc", "ratings".to_string()),
+                    ("fmt", "json".to_string()),
+                ],
+            )?;
+            let mut de = serde_json::Deserializer::from_str(&text);
+            ReleaseGroupPageSeed { keep: &keep }
+                .deserialize(&mut de)
+                .and_then(|page| de.end().map(|_| page))
+                .map_err(|e| MbError::Decode(format!("{} for {}", e, ALBUM_QUERY_STRING)))
+        })
+    }
+
+    /// Filter albums by simple release type and returns the albums found
+    /// Notice that this filters out also albums that do not have a specific year-month-day release date in the db
+    /// The date is chosen by `date_strategy`, which might need to fetch all releases of the artist.
+    /// Albums with one of the secondary types in `skip` are left out
+    pub fn get_albums_basic_filtered(
+        &self,
+        mb: &MbClient,
+        date_strategy: &DateStrategy,
+        countries: &[String],
+        skip: &[ReleaseType],
+    ) -> Result<Vec<Album>, MbError> {
+        let albs_resp = self.get_albums(mb, |rg| {
+            (rg.primary_type == Some(ReleaseType::Album)
+                && !rg.secondary_types.iter().any(|t| skip.contains(t)))
+                || (self.broadcasts && rg.primary_type == Some(ReleaseType::Broadcast))
+        })?;
+        let release_dates = if *date_strategy == DateStrategy::FirstRelease {
+            HashMap::new()
+        } else {
+            let releases = self.get_releases(mb, "release-groups+media")?;
+            date_strategy.release_dates(releases, countries)
+        };
+        let mut albs = albs_resp
+            .into_iter()
+            .map(|a: ReleaseGroup| {
+                let date = if *date_strategy == DateStrategy::FirstRelease {
+                    a.first_release_date.as_deref().and_then(parse_date)
+                } else {
+                    release_dates.get(&a.id).copied()
+                };
+                Album {
+                    id: a.id,
+                    artist: self.name.to_owned(),
+                    title: a.title,
+                    date,
+                    release_type: if a.primary_type == Some(ReleaseType::Broadcast) {
+                        ReleaseType::Broadcast
+                    } else {
+                        a.secondary_types
+                            .first()
+                            .unwrap_or(&ReleaseType::Album)
+                            .to_owned()
+                    },
+                    rating: a.rating.as_ref().and_then(RatingResponse::to_rating),
+                    ..Default::default()
+                }
+            })
+            .filter(|a| a.date.is_some())
+            .collect::<Vec<_>>();
+        // episodes of a series often share a title, so only albums get deduplicated
+        let (episodes, albums): (Vec<_>, Vec<_>) = albs
+            .into_iter()
+            .partition(|a| a.release_type == ReleaseType::Broadcast);
+        albs = dedup_by_title(albums);
+        albs.extend(episodes);
+        albs.sort_by_key(|a| a.date);
+        Ok(albs)
+    }
+
+    /// Get all releases of this artist including the release group they belong to
+    /// `inc` are the additional includes for the browse request
+    fn get_releases(&self, mb: &MbClient, inc: &str) -> Result<Vec<Release>, MbError> {
+        browse_releases(mb, "artist", &self.id, inc)
+    }
+
+    /// Find releases that got added to album release groups we already know.
+    /// `release_counts` holds the number of releases per release group from the last check and gets updated.
+    /// Release groups we have not seen before only get recorded, they are new albums and not reissues.
+    pub fn get_reissues(
+        &self,
+        mb: &MbClient,
+        release_counts: &mut HashMap<Uuid, usize>,
+    ) -> Result<Vec<Album>, MbError> {
+        let releases = self.get_releases(mb, "release-groups")?;
+
+        let mut groups: HashMap<Uuid, (ReleaseGroup, Vec<Release>)> = HashMap::new();
+        for mut r in releases {
+            if matches!(
+                r.status,
+                Some(Status::Bootleg) | Some(Status::PseudoRelease)
+            ) {
+                continue;
+            }
+            if let Some(rg) = r.release_group.take() {
+                if rg.primary_type == Some(ReleaseType::Album) {
+                    groups
+                        .entry(rg.id)
+                        .or_insert_with(|| (rg, Vec::new()))
+                        .1
+                        .push(r);
+                }
+            }
+        }
+
+        let mut reissues = Vec::new();
+        for (id, (rg, releases)) in groups {
+            let count = releases.len();
+            let old_count = release_counts.insert(id, count);
+            if old_count.is_none_or(|old| count <= old) {
+                continue;
+            }
+            let newest = releases
+                .into_iter()
+                .map(|r| {
+                    let date = r.date.as_deref().and_then(parse_date);
+                    (date, r)
+                })
+                .max_by_key(|(date, _)| *date);
+            if let Some((date, release)) = newest {
+                reissues.push(Album {
+                    id: rg.id,
+                    artist: self.name.to_owned(),
+                    title: release.title,
+                    date,
+                    release_type: rg
+                        .secondary_types
+                        .first()
+                        .unwrap_or(&ReleaseType::Album)
+                        .to_owned(),
+                    ..Default::default()
+                });
+            }
+        }
+        reissues.sort_by_key(|a| a.date);
+        Ok(reissues)
+    }
+}
+
+/// JSON response for ReleaseGroup
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ReleaseGroup {
+    id: Uuid,
+    title: String,
+    #[serde(rename = "primary-type")]
+    primary_type: Option<ReleaseType>,
+    #[serde(rename = "first-release-date")]
+    first_release_date: Option<String>,
+    #[serde(rename = "secondary-types", default)]
+    secondary_types: Vec<ReleaseType>,
+    #[serde(rename = "artist-credit", default)]
+    artist_credit: Vec<ArtistCredit>,
+    #[serde(default)]
+    rating: Option<RatingResponse>,
+}
+
+/// JSON response for the rating of a release group, `value` is missing without votes
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RatingResponse {
+    value: Option<f64>,
+    #[serde(rename = "votes-count", default)]
+    votes_count: u32,
+}
+
+impl RatingResponse {
+    fn to_rating(&self) -> Option<Rating> {
+        let value = self.value?;
+        (self.votes_count > 0).then(|| Rating {
+            score: (value * 20.0).round().clamp(0.0, 100.0) as u8,
+            votes: self.votes_count,
+        })
+    }
+}
+
+/// JSON response for one credited artist
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ArtistCredit {
+    name: String,
+    #[serde(default)]
+    joinphrase: String,
+}
+
+/// JSON response for a release group search
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ReleaseGroupSearchResponse {
+    #[serde(rename = "release-groups")]
+    release_groups: Vec<ReleaseGroup>,
+    /// how many release groups matched in total
+    #[serde(default)]
+    count: Option<usize>,
+}
+
+/// JSON response for the releases browse
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ReleaseBrowseResponse {
+    #[serde(rename = "release-offset")]
+    release_offset: Option<usize>,
+    #[serde(rename = "release-count")]
+    release_count: Option<usize>,
+    releases: Vec<Release>,
+}
+
+/// JSON response for a single release
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Release {
+    id: Uuid,
+    title: String,
+    status: Option<Status>,
+    date: Option<String>,
+    #[serde(rename = "release-group")]
+    release_group: Option<ReleaseGroup>,
+    #[serde(rename = "release-events", default)]
+    release_events: Vec<ReleaseEvent>,
+    #[serde(default)]
+    media: Vec<Medium>,
+    #[serde(rename = "text-representation")]
+    text_representation: Option<TextRepresentation>,
+    barcode: Option<String>,
+    country: Option<String>,
+    packaging: Option<String>,
+    /// only there with `inc=labels`
+    #[serde(rename = "label-info", default)]
+    label_info: Vec<LabelInfo>,
+    /// only there with `inc=url-rels`
+    #[serde(default)]
+    relations: Vec<UrlRelation>,
+}
+
+/// JSON response for a relation of a release to an url, like a shop selling it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct UrlRelation {
+    #[serde(rename = "type")]
+    relation_type: String,
+    url: Option<UrlResponse>,
+}
+
+/// JSON response for a label of a release and its catalog number there
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct LabelInfo {
+    #[serde(rename = "catalog-number")]
+    catalog_number: Option<String>,
+    label: Option<LabelResponse>,
+}
+
+/// JSON response for a label
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct LabelResponse {
+    name: String,
+}
+
+/// What collectors need to tell the releases of a release group apart
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReleaseDetails {
+    pub id: Uuid,
+    pub title: String,
+    pub date: Option<String>,
+    pub country: Option<String>,
+    pub status: Option<String>,
+    /// the formats of all media, like ["CD", "CD"]
+    pub formats: Vec<String>,
+    pub barcode: Option<String>,
+    pub labels: Vec<String>,
+    pub catalog_numbers: Vec<String>,
+    pub packaging: Option<String>,
+}
+
+impl From<Release> for ReleaseDetails {
+    fn from(r: Release) -> Self {
+        let mut labels = Vec::new();
+        let mut catalog_numbers = Vec::new();
+        for l in r.label_info {
+            if let Some(label) = l.label {
+                if !labels.contains(&label.name) {
+                    labels.push(label.name);
+                }
+            }
+            catalog_numbers.extend(l.catalog_number.filter(|c| !c.is_empty()));
+        }
+        Self {
+            id: r.id,
+            title: r.title,
+            date: r.date.filter(|d| !d.is_empty()),
+            country: r.country,
+            status: r.status.map(|s| format!("{:?}", s)),
+            formats: r.media.into_iter().filter_map(|m| m.format).collect(),
+            barcode: r.barcode.filter(|b| !b.is_empty()),
+            labels,
+            catalog_numbers,
+            packaging: r.packaging,
+        }
+    }
+}
+
+impl ReleaseDetails {
+    /// The media like "2×12" Vinyl + CD", consecutive media of the same format are counted
+    pub fn media(&self) -> String {
+        let mut counted: Vec<(usize, &str)> = Vec::new();
+        for f in &self.formats {
+            match counted.last_mut() {
+                Some((n, last)) if last == f => *n += 1,
+                _ => counted.push((1, f)),
+            }
+        }
+        counted
+            .into_iter()
+            .map(|(n, f)| {
+                if n > 1 {
+                    format!("{}×{}", n, f)
+                } else {
+                    f.to_string()
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" + ")
+    }
+}
+
+/// The releases of the release group with `id` with their labels, catalog numbers and media, oldest first
+pub fn release_details(mb: &MbClient, id: &Uuid) -> Result<Vec<ReleaseDetails>, MbError> {
+    let mut details = browse_releases(mb, "release-group", id, "labels+media")?
+        .into_iter()
+        .map(ReleaseDetails::from)
+        .collect::<Vec<_>>();
+    // releases without a date last
+    details.sort_by(|a, b| (a.date.is_none(), &a.date).cmp(&(b.date.is_none(), &b.date)));
+    Ok(details)
+}
+
+/// JSON response for the language and script of a release
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct TextRepresentation {
+    /// ISO 639-3 code
+    language: Option<String>,
+    /// ISO 15924 code
+    script: Option<String>,
+}
+
+impl Release {
+    /// Is there a release event in one of the `countries`. A worldwide release counts for every country
+    pub fn released_in(&self, countries: &[String]) -> bool {
+        self.release_events.iter().any(|e| {
+            e.area.as_ref().is_some_and(|a| {
+                a.iso_3166_1_codes
+                    .iter()
+                    .any(|c| c == "XW" || countries.iter().any(|m| m.eq_ignore_ascii_case(c)))
+            })
+        })
+    }
+
+    /// The formats of the media of the release, like "CD"
+    pub fn formats(&self) -> impl Iterator<Item = &str> {
+        self.media.iter().filter_map(|m| m.format.as_deref())
+    }
+
+    /// The dates of the release events of the release that have a full date
+    pub fn event_dates(&self) -> impl Iterator<Item = Date> + '_ {
+        self.release_events
+            .iter()
+            .filter_map(|e| e.date.as_deref().and_then(parse_date))
+    }
+
+    /// The urls of shops where the release can be bought, musicbrainz calls these "purchase for mail-order" or "purchase for download"
+    pub fn store_urls(&self) -> impl Iterator<Item = &str> {
+        self.relations
+            .iter()
+            .filter(|r| r.relation_type.starts_with("purchase"))
+            .filter_map(|r| r.url.as_ref().map(|u| u.resource.as_str()))
+    }
+
+    /// The barcode of the release, if it has one
+    pub fn barcode(&self) -> Option<&str> {
+        self.barcode.as_deref().filter(|b| !b.is_empty())
+    }
+
+    /// Number of tracks on all media of the release
+    pub fn track_count(&self) -> u32 {
+        self.media.iter().map(|m| m.track_count).sum()
+    }
+
+    /// Is the text of the release in a language and script that are not excluded
+    /// Releases without language or script information are always usable
+    pub fn usable_text(&self, exclude_languages: &[String], exclude_scripts: &[String]) -> bool {
+        let excluded = |value: &Option<String>, excludes: &[String]| {
+            value
+                .as_ref()
+                .is_some_and(|v| excludes.iter().any(|e| e.eq_ignore_ascii_case(v)))
+        };
+        self.text_representation.as_ref().is_none_or(|t| {
+            !excluded(&t.language, exclude_languages) && !excluded(&t.script, exclude_scripts)
+        })
+    }
+}
+
+/// JSON response for a release event, i.e., when a release got released where
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ReleaseEvent {
+    date: Option<String>,
+    area: Option<Area>,
+}
+
+/// JSON response for an area
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Area {
+    #[serde(rename = "iso-3166-1-codes", default)]
+    iso_3166_1_codes: Vec<String>,
+}
+
+/// JSON response for a medium of a release
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Medium {
+    format: Option<String>,
+    #[serde(rename = "track-count", default)]
+    track_count: u32,
+    /// only there with `inc=recordings`
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+/// JSON response for a track on a medium
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Track {
+    recording: RecordingResponse,
+}
+
+/// JSON response for the recording of a track
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct RecordingResponse {
+    id: Uuid,
+}
+
+/// Which date decides if a release group is new
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, Clone, Copy)]
+#[serde(rename_all = "kebab-case")]
+pub enum DateStrategy {
+    /// the `first-release-date` of the release group
+    #[default]
+    FirstRelease,
+    /// the earliest release event in one of the configured countries
+    Country,
+    /// the latest release with a digital medium
+    Digital,
+}
+
+impl DateStrategy {
+    /// Compute the date for every release group from the `releases`
+    fn release_dates(&self, releases: Vec<Release>, countries: &[String]) -> HashMap<Uuid, Date> {
+        let parse = |s: &Option<String>| s.as_deref().and_then(parse_date);
+        let mut dates: HashMap<Uuid, Date> = HashMap::new();
+        for r in releases {
+            let Some(rg) = &r.release_group else {
+                continue;
+            };
+            match self {
+                DateStrategy::FirstRelease => {
+                    if let Some(d) = parse(&rg.first_release_date) {
+                        dates.insert(rg.id, d);
+                    }
+                }
+                DateStrategy::Country => {
+                    let earliest = r
+                        .release_events
+                        .iter()
+                        .filter(|e| {
+                            e.area.as_ref().is_some_and(|a| {
+                                a.iso_3166_1_codes
+                                    .iter()
+                                    .any(|c| countries.iter().any(|m| m.eq_ignore_ascii_case(c)))
+                            })
+                        })
+                        .filter_map(|e| parse(&e.date))
+                        .min();
+                    if let Some(d) = earliest {
+                        let entry = dates.entry(rg.id).or_insert(d);
+                        *entry = (*entry).min(d);
+                    }
+                }
+                DateStrategy::Digital => {
+                    let digital = r
+                        .media
+                        .iter()
+                        .any(|m| m.format.as_deref() == Some("Digital Media"));
+                    if let Some(d) = parse(&r.date).filter(|_| digital) {
+                        let entry = dates.entry(rg.id).or_insert(d);
+                        *entry = (*entry).max(d);
+                    }
+                }
+            }
+        }
+        dates
+    }
+}
+
+/// type/status of release
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+enum Status {
+    Official,
+    Promotion,
+    Bootleg,
+    #[serde(rename = "Pseudo-Release")]
+    PseudoRelease,
+    Withdrawn,
+    Cancelled,
+    /// a status musicbrainz added after this program was written
+    #[serde(other)]
+    Unknown,
+}
+
+/// release type
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
+#[serde(from = "String", into = "String")]
+pub enum ReleaseType {
+    None,
+    EP,
+    #[default]
+    Album,
+    Single,
+    Other,
+    Broadcast,
+    Compilation,
+    Spokenword,
+    Interview,
+    Soundtrack,
+    Audiobook,
+    DJMix,
+    Mixtape,
+    Live,
+    Remix,
+    Demo,
+    AudioDrama,
+    /// a type musicbrainz added after this program was written, with the name musicbrainz uses
+    Unknown(String),
+}
+
+/// The release types we know with the names musicbrainz uses for them
+const RELEASE_TYPE_NAMES: &[(ReleaseType, &str)] = &[
+    (ReleaseType::None, "None"),
+    (ReleaseType::EP, "EP"),
+    (ReleaseType::Album, "Album"),
+    (ReleaseType::Single, "Single"),
+    (ReleaseType::Other, "Other"),
+    (ReleaseType::Broadcast, "Broadcast"),
+    (ReleaseType::Compilation, "Compilation"),
+    (ReleaseType::Spokenword, "Spokenword"),
+    (ReleaseType::Interview, "Interview"),
+    (ReleaseType::Soundtrack, "Soundtrack"),
+    (ReleaseType::Audiobook, "Audiobook"),
+    (ReleaseType::DJMix, "DJ-mix"),
+    (ReleaseType::Mixtape, "Mixtape/Street"),
+    (ReleaseType::Live, "Live"),
+    (ReleaseType::Remix, "Remix"),
+    (ReleaseType::Demo, "Demo"),
+    (ReleaseType::AudioDrama, "Audio drama"),
+];
+
+impl From<String> for ReleaseType {
+    fn from(s: String) -> Self {
+        RELEASE_TYPE_NAMES
+            .iter()
+            .find(|(_, name)| *name == s)
+            .map(|(t, _)| t.clone())
+            .unwrap_or(ReleaseType::Unknown(s))
+    }
+}
+
+impl From<ReleaseType> for String {
+    fn from(t: ReleaseType) -> Self {
+        match t {
+            ReleaseType::Unknown(s) => s,
+            t => RELEASE_TYPE_NAMES
+                .iter()
+                .find(|(known, _)| *known == t)
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for ReleaseType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReleaseType::Unknown(s) => write!(f, "{} (unknown type)", s),
+            t => write!(f, "{:?}", t),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    const ARTIST_ID: &str = "f59c5520-5f46-4d2c-b2c4-822eabf53419";
+
+    /// The headers of a request or a response, as name and value
+    type Headers = Vec<(String, String)>;
+
+    /// A minimal http server that answers requests with the canned `responses` in order
+    /// and remembers the request lines, headers and bodies it got
+    pub struct MockServer {
+        url: String,
+        requests: Arc<Mutex<Vec<String>>>,
+        headers: Arc<Mutex<Vec<Headers>>>,
+        bodies: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockServer {
+        pub fn start(responses: Vec<(u16, String)>) -> Self {
+            Self::start_with_headers(
+                responses
+                    .into_iter()
+                    .map(|(status, body)| (status, Headers::new(), body))
+                    .collect(),
+            )
+        }
+
+        /// Like `start`, the responses also send the given headers
+        pub fn start_with_headers(responses: Vec<(u16, Headers, String)>) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let headers = Arc::new(Mutex::new(Vec::new()));
+            let bodies = Arc::new(Mutex::new(Vec::new()));
+            let seen = requests.clone();
+            let seen_headers = headers.clone();
+            let seen_bodies = bodies.clone();
+            std::thread::spawn(move || {
+                for (status, response_headers, body) in responses {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    seen.lock().unwrap().push(line.trim().to_string());
+                    let mut request_headers = Vec::new();
+                    let mut len = 0;
+                    loop {
+                        let mut header = String::new();
+                        reader.read_line(&mut header).unwrap();
+                        if header.trim().is_empty() {
+                            break;
+                        }
+                        if let Some((name, value)) = header.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                len = value.trim().parse().unwrap();
+                            }
+                            request_headers
+                                .push((name.to_ascii_lowercase(), value.trim().to_string()));
+                        }
+                    }
+                    seen_headers.lock().unwrap().push(request_headers);
+                    let mut request_body = vec![0; len];
+                    reader.read_exact(&mut request_body).unwrap();
+                    seen_bodies
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8(request_body).unwrap());
+                    let extra = response_headers
+                        .iter()
+                        .map(|(name, value)| format!("{}: {}\r\n", name, value))
+                        .collect::<String>();
+                    write!(
+                        stream,
+                        "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n{}",
+                        status,
+                        body.len(),
+                        extra,
+                        body
+                    )
+                    .unwrap();
+                }
+            });
+            Self {
+                url,
+                requests,
+                headers,
+                bodies,
+            }
+        }
+
+        pub fn client(&self) -> MbClient {
+            let ratelimit = Ratelimiter::builder(100, Duration::from_millis(10))
+                .max_tokens(100)
+                .initial_available(100)
+                .build()
+                .unwrap();
+            let mut mb = MbClient::new(Client::new(), ratelimit);
+            mb.server = self.url.clone();
+            mb.retry_pause = Duration::from_millis(1);
+            mb.throttle_pause = Duration::from_millis(50);
+            mb
+        }
+
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        /// The value of the header `name` in the request number `request`
+        pub fn header(&self, request: usize, name: &str) -> Option<String> {
+            self.headers.lock().unwrap()[request]
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.clone())
+        }
+
+        pub fn bodies(&self) -> Vec<String> {
+            self.bodies.lock().unwrap().clone()
+        }
+
+        pub fn url(&self) -> &str {
+            &self.url
+        }
+    }
+
+    pub fn artist() -> Artist {
+        Artist {
+            name: "Test".to_string(),
+            id: Uuid::parse_str(ARTIST_ID).unwrap(),
+            search_string: "test".to_string(),
+            sort_name: "Test".to_string(),
+            aliases: vec!["Testers".to_string()],
+            ..Default::default()
+        }
+    }
+
+    pub fn release_group_page(offset: usize, count: usize, ids: std::ops::Range<usize>) -> String {
+        let groups: Vec<String> = ids
+            .map(|i| {
+                format!(
+                    r#"{{"id": "00000000-0000-0000-0000-{:012}", "title": "Album {}", "primary-type": "Album", "first-release-date": "2024-01-01", "secondary-types": []}}"#,
+                    i, i
+                )
+            })
+            .collect();
+        format!(
+            r#"{{"release-group-offset": {}, "release-group-count": {}, "release-groups": [{}]}}"#,
+            offset,
+            count,
+            groups.join(",")
+        )
+    }
+
+    #[test]
+    fn a_request_takes_one_permit() {
+        let ratelimit = Ratelimiter::builder(1, Duration::from_secs(60 * 60))
+            .max_tokens(10)
+            .initial_available(10)
+            .build()
+            .unwrap();
+        wait_for_ratelimit(&ratelimit);
+        assert_eq!(ratelimit.available(), 9);
+
+        let server = MockServer::start(vec![(200, release_group_page(0, 1, 0..1))]);
+        let mut mb = server.client();
+        mb.ratelimit = ratelimit;
+        artist()
+            .get_albums_basic_filtered(&mb, &DateStrategy::FirstRelease, &[], &[])
+            .unwrap();
+        assert_eq!(mb.ratelimit.available(), 8);
+    }
+
+    #[test]
+    fn same_request_is_sent_once() {
+        let body = format!(
+            r#"{{"artists": [{{"id": "{}", "name": "Test", "sort-name": "Test"}}]}}"#,
+            ARTIST_ID
+        );
+        let server = MockServer::start(vec![(200, body.clone()), (200, body)]);
+        let mb = server.client();
+        Artist::new(&mb, "test").unwrap();
+        let again = Artist::new(&mb, "test").unwrap();
+        assert_eq!(again.name, "Test");
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(mb.take_calls(), 1);
+        mb.forget();
+        Artist::new(&mb, "test").unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn memo_drops_the_least_recently_used_responses() {
+        let mut memo = Memo::new(10);
+        memo.insert("a".to_string(), Arc::from("aaaa"));
+        memo.insert("b".to_string(), Arc::from("bbbb"));
+        assert!(memo.get("a").is_some());
+        memo.insert("c".to_string(), Arc::from("cccc"));
+        assert!(memo.get("b").is_none());
+        assert_eq!(memo.get("a").as_deref(), Some("aaaa"));
+        assert_eq!(memo.bytes, 8);
+        memo.insert("big".to_string(), Arc::from("x".repeat(11).as_str()));
+        assert!(memo.get("big").is_none());
+        assert_eq!(memo.entries.len(), 2);
+    }
+
+    #[test]
+    fn artist_new_takes_first_result() {
+        let body = format!(
+            r#"{{"artists": [{{"id": "{}", "name": "Test", "sort-name": "Test, The", "aliases": [{{"name": "Testers"}}]}}, {{"id": "00000000-0000-0000-0000-000000000001", "name": "Other", "sort-name": "Other"}}]}}"#,
+            ARTIST_ID
+        );
+        let server = MockServer::start(vec![(200, body)]);
+        let artist = Artist::new(&server.client(), "test").unwrap();
+        assert_eq!(artist.name, "Test");
+        assert_eq!(artist.id, Uuid::parse_str(ARTIST_ID).unwrap());
+        assert_eq!(artist.sort_name, "Test, The");
+        assert_eq!(artist.search_string, "test");
+        assert_eq!(artist.aliases, vec!["Testers".to_string()]);
+        assert!(server.requests()[0].starts_with("GET /ws/2/artist/?query=test"));
+    }
+
+    #[test]
+    fn related_artists_include_label_artists() {
+        let artist_body = r#"{"relations": [
+                {"type": "member of band", "artist": {"id": "00000000-0000-0000-0000-000000000001", "name": "Member", "sort-name": "Member"}},
+                {"type": "recording contract", "label": {"id": "00000000-0000-0000-0000-000000000002", "name": "Label", "sort-name": "Label"}}
+            ]}"#
+        .to_string();
+        let label_body = format!(
+            r#"{{"relations": [
+                {{"type": "recording contract", "artist": {{"id": "{}", "name": "Test", "sort-name": "Test"}}}},
+                {{"type": "recording contract", "artist": {{"id": "00000000-0000-0000-0000-000000000003", "name": "Labelmate", "sort-name": "Labelmate"}}}}
+            ]}}"#,
+            ARTIST_ID
+        );
+        let server = MockServer::start(vec![(200, artist_body), (200, label_body)]);
+        let related = artist()
+            .get_related_artists(&server.client(), true)
+            .unwrap();
+        let names = related
+            .iter()
+            .map(|a| (a.name.as_str(), a.relation.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                ("Member", "member of band"),
+                ("Labelmate", "recording contract with Label")
+            ]
+        );
+        let requests = server.requests();
+        assert!(requests[0].contains("inc=artist-rels%2Blabel-rels"));
+        assert!(requests[1].starts_with("GET /ws/2/label/00000000-0000-0000-0000-000000000002"));
+    }
+
+    #[test]
+    fn many_release_groups_need_several_searches() {
+        let page = |ids: std::ops::Range<u128>| {
+            let groups = ids
+                .map(|i| {
+                    format!(
+                        r#"{{"id": "{}", "title": "T{}", "primary-type": "Album", "artist-credit": [{{"name": "A", "joinphrase": ""}}]}}"#,
+                        Uuid::from_u128(i),
+                        i
+                    )
+                })
+                .collect::<Vec<String>>();
+            (
+                200,
+                format!(
+                    r#"{{"count": 1000, "release-groups": [{}]}}"#,
+                    groups.join(",")
+                ),
+            )
+        };
+        let server = MockServer::start(vec![page(0..100), page(100..150)]);
+        let query = tag_query(
+            "post \"rock\"",
+            Date::from_calendar_date(2025, time::Month::January, 2).unwrap(),
+        );
+        assert_eq!(
+            query,
+            r#"tag:"post \"rock\"" AND primarytype:album AND firstreleasedate:[2025-01-02 TO *]"#
+        );
+        let albums = search_many_release_groups(&server.client(), &query, 150).unwrap();
+        assert_eq!(albums.len(), 150);
+        let requests = server.requests();
+        assert!(requests[1].contains("limit=50") && requests[1].contains("offset=100"));
+    }
+
+    #[test]
+    fn artist_new_without_results_is_not_found() {
+        let server = MockServer::start(vec![(200, r#"{"artists": []}"#.to_string())]);
+        let res = Artist::new(&server.client(), "nobody");
+        assert!(matches!(res, Err(MbError::NotFound(_))));
+    }
+
+    #[test]
+    fn skipped_types_are_not_fetched() {
+        let body = r#"{"release-group-offset": 0, "release-group-count": 3, "release-groups": [
+            {"id": "00000000-0000-0000-0000-000000000001", "title": "Studio", "primary-type": "Album", "first-release-date": "2024-01-01", "secondary-types": []},
+            {"id": "00000000-0000-0000-0000-000000000002", "title": "Live", "primary-type": "Album", "first-release-date": "2024-02-01", "secondary-types": ["Live"]},
+            {"id": "00000000-0000-0000-0000-000000000003", "title": "Remixes", "primary-type": "Album", "first-release-date": "2024-03-01", "secondary-types": ["Remix"]}
+        ]}"#;
+        let server = MockServer::start(vec![(200, body.to_string())]);
+        let albums = artist()
+            .get_albums_basic_filtered(
+                &server.client(),
+                &DateStrategy::FirstRelease,
+                &[],
+                &[ReleaseType::Live],
+            )
+            .unwrap();
+        let types = albums
+            .iter()
+            .map(|a| (a.title.as_str(), &a.release_type))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            types,
+            vec![
+                ("Studio", &ReleaseType::Album),
+                ("Remixes", &ReleaseType::Remix)
+            ]
+        );
+    }
+
+    #[test]
+    fn release_groups_are_cached_on_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = |ttl: Duration, refresh: bool| {
+            Some(ResponseCache::new(dir.path().to_path_buf(), ttl, refresh))
+        };
+        let fetch = |mb: &MbClient| {
+            artist()
+                .get_albums_basic_filtered(mb, &DateStrategy::FirstRelease, &[], &[])
+                .unwrap()
+                .len()
+        };
+        let files = || std::fs::read_dir(dir.path()).unwrap().count();
+        let hour = Duration::from_secs(60 * 60);
+        let first = MockServer::start(vec![
+            (200, release_group_page(0, 2, 0..2)),
+            (200, r#"{"release-groups": []}"#.to_string()),
+        ]);
+        let mb = first.client().cache(cache(hour, false));
+        assert_eq!(fetch(&mb), 2);
+        assert_eq!(files(), 1);
+        // searches are never cached
+        search_release_groups(&mb, "album").unwrap();
+        assert_eq!(files(), 1);
+
+        let cached = MockServer::start(vec![]);
+        let mut mb = cached.client().cache(cache(hour, false));
+        mb.server = first.url().to_string();
+        assert_eq!(fetch(&mb), 2);
+        assert!(cached.requests().is_empty());
+
+        // other servers have their own responses, --refresh asks them again
+        let mirror = MockServer::start(vec![
+            (200, release_group_page(0, 1, 0..1)),
+            (200, release_group_page(0, 3, 0..3)),
+        ]);
+        assert_eq!(fetch(&mirror.client().cache(cache(hour, false))), 1);
+        assert_eq!(files(), 2);
+        assert_eq!(fetch(&mirror.client().cache(cache(hour, true))), 3);
+        assert_eq!(fetch(&mirror.client().cache(cache(hour, false))), 3);
+        assert_eq!(mirror.requests().len(), 2);
+
+        ResponseCache::new(dir.path().to_path_buf(), Duration::ZERO, false).prune();
+        assert_eq!(files(), 0);
+    }
+
+    #[test]
+    fn broadcasts_are_fetched_for_followed_series() {
+        let body = r#"{"release-group-offset": 0, "release-group-count": 3, "release-groups": [
+            {"id": "00000000-0000-0000-0000-000000000001", "title": "Studio", "primary-type": "Album", "first-release-date": "2024-01-01", "secondary-types": []},
+            {"id": "00000000-0000-0000-0000-000000000002", "title": "Radio Hour", "primary-type": "Broadcast", "first-release-date": "2024-03-01", "secondary-types": []},
+            {"id": "00000000-0000-0000-0000-000000000003", "title": "Radio Hour", "primary-type": "Broadcast", "first-release-date": "2024-02-01", "secondary-types": []}
+        ]}"#;
+        let fetch = |broadcasts: bool| {
+            let server = MockServer::start(vec![(200, body.to_string())]);
+            let mut a = artist();
+            a.broadcasts = broadcasts;
+            a.get_albums_basic_filtered(&server.client(), &DateStrategy::FirstRelease, &[], &[])
+                .unwrap()
+                .into_iter()
+                .map(|a| (a.title, a.release_type))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fetch(false),
+            vec![("Studio".to_string(), ReleaseType::Album)]
+        );
+        assert_eq!(
+            fetch(true),
+            vec![
+                ("Studio".to_string(), ReleaseType::Album),
+                ("Radio Hour".to_string(), ReleaseType::Broadcast),
+                ("Radio Hour".to_string(), ReleaseType::Broadcast),
+            ]
+        );
+    }
+
+    #[test]
+    fn get_albums_follows_pages() {
+        let server = MockServer::start(vec![
+            (200, release_group_page(0, 150, 0..100)),
+            (200, release_group_page(100, 150, 100..150)),
+        ]);
+        let albums = artist().get_albums(&server.client(), |_| true).unwrap();
+        assert_eq!(albums.len(), 150);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[1].contains("offset=100"));
+    }
+
+    #[test]
+    fn get_albums_filters_while_parsing_and_pages_by_the_server_count() {
+        let server = MockServer::start(vec![
+            (200, release_group_page(0, 150, 0..100)),
+            (200, release_group_page(100, 150, 100..150)),
+        ]);
+        let albums = artist()
+            .get_albums(&server.client(), |rg| rg.title.ends_with('7'))
+            .unwrap();
+        assert_eq!(albums.len(), 15);
+        assert!(server.requests()[1].contains("offset=100"));
+    }
+
+    #[test]
+    fn get_albums_stops_on_empty_page() {
+        let server = MockServer::start(vec![
+            (200, release_group_page(0, 500, 0..100)),
+            (200, release_group_page(100, 500, 0..0)),
+        ]);
+        let albums = artist().get_albums(&server.client(), |_| true).unwrap();
+        assert_eq!(albums.len(), 100);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn get_albums_without_count_stops_on_short_page() {
+        let page = |ids| release_group_page(0, 0, ids).replace(r#""release-group-count": 0, "#, "");
+        let server = MockServer::start(vec![(200, page(0..100)), (200, page(100..120))]);
+        let albums = artist().get_albums(&server.client(), |_| true).unwrap();
+        assert_eq!(albums.len(), 120);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn paginate_stops_after_max_pages() {
+        let mut calls = 0;
+        let res = paginate("items", |_| {
+            calls += 1;
+            Ok(Page::new(
+                vec![0; HOW_MANY_RELEASE_RESULT as usize],
+                Some(usize::MAX),
+            ))
+        })
+        .unwrap();
+        assert_eq!(calls, MAX_PAGES);
+        assert_eq!(res.len(), MAX_PAGES * HOW_MANY_RELEASE_RESULT as usize);
+    }
+
+    #[test]
+    fn unknown_types_and_statuses_are_kept() {
+        let rg: ReleaseGroup = serde_json::from_str(
+            r#"{"id": "00000000-0000-0000-0000-000000000000", "title": "T", "primary-type": "Album", "secondary-types": ["Field recording", "DJ-mix"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            rg.secondary_types,
+            vec![
+                ReleaseType::Unknown("Field recording".to_string()),
+                ReleaseType::DJMix
+            ]
+        );
+        assert_eq!(
+            serde_json::to_string(&rg.secondary_types).unwrap(),
+            r#"["Field recording","DJ-mix"]"#
+        );
+        let status: Status = serde_json::from_str("\"Expunged\"").unwrap();
+        assert_eq!(status, Status::Unknown);
+    }
+
+    #[test]
+    fn recordings_come_from_the_earliest_official_release() {
+        let release = |n: u32, status: &str, date: &str, recordings: &[u32]| {
+            let tracks = recordings
+                .iter()
+                .map(|r| {
+                    format!(
+                        r#"{{"recording": {{"id": "00000000-0000-0000-0000-{:012x}"}}}}"#,
+                        r
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            format!(
+                r#"{{"id": "00000000-0000-0000-0000-{:012x}", "title": "T", "status": "{}", "date": "{}", "media": [{{"format": "CD", "track-count": {}, "tracks": [{}]}}]}}"#,
+                n,
+                status,
+                date,
+                recordings.len(),
+                tracks
+            )
+        };
+        let body = format!(
+            r#"{{"release-count": 3, "releases": [{}, {}, {}]}}"#,
+            release(1, "Official", "2024-05-01", &[11, 12]),
+            release(2, "Promotion", "2024-01-01", &[21]),
+            release(3, "Official", "2024-03-01", &[31, 32, 33])
+        );
+        let server = MockServer::start(vec![(200, body)]);
+        let recordings = release_group_recordings(&server.client(), &Uuid::from_u128(7)).unwrap();
+        assert_eq!(
+            recordings,
+            vec![
+                Uuid::from_u128(31),
+                Uuid::from_u128(32),
+                Uuid::from_u128(33)
+            ]
+        );
+        assert!(server.requests()[0].contains("release-group=00000000-0000-0000-0000-000000000007"));
+    }
+
+    #[test]
+    fn release_details_have_labels_and_catalog_numbers() {
+        let body = r#"{"release-count": 2, "releases": [
+            {"id": "00000000-0000-0000-0000-000000000002", "title": "A", "status": "Official", "barcode": "", "packaging": null, "media": [{"format": "Digital Media", "track-count": 9}]},
+            {"id": "00000000-0000-0000-0000-000000000001", "title": "A", "status": "Official", "date": "2024-03-01", "country": "GB", "barcode": "5012345678900", "packaging": "Gatefold Cover",
+             "label-info": [{"catalog-number": "WARP123", "label": {"name": "Warp"}}, {"catalog-number": "WARP123LP", "label": {"name": "Warp"}}],
+             "media": [{"format": "12\" Vinyl", "track-count": 5}, {"format": "12\" Vinyl", "track-count": 4}]}
+        ]}"#;
+        let server = MockServer::start(vec![(200, body.to_string())]);
+        let details = release_details(&server.client(), &Uuid::from_u128(7)).unwrap();
+        assert_eq!(details.len(), 2);
+        let vinyl = &details[0];
+        assert_eq!(vinyl.barcode.as_deref(), Some("5012345678900"));
+        assert_eq!(vinyl.labels, vec!["Warp".to_string()]);
+        assert_eq!(vinyl.catalog_numbers, vec!["WARP123", "WARP123LP"]);
+        assert_eq!(vinyl.media(), "2×12\" Vinyl");
+        assert_eq!(details[1].barcode, None);
+        assert!(server.requests()[0].contains("inc=labels%2Bmedia"));
+    }
+
+    #[test]
+    fn reissues_are_new_releases_in_known_release_groups() {
+        let release = |n: u32, date: &str| {
+            format!(
+                r#"{{"id": "00000000-0000-0000-0000-{:012}", "title": "Album Deluxe", "status": "Official", "date": "{}",
+                  "release-group": {{"id": "00000000-0000-0000-0000-000000000099", "title": "Album", "primary-type": "Album", "secondary-types": []}}}}"#,
+                n, date
+            )
+        };
+        let page = |releases: &[String]| {
+            format!(
+                r#"{{"release-count": {}, "releases": [{}]}}"#,
+                releases.len(),
+                releases.join(",")
+            )
+        };
+        let first = page(&[release(1, "2020-01-01")]);
+        let second = page(&[release(1, "2020-01-01"), release(2, "2024-05-01")]);
+        let server = MockServer::start(vec![(200, first), (200, second)]);
+        let mb = server.client();
+        let mut counts = HashMap::new();
+        // the first time we only learn how many releases there are
+        assert!(artist().get_reissues(&mb, &mut counts).unwrap().is_empty());
+        mb.forget();
+        let reissues = artist().get_reissues(&mb, &mut counts).unwrap();
+        assert_eq!(reissues.len(), 1);
+        assert_eq!(reissues[0].id, Uuid::from_u128(0x99));
+        assert_eq!(reissues[0].date, parse_date("2024-05-01"));
+        assert_eq!(counts[&Uuid::from_u128(0x99)], 2);
+    }
+
+    #[test]
+    fn ratings_need_votes() {
+        let rating = |json: &str| {
+            let rg: ReleaseGroup = serde_json::from_str(&format!(
+                r#"{{"id": "00000000-0000-0000-0000-000000000000", "title": "T"{}}}"#,
+                json
+            ))
+            .unwrap();
+            rg.rating.as_ref().and_then(RatingResponse::to_rating)
+        };
+        let r = rating(r#", "rating": {"votes-count": 3, "value": 4.15}"#).unwrap();
+        assert_eq!(
+            r,
+            Rating {
+                score: 83,
+                votes: 3
+            }
+        );
+        assert_eq!(r.stars(), "★★★★☆ 4.2");
+        assert_eq!(
+            rating(r#", "rating": {"votes-count": 0, "value": null}"#),
+            None
+        );
+        assert_eq!(rating(""), None);
+    }
+
+    #[test]
+    fn matching_fields_finds_sort_names_aliases_and_ids() {
+        let a = artist();
+        assert_eq!(
+            a.matching_fields("TEST"),
+            vec!["name", "sort name", "search string", "alias \"Testers\""]
+        );
+        assert_eq!(a.matching_fields("testers"), vec!["alias \"Testers\""]);
+        assert_eq!(a.matching_fields(ARTIST_ID), vec!["mbid"]);
+        assert!(a
+            .matching_fields("00000000-0000-0000-0000-000000000000")
+            .is_empty());
+        assert!(a.matching_fields("nobody").is_empty());
+    }
+
+    /// Random albums with few different ids, artists and titles so that there are many duplicates
+    fn random_albums(rng: &mut fastrand::Rng) -> Vec<Album> {
+        (0..rng.usize(0..50))
+            .map(|_| Album {
+                id: Uuid::from_u128(rng.u128(0..20)),
+                artist: format!("Artist {}", rng.u8(0..5)),
+                title: format!("Title {}", rng.u8(0..20)),
+                release_type: ReleaseType::Album,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dedup_release_groups_keeps_every_id_once() {
+        let mut rng = fastrand::Rng::with_seed(3);
+        for _ in 0..1000 {
+            let albums = random_albums(&mut rng);
+            let res = dedup_release_groups(albums.clone());
+            let ids = res
+                .iter()
+                .map(|a| a.id)
+                .collect::<std::collections::HashSet<Uuid>>();
+            assert_eq!(ids.len(), res.len(), "duplicate ids for {:?}", albums);
+            for a in &albums {
+                let kept = res.iter().find(|r| r.id == a.id).expect("dropped an id");
+                assert!(
+                    kept.artist == a.artist || kept.other_artists.contains(&a.artist),
+                    "lost artist {} of {}",
+                    a.artist,
+                    a.id
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn dedup_by_title_never_drops_different_titles() {
+        let mut rng = fastrand::Rng::with_seed(4);
+        for _ in 0..1000 {
+            let albums = random_albums(&mut rng);
+            let res = dedup_by_title(albums.clone());
+            let titles = albums
+                .iter()
+                .map(|a| crate::config::canonical_name(&a.title))
+                .collect::<std::collections::HashSet<String>>();
+            assert_eq!(res.len(), titles.len(), "for {:?}", albums);
+            for a in &albums {
+                assert!(
+                    res.iter().any(|r| r.title == a.title),
+                    "dropped {} for {:?}",
+                    a.title,
+                    albums
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn not_found_is_not_retried() {
+        let server = MockServer::start(vec![(404, String::new())]);
+        let res = artist().get_albums(&server.client(), |_| true);
+        assert!(matches!(res, Err(MbError::NotFound(_))));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn rate_limit_is_retried() {
+        let server = MockServer::start(vec![
+            (503, String::new()),
+            (429, String::new()),
+            (200, release_group_page(0, 1, 0..1)),
+        ]);
+        let albums = artist().get_albums(&server.client(), |_| true).unwrap();
+        assert_eq!(albums.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn throttle_hints_pause_all_requests() {
+        let hint = r#"{"error": "Your requests are exceeding the allowable rate limit. Please see http://wiki.musicbrainz.org/XMLWebService for more information."}"#;
+        let default = Duration::from_secs(10);
+        assert_eq!(
+            throttle_pause(Some("3"), "", default),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(throttle_pause(None, hint, default), Some(default));
+        assert_eq!(throttle_pause(None, r#"{"error": "down"}"#, default), None);
+        assert_eq!(throttle_pause(None, "<html>", default), None);
+
+        let server = MockServer::start(vec![
+            (503, hint.to_string()),
+            (200, release_group_page(0, 1, 0..1)),
+        ]);
+        let mb = server.client();
+        let started = Instant::now();
+        artist().get_albums(&mb, |_| true).unwrap();
+        assert!(started.elapsed() >= mb.throttle_pause);
+        assert!(mb.paused_until.lock().unwrap().is_some());
+    }
+
+    #[test]
+    fn throttles_slow_the_adaptive_rate_down() {
+        let server = MockServer::start(vec![
+            (503, String::new()),
+            (200, release_group_page(0, 1, 0..1)),
+        ]);
+        let mb = server
+            .client()
+            .adaptive(Some(AdaptiveRate::new(100, Some(40))));
+        assert_eq!(mb.ratelimit.refill_amount(), 40);
+        artist().get_albums(&mb, |_| true).unwrap();
+        assert_eq!(mb.learned_rate(), Some(20));
+        assert_eq!(
+            (mb.ratelimit.refill_amount(), mb.ratelimit.max_tokens()),
+            (20, 20)
+        );
+    }
+
+    #[test]
+    fn server_errors_give_up_after_retries() {
+        let server = MockServer::start(vec![(500, String::new()); RETRIES as usize + 1]);
+        let res = artist().get_albums(&server.client(), |_| true);
+        assert!(matches!(res, Err(MbError::ServerError(500))));
+        assert_eq!(server.requests().len(), RETRIES as usize + 1);
+    }
+
+    #[test]
+    fn broken_json_is_a_decode_error() {
+        let server = MockServer::start(vec![(200, "{\"release-groups\": [".to_string())]);
+        let res = artist().get_albums(&server.client(), |_| true);
+        assert!(matches!(res, Err(MbError::Decode(_))));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn replay_answers_recorded_requests() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start(vec![(200, release_group_page(0, 2, 0..2))]);
+        let recorded = artist()
+            .get_albums(
+                &server.client().record(Some(dir.path().to_path_buf())),
+                |_| true,
+            )
+            .unwrap();
+        // the server is gone after the first answer, so this can only work from the recording
+        let replayed = artist()
+            .get_albums(
+                &server.client().replay(Some(dir.path().to_path_buf())),
+                |_| true,
+            )
+            .unwrap();
+        assert_eq!(recorded.len(), replayed.len());
+        assert_eq!(server.requests().len(), 1);
     }
 }
@@ -0,0 +1,192 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::i18n::format_date;
+use crate::msgpack;
+use crate::output::{escape_html, OutputFormat};
+use crate::responses::Album;
+
+/// Everything followed artists released in one year
+#[derive(Debug, Serialize)]
+pub struct YearReview {
+    pub year: i32,
+    /// months with releases and how many, busiest first
+    pub months: Vec<(String, usize)>,
+    /// artists with more than one release and how many, most first
+    pub artists: Vec<(String, usize)>,
+    /// all releases of the year in the order they came out
+    pub albums: Vec<Album>,
+}
+
+/// Sort counts with the highest first, ties keep their order
+fn by_count(counts: impl IntoIterator<Item = (String, usize)>) -> Vec<(String, usize)> {
+    let mut res = counts.into_iter().collect::<Vec<_>>();
+    res.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    res
+}
+
+impl YearReview {
+    /// The review of `year` from the `albums` we know about
+    pub fn new(year: i32, albums: Vec<Album>) -> Self {
+        let mut albums = albums
+            .into_iter()
+            .filter(|a| a.date.is_some_and(|d| d.year() == year))
+            .collect::<Vec<Album>>();
+        albums.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.cmp(b)));
+
+        let mut months: BTreeMap<u8, (String, usize)> = BTreeMap::new();
+        let mut artists: BTreeMap<&str, usize> = BTreeMap::new();
+        for a in &albums {
+            if let Some(d) = a.date {
+                months
+                    .entry(d.month() as u8)
+                    .or_insert((d.month().to_string(), 0))
+                    .1 += 1;
+            }
+            for artist in std::iter::once(&a.artist).chain(a.other_artists.iter()) {
+                *artists.entry(artist).or_default() += 1;
+            }
+        }
+        let artists = by_count(
+            artists
+                .into_iter()
+                .filter(|(_, n)| *n > 1)
+                .map(|(a, n)| (a.to_string(), n)),
+        );
+        Self {
+            year,
+            months: by_count(months.into_values()),
+            artists,
+            albums,
+        }
+    }
+
+    /// Render the review in `format`
+    pub fn render(&self, format: OutputFormat) -> Result<Vec<u8>> {
+        let title = format!("Year in review {}", self.year);
+        let total = format!("{} releases by followed artists", self.albums.len());
+        let line = |a: &Album| {
+            format!(
+                "{} - {} - {} ({})",
+                format_date(a.date),
+                a.artists(),
+                a.title,
+                a.release_type
+            )
+        };
+        let mut out = String::new();
+        match format {
+            OutputFormat::Text => {
+                out.push_str(&format!(
+                    "{}\n{}\n\n{}\n",
+                    title,
+                    "=".repeat(title.len()),
+                    total
+                ));
+                out.push_str("\nBusiest months\n");
+                for (m, n) in &self.months {
+                    out.push_str(&format!("  {}: {}\n", m, n));
+                }
+                out.push_str("\nArtists with several releases\n");
+                for (a, n) in &self.artists {
+                    out.push_str(&format!("  {}: {}\n", a, n));
+                }
+                out.push_str("\nAll releases\n");
+                for a in &self.albums {
+                    out.push_str(&format!("  {}\n", line(a)));
+                }
+            }
+            OutputFormat::Markdown => {
+                out.push_str(&format!("# {}\n\n{}\n", title, total));
+                out.push_str("\n## Busiest months\n\n");
+                for (m, n) in &self.months {
+                    out.push_str(&format!("- {}: {}\n", m, n));
+                }
+                out.push_str("\n## Artists with several releases\n\n");
+                for (a, n) in &self.artists {
+                    out.push_str(&format!("- {}: {}\n", a, n));
+                }
+                out.push_str("\n## All releases\n\n");
+                for a in &self.albums {
+                    out.push_str(&format!("- {}\n", line(a)));
+                }
+            }
+            OutputFormat::Html => {
+                out.push_str(&format!(
+                    "<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<h1>{}</h1>\n<p>{}</p>\n",
+                    title, title, total
+                ));
+                out.push_str("<h2>Busiest months</h2>\n<ul>\n");
+                for (m, n) in &self.months {
+                    out.push_str(&format!("<li>{}: {}</li>\n", m, n));
+                }
+                out.push_str("</ul>\n<h2>Artists with several releases</h2>\n<ul>\n");
+                for (a, n) in &self.artists {
+                    out.push_str(&format!("<li>{}: {}</li>\n", escape_html(a), n));
+                }
+                out.push_str("</ul>\n<h2>All releases</h2>\n<ul>\n");
+                for a in &self.albums {
+                    out.push_str(&format!(
+                        "<li><a href=\"https://musicbrainz.org/release-group/{}\">{}</a></li>\n",
+                        a.id,
+                        escape_html(&line(a))
+                    ));
+                }
+                out.push_str("</ul>\n</body>\n</html>\n");
+            }
+            OutputFormat::Json => {
+                out = serde_json::to_string_pretty(self).context("JSON to string")?;
+                out.push('\n');
+            }
+            OutputFormat::Msgpack => return msgpack::to_vec_versioned(self),
+        }
+        Ok(out.into_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::ReleaseType;
+
+    use time::{Date, Month};
+    use uuid::Uuid;
+
+    fn album(n: u128, artist: &str, year: i32, month: Month) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: artist.to_string(),
+            title: format!("Album {}", n),
+            date: Some(Date::from_calendar_date(year, month, 1).unwrap()),
+            release_type: ReleaseType::Album,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn review_counts_months_and_artists() {
+        let review = YearReview::new(
+            2025,
+            vec![
+                album(1, "A", 2025, Month::March),
+                album(2, "B", 2025, Month::January),
+                album(3, "A", 2025, Month::March),
+                album(4, "A", 2024, Month::March),
+            ],
+        );
+        assert_eq!(
+            review
+                .albums
+                .iter()
+                .map(|a| a.id.as_u128())
+                .collect::<Vec<_>>(),
+            vec![2, 1, 3]
+        );
+        assert_eq!(
+            review.months,
+            vec![("March".to_string(), 2), ("January".to_string(), 1)]
+        );
+        assert_eq!(review.artists, vec![("A".to_string(), 2)]);
+    }
+}
@@ -0,0 +1,61 @@
+use anyhow::{anyhow, Context, Result};
+use std::process::Command;
+
+/// The secret that a password or token in the settings stands for.
+/// `file:<path>` is the first line of the file, `env:<NAME>` the environment variable and
+/// `command:<command>` the first line the shell command prints, like `command:pass show irc`.
+/// Everything else is the secret itself
+pub fn resolve(value: &str) -> Result<String> {
+    if let Some(path) = value.strip_prefix("file:") {
+        let s = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read the secret in {}", path))?;
+        Ok(first_line(&s))
+    } else if let Some(name) = value.strip_prefix("env:") {
+        std::env::var(name).with_context(|| format!("The secret {} is not set", name))
+    } else if let Some(command) = value.strip_prefix("command:") {
+        let output = Command::new("sh")
+            .args(["-c", command])
+            .output()
+            .with_context(|| format!("Could not run {}", command))?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "Getting the secret with {} failed with {}",
+                command,
+                output.status
+            ));
+        }
+        Ok(first_line(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// The first line of `s`, files and commands end with a newline that is not part of the secret
+fn first_line(s: &str) -> String {
+    s.lines().next().unwrap_or_default().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_come_from_files_variables_and_commands() {
+        assert_eq!(resolve("hunter2").unwrap(), "hunter2");
+        let path = std::env::temp_dir().join(format!("mbrg-secret-{}", fastrand::u64(..)));
+        std::fs::write(&path, "from file\nsecond line\n").unwrap();
+        assert_eq!(
+            resolve(&format!("file:{}", path.display())).unwrap(),
+            "from file"
+        );
+        std::fs::remove_file(&path).unwrap();
+        assert!(resolve(&format!("file:{}", path.display())).is_err());
+        assert_eq!(resolve("env:PATH").unwrap(), std::env::var("PATH").unwrap());
+        assert!(resolve("env:MBRG_SECRET_THAT_IS_NOT_SET").is_err());
+        assert_eq!(
+            resolve("command:echo from command").unwrap(),
+            "from command"
+        );
+        assert!(resolve("command:false").is_err());
+    }
+}
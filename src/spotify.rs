@@ -0,0 +1,450 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::time::Duration;
+
+use crate::i18n::tr;
+
+/// The Spotify web api
+pub const API_SERVER: &str = "https://api.spotify.com";
+/// The Spotify server for logging in
+pub const ACCOUNTS_SERVER: &str = "https://accounts.spotify.com";
+/// Where the browser gets sent after logging in, has to be set in the app on the Spotify dashboard
+const REDIRECT_ADDRESS: &str = "127.0.0.1:8888";
+/// What we are allowed to do with the account
+const SCOPES: &str = "playlist-modify-private playlist-modify-public";
+/// How often we retry when Spotify throttles us
+const RETRIES: usize = 3;
+/// How many tracks we add in one request, the most Spotify allows
+const TRACKS_PER_REQUEST: usize = 100;
+
+/// Settings for creating Spotify playlists
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpotifySettings {
+    /// the client id of an app from https://developer.spotify.com/dashboard
+    pub client_id: Option<String>,
+    /// the client secret of the same app
+    pub client_secret: Option<String>,
+    /// written by `spotify login`
+    pub refresh_token: Option<String>,
+}
+
+impl SpotifySettings {
+    /// The client id and secret, an error telling what to set if they are missing
+    fn app(&self) -> Result<(&str, &str)> {
+        match (&self.client_id, &self.client_secret) {
+            (Some(id), Some(secret)) => Ok((id, secret)),
+            _ => Err(anyhow!(
+                "Set settings.spotify.client_id and client_secret to the ones of your Spotify app"
+            )),
+        }
+    }
+}
+
+/// The redirect uri we register the login with
+fn redirect_uri() -> String {
+    format!("http://{}/callback", REDIRECT_ADDRESS)
+}
+
+/// JSON response for a token request, the refresh token only comes with a login
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+}
+
+/// Ask the accounts server for a token with the `form`
+fn request_token(
+    client: &Client,
+    accounts: &str,
+    settings: &SpotifySettings,
+    form: &[(&str, &str)],
+) -> Result<TokenResponse> {
+    let (id, secret) = settings.app()?;
+    let response = client
+        .post(format!("{}/api/token", accounts))
+        .basic_auth(id, Some(secret))
+        .form(form)
+        .send()
+        .context("Could not reach Spotify")?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Spotify refused the login with {}",
+            response.status()
+        ));
+    }
+    response
+        .json()
+        .context("Could not decode the Spotify token")
+}
+
+/// Let the user log in with the browser and return the refresh token
+pub fn login(client: &Client, accounts: &str, settings: &SpotifySettings) -> Result<String> {
+    let (id, _) = settings.app()?;
+    let url = Url::parse_with_params(
+        &format!("{}/authorize", accounts),
+        &[
+            ("response_type", "code"),
+            ("client_id", id),
+            ("scope", SCOPES),
+            ("redirect_uri", &redirect_uri()),
+        ],
+    )?;
+    let listener = TcpListener::bind(REDIRECT_ADDRESS)
+        .with_context(|| format!("Could not listen on {} for the login", REDIRECT_ADDRESS))?;
+    println!("{}\n{}", tr!("Open this url and allow the access:"), url);
+    let code = wait_for_code(&listener)?;
+    let token = request_token(
+        client,
+        accounts,
+        settings,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("redirect_uri", &redirect_uri()),
+        ],
+    )?;
+    token
+        .refresh_token
+        .ok_or_else(|| anyhow!("Spotify did not send a refresh token"))
+}
+
+/// Wait for the browser to come back from the login and return the code it has
+fn wait_for_code(listener: &TcpListener) -> Result<String> {
+    let (mut stream, _) = listener.accept().context("Waiting for the login")?;
+    let mut line = String::new();
+    BufReader::new(stream.try_clone()?).read_line(&mut line)?;
+    let path = line.split_whitespace().nth(1).unwrap_or_default();
+    let url = Url::parse(&format!("http://{}{}", REDIRECT_ADDRESS, path))?;
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.into_owned())
+    };
+    let (status, text) = if param("code").is_some() {
+        ("200 OK", "Logged in, you can close this page.")
+    } else {
+        ("400 Bad Request", "The login did not work.")
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        text.len(),
+        text
+    )?;
+    param("code").ok_or_else(|| {
+        anyhow!(
+            "The login failed: {}",
+            param("error").unwrap_or_else(|| "no code".to_string())
+        )
+    })
+}
+
+/// The id of the album in a Spotify url like https://open.spotify.com/album/<id>
+pub fn album_id_from_url(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    if url.host_str() != Some("open.spotify.com") {
+        return None;
+    }
+    let mut segments = url.path_segments()?.filter(|s| !s.starts_with("intl-"));
+    match (segments.next(), segments.next()) {
+        (Some("album"), Some(id)) if !id.is_empty() => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// The Spotify url of the album with `id`
+pub fn album_url(id: &str) -> String {
+    format!("https://open.spotify.com/album/{}", id)
+}
+
+/// JSON response for an album search
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    albums: Page<SpotifyAlbum>,
+}
+
+/// JSON response for a page of items, `next` is the url of the next page
+#[derive(Debug, Deserialize)]
+struct Page<T> {
+    items: Vec<T>,
+    next: Option<String>,
+}
+
+/// JSON response for an album
+#[derive(Debug, Deserialize)]
+struct SpotifyAlbum {
+    id: String,
+    name: String,
+}
+
+/// JSON response for a track
+#[derive(Debug, Clone, Deserialize)]
+pub struct SpotifyTrack {
+    pub id: String,
+    pub uri: String,
+    /// only in full track objects
+    #[serde(default)]
+    popularity: u32,
+}
+
+/// JSON response for several full tracks
+#[derive(Debug, Deserialize)]
+struct TracksResponse {
+    tracks: Vec<Option<SpotifyTrack>>,
+}
+
+/// JSON response for a created playlist
+#[derive(Debug, Deserialize)]
+struct CreatedPlaylist {
+    id: String,
+}
+
+/// A logged in connection to the Spotify web api
+pub struct Spotify {
+    client: Client,
+    server: String,
+    token: String,
+}
+
+impl Spotify {
+    /// Connect with the refresh token of the `settings`
+    pub fn connect(
+        client: Client,
+        accounts: &str,
+        server: &str,
+        settings: &SpotifySettings,
+    ) -> Result<Self> {
+        let refresh = settings
+            .refresh_token
+            .as_deref()
+            .ok_or_else(|| anyhow!("Run spotify login first"))?;
+        let token = request_token(
+            &client,
+            accounts,
+            settings,
+            &[("grant_type", "refresh_token"), ("refresh_token", refresh)],
+        )?;
+        Ok(Self {
+            client,
+            server: server.to_string(),
+            token: token.access_token,
+        })
+    }
+
+    /// Connect without an account with only the app of the `settings`, enough for searching
+    pub fn connect_app(
+        client: Client,
+        accounts: &str,
+        server: &str,
+        settings: &SpotifySettings,
+    ) -> Result<Self> {
+        let token = request_token(
+            &client,
+            accounts,
+            settings,
+            &[("grant_type", "client_credentials")],
+        )?;
+        Ok(Self {
+            client,
+            server: server.to_string(),
+            token: token.access_token,
+        })
+    }
+
+    /// Send the request from `build`, again after waiting if Spotify throttles us
+    fn send(&self, build: impl Fn() -> RequestBuilder) -> Result<Response> {
+        for _ in 0..RETRIES {
+            let response = build()
+                .bearer_auth(&self.token)
+                .send()
+                .context("Could not reach Spotify")?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                if !response.status().is_success() {
+                    return Err(anyhow!("Spotify answered with {}", response.status()));
+                }
+                return Ok(response);
+            }
+            let wait = response
+                .headers()
+                .get("Retry-After")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(1);
+            std::thread::sleep(Duration::from_secs(wait));
+        }
+        Err(anyhow!("Spotify kept throttling us"))
+    }
+
+    fn get<T: DeserializeOwned>(&self, url: &str, query: &[(&str, &str)]) -> Result<T> {
+        self.send(|| self.client.get(url).query(query))?
+            .json()
+            .context("Could not decode the Spotify response")
+    }
+
+    fn post<T: DeserializeOwned>(&self, url: &str, body: &Value) -> Result<T> {
+        self.send(|| self.client.post(url).json(body))?
+            .json()
+            .context("Could not decode the Spotify response")
+    }
+
+    /// The albums Spotify finds for `query`
+    fn search_albums(&self, query: &str) -> Result<Vec<SpotifyAlbum>> {
+        let resp: SearchResponse = self.get(
+            &format!("{}/v1/search", self.server),
+            &[("q", query), ("type", "album"), ("limit", "10")],
+        )?;
+        Ok(resp.albums.items)
+    }
+
+    /// The id of the album called `title` by `artist`, ignoring case
+    pub fn search_album(&self, artist: &str, title: &str) -> Result<Option<String>> {
+        let albums = self.search_albums(&format!("album:{} artist:{}", title, artist))?;
+        let title = title.to_lowercase();
+        Ok(albums
+            .into_iter()
+            .find(|a| a.name.to_lowercase() == title)
+            .map(|a| a.id))
+    }
+
+    /// The id of the album with the `barcode`
+    pub fn album_by_barcode(&self, barcode: &str) -> Result<Option<String>> {
+        Ok(self
+            .search_albums(&format!("upc:{}", barcode))?
+            .into_iter()
+            .next()
+            .map(|a| a.id))
+    }
+
+    /// All tracks of the album with `id` in order
+    pub fn album_tracks(&self, id: &str) -> Result<Vec<SpotifyTrack>> {
+        let mut url = format!("{}/v1/albums/{}/tracks?limit=50", self.server, id);
+        let mut tracks = Vec::new();
+        loop {
+            let page: Page<SpotifyTrack> = self.get(&url, &[])?;
+            tracks.extend(page.items);
+            match page.next {
+                Some(next) => url = next,
+                None => return Ok(tracks),
+            }
+        }
+    }
+
+    /// The most popular of the `tracks`, at most 50 of them get compared
+    pub fn most_popular(&self, tracks: &[SpotifyTrack]) -> Result<Option<SpotifyTrack>> {
+        if tracks.is_empty() {
+            return Ok(None);
+        }
+        let ids = tracks
+            .iter()
+            .take(50)
+            .map(|t| t.id.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let resp: TracksResponse =
+            self.get(&format!("{}/v1/tracks", self.server), &[("ids", &ids)])?;
+        Ok(resp
+            .tracks
+            .into_iter()
+            .flatten()
+            .max_by_key(|t| t.popularity))
+    }
+
+    /// Create a private playlist called `name` and return its id
+    pub fn create_playlist(&self, name: &str, description: &str) -> Result<String> {
+        let created: CreatedPlaylist = self.post(
+            &format!("{}/v1/me/playlists", self.server),
+            &json!({ "name": name, "description": description, "public": false }),
+        )?;
+        Ok(created.id)
+    }
+
+    /// Append the tracks with `uris` to the playlist with `id`
+    pub fn add_tracks(&self, id: &str, uris: &[String]) -> Result<()> {
+        for chunk in uris.chunks(TRACKS_PER_REQUEST) {
+            let _: Value = self.post(
+                &format!("{}/v1/playlists/{}/tracks", self.server, id),
+                &json!({ "uris": chunk }),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    #[test]
+    fn album_ids_from_urls() {
+        assert_eq!(
+            album_id_from_url("https://open.spotify.com/album/4aawyAB9vmqN3uQ7FjRGTy"),
+            Some("4aawyAB9vmqN3uQ7FjRGTy".to_string())
+        );
+        assert_eq!(
+            album_id_from_url("https://open.spotify.com/intl-de/album/4aawyAB9vmqN3uQ7FjRGTy?si=x"),
+            Some("4aawyAB9vmqN3uQ7FjRGTy".to_string())
+        );
+        assert_eq!(
+            album_id_from_url("https://open.spotify.com/artist/0OdUWJ0sBjDrqHygGUXeCF"),
+            None
+        );
+        assert_eq!(album_id_from_url("https://example.com/album/x"), None);
+    }
+
+    #[test]
+    fn playlist_from_searched_album() {
+        let server = MockServer::start(vec![
+            (200, r#"{"access_token": "access", "token_type": "Bearer"}"#.to_string()),
+            (
+                200,
+                r#"{"albums": {"items": [{"id": "other", "name": "Album (Deluxe)"}, {"id": "a1", "name": "album"}], "next": null}}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"items": [{"id": "t1", "uri": "spotify:track:t1"}, {"id": "t2", "uri": "spotify:track:t2"}], "next": null}"#
+                    .to_string(),
+            ),
+            (
+                200,
+                r#"{"tracks": [{"id": "t1", "uri": "spotify:track:t1", "popularity": 10}, {"id": "t2", "uri": "spotify:track:t2", "popularity": 50}]}"#
+                    .to_string(),
+            ),
+            (201, r#"{"id": "p1"}"#.to_string()),
+            (201, r#"{"snapshot_id": "s"}"#.to_string()),
+        ]);
+        let settings = SpotifySettings {
+            client_id: Some("id".to_string()),
+            client_secret: Some("secret".to_string()),
+            refresh_token: Some("refresh".to_string()),
+        };
+        let spotify =
+            Spotify::connect(Client::new(), server.url(), server.url(), &settings).unwrap();
+        let album = spotify.search_album("Artist", "Album").unwrap().unwrap();
+        assert_eq!(album, "a1");
+        let tracks = spotify.album_tracks(&album).unwrap();
+        let top = spotify.most_popular(&tracks).unwrap().unwrap();
+        assert_eq!(top.uri, "spotify:track:t2");
+        let playlist = spotify.create_playlist("New Releases", "").unwrap();
+        spotify.add_tracks(&playlist, &[top.uri]).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests[0], "POST /api/token HTTP/1.1");
+        assert!(requests[1].starts_with("GET /v1/search?q=album%3AAlbum+artist%3AArtist"));
+        assert!(requests[2].starts_with("GET /v1/albums/a1/tracks"));
+        assert_eq!(requests[3], "GET /v1/tracks?ids=t1%2Ct2 HTTP/1.1");
+        assert_eq!(requests[5], "POST /v1/playlists/p1/tracks HTTP/1.1");
+        let bodies = server.bodies();
+        assert_eq!(bodies[0], "grant_type=refresh_token&refresh_token=refresh");
+        assert_eq!(bodies[5], r#"{"uris":["spotify:track:t2"]}"#);
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// The first bytes of every gzip file
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// How state files like the config and the run history are written, all formats can always be read
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum StorageFormat {
+    /// indented json that is easy to edit by hand
+    #[default]
+    Pretty,
+    /// json without any whitespace
+    Compact,
+    /// compact json compressed with gzip, in a file ending in `.gz`
+    Gzip,
+}
+
+/// `path` with `.gz` appended
+fn gzip_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".gz");
+    PathBuf::from(p)
+}
+
+/// Does the state file for `path` exist in any format
+pub fn exists(path: &Path) -> bool {
+    path.exists() || gzip_path(path).exists()
+}
+
+/// The file for `path` that exists, `path.gz` if it is compressed
+pub fn existing_file(path: &Path) -> PathBuf {
+    let gz = gzip_path(path);
+    if gz.exists() {
+        gz
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// The bytes in the file `path`, decompressed if it is gzip
+pub fn read_bytes(path: &Path) -> Result<Vec<u8>> {
+    let bytes = fs::read(path).with_context(|| format!("Reading {}", path.display()))?;
+    if bytes.starts_with(&GZIP_MAGIC) {
+        let mut out = Vec::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_end(&mut out)
+            .with_context(|| format!("Decompressing {}", path.display()))?;
+        Ok(out)
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Read the json in `path` or its gzip compressed version `path.gz`
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let path = existing_file(path);
+    let bytes = read_bytes(&path)?;
+    serde_json::from_slice(&bytes).with_context(|| format!("Could not read {}", path.display()))
+}
+
+/// The backup of the state file for `path` that `backup` made, in any format
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".bak");
+    PathBuf::from(p)
+}
+
+/// Copy the state file for `path` to its backup before it gets replaced
+pub fn backup(path: &Path) -> Result<()> {
+    let file = existing_file(path);
+    if file.exists() {
+        fs::copy(&file, backup_path(path))
+            .with_context(|| format!("Backing up {}", file.display()))?;
+    }
+    Ok(())
+}
+
+/// Write `value` to `path` in `format`. The file is replaced atomically and the file in the other format is removed
+pub fn write_json<T: Serialize>(path: &Path, value: &T, format: StorageFormat) -> Result<()> {
+    let (target, other) = match format {
+        StorageFormat::Gzip => (gzip_path(path), path.to_path_buf()),
+        _ => (path.to_path_buf(), gzip_path(path)),
+    };
+    let bytes = match format {
+        StorageFormat::Pretty => serde_json::to_vec_pretty(value).context("JSON to string")?,
+        StorageFormat::Compact => serde_json::to_vec(value).context("JSON to string")?,
+        StorageFormat::Gzip => {
+            // compressing many tiny writes of the serializer is a lot slower than one big one
+            let json = serde_json::to_vec(value).context("JSON to string")?;
+            let mut e = GzEncoder::new(Vec::new(), Compression::default());
+            e.write_all(&json).context("Compressing")?;
+            e.finish().context("Compressing")?
+        }
+    };
+    let mut tmp = target.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let mut f = fs::File::create(&tmp).with_context(|| format!("Writing {}", tmp.display()))?;
+    f.write_all(&bytes)
+        .with_context(|| format!("Writing {}", tmp.display()))?;
+    fs::rename(&tmp, &target).with_context(|| format!("Replacing {}", target.display()))?;
+    if other.exists() {
+        fs::remove_file(&other).with_context(|| format!("Removing {}", other.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_format_reads_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let value = vec!["a".to_string(), "b".to_string()];
+        for format in [
+            StorageFormat::Gzip,
+            StorageFormat::Compact,
+            StorageFormat::Pretty,
+        ] {
+            write_json(&path, &value, format).unwrap();
+            assert_eq!(read_json::<Vec<String>>(&path).unwrap(), value);
+            assert_eq!(path.exists(), format != StorageFormat::Gzip);
+            assert_eq!(gzip_path(&path).exists(), format == StorageFormat::Gzip);
+        }
+    }
+
+    #[test]
+    fn backup_keeps_the_previous_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        backup(&path).unwrap();
+        assert!(!backup_path(&path).exists());
+        write_json(&path, &1, StorageFormat::Gzip).unwrap();
+        backup(&path).unwrap();
+        write_json(&path, &2, StorageFormat::Pretty).unwrap();
+        let bytes = read_bytes(&backup_path(&path)).unwrap();
+        assert_eq!(serde_json::from_slice::<i32>(&bytes).unwrap(), 1);
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use reqwest::blocking::Client;
+use std::collections::{BTreeMap, HashSet};
+use uuid::Uuid;
+
+use crate::listenbrainz;
+use crate::responses::{Artist, MbClient, MbError, RelatedArtist};
+
+/// Where suggestions come from
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SuggestSource {
+    /// relationships like band members, collaborations and labels
+    Musicbrainz,
+    /// artists people listen to together
+    Listenbrainz,
+}
+
+/// An artist we do not follow that is related to some we follow
+#[derive(Debug)]
+pub struct Suggestion {
+    pub artist: RelatedArtist,
+    /// how it is related to which followed artist, like "member of band of Some Band"
+    pub reasons: Vec<String>,
+    /// the first followed artist it is related to
+    pub related_to: String,
+    /// higher is more related to the followed artists
+    pub score: f64,
+}
+
+/// Errors for single artists together with the artist
+pub type SuggestErrors = Vec<(String, MbError)>;
+
+/// Collects suggestions for several followed artists
+struct Suggestions {
+    followed: HashSet<Uuid>,
+    found: BTreeMap<Uuid, Suggestion>,
+}
+
+impl Suggestions {
+    fn new(artists: &[Artist]) -> Self {
+        Self {
+            followed: artists.iter().map(|a| a.id).collect(),
+            found: BTreeMap::new(),
+        }
+    }
+
+    /// `related` is related to the followed artist `to` for `reason`, reasons we already have do not count again
+    fn add(&mut self, related: RelatedArtist, to: &Artist, reason: String, score: f64) {
+        if self.followed.contains(&related.id) {
+            return;
+        }
+        let s = self.found.entry(related.id).or_insert_with(|| Suggestion {
+            artist: related,
+            reasons: vec![],
+            related_to: to.name.clone(),
+            score: 0.0,
+        });
+        if !s.reasons.contains(&reason) {
+            s.reasons.push(reason);
+            s.score += score;
+        }
+    }
+
+    /// All suggestions, the most related first
+    fn sorted(self) -> Vec<Suggestion> {
+        let mut res = self.found.into_values().collect::<Vec<Suggestion>>();
+        res.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.artist.sort_name.cmp(&b.artist.sort_name))
+        });
+        res
+    }
+}
+
+/// Suggestions from the relations of the followed `artists`, the ones related to the most of them first.
+/// Errors for single artists are returned with the artist, fatal errors stop everything
+pub fn suggest(
+    mb: &MbClient,
+    artists: &[Artist],
+    labels: bool,
+    mut progress: impl FnMut(&Artist),
+) -> Result<(Vec<Suggestion>, SuggestErrors), MbError> {
+    let mut suggestions = Suggestions::new(artists);
+    let mut errors = Vec::new();
+    for a in artists {
+        progress(a);
+        match a.get_related_artists(mb, labels) {
+            Ok(related) => {
+                for r in related {
+                    let reason = format!("{} of {}", r.relation, a.name);
+                    suggestions.add(r, a, reason, 1.0);
+                }
+            }
+            Err(e) if e.is_fatal() => return Err(e),
+            Err(e) => errors.push((a.name.clone(), e)),
+        }
+    }
+    Ok((suggestions.sorted(), errors))
+}
+
+/// Suggestions from the ListenBrainz similar artists of the followed `artists` at `server`.
+/// The similarities to each followed artist add up, so artists similar to many of them come first
+pub fn suggest_similar(
+    client: &Client,
+    server: &str,
+    artists: &[Artist],
+    mut progress: impl FnMut(&Artist),
+) -> (Vec<Suggestion>, Vec<(String, anyhow::Error)>) {
+    let mut suggestions = Suggestions::new(artists);
+    let mut errors = Vec::new();
+    for a in artists {
+        progress(a);
+        match listenbrainz::similar_artists(client, server, a.id) {
+            Ok(similar) => {
+                // scores of different artists are not comparable, the most similar one gets 1
+                let max = similar.first().map(|s| s.score).unwrap_or(1.0).max(1.0);
+                for s in similar {
+                    let related = RelatedArtist {
+                        id: s.id,
+                        sort_name: s.name.clone(),
+                        name: s.name,
+                        relation: "similar".to_string(),
+                    };
+                    suggestions.add(related, a, format!("similar to {}", a.name), s.score / max);
+                }
+            }
+            Err(e) => errors.push((a.name.clone(), e)),
+        }
+    }
+    (suggestions.sorted(), errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+    use crate::responses::ArtistSource;
+
+    fn artist(n: u128, name: &str) -> Artist {
+        RelatedArtist {
+            id: Uuid::from_u128(n),
+            name: name.to_string(),
+            sort_name: name.to_string(),
+            relation: String::new(),
+        }
+        .to_artist(ArtistSource::Manual, time::Date::MIN)
+    }
+
+    fn relation(n: u128, name: &str) -> String {
+        format!(
+            r#"{{"type": "collaboration", "artist": {{"id": "{}", "name": "{}", "sort-name": "{}"}}}}"#,
+            Uuid::from_u128(n),
+            name,
+            name
+        )
+    }
+
+    #[test]
+    fn suggestions_related_to_more_artists_come_first() {
+        let server = MockServer::start(vec![
+            (
+                200,
+                format!(
+                    r#"{{"relations": [{}, {}]}}"#,
+                    relation(10, "Once"),
+                    relation(2, "B")
+                ),
+            ),
+            (
+                200,
+                format!(
+                    r#"{{"relations": [{}, {}]}}"#,
+                    relation(11, "Twice"),
+                    relation(10, "Once")
+                ),
+            ),
+            (
+                200,
+                format!(r#"{{"relations": [{}]}}"#, relation(11, "Twice")),
+            ),
+        ]);
+        let artists = vec![artist(1, "A"), artist(2, "B"), artist(3, "C")];
+        let (suggestions, errors) = suggest(&server.client(), &artists, false, |_| {}).unwrap();
+        assert!(errors.is_empty());
+        let names = suggestions
+            .iter()
+            .map(|s| (s.artist.name.as_str(), s.reasons.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![("Once", 2), ("Twice", 2)]);
+        assert_eq!(suggestions[1].related_to, "B");
+    }
+
+    #[test]
+    fn similar_artists_add_up_normalized_scores() {
+        let similar = |entries: &[(u128, &str, u32)]| {
+            let list = entries
+                .iter()
+                .map(|(n, name, score)| {
+                    format!(
+                        r#"{{"artist_mbid": "{}", "name": "{}", "score": {}}}"#,
+                        Uuid::from_u128(*n),
+                        name,
+                        score
+                    )
+                })
+                .collect::<Vec<String>>();
+            (200, format!("[{}]", list.join(", ")))
+        };
+        let server = MockServer::start(vec![
+            similar(&[(10, "Top of A", 1000), (12, "Both", 500), (2, "B", 800)]),
+            similar(&[(12, "Both", 10), (11, "Top of B", 20)]),
+        ]);
+        let artists = vec![artist(1, "A"), artist(2, "B")];
+        let (suggestions, errors) = suggest_similar(&Client::new(), server.url(), &artists, |_| {});
+        assert!(errors.is_empty());
+        let names = suggestions
+            .iter()
+            .map(|s| s.artist.name.as_str())
+            .collect::<Vec<_>>();
+        // Both: 0.5 + 0.5, the tops get 1 each and sort by name
+        assert_eq!(names, vec!["Both", "Top of A", "Top of B"]);
+    }
+}
@@ -0,0 +1,341 @@
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use time::Date;
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::responses::{Album, Artist, ArtistSource};
+
+/// The parts of a config that are shared between machines.
+/// A complete config can be read as this as well
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SyncFile {
+    #[serde(alias = "artist_full")]
+    pub artists: Vec<Artist>,
+    #[serde(default)]
+    pub ignore_paths: Vec<String>,
+    #[serde(default)]
+    pub watched: Vec<Album>,
+    /// only set when a complete config was read
+    #[serde(default, skip_serializing)]
+    pub last_checked_time: Option<Date>,
+}
+
+impl SyncFile {
+    /// The shareable part of `c`, without the state of this machine
+    pub fn export(c: &Config) -> Self {
+        let artists = c
+            .artist_full
+            .iter()
+            .cloned()
+            .map(|mut a| {
+                a.last_checked = None;
+                a.last_release = None;
+                a
+            })
+            .collect();
+        Self {
+            artists,
+            ignore_paths: c.ignore_paths.clone(),
+            watched: c.watched.clone(),
+            last_checked_time: None,
+        }
+    }
+
+    /// Read a sync file or config from a local file or a http(s) url
+    pub fn read(source: &str, client: &reqwest::blocking::Client) -> Result<Self> {
+        let s = read_source(source, client)?;
+        serde_json::from_str(&s).with_context(|| format!("Could not read {}", source))
+    }
+}
+
+/// The content of a local file or a http(s) url
+pub fn read_source(source: &str, client: &reqwest::blocking::Client) -> Result<String> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        client
+            .get(source)
+            .send()
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.text())
+            .with_context(|| format!("Downloading {}", source))
+    } else {
+        std::fs::read_to_string(PathBuf::from(source))
+            .with_context(|| format!("Reading {}", source))
+    }
+}
+
+/// What changed in a merge
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    pub added_artists: usize,
+    pub updated_artists: usize,
+    pub added_ignores: usize,
+    pub added_watched: usize,
+}
+
+/// Merge `other` into `c`. Artists, ignores and watched albums are united,
+/// for artists in both the one that was checked last wins and the newest last check of the configs is kept
+pub fn merge(c: &mut Config, other: SyncFile) -> MergeSummary {
+    let mut summary = MergeSummary::default();
+    for a in other.artists {
+        if let Some(ours) = c.artist_full.iter_mut().find(|o| o.id == a.id) {
+            if a.last_checked > ours.last_checked {
+                *ours = a;
+                summary.updated_artists += 1;
+            }
+        } else {
+            c.artist_full.push(a);
+            summary.added_artists += 1;
+        }
+    }
+    c.artist_full.sort_unstable();
+    for i in other.ignore_paths {
+        if !c.ignore_paths.contains(&i) {
+            c.ignore_paths.push(i);
+            summary.added_ignores += 1;
+        }
+    }
+    for w in other.watched {
+        if !c.watched.iter().any(|o| o.id == w.id) {
+            c.watched.push(w);
+            summary.added_watched += 1;
+        }
+    }
+    if let Some(t) = other.last_checked_time {
+        c.last_checked_time = c.last_checked_time.max(t);
+    }
+    summary
+}
+
+/// An artist in a shared follow list
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedArtist {
+    pub name: String,
+    pub sort_name: String,
+}
+
+/// A compact follow list for other users, keyed by MBID
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ShareList {
+    pub artists: BTreeMap<Uuid, SharedArtist>,
+}
+
+impl ShareList {
+    /// The follow list of `c` without archived artists
+    pub fn export(c: &Config) -> Self {
+        Self {
+            artists: c
+                .artist_full
+                .iter()
+                .filter(|a| !a.archived)
+                .map(|a| {
+                    (
+                        a.id,
+                        SharedArtist {
+                            name: a.name.clone(),
+                            sort_name: a.sort_name.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Add all artists we do not follow yet to `c` and return how many were added
+    pub fn import(self, c: &mut Config, today: Date) -> usize {
+        let mut added = 0;
+        for (id, a) in self.artists {
+            if c.artist_full.iter().any(|o| o.id == id) {
+                continue;
+            }
+            c.artist_full.push(
+                Artist {
+                    search_string: a.name.clone(),
+                    name: a.name,
+                    id,
+                    sort_name: a.sort_name,
+                    ..Default::default()
+                }
+                .added_by(ArtistSource::Import("shared list".to_string()), today),
+            );
+            added += 1;
+        }
+        c.artist_full.sort_unstable();
+        added
+    }
+}
+
+/// Programs that can encrypt a shared list
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encryption {
+    Age,
+    Gpg,
+}
+
+/// Run `program` with `args`, feed it `input` and return its output
+fn pipe(program: &str, args: &[&str], input: &[u8]) -> Result<Vec<u8>> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Could not run {}", program))?;
+    let mut stdin = child.stdin.take().context("No stdin")?;
+    let input = input.to_vec();
+    // write in another thread so a full stdout pipe can not block us
+    let writer = std::thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output().context("Waiting for child")?;
+    writer
+        .join()
+        .map_err(|_| anyhow!("Writing to {} panicked", program))?
+        .with_context(|| format!("Writing to {}", program))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(anyhow!("{} exited with {}", program, output.status))
+    }
+}
+
+/// Encrypt `s` for `recipient` into ascii armored text
+pub fn encrypt(s: &str, encryption: Encryption, recipient: &str) -> Result<String> {
+    let out = match encryption {
+        Encryption::Age => pipe("age", &["--armor", "--recipient", recipient], s.as_bytes())?,
+        Encryption::Gpg => pipe(
+            "gpg",
+            &["--encrypt", "--armor", "--recipient", recipient],
+            s.as_bytes(),
+        )?,
+    };
+    String::from_utf8(out).context("Encrypted output is not ascii armored")
+}
+
+/// Decrypt `s` if it is an age or gpg message, with the age `identity` file if given
+pub fn decrypt(s: &str, identity: &Option<PathBuf>) -> Result<String> {
+    let out = if s.starts_with("-----BEGIN AGE ENCRYPTED FILE-----") {
+        let identity = identity
+            .as_ref()
+            .ok_or_else(|| anyhow!("Need an --identity to decrypt an age file"))?;
+        pipe(
+            "age",
+            &["--decrypt", "--identity", &identity.to_string_lossy()],
+            s.as_bytes(),
+        )?
+    } else if s.starts_with("-----BEGIN PGP MESSAGE-----") {
+        pipe("gpg", &["--decrypt", "--quiet"], s.as_bytes())?
+    } else {
+        return Ok(s.to_string());
+    };
+    String::from_utf8(out).context("Decrypted list is not utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::Month;
+
+    fn date(month: Month) -> Date {
+        Date::from_calendar_date(2024, month, 1).unwrap()
+    }
+
+    fn artist(id: u128, name: &str, last_checked: Option<Date>) -> Artist {
+        Artist {
+            name: name.to_string(),
+            id: Uuid::from_u128(id),
+            sort_name: name.to_string(),
+            last_checked,
+            last_release: last_checked,
+            ..Default::default()
+        }
+    }
+
+    fn album(id: u128) -> Album {
+        Album {
+            id: Uuid::from_u128(id),
+            title: format!("Album {}", id),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn merge_unites_and_keeps_the_newest_check() {
+        let mut c = Config {
+            artist_full: vec![
+                artist(1, "Old here", Some(date(Month::January))),
+                artist(2, "New here", Some(date(Month::March))),
+            ],
+            ignore_paths: vec!["a".to_string()],
+            watched: vec![album(10)],
+            last_checked_time: date(Month::March),
+            ..Default::default()
+        };
+        let other = SyncFile {
+            artists: vec![
+                artist(1, "Old here renamed", Some(date(Month::February))),
+                artist(2, "New here renamed", Some(date(Month::February))),
+                artist(3, "Only there", None),
+            ],
+            ignore_paths: vec!["a".to_string(), "b".to_string()],
+            watched: vec![album(10), album(11)],
+            last_checked_time: Some(date(Month::February)),
+        };
+
+        let summary = merge(&mut c, other);
+
+        assert_eq!(summary.added_artists, 1);
+        assert_eq!(summary.updated_artists, 1);
+        assert_eq!(summary.added_ignores, 1);
+        assert_eq!(summary.added_watched, 1);
+        let names = c
+            .artist_full
+            .iter()
+            .map(|a| a.name.as_str())
+            .collect::<Vec<&str>>();
+        assert!(names.contains(&"Old here renamed"));
+        assert!(names.contains(&"New here"));
+        assert!(names.contains(&"Only there"));
+        assert_eq!(c.ignore_paths, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(
+            c.watched.iter().map(|a| a.id).collect::<Vec<Uuid>>(),
+            vec![Uuid::from_u128(10), Uuid::from_u128(11)]
+        );
+        assert_eq!(c.last_checked_time, date(Month::March));
+    }
+
+    #[test]
+    fn merge_takes_a_newer_last_check_time() {
+        let mut c = Config {
+            last_checked_time: date(Month::January),
+            ..Default::default()
+        };
+        let other = SyncFile {
+            last_checked_time: Some(date(Month::February)),
+            ..Default::default()
+        };
+        merge(&mut c, other);
+        assert_eq!(c.last_checked_time, date(Month::February));
+    }
+
+    #[test]
+    fn export_leaves_out_the_state_of_this_machine() {
+        let c = Config {
+            artist_full: vec![artist(1, "Checked", Some(date(Month::January)))],
+            ignore_paths: vec!["a".to_string()],
+            watched: vec![album(10)],
+            last_checked_time: date(Month::January),
+            ..Default::default()
+        };
+        let export = SyncFile::export(&c);
+        assert_eq!(export.artists.len(), 1);
+        assert_eq!(export.artists[0].last_checked, None);
+        assert_eq!(export.artists[0].last_release, None);
+        assert_eq!(export.ignore_paths, c.ignore_paths);
+        assert_eq!(export.watched.len(), 1);
+        let json = serde_json::to_value(&export).unwrap();
+        assert!(json.get("last_checked_time").is_none());
+    }
+}
@@ -0,0 +1,238 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::RwLock;
+use yansi::{Attribute, Color, Style};
+
+/// The palettes we ship
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Palette {
+    /// blue, yellow and magenta, upcoming albums are dim and italic instead of a different color
+    #[default]
+    Colorblind,
+    /// red artists, blue dates and green titles, upcoming albums are struck through
+    Classic,
+}
+
+/// How albums are printed, the styles are words like "bold blue", "#0072b2" or "bright-yellow underline"
+/// and replace the ones of the palette
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeSettings {
+    pub palette: Palette,
+    pub artist: Option<String>,
+    pub date: Option<String>,
+    pub title: Option<String>,
+    pub release_type: Option<String>,
+    /// added to the styles above for albums that are not released yet
+    pub upcoming: Option<String>,
+}
+
+/// The styles for the parts of an album
+#[derive(Debug, Clone, PartialEq)]
+pub struct Theme {
+    pub artist: Style,
+    pub date: Style,
+    pub title: Style,
+    pub release_type: Style,
+    /// what changes for albums that are not released yet
+    pub upcoming: Look,
+}
+
+/// Words for the colors, bright ones are `bright-<color>`
+const COLORS: &[(&str, Color)] = &[
+    ("black", Color::Black),
+    ("red", Color::Red),
+    ("green", Color::Green),
+    ("yellow", Color::Yellow),
+    ("blue", Color::Blue),
+    ("magenta", Color::Magenta),
+    ("cyan", Color::Cyan),
+    ("white", Color::White),
+    ("bright-black", Color::BrightBlack),
+    ("bright-red", Color::BrightRed),
+    ("bright-green", Color::BrightGreen),
+    ("bright-yellow", Color::BrightYellow),
+    ("bright-blue", Color::BrightBlue),
+    ("bright-magenta", Color::BrightMagenta),
+    ("bright-cyan", Color::BrightCyan),
+    ("bright-white", Color::BrightWhite),
+];
+
+/// Words for the attributes
+const ATTRIBUTES: &[(&str, Attribute)] = &[
+    ("bold", Attribute::Bold),
+    ("dim", Attribute::Dim),
+    ("italic", Attribute::Italic),
+    ("underline", Attribute::Underline),
+    ("strike", Attribute::Strike),
+    ("invert", Attribute::Invert),
+];
+
+/// The color in `#rrggbb`
+fn hex_color(s: &str) -> Option<Color> {
+    let hex = s.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// A color and attributes that can be put on top of a style
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Look {
+    pub color: Option<Color>,
+    pub attributes: Vec<Attribute>,
+}
+
+impl Look {
+    /// The look in `spec`, words separated by spaces
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut res = Look::default();
+        for word in spec.split_whitespace() {
+            let word = word.to_lowercase();
+            if let Some((_, c)) = COLORS.iter().find(|(n, _)| *n == word) {
+                res.color = Some(*c);
+            } else if let Some((_, a)) = ATTRIBUTES.iter().find(|(n, _)| *n == word) {
+                res.attributes.push(*a);
+            } else if let Some(c) = hex_color(&word) {
+                res.color = Some(c);
+            } else {
+                return Err(anyhow!(
+                    "Unknown style {} in settings.theme, use colors like blue or bright-blue, #rrggbb or bold, dim, italic, underline, strike and invert",
+                    word
+                ));
+            }
+        }
+        Ok(res)
+    }
+
+    /// `base` with this look on top
+    pub fn apply(&self, base: Style) -> Style {
+        let style = match self.color {
+            Some(c) => base.fg(c),
+            None => base,
+        };
+        self.attributes.iter().fold(style, |s, a| s.attr(*a))
+    }
+}
+
+impl Palette {
+    /// The styles of the palette
+    fn theme(&self) -> Theme {
+        match self {
+            Palette::Colorblind => Theme {
+                artist: Style::new().fg(Color::BrightBlue).bold(),
+                date: Style::new().fg(Color::Cyan),
+                title: Style::new().fg(Color::BrightYellow).bold(),
+                release_type: Style::new().fg(Color::Magenta),
+                upcoming: Look {
+                    color: None,
+                    attributes: vec![Attribute::Dim, Attribute::Italic],
+                },
+            },
+            Palette::Classic => Theme {
+                artist: Style::new().red().bold(),
+                date: Style::new().blue().bold(),
+                title: Style::new().green().bold(),
+                release_type: Style::new().yellow(),
+                upcoming: Look {
+                    color: None,
+                    attributes: vec![Attribute::Strike],
+                },
+            },
+        }
+    }
+}
+
+impl Theme {
+    /// The theme of `settings`, fails for styles we do not know
+    pub fn from_settings(settings: &ThemeSettings) -> Result<Self> {
+        let palette = settings.palette.theme();
+        let style = |spec: &Option<String>, default: Style| match spec {
+            Some(s) => Look::parse(s).map(|l| l.apply(Style::new())),
+            None => Ok(default),
+        };
+        Ok(Theme {
+            artist: style(&settings.artist, palette.artist)?,
+            date: style(&settings.date, palette.date)?,
+            title: style(&settings.title, palette.title)?,
+            release_type: style(&settings.release_type, palette.release_type)?,
+            upcoming: match &settings.upcoming {
+                Some(s) => Look::parse(s)?,
+                None => palette.upcoming,
+            },
+        })
+    }
+
+    /// The styles for an album that is not released yet, the upcoming look on top of the others
+    pub fn upcoming(&self) -> Theme {
+        Theme {
+            artist: self.upcoming.apply(self.artist),
+            date: self.upcoming.apply(self.date),
+            title: self.upcoming.apply(self.title),
+            release_type: self.upcoming.apply(self.release_type),
+            upcoming: self.upcoming.clone(),
+        }
+    }
+}
+
+/// The theme from the settings
+static CURRENT: RwLock<Option<Theme>> = RwLock::new(None);
+
+/// Use the theme of `settings` for all output
+pub fn set(settings: &ThemeSettings) -> Result<()> {
+    let theme = Theme::from_settings(settings)?;
+    if let Ok(mut t) = CURRENT.write() {
+        *t = Some(theme);
+    }
+    Ok(())
+}
+
+/// The theme we print with, the default palette before the settings are read
+pub fn current() -> Theme {
+    CURRENT
+        .read()
+        .ok()
+        .and_then(|t| t.clone())
+        .unwrap_or_else(|| Palette::default().theme())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_are_parsed() {
+        let style = |s: &str| Look::parse(s).map(|l| l.apply(Style::new()));
+        assert_eq!(
+            style("Bold bright-blue").unwrap(),
+            Style::new().fg(Color::BrightBlue).bold()
+        );
+        assert_eq!(
+            style("#0072b2 underline").unwrap(),
+            Style::new().fg(Color::Rgb(0, 0x72, 0xb2)).underline()
+        );
+        assert!(style("blurple").is_err());
+        assert!(style("#12345").is_err());
+    }
+
+    #[test]
+    fn settings_replace_the_palette() {
+        let theme = Theme::from_settings(&ThemeSettings {
+            palette: Palette::Classic,
+            title: Some("cyan".to_string()),
+            upcoming: Some("dim red".to_string()),
+            ..ThemeSettings::default()
+        })
+        .unwrap();
+        assert_eq!(theme.artist, Style::new().red().bold());
+        assert_eq!(theme.title, Style::new().cyan());
+        let upcoming = theme.upcoming();
+        assert_eq!(upcoming.title, Style::new().red().dim());
+        assert_eq!(upcoming.artist, Style::new().red().bold().dim());
+        assert!(Theme::from_settings(&ThemeSettings {
+            date: Some("sparkly".to_string()),
+            ..ThemeSettings::default()
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use time::Date;
+
+use crate::config::data_dir;
+
+/// File name of the api usage in the data dir
+const USAGE_FILE: &str = "api_calls.json";
+/// How many days of usage we keep
+const KEEP_DAYS: i64 = 30;
+/// More requests than this to musicbrainz.org on one day are more than a personal tool should need
+pub const DAILY_CALLS_WARNING: usize = 5000;
+/// Checking more often than this against musicbrainz.org is not nice
+pub const MIN_RECOMMENDED_INTERVAL_HOURS: u64 = 6;
+
+/// Number of api requests per day
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Usage {
+    days: BTreeMap<Date, usize>,
+}
+
+/// The path of the usage file
+fn usage_path() -> Result<PathBuf> {
+    let mut path = data_dir()?;
+    path.push(USAGE_FILE);
+    Ok(path)
+}
+
+/// Add `calls` requests to the usage of `today` and return the requests of today
+pub fn record_calls(today: Date, calls: usize) -> Result<usize> {
+    let path = usage_path()?;
+    let mut usage = if path.exists() {
+        let s = fs::read_to_string(&path).context("Reading api usage")?;
+        serde_json::from_str::<Usage>(&s).unwrap_or_default()
+    } else {
+        Usage::default()
+    };
+    *usage.days.entry(today).or_insert(0) += calls;
+    let oldest = today - time::Duration::days(KEEP_DAYS);
+    usage.days.retain(|d, _| *d > oldest);
+    let s = serde_json::to_string_pretty(&usage).context("JSON to string")?;
+    fs::write(path, s).context("Writing api usage")?;
+    Ok(usage.days[&today])
+}
@@ -0,0 +1,227 @@
+use anyhow::{anyhow, Context, Result};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::i18n::format_date;
+use crate::nfo::COVER_ART_SERVER;
+use crate::notify::{albums_body, musicbrainz_url, Notifier};
+use crate::responses::{Album, ReleaseType};
+
+/// Slack shows at most this many blocks in one message
+const MAX_SLACK_BLOCKS: usize = 50;
+/// Discord shows at most this many embeds in one message
+const MAX_DISCORD_EMBEDS: usize = 10;
+
+/// Settings for an incoming webhook of Slack or Discord
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookSettings {
+    pub url: String,
+}
+
+/// A small cover of the release group of `a`, chat clients load it themselves
+fn cover_url(a: &Album) -> String {
+    format!("{}/release-group/{}/front-250", COVER_ART_SERVER, a.id)
+}
+
+/// The color of the bar next to an album in Discord, every kind of release type has its own
+fn type_color(t: &ReleaseType) -> u32 {
+    match t {
+        ReleaseType::Album => 0xBA478F,
+        ReleaseType::EP | ReleaseType::Single => 0x5B8DEF,
+        ReleaseType::Live => 0xF2A541,
+        ReleaseType::Compilation
+        | ReleaseType::Remix
+        | ReleaseType::DJMix
+        | ReleaseType::Mixtape => 0x43B581,
+        ReleaseType::Soundtrack => 0x9B59B6,
+        _ => 0x99AAB5,
+    }
+}
+
+/// Post `payload` to the webhook `url` of `service`
+fn post(client: &Client, service: &str, url: &str, payload: &Value) -> Result<()> {
+    let response = client
+        .post(url)
+        .json(payload)
+        .send()
+        .with_context(|| format!("Could not reach {}", service))?;
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(anyhow!("{} answered with {}", service, response.status()))
+    }
+}
+
+/// Posts Slack blocks to an incoming webhook
+pub struct SlackNotifier {
+    pub client: Client,
+    pub settings: WebhookSettings,
+}
+
+/// Slack mrkdwn needs these escaped
+fn slack_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// The blocks of a Slack message about `albums`, a header and one section with the cover for every album
+fn slack_blocks(title: &str, albums: &[&Album]) -> Value {
+    let header = json!({
+        "type": "header",
+        "text": { "type": "plain_text", "text": title },
+    });
+    let sections = albums.iter().map(|a| {
+        json!({
+            "type": "section",
+            "text": {
+                "type": "mrkdwn",
+                "text": format!(
+                    "*<{}|{}>*\n{}\n{} · {}",
+                    musicbrainz_url(a),
+                    slack_escape(&a.title),
+                    slack_escape(&a.artists()),
+                    a.release_type,
+                    format_date(a.date)
+                ),
+            },
+            "accessory": { "type": "image", "image_url": cover_url(a), "alt_text": a.title },
+        })
+    });
+    std::iter::once(header)
+        .chain(sections)
+        .take(MAX_SLACK_BLOCKS)
+        .collect()
+}
+
+impl Notifier for SlackNotifier {
+    fn name(&self) -> &str {
+        "slack"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        post(
+            &self.client,
+            "Slack",
+            &self.settings.url,
+            &json!({ "text": format!("*{}*\n{}", slack_escape(title), slack_escape(body)) }),
+        )
+    }
+
+    fn send_albums(&self, title: &str, albums: &[&Album]) -> Result<()> {
+        // the text is what notifications on phones show
+        let payload = json!({
+            "text": format!("{}\n{}", title, albums_body(albums)),
+            "blocks": slack_blocks(title, albums),
+        });
+        post(&self.client, "Slack", &self.settings.url, &payload)
+    }
+}
+
+/// Posts Discord embeds to a webhook
+pub struct DiscordNotifier {
+    pub client: Client,
+    pub settings: WebhookSettings,
+}
+
+/// The Discord embed of album `a`
+fn discord_embed(a: &Album) -> Value {
+    json!({
+        "title": a.title,
+        "url": musicbrainz_url(a),
+        "description": a.artists(),
+        "color": type_color(&a.release_type),
+        "thumbnail": { "url": cover_url(a) },
+        "fields": [
+            { "name": "Date", "value": format_date(a.date), "inline": true },
+            { "name": "Type", "value": a.release_type.to_string(), "inline": true },
+        ],
+    })
+}
+
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "discord"
+    }
+
+    fn send(&self, title: &str, body: &str) -> Result<()> {
+        post(
+            &self.client,
+            "Discord",
+            &self.settings.url,
+            &json!({ "content": format!("**{}**\n{}", title, body) }),
+        )
+    }
+
+    fn send_albums(&self, title: &str, albums: &[&Album]) -> Result<()> {
+        for (i, chunk) in albums.chunks(MAX_DISCORD_EMBEDS).enumerate() {
+            let mut payload = json!({
+                "embeds": chunk.iter().map(|a| discord_embed(a)).collect::<Vec<Value>>(),
+            });
+            if i == 0 {
+                payload["content"] = json!(format!("**{}**", title));
+            }
+            post(&self.client, "Discord", &self.settings.url, &payload)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::responses::tests::MockServer;
+
+    use uuid::Uuid;
+
+    fn album(n: u128) -> Album {
+        Album {
+            id: Uuid::from_u128(n),
+            artist: "A & B".to_string(),
+            title: format!("Album {}", n),
+            release_type: ReleaseType::Live,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn slack_blocks_with_covers() {
+        let a = album(1);
+        let blocks = slack_blocks("1 new albums", &[&a]);
+        assert_eq!(blocks[0]["type"], "header");
+        let text = blocks[1]["text"]["text"].as_str().unwrap();
+        assert!(text.starts_with(
+            "*<https://musicbrainz.org/release-group/00000000-0000-0000-0000-000000000001|Album 1>*\nA &amp; B\nLive"
+        ));
+        assert_eq!(
+            blocks[1]["accessory"]["image_url"],
+            "https://coverartarchive.org/release-group/00000000-0000-0000-0000-000000000001/front-250"
+        );
+    }
+
+    #[test]
+    fn discord_embeds_are_chunked() {
+        let server = MockServer::start(vec![(204, String::new()); 2]);
+        let discord = DiscordNotifier {
+            client: Client::new(),
+            settings: WebhookSettings {
+                url: format!("{}/api/webhooks/1/x", server.url()),
+            },
+        };
+        let albums = (0..12).map(album).collect::<Vec<Album>>();
+        discord
+            .send_albums("12 new albums", &albums.iter().collect::<Vec<&Album>>())
+            .unwrap();
+        let bodies = server
+            .bodies()
+            .iter()
+            .map(|b| serde_json::from_str::<Value>(b).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(bodies[0]["content"], "**12 new albums**");
+        assert_eq!(bodies[0]["embeds"].as_array().unwrap().len(), 10);
+        assert_eq!(bodies[0]["embeds"][0]["color"], 0xF2A541);
+        assert!(bodies[1].get("content").is_none());
+        assert_eq!(bodies[1]["embeds"].as_array().unwrap().len(), 2);
+    }
+}